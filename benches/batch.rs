@@ -0,0 +1,48 @@
+//! 100 个IP的批量查询：逐个等待每次查询与按 batch_parallelism 并发查询的对比。
+//! 使用与集成测试相同的夹具数据库，运行 `cargo bench --bench batch`
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use futures::stream::{self, StreamExt};
+use ipgeo::config::Config;
+use ipgeo::geo::get_ip_info;
+
+// 夹具中各数据库都有记录的网段，每个网段取 25 个不同的地址
+fn batch_ips() -> Vec<String> {
+    ["8.8.8", "114.114.114", "1.2.8", "1.0.0"]
+        .iter()
+        .flat_map(|prefix| (1..=25).map(move |host| format!("{}.{}", prefix, host)))
+        .collect()
+}
+
+fn batch(c: &mut Criterion) {
+    common::setup();
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let ips = batch_ips();
+    let parallelism = Config::global().batch_parallelism;
+
+    let mut group = c.benchmark_group("batch_100");
+    group.bench_function("sequential", |b| {
+        b.to_async(&runtime).iter(|| async {
+            for ip in &ips {
+                get_ip_info(ip).await.expect("fixture lookup");
+            }
+        })
+    });
+    group.bench_function("concurrent", |b| {
+        b.to_async(&runtime).iter(|| async {
+            let results: Vec<_> = stream::iter(&ips)
+                .map(|ip| get_ip_info(ip))
+                .buffer_unordered(parallelism)
+                .collect()
+                .await;
+            assert!(results.iter().all(Result::is_ok));
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, batch);
+criterion_main!(benches);
@@ -0,0 +1,43 @@
+#[cfg(feature = "embedded-fallback")]
+#[allow(dead_code)]
+#[path = "src/geo/fallback.rs"]
+mod fallback;
+
+// 启用 grpc 特性时用纯 Rust 的 protox 编译 proto，不依赖系统安装的 protoc
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/ipgeo.proto");
+        let descriptors = protox::compile(["proto/ipgeo.proto"], ["proto"])
+            .expect("failed to compile proto/ipgeo.proto");
+        tonic_build::configure()
+            .build_client(true)
+            .compile_fds(descriptors)
+            .expect("failed to generate gRPC code");
+    }
+    #[cfg(feature = "embedded-fallback")]
+    embed_fallback_table();
+}
+
+// 把 `start,end,country[,name]` CSV 编码后写入 OUT_DIR。默认使用仓库中的 assets/fallback-country.csv，
+// IPGEO_FALLBACK_CSV 可以换成更精细的数据；读不到或编码后为空时构建失败
+#[cfg(feature = "embedded-fallback")]
+fn embed_fallback_table() {
+    println!("cargo:rerun-if-env-changed=IPGEO_FALLBACK_CSV");
+    let path = std::env::var("IPGEO_FALLBACK_CSV").unwrap_or_else(|_| {
+        format!("{}/assets/fallback-country.csv", std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR"))
+    });
+    println!("cargo:rerun-if-changed={}", path);
+    let csv = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("embedded-fallback: failed to read {}: {}", path, e));
+    let (table, skipped) = fallback::encode_csv(&csv);
+    if fallback::FallbackTable::parse(&table).is_none_or(|table| table.is_empty()) {
+        panic!("embedded-fallback: {} contains no usable `start,end,country` rows", path);
+    }
+    if skipped > 0 {
+        println!("cargo:warning=skipped {} unparseable rows in {}", skipped, path);
+    }
+    let out = std::path::Path::new(&std::env::var("OUT_DIR").expect("OUT_DIR")).join("fallback.bin");
+    std::fs::write(out, table).expect("failed to write embedded fallback table");
+}
@@ -0,0 +1,38 @@
+//! 把查询接口挂到已有的 axum 应用下，不启动第二个进程：
+//!
+//! ```sh
+//! cargo run --example embedded -- ./data
+//! curl http://127.0.0.1:3000/geo/8.8.8.8
+//! ```
+
+use std::sync::Arc;
+
+use axum::routing::get;
+use axum::Router;
+use ipgeo::api::router_with_state;
+use ipgeo::config::Config;
+use ipgeo::geo::GeoResolver;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = std::env::args().nth(1).unwrap_or_else(|| "data".to_string());
+    let resolver = Arc::new(GeoResolver::new(Config {
+        data_dir: data_dir.into(),
+        ..Config::from_env()
+    })?);
+    // 可选：由宿主应用决定是否在后台下载和定时更新数据库
+    resolver.spawn_updater();
+
+    let app = Router::new()
+        .route("/", get(|| async { "host application" }))
+        .nest("/geo", router_with_state(resolver.clone()));
+
+    // 这里没有使用 into_make_service_with_connect_info，客户端地址只从代理头部读取；
+    // 需要按对端地址识别时改用 app.into_make_service_with_connect_info::<SocketAddr>()
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async { tokio::signal::ctrl_c().await.ok(); })
+        .await?;
+    resolver.shutdown();
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use std::net::SocketAddr;
+use std::time::Instant;
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request},
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{field, info, info_span, warn, Instrument};
+use crate::config::{Config, PrivacyMode};
+use crate::metrics::timing::{with_timings, RequestTimings};
+use crate::models::RequestId;
+use crate::utils::{is_truthy, mask_ip};
+use super::admin::is_admin;
+use super::api::get_real_ip_with_source;
+use super::trace_context::trace_context;
+
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// 上游传入的请求ID只接受不超过 128 个字母、数字和 `-_.:`，避免把任意内容写进日志和响应
+fn is_well_formed(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'))
+}
+
+// 沿用上游传入的合法请求ID，否则生成按时间排序的 UUIDv7
+fn request_id(request: &Request) -> String {
+    request.headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|id| is_well_formed(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::now_v7().to_string())
+}
+
+// `debug_timing=1` 只对带管理凭据的请求生效
+fn wants_timing(request: &Request) -> bool {
+    let requested = request.uri().query().is_some_and(|query| {
+        query.split('&').any(|pair| match pair.split_once('=') {
+            Some(("debug_timing", value)) => is_truthy(value),
+            None => pair == "debug_timing",
+            _ => false,
+        })
+    });
+    requested && is_admin(request)
+}
+
+/// 每个请求输出一条结构化访问日志，并在响应头和错误信封中回传 X-Request-Id
+pub async fn access_log(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let request_id = request_id(&request);
+    let method = request.method().clone();
+    // 路径里可能带着被查询的IP，脱敏模式下只记录路由模板
+    let path = match Config::global().privacy_mode {
+        PrivacyMode::Full => request.uri().path().to_string(),
+        _ => request.extensions()
+            .get::<MatchedPath>()
+            .map(|p| p.as_str().to_string())
+            .unwrap_or_else(|| "[redacted]".to_string()),
+    };
+    let client = request.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| get_real_ip_with_source(request.headers(), *addr));
+
+    let span = info_span!(
+        "request",
+        request_id = %request_id,
+        trace_id = field::Empty,
+        parent_span_id = field::Empty,
+    );
+    if let Some(context) = trace_context(request.headers()) {
+        span.record("trace_id", field::display(&context.trace_id));
+        span.record("parent_span_id", field::display(&context.parent_id));
+        #[cfg(feature = "otlp")]
+        crate::server::set_remote_parent(&span, &context);
+    }
+    let timings = RequestTimings::new(wants_timing(&request));
+    let mut response = RequestId::scope(&request_id, with_timings(timings.clone(), next.run(request)))
+        .instrument(span.clone())
+        .await;
+
+    let (client_ip, ip_source) = match client {
+        Some((ip, source)) => (mask_ip(ip), source),
+        None => (String::new(), "unknown"),
+    };
+    let latency = start.elapsed();
+    let slow_request = Config::global().slow_request;
+    span.in_scope(|| {
+        if !slow_request.is_zero() && latency >= slow_request {
+            let breakdown = timings.breakdown();
+            warn!(
+                method = %method,
+                path = %path,
+                total_ms = latency.as_millis() as u64,
+                stages = %breakdown,
+                shared = breakdown.shared,
+                "slow request"
+            );
+        }
+        info!(
+            method = %method,
+            path = %path,
+            client_ip = %client_ip,
+            ip_source,
+            status = response.status().as_u16(),
+            latency_us = latency.as_micros() as u64,
+            "request completed"
+        );
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER.clone(), value);
+    }
+    response
+}
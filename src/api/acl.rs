@@ -0,0 +1,43 @@
+//! 按客户端IP的允许/拒绝列表限制访问。客户端IP与日志中一致，经过代理头部识别；
+//! 管理接口只使用 ADMIN_ALLOW / ADMIN_DENY，不受公开接口的列表影响。
+
+use std::net::SocketAddr;
+use axum::{
+    extract::{ConnectInfo, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::debug;
+use crate::config::{AccessList, Config};
+use crate::models::IpGeoError;
+use crate::utils::mask_ip;
+use super::api::get_real_ip_with_source;
+
+async fn enforce(list: &AccessList, request: Request, next: Next) -> Response {
+    if list.is_empty() {
+        return next.run(request).await;
+    }
+    // 没有对端地址时无法判断，按拒绝处理
+    let permitted = request.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| get_real_ip_with_source(request.headers(), *addr).0)
+        .filter(|ip| {
+            let permitted = list.permits(*ip);
+            if !permitted {
+                debug!("Rejecting client {}", mask_ip(*ip));
+            }
+            permitted
+        });
+    match permitted {
+        Some(_) => next.run(request).await,
+        None => IpGeoError::Forbidden.into_response(),
+    }
+}
+
+pub async fn client_acl(request: Request, next: Next) -> Response {
+    enforce(&Config::global().client_acl, request, next).await
+}
+
+pub async fn admin_acl(request: Request, next: Next) -> Response {
+    enforce(&Config::global().admin_acl, request, next).await
+}
@@ -0,0 +1,304 @@
+use axum::{
+    extract::{rejection::JsonRejection, ConnectInfo, Path, Query, Request},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use crate::config::Config;
+use crate::cache::CacheManager;
+use crate::geo::{
+    clear_dns_cache, database_type, dns_cache_capacity, init_asn_data, DatabaseManager, downloadable_database, downloadable_databases, get_asn_reader, get_city_reader,
+    get_country_reader, get_geocn_reader, list_generations, load_databases_from, load_overrides, read_reader, reload_database,
+    rollback_database,
+};
+use crate::models::{IpGeoError, LookupOptions, ParamError};
+use crate::server::{log_filter, set_log_filter, ClientCertificate, LogFilterError, LogFilterState};
+use crate::utils::{is_private_ip, mask_input, network_for, parse_ip_lenient};
+use super::acl::admin_acl;
+use super::routes::reserve;
+use super::api::trace_real_ip;
+use super::state::AppState;
+
+static ADMIN_TOKEN_HEADER: HeaderName = HeaderName::from_static("x-admin-token");
+
+// 常量时间比较，避免通过响应时间猜测令牌
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+// 支持 `Authorization: Bearer <token>` 或 `X-Admin-Token: <token>`
+fn request_token(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| headers.get(&ADMIN_TOKEN_HEADER).and_then(|v| v.to_str().ok()))
+        .map(str::trim)
+}
+
+/// 管理接口凭据的检查结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AdminAuth {
+    Granted,
+    /// 既没有令牌也没有通过校验的客户端证书
+    Missing,
+    /// 令牌错误
+    Rejected,
+}
+
+/// 通过 ADMIN_CLIENT_CA 校验的客户端证书直接放行，否则检查管理令牌
+pub(crate) fn admin_auth(request: &Request) -> AdminAuth {
+    if request.extensions().get::<ClientCertificate>().is_some_and(|cert| cert.verified) {
+        return AdminAuth::Granted;
+    }
+    match (&Config::global().admin_token, request_token(request.headers())) {
+        (_, None) => AdminAuth::Missing,
+        (Some(expected), Some(given)) if token_matches(given, expected) => AdminAuth::Granted,
+        _ => AdminAuth::Rejected,
+    }
+}
+
+/// 请求是否带有管理凭据
+pub(crate) fn is_admin(request: &Request) -> bool {
+    admin_auth(request) == AdminAuth::Granted
+}
+
+/// 所有管理接口共用的认证：缺少凭据返回 401 UNAUTHORIZED，令牌错误返回 403 ADMIN_FORBIDDEN
+pub async fn require_admin(request: Request, next: Next) -> Response {
+    match admin_auth(&request) {
+        AdminAuth::Granted => next.run(request).await,
+        AdminAuth::Missing => IpGeoError::Unauthorized.into_response(),
+        AdminAuth::Rejected => IpGeoError::AdminForbidden.into_response(),
+    }
+}
+
+/// `compare=1` 每个地址要分别取两个数据库的回答，与管理接口使用同样的凭据检查；不带该参数的查询不受影响
+pub async fn require_admin_for_compare(request: Request, next: Next) -> Response {
+    let compare = Query::<LookupOptions>::try_from_uri(request.uri()).is_ok_and(|Query(options)| options.compare);
+    if !compare {
+        return next.run(request).await;
+    }
+    require_admin(request, next).await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DebugQuery {
+    db: Option<String>,
+}
+
+// 直接返回 mmdb 中的原始记录，不经过 IpInfo 转换
+fn raw_lookup(reader: &maxminddb::Reader<Vec<u8>>, db: &str, ip: IpAddr) -> serde_json::Value {
+    let (record, network) = match reader.lookup_prefix::<serde_json::Value>(ip) {
+        Ok((record, prefix_len)) => (record, Some(network_for(ip, prefix_len as u8).to_string())),
+        Err(_) => (serde_json::Value::Null, None),
+    };
+    serde_json::json!({
+        "ip": ip.to_string(),
+        "db": db,
+        "database_type": reader.metadata.database_type,
+        "build_epoch": reader.metadata.build_epoch,
+        "network": network,
+        "record": record,
+    })
+}
+
+fn db_name(db: &str) -> &'static str {
+    match db {
+        "asn" => "GeoLite2-ASN.mmdb",
+        "geocn" => "GeoCN.mmdb",
+        "country" => "GeoLite2-Country.mmdb",
+        _ => "GeoLite2-City.mmdb",
+    }
+}
+
+pub async fn debug_record(
+    Path(ip): Path<String>,
+    Query(query): Query<DebugQuery>,
+) -> Result<Response, IpGeoError> {
+    let ip: IpAddr = parse_ip_lenient(&ip).map_err(|_| IpGeoError::InvalidIp(mask_input(&ip)))?;
+    if is_private_ip(ip) {
+        return Err(IpGeoError::PrivateIp(mask_input(&ip.to_string())));
+    }
+
+    let db = query.db.as_deref().unwrap_or("city");
+    let reader = match db {
+        "city" => get_city_reader(),
+        "country" => get_country_reader(),
+        "asn" => get_asn_reader(),
+        "geocn" => get_geocn_reader(),
+        other => return Err(IpGeoError::InvalidParameter(ParamError::UnknownDebugDatabase(other.to_string()))),
+    };
+    let body = match read_reader(&reader).as_ref() {
+        Some(reader) => raw_lookup(reader, db, ip),
+        None => return Err(IpGeoError::DatabaseUnavailable(db_name(db))),
+    };
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/json; charset=utf-8")],
+        Json(body)
+    ).into_response())
+}
+
+// 回显时隐藏凭据类头部
+const SENSITIVE_HEADERS: [&str; 4] = ["authorization", "x-admin-token", "cookie", "proxy-authorization"];
+
+/// 回显全部请求头以及真实IP的判定过程
+pub async fn debug_headers(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Response {
+    let mut echoed: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for (name, value) in headers.iter() {
+        let value = if SENSITIVE_HEADERS.contains(&name.as_str()) {
+            "[redacted]".to_string()
+        } else {
+            String::from_utf8_lossy(value.as_bytes()).into_owned()
+        };
+        echoed.entry(name.as_str()).or_default().push(value);
+    }
+
+    let body = serde_json::json!({
+        "headers": echoed,
+        "decision": trace_real_ip(&headers, addr),
+    });
+    (
+        [(header::CONTENT_TYPE, "application/json; charset=utf-8")],
+        Json(body)
+    ).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RollbackQuery {
+    db: Option<String>,
+}
+
+/// 数据库当前及保留的旧版本，按文件名分组
+pub async fn generations() -> Response {
+    let data_dir = &Config::global().data_dir;
+    let body: BTreeMap<&str, _> = downloadable_databases()
+        .map(|name| (name, list_generations(&data_dir.join(name))))
+        .collect();
+    (
+        [(header::CONTENT_TYPE, "application/json; charset=utf-8")],
+        Json(body)
+    ).into_response()
+}
+
+/// 把数据库换回上一代版本并重新加载
+pub async fn rollback(Query(query): Query<RollbackQuery>) -> Result<Response, IpGeoError> {
+    let db = query.db.unwrap_or_default();
+    let name = downloadable_database(&db).ok_or_else(|| IpGeoError::InvalidParameter(
+        ParamError::UnknownRollbackDatabase(crate::utils::sanitize_echo(&db))
+    ))?;
+    let path = Config::global().data_dir.join(name);
+    rollback_database(&path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => IpGeoError::NotFound(format!("{}.1", name)),
+        _ => IpGeoError::IoError(e),
+    })?;
+    if let Some(db_type) = database_type(name) {
+        reload_database(db_type, &path)?;
+    }
+    tracing::info!("Rolled back {} to the previous generation", name);
+
+    let body = serde_json::json!({
+        "db": name,
+        "generations": list_generations(&path),
+    });
+    Ok((
+        [(header::CONTENT_TYPE, "application/json; charset=utf-8")],
+        Json(body)
+    ).into_response())
+}
+
+/// 重新读取数据目录中的数据库文件和 overrides.json，用于手动替换文件之后
+pub async fn reload() -> Result<Response, IpGeoError> {
+    let data_dir = &Config::global().data_dir;
+    load_databases_from(data_dir)?;
+    let overrides = load_overrides(data_dir)?;
+    tracing::info!("Reloaded databases and {} overrides from {:?}", overrides, data_dir);
+    Ok((
+        [(header::CONTENT_TYPE, "application/json; charset=utf-8")],
+        Json(serde_json::json!({ "overrides": overrides }))
+    ).into_response())
+}
+
+fn cache_report() -> Response {
+    let body = serde_json::json!({
+        "caches": CacheManager::global().stats(),
+        // 解析器内部的缓存没有命中统计，只能报告容量
+        "dns": { "capacity": dns_cache_capacity() },
+    });
+    (
+        [(header::CONTENT_TYPE, "application/json; charset=utf-8")],
+        Json(body)
+    ).into_response()
+}
+
+/// 各缓存的条目数、命中统计和估算内存
+pub async fn cache_stats() -> Response {
+    cache_report()
+}
+
+/// 清空 ASN、关键词和查询结果缓存以及 DNS 缓存，随后从 asn_info.json 重新填充 ASN 数据
+pub async fn flush_cache() -> Result<Response, IpGeoError> {
+    CacheManager::global().flush();
+    clear_dns_cache();
+    init_asn_data(&DatabaseManager::new(Config::global().data_dir.clone()))?;
+    tracing::info!("Caches flushed by admin request");
+    Ok(cache_report())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogLevelBody {
+    filter: String,
+}
+
+fn log_filter_error(e: LogFilterError) -> IpGeoError {
+    match e {
+        LogFilterError::Invalid(filter, err) => IpGeoError::InvalidParameter(ParamError::LogFilter(filter, err)),
+        LogFilterError::Unavailable => IpGeoError::Rejected(StatusCode::SERVICE_UNAVAILABLE, e.to_string()),
+    }
+}
+
+fn log_filter_response(state: LogFilterState) -> Response {
+    (
+        [(header::CONTENT_TYPE, "application/json; charset=utf-8")],
+        Json(state)
+    ).into_response()
+}
+
+/// 当前生效的日志过滤规则和启动时的规则
+pub async fn get_log_level() -> Result<Response, IpGeoError> {
+    log_filter().map(log_filter_response).ok_or_else(|| log_filter_error(LogFilterError::Unavailable))
+}
+
+/// 换成请求体中的日志过滤规则，如 `{"filter": "ipgeo=debug,tower_http=info"}`；无法解析时保持原有规则
+pub async fn put_log_level(body: Result<Json<LogLevelBody>, JsonRejection>) -> Result<Response, IpGeoError> {
+    let Json(body) = body.map_err(|e| IpGeoError::InvalidParameter(ParamError::LogLevelBody(e.body_text())))?;
+    set_log_filter(&body.filter).map(log_filter_response).map_err(log_filter_error)
+}
+
+/// 管理接口的路径前缀
+pub const ADMIN_PREFIX: &str = "/admin";
+
+/// 全部管理接口，挂在 /admin 下，统一经过 ADMIN_ALLOW / ADMIN_DENY 和凭据检查。
+/// 没有配置任何管理凭据时不应挂载，/admin 下的路径随之返回 404
+pub fn admin_router() -> Router<AppState> {
+    let routes = Router::new()
+        .route("/debug/headers", get(debug_headers))
+        .route("/debug/{ip}", get(debug_record))
+        .route("/generations", get(generations))
+        .route("/rollback", post(rollback))
+        .route("/reload", post(reload))
+        .route("/cache", get(cache_stats).delete(flush_cache))
+        .route("/log_level", get(get_log_level).put(put_log_level))
+        .route_layer(middleware::from_fn(require_admin))
+        .route_layer(middleware::from_fn(admin_acl));
+    reserve(ADMIN_PREFIX);
+    Router::new().nest(ADMIN_PREFIX, routes)
+}
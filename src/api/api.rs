@@ -1,16 +1,47 @@
 use axum::{
-    extract::{Path, Query, ConnectInfo},
-    routing::get,
+    error_handling::HandleErrorLayer,
+    extract::{rejection::{JsonRejection, QueryRejection}, DefaultBodyLimit, Path, Query, ConnectInfo},
+    Extension,
+    BoxError,
+    middleware,
+    routing::{get, post},
     Router,
     Json,
-    http::{StatusCode, HeaderMap, HeaderName},
-    response::{IntoResponse, Response},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    response::{sse::{Event, KeepAlive, Sse}, IntoResponse, Response},
 };
-use std::collections::HashMap;
+use axum::body::{Body, Bytes};
+use futures::{future, stream::{self, StreamExt}};
+use std::convert::Infallible;
+use tokio_util::codec::{FramedRead, LinesCodec};
+use tokio_util::io::StreamReader;
+use serde::Serialize;
 use std::net::{IpAddr, SocketAddr};
-use crate::geo::{get_ip_info, resolve_host};
-use crate::utils::is_private_ip;
-use tracing::{debug, info};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::MissedTickBehavior;
+use crate::config::{Config, PrivateTargetPolicy};
+use crate::geo::{database_state, lookup_resolved, GeoResolver, resolve_host_with_name, DatabaseState, ResolvedHost};
+use crate::metrics::{timing, Metrics};
+use crate::metrics::stats::{parse_window, TrafficStats};
+use crate::models::{IpGeoError, IpInfo, Lang, LookupOptions, NameLocales, ParamError};
+use crate::utils::{is_private_ip, looks_like_file, mask_ip, parse_ip_lenient, sanitize_echo};
+use super::access_log::{access_log, REQUEST_ID_HEADER};
+use super::acl::client_acl;
+use super::admin::{admin_router, require_admin, require_admin_for_compare};
+use super::errors::{allow_options, catch_panic, json_errors, method_not_allowed, negotiate_lang, not_found};
+use super::format::negotiate_format;
+use super::openapi::{openapi_json, CommonParams, HostQuery, StatsQuery};
+use super::routes::{is_reserved, reserve, trim_trailing_slash, ReservedRoute};
+use super::state::{track_in_flight, AppState};
+use super::version::{deprecate_unversioned, ApiVersion};
+use tower_http::compression::{
+    predicate::{NotForContentType, Predicate, SizeAbove},
+    CompressionLayer,
+};
+use tower::ServiceBuilder;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tracing::{debug, warn};
 use once_cell::sync::Lazy;
 
 // 使用静态HeaderName避免重复解析
@@ -25,59 +56,119 @@ static CDN_HEADERS: Lazy<[(HeaderName, &'static str); 8]> = Lazy::new(|| [
     (HeaderName::from_static("x-forwarded-for"), "General"),
 ]);
 
+static NDJSON: &str = "application/x-ndjson";
+
+/// /events/self 心跳注释的间隔，避免代理关闭空闲连接
+const EVENTS_HEARTBEAT: Duration = Duration::from_secs(15);
+
+/// 流式批量查询中一行的最大字节数
+const STREAM_LINE_MAX_BYTES: usize = 4096;
+
 static FORWARDED_HEADER: Lazy<HeaderName> = Lazy::new(|| HeaderName::from_static("forwarded"));
 
 #[inline]
 pub fn get_real_ip(headers: &HeaderMap, socket_addr: SocketAddr) -> IpAddr {
-    let socket_ip = socket_addr.ip();
+    let start = Instant::now();
+    let (ip, source) = get_real_ip_with_source(headers, socket_addr);
+    timing::record_stage("ip_extraction", start.elapsed());
+    Metrics::global().record_client_ip_source(source);
+    ip
+}
+
+// 按优先级排列的可识别头部：先CDN专用头，再 X-Real-IP、X-Forwarded-For，最后标准 Forwarded
+fn recognized_headers() -> impl Iterator<Item = (&'static HeaderName, &'static str)> {
+    CDN_HEADERS.iter()
+        .map(|(header, provider)| (header, *provider))
+        .chain(std::iter::once((&*FORWARDED_HEADER, "Standard")))
+}
+
+// 从单个头部的值中提取IP
+fn extract_header_ip(header: &HeaderName, value: &str) -> Option<IpAddr> {
+    if header == CDN_HEADERS[7].0 {
+        value.split(',').next().and_then(|s| s.trim().parse().ok())
+    } else if header == *FORWARDED_HEADER {
+        parse_forwarded_header(value)
+    } else {
+        value.trim().parse().ok()
+    }
+}
 
-    // 1. 优先检查cdn的头部
-    for (header, provider) in CDN_HEADERS.iter().take(6) {
+/// 返回客户端真实IP以及它来自哪个头部（未命中任何头部时为 "socket"）
+pub fn get_real_ip_with_source(headers: &HeaderMap, socket_addr: SocketAddr) -> (IpAddr, &'static str) {
+    for (header, provider) in recognized_headers() {
         if let Some(ip) = headers.get(header)
             .and_then(|v| v.to_str().ok())
-            .and_then(|s| s.trim().parse::<IpAddr>().ok())
-            .filter(|ip| !is_private_ip(*ip))
-        {
-            debug!("使用 {}({}) 中的IP: {}", header.as_str(), provider, ip);
-            return ip;
-        }
-    }
-    // 2. 优先检查通用的 X-Real-IP
-    if let Some(real_ip) = headers.get(&CDN_HEADERS[6].0)
-        .and_then(|v| v.to_str().ok())
-        .and_then(|s| s.parse::<IpAddr>().ok())
-        .filter(|ip| !is_private_ip(*ip))
-    {
-        debug!("使用 X-Real-IP 中的IP: {}", real_ip);
-        return real_ip;
-    }
-    
-    // 3. 检查通用的 X-Forwarded-For
-    if let Some(forwarded_for) = headers.get(&CDN_HEADERS[7].0)
-        .and_then(|v| v.to_str().ok())
-    {
-        if let Some(ip) = forwarded_for
-            .split(',')
-            .next()
-            .and_then(|s| s.trim().parse::<IpAddr>().ok())
+            .and_then(|v| extract_header_ip(header, v))
             .filter(|ip| !is_private_ip(*ip))
         {
-            debug!("使用 X-Forwarded-For 中的IP: {}", ip);
-            return ip;
+            debug!("使用 {}({}) 中的IP: {}", header.as_str(), provider, mask_ip(ip));
+            return (ip, header.as_str());
         }
     }
-    
-    // 4. 最后检查标准 Forwarded 头
-    if let Some(forwarded) = headers.get(&*FORWARDED_HEADER)
-        .and_then(|v| v.to_str().ok())
-        .and_then(parse_forwarded_header)
-        .filter(|ip| !is_private_ip(*ip))
-    {
-        debug!("使用 Forwarded 头中的IP: {}", forwarded);
-        return forwarded;
+
+    (socket_addr.ip(), "socket")
+}
+
+/// 单个可识别头部的判定结果
+#[derive(Debug, Serialize)]
+pub struct HeaderCandidate {
+    pub header: &'static str,
+    pub provider: &'static str,
+    pub value: String,
+    pub ip: Option<IpAddr>,
+    /// accepted、not_an_ip、private_ip 或 shadowed（更高优先级的头部已命中）
+    pub verdict: &'static str,
+}
+
+/// get_real_ip 的完整判定过程，用于排查多层代理下取错IP的问题
+#[derive(Debug, Serialize)]
+pub struct IpDecisionTrace {
+    pub peer: SocketAddr,
+    pub candidates: Vec<HeaderCandidate>,
+    pub ip: IpAddr,
+    pub source: &'static str,
+    pub reason: String,
+}
+
+pub fn trace_real_ip(headers: &HeaderMap, socket_addr: SocketAddr) -> IpDecisionTrace {
+    let mut candidates = Vec::new();
+    let mut decision: Option<(IpAddr, &'static str)> = None;
+
+    for (header, provider) in recognized_headers() {
+        let Some(value) = headers.get(header) else {
+            continue;
+        };
+        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+        let ip = extract_header_ip(header, &value);
+        let verdict = match ip {
+            None => "not_an_ip",
+            Some(ip) if is_private_ip(ip) => "private_ip",
+            Some(_) if decision.is_some() => "shadowed",
+            Some(ip) => {
+                decision = Some((ip, header.as_str()));
+                "accepted"
+            }
+        };
+        candidates.push(HeaderCandidate {
+            header: header.as_str(),
+            provider,
+            value,
+            ip,
+            verdict,
+        });
     }
 
-    socket_ip
+    let (ip, source, reason) = match decision {
+        Some((ip, source)) => (ip, source, format!("header {}", source)),
+        None => (socket_addr.ip(), "socket", "socket fallback".to_string()),
+    };
+    IpDecisionTrace {
+        peer: socket_addr,
+        candidates,
+        ip,
+        source,
+        reason,
+    }
 }
 
 // 优化Forwarded头解析
@@ -94,97 +185,730 @@ fn parse_forwarded_header(header_value: &str) -> Option<IpAddr> {
         })
 }
 
-async fn handle_ip_lookup(ip: IpAddr) -> Response {
-    if is_private_ip(ip) {
-        let addr = match ip {
-            IpAddr::V4(ip) => {
-                if ip.octets()[0] == 127 {
-                    "127.0.0.0/8"
-                } else if ip.octets()[0] == 10 {
-                    "10.0.0.0/8"
-                } else if ip.octets()[0] == 172 && (ip.octets()[1] >= 16) {
-                    "172.16.0.0/12"
-                } else if ip.octets()[0] == 192 && ip.octets()[1] == 168 {
-                    "192.168.0.0/16"
-                } else {
-                    "private"
-                }
-            },
-            IpAddr::V6(ip) => {
-                if ip.segments()[0] & 0xffc0 == 0xfe80 {
-                    "fe80::/10"
-                } else if ip.segments()[0] & 0xfe00 == 0xfc00 {
-                    "fc00::/7"
-                } else {
-                    "private"
-                }
+/// 一次查询的目标：解析得到的地址，以及 `me`、`self` 这类指向调用方的别名
+struct Target {
+    resolved: ResolvedHost,
+    /// 提交的原始输入，查询调用方自身且没有输入时为 None
+    query: Option<String>,
+    alias: Option<String>,
+    /// 查询的是调用方自身，不受 PRIVATE_TARGET_POLICY 限制
+    caller: bool,
+}
+
+impl Target {
+    fn caller(ip: IpAddr) -> Self {
+        Self { resolved: ResolvedHost::from_ip(ip), query: None, alias: None, caller: true }
+    }
+}
+
+impl From<ResolvedHost> for Target {
+    fn from(resolved: ResolvedHost) -> Self {
+        Self { resolved, query: None, alias: None, caller: false }
+    }
+}
+
+// `me` 和 `self`（不区分大小写）表示查询调用方自身，返回实际使用的写法
+fn self_alias(host: &str) -> Option<&str> {
+    let host = host.trim();
+    (host.eq_ignore_ascii_case("me") || host.eq_ignore_ascii_case("self")).then_some(host)
+}
+
+// 别名按调用方查询，和 `/` 一样不拒绝私有地址；其余输入正常解析
+async fn resolve_target(host: &str, caller: IpAddr) -> Result<Target, IpGeoError> {
+    let target = match self_alias(host) {
+        Some(alias) => Target {
+            alias: Some(alias.to_string()),
+            ..Target::caller(caller)
+        },
+        None => resolve_host_with_name(host).await.map(Target::from)?,
+    };
+    Ok(Target { query: Some(host.to_string()), ..target })
+}
+
+// 查询单个目标并按接口版本输出，私有地址按 PRIVATE_TARGET_POLICY 处理
+async fn lookup_json(target: Target, options: LookupOptions, version: ApiVersion) -> Result<serde_json::Value, IpGeoError> {
+    let ip = target.resolved.ip;
+    let policy = match (target.caller, is_private_ip(ip)) {
+        (false, true) => Config::global().private_target_policy,
+        _ => PrivateTargetPolicy::Allow,
+    };
+    if policy == PrivateTargetPolicy::Reject {
+        return Err(IpGeoError::PrivateTarget(ip.to_string()));
+    }
+    let mut info = lookup_resolved(target.resolved, options).await?;
+    if policy == PrivateTargetPolicy::Redact {
+        return Ok(serde_json::json!({ "ip": info.ip, "type": info.r#type.unwrap_or_else(|| "私有地址".to_string()) }));
+    }
+    record_lookup_breakdown(&info);
+    if target.alias.is_some() {
+        info.host = target.alias;
+    }
+    if options.echo() {
+        info.query = target.query;
+    }
+    let start = Instant::now();
+    let json = version.shape(info, options.asn_format).map_err(|e| IpGeoError::IoError(e.into()));
+    timing::record_stage("serialization", start.elapsed());
+    json
+}
+
+// 按国家和网络类型统计查询
+fn record_lookup_breakdown(info: &IpInfo) {
+    let metrics = Metrics::global();
+    if Config::global().metrics_country_breakdown {
+        metrics.record_country(info.country.as_ref().map(|country| country.code.as_str()));
+    }
+    metrics.record_network_type(info.r#type.as_deref());
+}
+
+async fn handle_ip_lookup(target: Target, options: LookupOptions, version: ApiVersion) -> Response {
+    match lookup_json(target, options, version).await {
+        Ok(mut json) => {
+            if let Some(stats) = TrafficStats::global() {
+                stats.record(json["country"]["code"].as_str(), json["type"].as_str());
             }
-        };
-        
-        let json = serde_json::json!({
-            "ip": ip.to_string(),
-            "addr": addr
-        });
-        
-        return (
-            [(axum::http::header::CONTENT_TYPE, "application/json; charset=utf-8")],
-            Json(json)
-        ).into_response();
+            // 序列化本身的耗时不在其中，只出现在慢请求日志里；v1 的结构冻结，不输出
+            let breakdown = timing::exposed_breakdown().filter(|_| version != ApiVersion::V1);
+            if let (Some(breakdown), Some(object)) = (breakdown, json.as_object_mut()) {
+                object.insert("timing".to_string(), serde_json::json!(breakdown));
+            }
+            let start = Instant::now();
+            let response = (
+                [(header::CONTENT_TYPE, "application/json; charset=utf-8")],
+                Json(json)
+            ).into_response();
+            timing::record_stage("serialization", start.elapsed());
+            response
+        }
+        Err(e) => e.into_response(),
     }
-    
-    let ip_str = ip.to_string();
-    
-    match get_ip_info(&ip_str).await {
-        Ok(info) => (
-            [(axum::http::header::CONTENT_TYPE, "application/json; charset=utf-8")],
-            Json(info)
+}
+
+// detail 等查询选项，取值不合法时返回参数错误；没有 `lang` 参数时按 Accept-Language 选择名称语言
+fn lookup_options(query: Result<Query<LookupOptions>, QueryRejection>, headers: &HeaderMap) -> Result<LookupOptions, IpGeoError> {
+    query
+        .map(|Query(options)| LookupOptions {
+            locales: options.locales.or_else(|| headers
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(NameLocales::from_accept_language)),
+            ..options
+        })
+        .map_err(|_| IpGeoError::InvalidParameter(ParamError::LookupOptions))
+}
+
+async fn lookup_host(host: &str, caller: IpAddr, options: LookupOptions, version: ApiVersion) -> Result<serde_json::Value, IpGeoError> {
+    let target = resolve_target(host, caller).await?;
+    lookup_json(target, options, version).await
+}
+
+// 批量查询中的单个主机，失败时返回带输入的错误信封
+async fn batch_item(host: String, caller: IpAddr, options: LookupOptions, version: ApiVersion) -> serde_json::Value {
+    lookup_host(&host, caller, options, version).await.unwrap_or_else(|e| {
+        let mut json = e.to_json();
+        json["query"] = host.into();
+        json
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/batch",
+    tag = "lookup",
+    params(CommonParams),
+    request_body(content = Vec<String>, description = "主机名数组，条数不超过 BATCH_MAX_SIZE"),
+    responses(
+        (status = 200, description = "与输入顺序一致的结果数组，失败项为带 query 的错误信封", body = Vec<BatchItem>),
+        (status = 400, description = "请求体不是字符串数组或条数超限", body = ErrorBody),
+        (status = 503, description = "服务过载", body = ErrorBody),
+        (status = 504, description = "请求处理超时", body = ErrorBody),
+    ),
+)]
+pub async fn batch(
+    Extension(version): Extension<ApiVersion>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    options: Result<Query<LookupOptions>, QueryRejection>,
+    body: Result<Json<Vec<String>>, JsonRejection>,
+) -> Response {
+    let options = match lookup_options(options, &headers) {
+        Ok(options) => options,
+        Err(e) => return e.into_response(),
+    };
+    let hosts = match body {
+        Ok(Json(hosts)) => hosts,
+        Err(e) if e.status() == StatusCode::PAYLOAD_TOO_LARGE => return IpGeoError::Rejected(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Request body exceeds {} bytes", Config::global().max_body_bytes),
         ).into_response(),
-        Err(e) => e.into_response(),
+        Err(e) => return IpGeoError::InvalidParameter(ParamError::BatchBody(e.body_text())).into_response(),
+    };
+    batch_response(hosts, get_real_ip(&headers, addr), options, version).await
+}
+
+// 并发查询多个主机，按输入顺序返回结果数组，条数受 batch_max_size 限制
+async fn batch_response(hosts: Vec<String>, caller: IpAddr, options: LookupOptions, version: ApiVersion) -> Response {
+    let config = Config::global();
+    // 结果可能被异步处理，默认带上输入和计算时间
+    let options = options.with_echo_default(version.echoes_batches());
+    if hosts.len() > config.batch_max_size {
+        return IpGeoError::InvalidParameter(ParamError::BatchTooLarge(config.batch_max_size)).into_response();
     }
+
+    let mut results: Vec<(usize, serde_json::Value)> = stream::iter(hosts.into_iter().enumerate())
+        .map(|(index, host)| async move { (index, batch_item(host, caller, options, version).await) })
+        .buffer_unordered(config.batch_parallelism)
+        .collect()
+        .await;
+    results.sort_unstable_by_key(|(index, _)| *index);
+    let results: Vec<serde_json::Value> = results.into_iter().map(|(_, value)| value).collect();
+
+    (
+        [(header::CONTENT_TYPE, "application/json; charset=utf-8")],
+        Json(results)
+    ).into_response()
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/batch/stream",
+    tag = "lookup",
+    params(CommonParams),
+    request_body(content = String, content_type = "text/plain", description = "每行一个主机名，空行跳过，行数不超过 STREAM_MAX_ROWS"),
+    responses(
+        (status = 200, description = "每行一个 JSON 对象，与输入顺序一致；失败项为带 query 和 line 的错误信封", body = BatchItem, content_type = "application/x-ndjson"),
+        (status = 400, description = "查询参数不合法", body = ErrorBody),
+        (status = 401, description = "缺少或错误的 ADMIN_TOKEN", body = ErrorBody),
+    ),
+)]
+pub async fn batch_stream(
+    Extension(version): Extension<ApiVersion>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    options: Result<Query<LookupOptions>, QueryRejection>,
+    body: Body,
+) -> Response {
+    let options = match lookup_options(options, &headers) {
+        Ok(options) => options,
+        Err(e) => return e.into_response(),
+    };
+    let caller = get_real_ip(&headers, addr);
+    let options = options.with_echo_default(version.echoes_batches());
+    // 响应体在处理函数返回之后才被读取，语言要在这里确定
+    let lang = Lang::current();
+    let config = Config::global();
+    let max_rows = config.stream_max_rows;
+
+    let reader = StreamReader::new(body.into_data_stream().map(|chunk| chunk.map_err(std::io::Error::other)));
+    // 读取出错（行过长、非 UTF-8、连接中断）后 FramedRead 不再产出，错误行就是最后一行
+    let lines = FramedRead::new(reader, LinesCodec::new_with_max_length(STREAM_LINE_MAX_BYTES))
+        .enumerate()
+        .filter(|(_, line)| future::ready(!matches!(line, Ok(line) if line.trim().is_empty())))
+        .take(max_rows.saturating_add(1))
+        .enumerate();
+
+    // buffered 只在有空位时拉取下一行；客户端读得慢时响应体不被轮询，输入也就停止读取
+    let output = lines
+        .map(move |(row, (index, line))| async move {
+            let (query, result) = match line {
+                Ok(host) if row == max_rows => (
+                    host.trim().to_string(),
+                    Err(IpGeoError::InvalidParameter(ParamError::TooManyRows(max_rows))),
+                ),
+                Ok(host) => {
+                    let host = host.trim().to_string();
+                    let result = lookup_host(&host, caller, options, version).await;
+                    (host, result)
+                }
+                Err(e) => (String::new(), Err(IpGeoError::InvalidParameter(ParamError::UnreadableBody(e.to_string())))),
+            };
+            let json = result.unwrap_or_else(|e| {
+                let mut json = e.to_json_in(lang);
+                json["query"] = query.into();
+                json["line"] = (index + 1).into();
+                json
+            });
+            let mut line = serde_json::to_vec(&json).unwrap_or_default();
+            line.push(b'\n');
+            Ok::<_, Infallible>(Bytes::from(line))
+        })
+        .buffered(config.batch_parallelism);
+
+    (
+        [(header::CONTENT_TYPE, NDJSON)],
+        Body::from_stream(output),
+    ).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/events/self",
+    tag = "lookup",
+    params(CommonParams),
+    responses(
+        (status = 200, description = "连接建立时和之后每隔 EVENTS_INTERVAL_SECS 推送一次 `ip` 事件，data 为 `{\"changed\": bool, \"info\": IpInfo}`；每 15 秒一次心跳注释", content_type = "text/event-stream"),
+        (status = 400, description = "查询参数不合法", body = ErrorBody),
+    ),
+)]
+pub async fn events_self(
+    Extension(version): Extension<ApiVersion>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    options: Result<Query<LookupOptions>, QueryRejection>,
+) -> Response {
+    let options = match lookup_options(options, &headers) {
+        Ok(options) => options,
+        Err(e) => return e.into_response(),
+    };
+    // 同一连接的请求头不会变，调用方地址只取一次；之后按间隔重新查询，数据库更新或覆盖规则变化时 changed 为 true。
+    // 计算时间每次都不同，不输出，否则 changed 总为 true
+    let caller = get_real_ip(&headers, addr);
+    let options = LookupOptions { echo: Some(false), ..options };
+    let lang = Lang::current();
+    let mut ticks = tokio::time::interval(Config::global().events_interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    // 不另起任务：客户端断开后响应体被丢弃，定时器和查询随之结束
+    let events = stream::unfold((ticks, None), move |(mut ticks, previous)| async move {
+        ticks.tick().await;
+        let (event, current) = match lookup_json(Target::caller(caller), options, version).await {
+            Ok(info) => {
+                let changed = previous.as_ref().is_some_and(|previous| previous != &info);
+                let event = Event::default().event("ip").json_data(serde_json::json!({ "changed": changed, "info": info }));
+                (event, Some(info))
+            }
+            Err(e) => (Event::default().event("error").json_data(e.to_json_in(lang)), previous),
+        };
+        Some((event, (ticks, current)))
+    });
+
+    Sse::new(events)
+        .keep_alive(KeepAlive::new().interval(EVENTS_HEARTBEAT))
+        .into_response()
+}
+
+// 收集 `host` 或其别名 `ip` 的全部取值，支持重复参数和逗号分隔的列表。
+// 取值去掉首尾空白，空值视为未指定；两者同时出现且取值不同时无法判断以哪个为准
+fn query_hosts(params: &[(String, String)]) -> Result<Vec<String>, IpGeoError> {
+    let values = |name: &str| -> Vec<String> {
+        params.iter()
+            .filter(|(key, _)| key == name)
+            .flat_map(|(_, value)| value.split(','))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    let hosts = values("host");
+    let ips = values("ip");
+    match (hosts.is_empty(), ips.is_empty()) {
+        (_, true) => Ok(hosts),
+        (true, false) => Ok(ips),
+        (false, false) if hosts == ips => Ok(hosts),
+        (false, false) => Err(IpGeoError::InvalidParameter(ParamError::HostConflict)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "lookup",
+    params(CommonParams),
+    responses(
+        (status = 200, description = "查询结果", body = IpInfo, content_type = ["application/json", "application/msgpack"]),
+        (status = 400, description = "IP、域名或参数无效", body = ErrorBody),
+        (status = 503, description = "服务过载或数据库未加载", body = ErrorBody),
+        (status = 504, description = "域名解析或请求处理超时", body = ErrorBody),
+    ),
+)]
 pub async fn root(
+    Extension(version): Extension<ApiVersion>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
+    options: Result<Query<LookupOptions>, QueryRejection>,
 ) -> Response {
+    let options = match lookup_options(options, &headers) {
+        Ok(options) => options,
+        Err(e) => return e.into_response(),
+    };
     let ip = get_real_ip(&headers, addr);
-    handle_ip_lookup(ip).await
+    handle_ip_lookup(Target::caller(ip), options, version).await
 }
 
+#[utoipa::path(
+    get,
+    path = "/api",
+    tag = "lookup",
+    params(HostQuery, CommonParams),
+    responses(
+        (status = 200, description = "查询结果；指定多个主机时为与 /api/batch 相同的数组", body = IpInfo, content_type = ["application/json", "application/msgpack"]),
+        (status = 400, description = "IP、域名或参数无效，或 ip 与 host 取值冲突", body = ErrorBody),
+        (status = 503, description = "服务过载或数据库未加载", body = ErrorBody),
+        (status = 504, description = "域名解析或请求处理超时", body = ErrorBody),
+    ),
+)]
 pub async fn api(
-    Query(params): Query<HashMap<String, String>>,
+    Extension(version): Extension<ApiVersion>,
+    Query(params): Query<Vec<(String, String)>>,
     headers: HeaderMap,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    options: Result<Query<LookupOptions>, QueryRejection>,
 ) -> Response {
-    let ip = if let Some(host) = params.get("host") {
-        match resolve_host(host).await {
-            Ok(ip) => ip,
+    let options = match lookup_options(options, &headers) {
+        Ok(options) => options,
+        Err(e) => return e.into_response(),
+    };
+    let mut hosts = match query_hosts(&params) {
+        Ok(hosts) => hosts,
+        Err(e) => return e.into_response(),
+    };
+    let caller = get_real_ip(&headers, addr);
+    if hosts.len() > 1 {
+        return batch_response(hosts, caller, options, version).await;
+    }
+
+    let target = if let Some(host) = hosts.pop() {
+        match resolve_target(&host, caller).await {
+            Ok(target) => target,
             Err(e) => return e.into_response(),
         }
     } else {
-        get_real_ip(&headers, addr)
+        Target::caller(caller)
     };
     
-    handle_ip_lookup(ip).await
+    handle_ip_lookup(target, options, version).await
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/{host}",
+    tag = "lookup",
+    params(("host" = String, Path, description = "IP、域名或 URL，`me` 或 `self` 表示客户端自身"), CommonParams),
+    responses(
+        (status = 200, description = "查询结果", body = IpInfo, content_type = ["application/json", "application/msgpack"]),
+        (status = 400, description = "IP、域名或参数无效", body = ErrorBody),
+        (status = 404, description = "静态文件等不做解析的路径", body = ErrorBody),
+        (status = 503, description = "服务过载或数据库未加载", body = ErrorBody),
+        (status = 504, description = "域名解析或请求处理超时", body = ErrorBody),
+    ),
+)]
 pub async fn path_api(
+    Extension(version): Extension<ApiVersion>,
     Path(host): Path<String>,
-    _headers: HeaderMap,
-    _addr: ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    options: Result<Query<LookupOptions>, QueryRejection>,
 ) -> Response {
-    let ip = match resolve_host(&host).await {
-        Ok(ip) => ip,
+    // 客户端有时会把首尾空格编码进路径，全是空白时与不带主机名相同
+    let host = host.trim();
+    // 浏览器和爬虫请求的静态文件不当作域名解析
+    if looks_like_file(host) {
+        return IpGeoError::NotFound(sanitize_echo(host)).into_response();
+    }
+    let options = match lookup_options(options, &headers) {
+        Ok(options) => options,
         Err(e) => return e.into_response(),
     };
+
+    let caller = get_real_ip(&headers, addr);
+    let target = if host.is_empty() {
+        Target::caller(caller)
+    } else {
+        match resolve_target(host, caller).await {
+            Ok(target) => target,
+            Err(e) => return e.into_response(),
+        }
+    };
     
-    handle_ip_lookup(ip).await
+    handle_ip_lookup(target, options, version).await
 }
 
-pub fn create_router() -> Router {
-    Router::new()
+// `/{host}` 的首段可能是打错的接口路径：已注册或预留的首段，以及不允许单标签主机名时
+// 不含点的名字都直接返回 404，不做解析
+fn shadows_route(host: &str) -> bool {
+    is_reserved(host)
+        || (!Config::global().allow_single_label_hosts
+            && !host.contains(['.', ':'])
+            && self_alias(host).is_none()
+            && parse_ip_lenient(host).is_err())
+}
+
+/// 顶层的 `/{host}`，与 `/api/{host}` 相同，但不会遮住其他路由
+pub async fn host_path(
+    version: Extension<ApiVersion>,
+    Path(host): Path<String>,
+    headers: HeaderMap,
+    addr: ConnectInfo<SocketAddr>,
+    options: Result<Query<LookupOptions>, QueryRejection>,
+) -> Response {
+    let trimmed = host.trim();
+    if !trimmed.is_empty() && shadows_route(trimmed) {
+        return IpGeoError::NotFound(sanitize_echo(trimmed)).into_response();
+    }
+    path_api(version, Path(host), headers, addr, options).await
+}
+
+// 没有图标，返回空响应避免被当作域名查询
+pub async fn favicon() -> Response {
+    (
+        StatusCode::NO_CONTENT,
+        [(header::CACHE_CONTROL, "public, max-age=86400")],
+    ).into_response()
+}
+
+pub async fn robots() -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+            (header::CACHE_CONTROL, "public, max-age=86400"),
+        ],
+        "User-agent: *\nDisallow: /\n",
+    ).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "ops",
+    responses(
+        (status = 200, description = "可以接收流量；status 为 ready 或 degraded（部分数据库缺失），auto_update 为 false 时数据库由外部维护，profile 为 full 或 lite，data_dir 为实际使用的数据目录；曾恢复过中毒的锁时 lock_recoveries 按数据列出次数", body = Object),
+        (status = 503, description = "数据库仍在首次下载，status 为 initializing", body = Object),
+    ),
+)]
+pub async fn healthz() -> Response {
+    let state = database_state();
+    let status = match state {
+        DatabaseState::Initializing => StatusCode::SERVICE_UNAVAILABLE,
+        DatabaseState::Ready | DatabaseState::Degraded => StatusCode::OK,
+    };
+    let mut body = serde_json::json!({
+        "status": state,
+        // false 表示数据库由外部维护，服务不会自行更新
+        "auto_update": Config::global().db_auto_update,
+        // lite 时使用 GeoLite2-Country，没有省市和坐标
+        "profile": Config::global().db_profile,
+        // 由 --data-dir、MMDB_PATH、IPGEO_DATA_DIR 依次决定的数据目录
+        "data_dir": Config::global().data_dir,
+    });
+    // 只在发生过时输出：某个数据的锁曾因 panic 中毒，已恢复但说明进程中出现过错误
+    let recoveries = Metrics::global().lock_recoveries();
+    if !recoveries.is_empty() {
+        body["lock_recoveries"] = serde_json::json!(recoveries);
+    }
+    (
+        status,
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(body),
+    ).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "ops",
+    responses((status = 200, description = "Prometheus 文本格式的运行指标", body = String, content_type = "text/plain")),
+)]
+pub async fn metrics() -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        Metrics::global().render(),
+    ).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats",
+    tag = "ops",
+    params(StatsQuery),
+    responses(
+        (status = 200, description = "最近一段时间查询的国家和网络类型分布，不包含IP", body = StatsSummary),
+        (status = 400, description = "window 或 top 无效", body = ErrorBody),
+    ),
+)]
+pub async fn stats(query: Result<Query<StatsQuery>, QueryRejection>) -> Result<Response, IpGeoError> {
+    let invalid = || IpGeoError::InvalidParameter(ParamError::StatsWindow);
+    let Query(query) = query.map_err(|_| invalid())?;
+    // 只在启用时注册路由
+    let Some(stats) = TrafficStats::global() else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let window = match query.window.as_deref() {
+        Some(window) => parse_window(window).ok_or_else(invalid)?,
+        None => stats.window_minutes(),
+    };
+    Ok((
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(stats.summary(window, query.top.unwrap_or(10))),
+    ).into_response())
+}
+
+// 过载和超时也使用统一的JSON错误格式
+async fn handle_middleware_error(err: BoxError) -> Response {
+    let metrics = Metrics::global();
+    if err.is::<tower::load_shed::error::Overloaded>() {
+        Metrics::incr(&metrics.requests_rejected);
+        IpGeoError::Overloaded.into_response()
+    } else if err.is::<tower::timeout::error::Elapsed>() {
+        Metrics::incr(&metrics.requests_timed_out);
+        IpGeoError::RequestTimeout.into_response()
+    } else {
+        IpGeoError::IoError(std::io::Error::other(err.to_string())).into_response()
+    }
+}
+
+// gzip/deflate/br 压缩，跳过小响应
+fn compression_layer(min_size: u16) -> CompressionLayer<impl Predicate> {
+    let predicate = SizeAbove::new(min_size)
+        .and(NotForContentType::GRPC)
+        .and(NotForContentType::IMAGES)
+        .and(NotForContentType::SSE)
+        // 流式输出逐行发送，压缩会把多行攒在一起
+        .and(NotForContentType::const_new(NDJSON));
+    CompressionLayer::new()
+        .gzip(true)
+        .deflate(true)
+        .br(true)
+        .compress_when(predicate)
+}
+
+fn cors_layer(config: &Config) -> CorsLayer {
+    let origin = if config.cors_allow_origins.iter().any(|o| o == "*") {
+        AllowOrigin::from(Any)
+    } else {
+        AllowOrigin::list(config.cors_allow_origins.iter().filter_map(|o| {
+            HeaderValue::from_str(o)
+                .map_err(|_| warn!("Ignoring invalid CORS origin: {}", o))
+                .ok()
+        }))
+    };
+
+    CorsLayer::new()
+        .allow_origin(origin)
+        .allow_methods([Method::GET, Method::POST])
+        .allow_headers([header::ACCEPT, header::CONTENT_TYPE, REQUEST_ID_HEADER.clone()])
+        .expose_headers([REQUEST_ID_HEADER.clone()])
+        .max_age(config.cors_max_age)
+}
+
+// 查询接口，按挂载的版本输出
+fn lookup_routes(version: ApiVersion) -> Router<AppState> {
+    let mut router = Router::new()
         .route("/", get(root))
-        .route("/api", get(api))
-        .route("/api/{host}", get(path_api))
-        .route("/{host}", get(path_api))
+        .reserved_route("/api", get(api))
+        .reserved_route("/api/batch", post(batch).layer(DefaultBodyLimit::max(Config::global().max_body_bytes)))
+        .reserved_route("/api/{host}", get(path_api))
+        .reserved_route("/events/self", get(events_self))
+        .route("/{host}", get(host_path));
+    // 开销大，只对持有管理凭据的调用方开放
+    if Config::global().admin_enabled() {
+        router = router.reserved_route("/api/batch/stream", post(batch_stream).route_layer(middleware::from_fn(require_admin)));
+    }
+    router
+        .route_layer(middleware::from_fn(require_admin_for_compare))
+        .layer(Extension(version))
+}
+
+// 健康检查和指标，配置了 admin_bind 时只在管理端口提供
+fn ops_routes() -> Router<AppState> {
+    let mut router = Router::new()
+        .reserved_route("/metrics", get(metrics))
+        .reserved_route("/healthz", get(healthz));
+    if TrafficStats::global().is_some() {
+        router = router.reserved_route("/stats", get(stats));
+    }
+    router
+}
+
+pub fn create_router(state: AppState) -> Router {
+    let config = Config::global();
+    let mut router = Router::new()
+        .reserved_route("/favicon.ico", get(favicon))
+        .reserved_route("/apple-touch-icon.png", get(favicon))
+        .reserved_route("/apple-touch-icon-precomposed.png", get(favicon))
+        .reserved_route("/robots.txt", get(robots))
+        .reserved_route("/openapi.json", get(openapi_json));
+    for version in ApiVersion::ALL {
+        reserve(version.prefix());
+        router = router.nest(version.prefix(), lookup_routes(version));
+    }
+    // 不带版本的路径是最新版本的别名
+    router = router.merge(lookup_routes(ApiVersion::LATEST).layer(middleware::from_fn(deprecate_unversioned)));
+    if config.admin_bind.is_none() {
+        router = router.merge(ops_routes());
+    }
+    // 只作用于以上路由，管理接口有自己的列表
+    router = router.route_layer(middleware::from_fn(client_acl));
+
+    if config.admin_enabled() && config.admin_bind.is_none() {
+        router = router.merge(admin_router());
+    }
+    #[cfg(feature = "swagger-ui")]
+    {
+        router = router.merge(super::openapi::swagger_router());
+    }
+    with_common_layers(router, state)
+}
+
+/// 供其他 axum 应用通过 Router::nest 挂载的完整路由，不绑定端口、不启动后台任务。
+/// 宿主应用没有提供 ConnectInfo 时只按代理头部识别客户端
+pub fn router_with_state(resolver: Arc<GeoResolver>) -> Router {
+    create_router(AppState::with_shutdown(resolver.shutdown_token()))
+        .layer(middleware::map_request(default_connect_info))
+}
+
+// 没有对端地址时使用未指定地址，get_real_ip 只能依靠请求头
+async fn default_connect_info(mut request: axum::extract::Request) -> axum::extract::Request {
+    if request.extensions().get::<ConnectInfo<SocketAddr>>().is_none() {
+        request.extensions_mut().insert(ConnectInfo(SocketAddr::from(([0, 0, 0, 0], 0))));
+    }
+    request
+}
+
+/// admin_bind 端口上的路由：只有 /healthz、/metrics 和管理接口
+pub fn create_admin_router(state: AppState) -> Router {
+    let mut router = ops_routes();
+    if Config::global().admin_enabled() {
+        router = router.merge(admin_router());
+    }
+    with_common_layers(router, state)
+}
+
+// 错误格式、过载保护、压缩、CORS 和访问日志，两个端口共用
+fn with_common_layers(router: Router<AppState>, state: AppState) -> Router {
+    let config = Config::global();
+    let mut router = router
+        .fallback(not_found)
+        .method_not_allowed_fallback(method_not_allowed)
+        // 在语言和请求ID的作用域内，500 的错误信封与其他错误一致
+        .layer(middleware::from_fn(catch_panic))
+        .layer(middleware::from_fn(json_errors))
+        .layer(
+            ServiceBuilder::new()
+                .layer(HandleErrorLayer::new(handle_middleware_error))
+                .load_shed()
+                .concurrency_limit(config.max_in_flight)
+                .timeout(config.request_timeout)
+        )
+        .layer(middleware::from_fn(negotiate_lang))
+        // 在压缩之前转换格式，超时和过载的错误响应也会被转换
+        .layer(middleware::from_fn(negotiate_format));
+
+    if config.compression {
+        router = router.layer(compression_layer(config.compression_min_size));
+    }
+    if !config.cors_allow_origins.is_empty() {
+        router = router.layer(cors_layer(config));
+    }
+
+    let router = router
+        .layer(middleware::from_fn_with_state(state.clone(), track_in_flight))
+        .layer(middleware::from_fn(access_log))
+        .with_state(state);
+    // Router::layer 在路由匹配之后才执行，末尾斜杠要在外层路由中先去掉，
+    // 这样 `/api/` 和 `/8.8.8.8/` 与不带斜杠的路径完全相同。
+    // axum 在路由的最外层才写入 Allow 头，所以补充 OPTIONS 也放在这里
+    Router::new()
+        .fallback_service(router)
+        .layer(middleware::map_request(trim_trailing_slash))
+        .layer(middleware::map_response(allow_options))
 } 
\ No newline at end of file
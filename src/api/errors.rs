@@ -0,0 +1,120 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use axum::{
+    extract::{Query, Request},
+    http::{header, HeaderValue, Method, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::FutureExt;
+use tracing::error;
+use crate::models::{IpGeoError, Lang, RequestId};
+use crate::utils::sanitize_echo;
+
+// 框架层错误说明通常很短，超出时只取状态码的标准说明
+const REJECTION_BODY_LIMIT: usize = 4096;
+
+/// 未匹配任何路由
+pub async fn not_found(uri: Uri) -> Response {
+    IpGeoError::NotFound(sanitize_echo(uri.path())).into_response()
+}
+
+/// 路由存在但请求方法不支持；OPTIONS 返回空的 204，允许的方法由 axum 写入 Allow 头
+pub async fn method_not_allowed(method: Method) -> Response {
+    if method == Method::OPTIONS {
+        return StatusCode::NO_CONTENT.into_response();
+    }
+    IpGeoError::MethodNotAllowed.into_response()
+}
+
+/// 每个路由都回答 OPTIONS，在 axum 生成的 Allow 头中补上
+pub async fn allow_options(mut response: Response) -> Response {
+    let Some(allow) = response.headers().get(header::ALLOW).and_then(|v| v.to_str().ok()) else {
+        return response;
+    };
+    if allow.split(',').any(|method| method.trim() == "OPTIONS") {
+        return response;
+    }
+    let allow = if allow.is_empty() { "OPTIONS".to_string() } else { format!("{},OPTIONS", allow) };
+    if let Ok(value) = HeaderValue::from_str(&allow) {
+        response.headers_mut().insert(header::ALLOW, value);
+    }
+    response
+}
+
+// panic 的参数通常是 &str 或 String
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload.downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string panic payload")
+}
+
+/// 处理函数 panic 时返回 500 的错误信封，而不是直接断开连接；panic 的内容和请求ID记录在错误日志中
+pub async fn catch_panic(request: Request, next: Next) -> Response {
+    match AssertUnwindSafe(next.run(request)).catch_unwind().await {
+        Ok(response) => response,
+        Err(payload) => {
+            error!(
+                request_id = RequestId::current().unwrap_or_default(),
+                panic = panic_message(payload.as_ref()),
+                "handler panicked"
+            );
+            IpGeoError::InternalPanic.into_response()
+        }
+    }
+}
+
+fn is_json(response: &Response) -> bool {
+    response.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"))
+}
+
+/// 把提取器拒绝等非JSON的错误响应改写为统一的 `{code, error, message}` 信封
+pub async fn json_errors(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error()) || is_json(&response) {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let message = axum::body::to_bytes(body, REJECTION_BODY_LIMIT)
+        .await
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes.to_vec()).ok())
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+        .unwrap_or_else(|| status.canonical_reason().unwrap_or("Error").to_string());
+
+    let mut rewritten = IpGeoError::Rejected(status, message).into_response();
+    // 保留 Allow、Retry-After 等原有响应头
+    for (name, value) in parts.headers.iter() {
+        if name != header::CONTENT_TYPE && name != header::CONTENT_LENGTH {
+            rewritten.headers_mut().append(name.clone(), value.clone());
+        }
+    }
+    rewritten
+}
+
+// `lang` 查询参数优先，其次是 Accept-Language，都没有时使用中文
+fn request_lang(request: &Request) -> Lang {
+    let from_query = Query::<HashMap<String, String>>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|Query(params)| params.get("lang").and_then(|v| Lang::from_tag(v)));
+    from_query
+        .or_else(|| request.headers()
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Lang::from_accept_language))
+        .unwrap_or_default()
+}
+
+/// 协商错误说明的语言，作用于整个请求
+pub async fn negotiate_lang(request: Request, next: Next) -> Response {
+    let lang = request_lang(&request);
+    lang.scope(next.run(request)).await
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use axum::{
+    body::Body,
+    extract::{Query, Request},
+    http::{header, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use crate::models::IpGeoError;
+use crate::utils::is_truthy;
+
+pub const MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+
+/// 响应体的编码方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Json,
+    PrettyJson,
+    MsgPack,
+}
+
+/// Accept 头中是否列出了指定的媒体类型（忽略参数和 q 值）
+pub fn accepts(headers: &HeaderMap, media_type: &str) -> bool {
+    headers.get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|range| range.split(';').next())
+        .any(|range| range.trim().eq_ignore_ascii_case(media_type))
+}
+
+// 查询参数优先：`format=msgpack|json`、`pretty=1`，其次是 Accept 头
+fn negotiate(request: &Request) -> Format {
+    let params = Query::<HashMap<String, String>>::try_from_uri(request.uri())
+        .map(|Query(params)| params)
+        .unwrap_or_default();
+    let headers = request.headers();
+
+    match params.get("format").map(|v| v.trim().to_ascii_lowercase()).as_deref() {
+        Some("msgpack") => return Format::MsgPack,
+        Some("json") => {}
+        _ if accepts(headers, MSGPACK_CONTENT_TYPE) || accepts(headers, "application/x-msgpack") => {
+            return Format::MsgPack;
+        }
+        _ => {}
+    }
+
+    let pretty = params.get("pretty")
+        .map(|v| is_truthy(v))
+        .unwrap_or_else(|| accepts(headers, "application/json+pretty"));
+    if pretty { Format::PrettyJson } else { Format::Json }
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers.get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"))
+}
+
+fn encode(value: &serde_json::Value, format: Format) -> Result<(Vec<u8>, &'static str), IpGeoError> {
+    let encoded = match format {
+        Format::MsgPack => rmp_serde::to_vec_named(value)
+            .map(|bytes| (bytes, MSGPACK_CONTENT_TYPE))
+            .map_err(std::io::Error::other)?,
+        // 中文保持 UTF-8 原样输出
+        _ => serde_json::to_string_pretty(value)
+            .map(|text| (format!("{}\n", text).into_bytes(), "application/json; charset=utf-8"))
+            .map_err(std::io::Error::other)?,
+    };
+    Ok(encoded)
+}
+
+/// 按请求把JSON响应（包括错误信封）改为缩进JSON或 MessagePack
+pub async fn negotiate_format(request: Request, next: Next) -> Response {
+    let format = negotiate(&request);
+    let response = next.run(request).await;
+    if format == Format::Json || !is_json(response.headers()) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => return IpGeoError::IoError(std::io::Error::other(e)).into_response(),
+    };
+    let Ok(value) = serde_json::from_slice::<serde_json::Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    match encode(&value, format) {
+        Ok((encoded, content_type)) => {
+            parts.headers.remove(header::CONTENT_LENGTH);
+            parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+            Response::from_parts(parts, Body::from(encoded))
+        }
+        Err(e) => e.into_response(),
+    }
+}
@@ -1,2 +1,15 @@
+pub mod access_log;
+pub mod acl;
+pub mod admin;
 pub mod api;
-pub use api::*; 
\ No newline at end of file
+pub mod errors;
+pub mod format;
+pub mod openapi;
+pub mod routes;
+pub mod state;
+pub mod trace_context;
+mod v1;
+pub mod version;
+
+pub use api::*;
+pub use state::*;
@@ -0,0 +1,214 @@
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use utoipa::openapi::{Deprecated, OpenApi as OpenApiDoc};
+use utoipa::{IntoParams, Modify, OpenApi, ToSchema};
+use super::version::ApiVersion;
+use crate::metrics::stats::{CountryCount, StatsSummary, TypeCount};
+use crate::models::{AsnFormat, AsnInfo, CityInfo, ComparedAnswer, ContinentInfo, CountryInfo, Detail, ErrorBody, GeoComparison, IpInfo, Location, NetworkCategory, RegionStyle, RirInfo, Traits};
+
+/// `format` 参数的取值
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseFormat {
+    Json,
+    Msgpack,
+}
+
+/// `/api` 的查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HostQuery {
+    /// IP、域名或 URL，省略或为 `me`、`self` 时查询客户端自身。可重复或用逗号分隔，多个时返回数组
+    pub host: Option<Vec<String>>,
+    /// host 的别名，与 host 同时出现时取值必须相同
+    pub ip: Option<Vec<String>>,
+}
+
+/// 所有查询接口共用的参数
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CommonParams {
+    /// 查询的详细程度，默认 standard
+    #[param(inline)]
+    pub detail: Option<Detail>,
+    /// 国家、城市等名称和错误说明的语言，如 zh、en-US、ja，可用逗号分隔多个，优先于 Accept-Language
+    pub lang: Option<String>,
+    /// 响应编码，优先于 Accept 头
+    #[param(inline)]
+    pub format: Option<ResponseFormat>,
+    /// 输出缩进的JSON，`?pretty` 与 `?pretty=1` 等价
+    pub pretty: Option<bool>,
+    /// 输出 sources 字段，标注各字段组来自哪个数据库及其构建日期
+    pub sources: Option<bool>,
+    /// 坐标保留的小数位数，只能比服务端的 COORD_PRECISION 更粗
+    pub precision: Option<u8>,
+    /// ASN 的写法，默认 number
+    #[param(inline)]
+    pub asn_format: Option<AsnFormat>,
+    /// 输出哪些地区字段，默认 both
+    #[param(inline)]
+    pub regions: Option<RegionStyle>,
+    /// 输出 RIR 分配信息（注册机构、分配日期和网段），detail=full 时总是输出
+    pub rir: Option<bool>,
+    /// 输出 query（原始输入）、timestamp（计算时间）和 cached，/v2 的批量接口默认开启，单个查询默认关闭
+    pub echo: Option<bool>,
+    /// 对有 GeoCN 记录的地址同时输出 GeoLite2-City 的地区和位置，以及省级是否不一致；需要管理凭据
+    pub compare: Option<bool>,
+}
+
+/// `/stats` 的查询参数
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatsQuery {
+    /// 统计窗口，如 30m、1h、2d，默认且最多为 STATS_WINDOW_HOURS
+    pub window: Option<String>,
+    /// 国家和网络类型各返回前几项，默认 10
+    pub top: Option<usize>,
+}
+
+/// 批量查询中失败的单项
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BatchError {
+    pub code: u16,
+    pub error: String,
+    pub message: String,
+    /// 对应的输入，无法读取的行为空字符串
+    pub query: String,
+    /// 流式批量查询中对应的输入行号，从 1 开始
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u64>,
+}
+
+/// 批量查询结果数组中的一项
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum BatchItem {
+    Info(Box<IpInfo>),
+    Error(BatchError),
+}
+
+// 同一个处理函数同时挂在 /api/{host} 和 /{host} 上；查询接口另有 /v1、/v2 前缀的固定版本，
+// 不带前缀的路径标记为 deprecated
+struct Routing;
+
+impl Modify for Routing {
+    fn modify(&self, openapi: &mut OpenApiDoc) {
+        let paths = &mut openapi.paths.paths;
+        if let Some(mut item) = paths.get("/api/{host}").cloned() {
+            if let Some(operation) = item.operations.values_mut().next() {
+                operation.operation_id = Some("path_lookup".to_string());
+            }
+            paths.insert("/{host}".to_string(), item);
+        }
+
+        for path in ["/", "/api", "/api/batch", "/api/batch/stream", "/events/self", "/api/{host}", "/{host}"] {
+            let Some(item) = paths.get_mut(path) else {
+                continue;
+            };
+            let unversioned = item.clone();
+            for operation in item.operations.values_mut() {
+                operation.deprecated = Some(Deprecated::True);
+            }
+            for version in ApiVersion::ALL {
+                let prefix = version.prefix();
+                let mut versioned = unversioned.clone();
+                for operation in versioned.operations.values_mut() {
+                    operation.operation_id = operation.operation_id.as_ref().map(|id| format!("{}_{}", prefix.trim_start_matches('/'), id));
+                }
+                let versioned_path = if path == "/" { prefix.to_string() } else { format!("{}{}", prefix, path) };
+                paths.insert(versioned_path, versioned);
+            }
+        }
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "IP Geo API",
+        description = "IP 与域名的地理位置、ASN 和运营商查询",
+        license(name = "GPL-3.0"),
+    ),
+    paths(
+        super::api::root,
+        super::api::api,
+        super::api::path_api,
+        super::api::batch,
+        super::api::batch_stream,
+        super::api::events_self,
+        super::api::metrics,
+        super::api::healthz,
+        super::api::stats,
+    ),
+    components(schemas(
+        IpInfo, AsnInfo, Location, CountryInfo, CityInfo, ContinentInfo, NetworkCategory, RirInfo, Traits,
+        GeoComparison, ComparedAnswer,
+        Detail, ResponseFormat, ErrorBody, BatchItem, BatchError, StatsSummary, CountryCount, TypeCount,
+    )),
+    modifiers(&Routing),
+    tags((name = "lookup", description = "地理位置查询"), (name = "ops", description = "运维接口")),
+)]
+pub struct ApiDoc;
+
+static SPEC: Lazy<serde_json::Value> = Lazy::new(|| {
+    serde_json::to_value(ApiDoc::openapi()).expect("OpenAPI document serializes")
+});
+
+/// GET /openapi.json
+pub async fn openapi_json() -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "application/json; charset=utf-8"),
+            (header::CACHE_CONTROL, "public, max-age=3600"),
+        ],
+        Json(SPEC.clone()),
+    ).into_response()
+}
+
+#[cfg(feature = "swagger-ui")]
+mod swagger {
+    use std::sync::Arc;
+    use axum::{
+        extract::Path,
+        http::{header, StatusCode},
+        response::{IntoResponse, Redirect, Response},
+        routing::get,
+        Router,
+    };
+    use once_cell::sync::Lazy;
+    use crate::api::AppState;
+    use crate::api::routes::ReservedRoute;
+    use crate::models::IpGeoError;
+
+    static SWAGGER_CONFIG: Lazy<Arc<utoipa_swagger_ui::Config<'static>>> =
+        Lazy::new(|| Arc::new(utoipa_swagger_ui::Config::from("../openapi.json")));
+
+    async fn swagger_file(tail: Option<Path<String>>) -> Response {
+        let tail = tail.map(|Path(tail)| tail).unwrap_or_default();
+        match utoipa_swagger_ui::serve(&tail, SWAGGER_CONFIG.clone()) {
+            Ok(Some(file)) => (
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, file.content_type)],
+                file.bytes.into_owned(),
+            ).into_response(),
+            Ok(None) => IpGeoError::NotFound(crate::utils::sanitize_echo(&format!("/docs/{}", tail))).into_response(),
+            Err(e) => IpGeoError::IoError(std::io::Error::other(e.to_string())).into_response(),
+        }
+    }
+
+    /// /docs 下的 Swagger UI 页面和静态资源
+    pub fn swagger_router() -> Router<AppState> {
+        Router::new()
+            .reserved_route("/docs", get(|| async { Redirect::permanent("docs/") }))
+            .reserved_route("/docs/", get(swagger_file))
+            .reserved_route("/docs/{*tail}", get(swagger_file))
+    }
+}
+
+#[cfg(feature = "swagger-ui")]
+pub use swagger::swagger_router;
@@ -0,0 +1,67 @@
+use axum::extract::Request;
+use axum::http::uri::PathAndQuery;
+use axum::http::Uri;
+use axum::routing::MethodRouter;
+use axum::Router;
+use dashmap::DashSet;
+use once_cell::sync::Lazy;
+use super::state::AppState;
+
+// 不能作为 `/{host}` 主机名的路径首段。除了已注册路由的首段，
+// 也预留了将来可能出现的接口，避免新路由改变已有 URL 的含义
+static RESERVED_SEGMENTS: Lazy<DashSet<&'static str>> = Lazy::new(|| {
+    ["api", "admin", "metrics", "healthz", "version", "docs", "debug"].into_iter().collect()
+});
+
+/// 把路径的首段加入保留集合，`{host}` 这样的参数段不保留
+pub fn reserve(path: &'static str) {
+    if let Some(segment) = path.trim_start_matches('/').split('/').next() {
+        if !segment.is_empty() && !segment.starts_with('{') {
+            RESERVED_SEGMENTS.insert(segment);
+        }
+    }
+}
+
+/// 首段是否已被路由占用，大小写不敏感
+pub fn is_reserved(segment: &str) -> bool {
+    RESERVED_SEGMENTS.contains(segment.to_ascii_lowercase().as_str())
+}
+
+/// 顶层路由都通过它注册，新接口自动加入保留集合
+pub trait ReservedRoute {
+    fn reserved_route(self, path: &'static str, method_router: MethodRouter<AppState>) -> Self;
+}
+
+impl ReservedRoute for Router<AppState> {
+    fn reserved_route(self, path: &'static str, method_router: MethodRouter<AppState>) -> Self {
+        reserve(path);
+        self.route(path, method_router)
+    }
+}
+
+// 依赖末尾斜杠解析相对路径的页面，如 Swagger UI 的首页
+const KEEP_TRAILING_SLASH: &[&str] = &["/docs/"];
+
+/// 去掉路径末尾的斜杠，查询参数保持不变，`/api/` 与 `/api` 等价
+pub async fn trim_trailing_slash(mut request: Request) -> Request {
+    let path = request.uri().path();
+    if path.len() <= 1 || !path.ends_with('/') || KEEP_TRAILING_SLASH.contains(&path) {
+        return request;
+    }
+    let trimmed = match path.trim_end_matches('/') {
+        "" => "/",
+        trimmed => trimmed,
+    };
+    let path_and_query = match request.uri().query() {
+        Some(query) => format!("{}?{}", trimmed, query),
+        None => trimmed.to_string(),
+    };
+    let mut parts = request.uri().clone().into_parts();
+    if let Ok(path_and_query) = PathAndQuery::try_from(path_and_query) {
+        parts.path_and_query = Some(path_and_query);
+        if let Ok(uri) = Uri::from_parts(parts) {
+            *request.uri_mut() = uri;
+        }
+    }
+    request
+}
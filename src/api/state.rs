@@ -0,0 +1,55 @@
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+// 路由共享状态
+#[derive(Clone, Default)]
+pub struct AppState {
+    /// 关闭信号，后台任务据此退出
+    pub shutdown: CancellationToken,
+    in_flight: Arc<AtomicUsize>,
+    drain_deadline: Arc<OnceLock<Instant>>,
+}
+
+impl AppState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 使用外部提供的关闭信号，例如嵌入时与 GeoResolver 共用
+    pub fn with_shutdown(shutdown: CancellationToken) -> Self {
+        Self { shutdown, ..Self::default() }
+    }
+
+    /// 当前正在处理的请求数
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// 开始关闭时确定 drain 的截止时间，HTTP 和 gRPC 共用；之后的调用返回同一个时间
+    pub fn drain_deadline(&self, timeout: Duration) -> Instant {
+        *self.drain_deadline.get_or_init(|| Instant::now() + timeout)
+    }
+}
+
+// 请求结束（包括被取消）时递减计数
+struct InFlightGuard(Arc<AtomicUsize>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub async fn track_in_flight(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    state.in_flight.fetch_add(1, Ordering::Relaxed);
+    let _guard = InFlightGuard(state.in_flight.clone());
+    next.run(request).await
+}
@@ -0,0 +1,48 @@
+//! W3C Trace Context：从上游网关传入的 `traceparent` 头中取出 trace ID，
+//! 记录到请求的 span 上，使网关和本服务的日志、追踪可以关联。
+
+use axum::http::{HeaderMap, HeaderName};
+
+pub static TRACEPARENT_HEADER: HeaderName = HeaderName::from_static("traceparent");
+
+/// `traceparent` 中的追踪上下文
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    /// 32 位小写十六进制
+    pub trace_id: String,
+    /// 上游 span 的 ID，16 位小写十六进制
+    pub parent_id: String,
+    pub sampled: bool,
+}
+
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len && value.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// 解析 `version-trace_id-parent_id-flags`，全零的 ID 和版本 ff 无效
+pub fn parse_traceparent(value: &str) -> Option<TraceContext> {
+    let mut parts = value.trim().split('-');
+    let (version, trace_id, parent_id, flags) = (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+    // 版本 00 不允许多余的字段，更高的版本按规范忽略它们
+    if !is_hex(version, 2) || version == "ff" || (version == "00" && parts.next().is_some()) {
+        return None;
+    }
+    if !is_hex(trace_id, 32) || !is_hex(parent_id, 16) || !is_hex(flags, 2) {
+        return None;
+    }
+    if trace_id.bytes().all(|b| b == b'0') || parent_id.bytes().all(|b| b == b'0') {
+        return None;
+    }
+    let flags = u8::from_str_radix(flags, 16).ok()?;
+    Some(TraceContext {
+        trace_id: trace_id.to_string(),
+        parent_id: parent_id.to_string(),
+        sampled: flags & 0x01 != 0,
+    })
+}
+
+pub fn trace_context(headers: &HeaderMap) -> Option<TraceContext> {
+    headers.get(&TRACEPARENT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_traceparent)
+}
@@ -0,0 +1,151 @@
+//! 冻结的 v1 响应结构。内部模型调整或新增字段时，在这里做转换，保证 /v1 的输出不变；
+//! 新字段只出现在最新版本中。
+
+use serde::Serialize;
+use crate::models::{self, IpInfo};
+
+/// 按 v1 的契约输出查询结果
+pub(super) fn shape(info: IpInfo) -> Result<serde_json::Value, serde_json::Error> {
+    serde_json::to_value(V1IpInfo::from(info))
+}
+
+#[derive(Serialize)]
+struct V1Asn {
+    number: u32,
+    name: String,
+    info: String,
+}
+
+#[derive(Serialize)]
+struct V1Location {
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_zone: Option<String>,
+}
+
+#[derive(Serialize)]
+struct V1Country {
+    code: String,
+    name: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    is_eu: bool,
+}
+
+#[derive(Serialize)]
+struct V1City {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    geoname_id: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct V1Continent {
+    code: String,
+    name: String,
+}
+
+#[derive(Serialize)]
+struct V1Traits {
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    is_anonymous_proxy: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    is_satellite_provider: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    is_anycast: bool,
+}
+
+/// v1 的查询结果，字段名、类型和省略规则都不再改变
+#[derive(Serialize)]
+struct V1IpInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cnames: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    cnames_truncated: bool,
+    ip: String,
+    #[serde(rename = "as", skip_serializing_if = "Option::is_none")]
+    asn: Option<V1Asn>,
+    addr: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<V1Location>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    accuracy_radius: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    postal: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    continent: Option<V1Continent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    country: Option<V1Country>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    registered_country: Option<V1Country>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    represented_country: Option<V1Country>,
+    /// v1 中始终是字符串数组
+    #[serde(skip_serializing_if = "Option::is_none")]
+    regions: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    regions_short: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    city: Option<V1City>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    traits: Option<V1Traits>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    isp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    organization: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    domain: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rdns: Option<String>,
+}
+
+impl From<models::CountryInfo> for V1Country {
+    fn from(country: models::CountryInfo) -> Self {
+        Self { code: country.code, name: country.name, is_eu: country.is_eu }
+    }
+}
+
+impl From<IpInfo> for V1IpInfo {
+    fn from(info: IpInfo) -> Self {
+        Self {
+            host: info.host,
+            cnames: info.cnames,
+            cnames_truncated: info.cnames_truncated,
+            ip: info.ip,
+            asn: info.asn.map(|asn| V1Asn {
+                number: asn.number_or_zero(),
+                info: asn.info_or_name().to_string(),
+                name: asn.name,
+            }),
+            addr: info.addr,
+            location: info.location.map(|location| V1Location {
+                latitude: location.latitude,
+                longitude: location.longitude,
+                time_zone: location.time_zone,
+            }),
+            accuracy_radius: info.accuracy_radius,
+            postal: info.postal,
+            continent: info.continent.map(|continent| V1Continent { code: continent.code, name: continent.name }),
+            country: info.country.map(V1Country::from),
+            registered_country: info.registered_country.map(V1Country::from),
+            represented_country: info.represented_country.map(V1Country::from),
+            regions: info.regions,
+            regions_short: info.regions_short,
+            city: info.city.map(|city| V1City { name: city.name, geoname_id: city.geoname_id }),
+            kind: info.r#type,
+            traits: info.traits.map(|traits| V1Traits {
+                is_anonymous_proxy: traits.is_anonymous_proxy,
+                is_satellite_provider: traits.is_satellite_provider,
+                is_anycast: traits.is_anycast,
+            }),
+            isp: info.isp,
+            organization: info.organization,
+            domain: info.domain,
+            rdns: info.rdns,
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! 接口版本：按路由挂载位置选择响应结构。/v1 的结构冻结在 v1 模块中，
+//! 最新版本直接输出内部模型，只对 `as` 做按请求的格式转换。
+
+use axum::{
+    extract::{OriginalUri, Request},
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use serde::Serialize;
+use crate::config::Config;
+use crate::models::{self, AsnFormat, IpInfo};
+
+/// 接口版本，由路由挂载位置决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    /// 未带版本前缀的路由对应的版本
+    pub const LATEST: ApiVersion = ApiVersion::V2;
+
+    /// 所有挂载的版本
+    pub const ALL: [ApiVersion; 2] = [ApiVersion::V1, ApiVersion::V2];
+
+    pub fn prefix(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "/v1",
+            ApiVersion::V2 => "/v2",
+        }
+    }
+
+    /// 批量接口是否默认输出 query 和 timestamp；/v1 的响应格式固定，只在指定 echo 时计算
+    pub fn echoes_batches(self) -> bool {
+        self != ApiVersion::V1
+    }
+
+    /// 按该版本的契约输出查询结果
+    pub fn shape(self, info: IpInfo, asn_format: AsnFormat) -> Result<serde_json::Value, serde_json::Error> {
+        match self {
+            ApiVersion::V1 => super::v1::shape(info),
+            ApiVersion::V2 => serde_json::to_value(V2IpInfo::new(info, asn_format)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct V2Asn {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    number: Option<u32>,
+    /// 只在 asn_format=string|both 时输出
+    #[serde(skip_serializing_if = "Option::is_none")]
+    asn: Option<String>,
+    /// ASN_OMIT_UNKNOWN 开启时省略空名称
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// ASN_OMIT_UNKNOWN 关闭时总是输出
+    #[serde(skip_serializing_if = "Option::is_none")]
+    info: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    route: Option<String>,
+}
+
+/// 最新版本的查询结果：内部模型的全部字段，`as` 按 asn_format 输出
+#[derive(Serialize)]
+struct V2IpInfo {
+    #[serde(rename = "as", skip_serializing_if = "Option::is_none")]
+    asn: Option<V2Asn>,
+    #[serde(flatten)]
+    info: IpInfo,
+}
+
+impl V2Asn {
+    fn new(asn: models::AsnInfo, format: AsnFormat) -> Self {
+        let mut number = asn.number;
+        let label = number.filter(|_| format != AsnFormat::Number).map(models::format_asn);
+        if format == AsnFormat::String && label.is_some() {
+            number = None;
+        }
+        Self {
+            number,
+            asn: label,
+            name: Some(asn.name).filter(|name| !name.is_empty() || !Config::global().asn_omit_unknown),
+            info: asn.info,
+            route: asn.route,
+        }
+    }
+}
+
+impl V2IpInfo {
+    fn new(mut info: IpInfo, asn_format: AsnFormat) -> Self {
+        let asn = info.asn.take().map(|asn| V2Asn::new(asn, asn_format));
+        Self { asn, info }
+    }
+}
+
+static DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+
+/// 未带版本前缀的路由：提示客户端改用固定版本的路径
+pub async fn deprecate_unversioned(request: Request, next: Next) -> Response {
+    let path = match request.uri().path() {
+        "/" => "",
+        path => path,
+    };
+    // 通过 Router::nest 挂载时 uri 已去掉挂载点，从原始路径中找回
+    let mount = request.extensions().get::<OriginalUri>()
+        .and_then(|original| original.path().trim_end_matches('/').strip_suffix(path).map(str::to_string))
+        .unwrap_or_default();
+    let successor = format!("<{}{}{}>; rel=\"successor-version\"", mount, ApiVersion::LATEST.prefix(), path);
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(DEPRECATION.clone(), HeaderValue::from_static("true"));
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.append(axum::http::header::LINK, link);
+    }
+    response
+}
@@ -1,16 +1,10 @@
-use std::sync::OnceLock;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{OnceLock, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
 use dashmap::DashMap;
+use serde::Serialize;
 use serde_json::Value;
-
-trait IntoString {
-    fn into_string(self) -> String;
-}
-
-impl IntoString for Box<str> {
-    fn into_string(self) -> String {
-        self.into()
-    }
-}
+use super::results::ResultCache;
 
 // ASN类型枚举
 #[derive(Clone, PartialEq, Eq)]
@@ -58,10 +52,73 @@ impl Default for KeywordCache {
     }
 }
 
+// 单个缓存的访问计数
+#[derive(Default)]
+pub(super) struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    /// 清空缓存时移除的条目，结果缓存还包括过期、淘汰和数据更新后作废的条目
+    pub(super) evictions: AtomicU64,
+}
+
+impl CacheCounters {
+    #[inline]
+    pub(super) fn record<T>(&self, found: Option<T>) -> Option<T> {
+        let counter = if found.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    pub(super) fn stats(&self, entries: usize, memory_bytes: usize) -> CacheStats {
+        CacheStats {
+            entries,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            memory_bytes,
+        }
+    }
+}
+
+/// 缓存的条目数、命中统计和估算的内存占用
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub memory_bytes: usize,
+}
+
+// 条目的估算大小：键、值结构体以及名称和类型字符串
+fn entry_size<K>(key_len: usize, name: &str, type_info: &AsnType) -> usize {
+    let type_len = match type_info {
+        AsnType::Type(t) => t.len(),
+        AsnType::Other => 0,
+    };
+    std::mem::size_of::<K>() + std::mem::size_of::<AsnInfo>() + key_len + name.len() + type_len
+}
+
+// 组织名称按非字母数字字符切分为小写单词，前后留空格，关键词按整词匹配
+fn org_words(name: &str) -> String {
+    let words: Vec<String> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    format!(" {} ", words.join(" "))
+}
+
 // 全局缓存管理器
 pub struct CacheManager {
     asn_cache: DashMap<u32, AsnInfo>,
     keyword_cache: KeywordCache,
+    /// asn_info.json 中 keyword_types 的关键词和对应类型，按文件中的顺序匹配
+    keyword_types: RwLock<Vec<(String, AsnType)>>,
+    /// asn_info.json 中 hosting_asns 列出的托管服务商ASN，查询结果标记为数据中心
+    hosting_asns: RwLock<HashSet<u32>>,
+    asn_counters: CacheCounters,
+    keyword_counters: CacheCounters,
 }
 
 // 全局单例
@@ -73,26 +130,107 @@ impl CacheManager {
             CacheManager {
                 asn_cache: DashMap::with_capacity(1000),
                 keyword_cache: KeywordCache::default(),
+                keyword_types: RwLock::default(),
+                hosting_asns: RwLock::default(),
+                asn_counters: CacheCounters::default(),
+                keyword_counters: CacheCounters::default(),
             }
         })
     }
 
     // ASN缓存方法
     pub fn get_asn_info(&self, asn: u32) -> Option<(Box<str>, AsnType)> {
-        self.asn_cache.get(&asn)
-            .map(|info| (info.name.clone(), info.type_info.clone()))
+        self.asn_counters.record(self.asn_cache.get(&asn)
+            .map(|info| (info.name.clone(), info.type_info.clone())))
     }
 
     // 关键词缓存方法
     pub fn get_keyword_info(&self, keyword: &str) -> Option<(Box<str>, AsnType)> {
-        self.keyword_cache.isp_map
+        self.keyword_counters.record(self.keyword_cache.isp_map
             .get(keyword)
             .or_else(|| self.keyword_cache.org_map.get(keyword))
-            .map(|info| (info.name.clone(), info.type_info.clone()))
+            .map(|info| (info.name.clone(), info.type_info.clone())))
+    }
+
+    /// 按组织名称中的关键词推断未收录ASN的类型，如 "university"、"internet exchange"
+    pub fn classify_org(&self, org: &str) -> Option<AsnType> {
+        let words = org_words(org);
+        self.keyword_types.read().ok()?
+            .iter()
+            .find(|(keyword, _)| words.contains(keyword.as_str()))
+            .map(|(_, type_info)| type_info.clone())
+    }
+
+    pub fn is_hosting_asn(&self, asn: u32) -> bool {
+        self.hosting_asns.read().is_ok_and(|asns| asns.contains(&asn))
+    }
+
+    /// 各缓存的统计，键为缓存名
+    pub fn stats(&self) -> BTreeMap<&'static str, CacheStats> {
+        let asn_memory = self.asn_cache.iter()
+            .map(|entry| entry_size::<u32>(0, &entry.name, &entry.type_info))
+            .sum();
+        let keyword_memory = self.keyword_cache.isp_map.iter()
+            .chain(self.keyword_cache.org_map.iter())
+            .map(|entry| entry_size::<Box<str>>(entry.key().len(), &entry.name, &entry.type_info))
+            .sum();
+        let keyword_entries = self.keyword_cache.isp_map.len() + self.keyword_cache.org_map.len();
+        let mut stats = BTreeMap::from([
+            ("asn", self.asn_counters.stats(self.asn_cache.len(), asn_memory)),
+            ("keyword", self.keyword_counters.stats(keyword_entries, keyword_memory)),
+        ]);
+        if let Some(results) = ResultCache::global() {
+            stats.insert("results", results.stats());
+        }
+        stats
+    }
+
+    /// 清空所有缓存，包括查询结果缓存。DashMap 按分片加锁，并发的查询只会看到未命中
+    pub fn flush(&self) {
+        if let Some(results) = ResultCache::global() {
+            results.clear();
+        }
+
+        let asn_entries = self.asn_cache.len() as u64;
+        self.asn_cache.clear();
+        self.asn_counters.evictions.fetch_add(asn_entries, Ordering::Relaxed);
+
+        let keyword_entries = (self.keyword_cache.isp_map.len() + self.keyword_cache.org_map.len()) as u64;
+        self.keyword_cache.isp_map.clear();
+        self.keyword_cache.org_map.clear();
+        self.keyword_counters.evictions.fetch_add(keyword_entries, Ordering::Relaxed);
     }
 
     // 初始化ASN数据
     pub fn init_asn_data(&self, data: &Value) {
+        // 整体替换，旧版文件没有 keyword_types 时清空
+        let keyword_types = data.get("keyword_types")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|section| Some((
+                section.get("type").and_then(Value::as_str)?,
+                section.get("keywords").and_then(Value::as_array)?,
+            )))
+            .flat_map(|(type_str, keywords)| keywords.iter()
+                .filter_map(Value::as_str)
+                .map(org_words)
+                .filter(|keyword| !keyword.trim().is_empty())
+                .map(move |keyword| (keyword, AsnType::from_str(type_str))))
+            .collect();
+        if let Ok(mut current) = self.keyword_types.write() {
+            *current = keyword_types;
+        }
+        let hosting_asns = data.get("hosting_asns")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|asn| asn.as_u64().and_then(|asn| u32::try_from(asn).ok()))
+            .collect();
+        if let Ok(mut current) = self.hosting_asns.write() {
+            *current = hosting_asns;
+        }
+
         if let Some(asn_info) = data.get("asn_info").and_then(Value::as_object) {
             let expected_size = asn_info.len();
             let mut keyword_buffer = Vec::with_capacity(expected_size * 2);
@@ -1,2 +1,12 @@
 pub mod cache;
-pub use cache::*; 
\ No newline at end of file
+pub mod refresh;
+pub mod results;
+pub mod singleflight;
+pub mod snapshot;
+pub mod warmup;
+pub use cache::*;
+pub use refresh::*;
+pub use results::*;
+pub use singleflight::*;
+pub use snapshot::*;
+pub use warmup::*;
@@ -0,0 +1,52 @@
+use std::future::Future;
+use std::sync::Arc;
+use dashmap::DashSet;
+use futures::stream::{self, StreamExt};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use crate::metrics::Metrics;
+use super::results::ResultKey;
+
+/// 结果缓存的提前刷新：命中的条目接近过期时在后台重新计算。队列有界，满时直接丢弃；
+/// 同一个键在队列中或正在刷新时不重复入队
+pub struct RefreshQueue {
+    sender: mpsc::Sender<ResultKey>,
+    pending: Arc<DashSet<ResultKey>>,
+}
+
+impl RefreshQueue {
+    /// 在当前运行时中启动后台任务，以最多 concurrency 个并发执行 refresh
+    pub fn spawn<F, Fut>(capacity: usize, concurrency: usize, refresh: F) -> Self
+    where
+        F: Fn(ResultKey) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        let pending = Arc::new(DashSet::new());
+        let done = Arc::clone(&pending);
+        let keys = stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|key| (key, receiver))
+        });
+        tokio::spawn(keys.for_each_concurrent(concurrency.max(1), move |key| {
+            let refreshed = refresh(key);
+            let done = Arc::clone(&done);
+            async move {
+                refreshed.await;
+                Metrics::incr(&Metrics::global().result_cache_refreshes);
+                done.remove(&key);
+            }
+        }));
+        Self { sender, pending }
+    }
+
+    /// 把 key 加入刷新队列；已经在队列中时什么也不做，队列已满时丢弃并计入指标
+    pub fn enqueue(&self, key: ResultKey) {
+        if !self.pending.insert(key) {
+            return;
+        }
+        if let Err(TrySendError::Full(key) | TrySendError::Closed(key)) = self.sender.try_send(key) {
+            self.pending.remove(&key);
+            Metrics::incr(&Metrics::global().result_cache_refresh_drops);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::net::IpAddr;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use moka::notification::RemovalCause;
+use moka::sync::Cache;
+use moka::Expiry;
+use crate::config::Config;
+use crate::models::{IpInfo, LookupOptions};
+use super::cache::{CacheCounters, CacheStats};
+
+/// 结果缓存的键：IP 和规范化之后的查询选项
+pub type ResultKey = (IpAddr, LookupOptions);
+
+/// 缓存的查询结果
+#[derive(Debug)]
+pub struct CachedResult {
+    pub info: IpInfo,
+    /// 最初的计算时间，命中时作为 timestamp 输出，也用来计算剩余的有效期
+    pub computed_at: SystemTime,
+    /// 计算时的数据代数，与当前代数不同时视为未命中
+    pub generation: u64,
+    size: u32,
+    hits: AtomicU32,
+}
+
+impl CachedResult {
+    pub fn hits(&self) -> u32 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// 估算的内存占用
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// 距离计算时的时长，时钟回拨时为零
+    pub fn age(&self) -> Duration {
+        SystemTime::now().duration_since(self.computed_at).unwrap_or_default()
+    }
+}
+
+/// 查询结果的估算内存占用：结构体本身加上 MessagePack 编码后的长度，近似于其中字符串和数组的堆内存
+pub fn calculate_ipinfo_size(info: &IpInfo) -> usize {
+    std::mem::size_of::<IpInfo>() + rmp_serde::to_vec_named(info).map_or(0, |bytes| bytes.len())
+}
+
+// 有效期从最初的计算时间算起，从快照恢复的条目只保留剩余的时间
+struct RemainingTtl(Duration);
+
+impl Expiry<ResultKey, Arc<CachedResult>> for RemainingTtl {
+    fn expire_after_create(&self, _key: &ResultKey, value: &Arc<CachedResult>, _created_at: Instant) -> Option<Duration> {
+        Some(self.0.saturating_sub(value.age()))
+    }
+}
+
+/// get_ip_info 的结果缓存，按估算的内存占用淘汰，条目在 RESULT_CACHE_TTL_SECS 之后过期
+pub struct ResultCache {
+    entries: Cache<ResultKey, Arc<CachedResult>>,
+    ttl: Duration,
+    counters: Arc<CacheCounters>,
+}
+
+static RESULT_CACHE: OnceLock<Option<ResultCache>> = OnceLock::new();
+
+impl ResultCache {
+    pub fn new(ttl: Duration, max_bytes: u64) -> Self {
+        let counters = Arc::new(CacheCounters::default());
+        let evictions = Arc::clone(&counters);
+        let entries = Cache::builder()
+            .max_capacity(max_bytes)
+            .weigher(|_key: &ResultKey, value: &Arc<CachedResult>| value.size)
+            .expire_after(RemainingTtl(ttl))
+            // 同一个键重新计算后的替换不算移除
+            .eviction_listener(move |_key, _value, cause| {
+                if cause != RemovalCause::Replaced {
+                    evictions.evictions.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+            .build();
+        Self { entries, ttl, counters }
+    }
+
+    /// 按全局配置创建的缓存，RESULT_CACHE_TTL_SECS 或 RESULT_CACHE_MAX_BYTES 为 0 时为 None
+    pub fn global() -> Option<&'static ResultCache> {
+        RESULT_CACHE.get_or_init(|| {
+            let config = Config::global();
+            (!config.result_cache_ttl.is_zero() && config.result_cache_max_bytes > 0)
+                .then(|| ResultCache::new(config.result_cache_ttl, config.result_cache_max_bytes))
+        }).as_ref()
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// 当前代数下的结果；其他代数算出的条目直接移除
+    pub fn get(&self, key: &ResultKey, generation: u64) -> Option<Arc<CachedResult>> {
+        let entry = match self.entries.get(key) {
+            Some(entry) if entry.generation != generation => {
+                self.entries.invalidate(key);
+                None
+            }
+            entry => entry,
+        };
+        let entry = self.counters.record(entry)?;
+        entry.hits.fetch_add(1, Ordering::Relaxed);
+        Some(entry)
+    }
+
+    pub fn insert(&self, key: ResultKey, info: IpInfo, computed_at: SystemTime, generation: u64) {
+        self.insert_with_hits(key, info, computed_at, generation, 0);
+    }
+
+    /// 带上已有的命中次数插入，用于从快照恢复；已经过期的结果不插入
+    pub fn insert_with_hits(&self, key: ResultKey, info: IpInfo, computed_at: SystemTime, generation: u64, hits: u32) -> bool {
+        let size = u32::try_from(calculate_ipinfo_size(&info) + std::mem::size_of::<ResultKey>()).unwrap_or(u32::MAX);
+        let entry = CachedResult { info, computed_at, generation, size, hits: AtomicU32::new(hits) };
+        if entry.age() >= self.ttl {
+            return false;
+        }
+        self.entries.insert(key, Arc::new(entry));
+        true
+    }
+
+    /// 当前代数下命中最多的条目，按命中次数降序，条数不超过 limit、估算内存合计不超过 max_bytes
+    pub fn hottest(&self, generation: u64, limit: usize, max_bytes: u64) -> Vec<(ResultKey, Arc<CachedResult>)> {
+        let mut entries: Vec<_> = self.entries.iter()
+            .filter(|(_, entry)| entry.generation == generation)
+            .map(|(key, entry)| (*key, entry))
+            .collect();
+        entries.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.hits()));
+        let mut total = 0u64;
+        entries.into_iter()
+            .take_while(|(_, entry)| {
+                total += u64::from(entry.size);
+                total <= max_bytes
+            })
+            .take(limit)
+            .collect()
+    }
+
+    /// 条目的年龄是否已超过 TTL 的 fraction 倍，应当在后台提前刷新；fraction 为 0 时不刷新
+    pub fn needs_refresh(&self, entry: &CachedResult, fraction: f64) -> bool {
+        fraction > 0.0 && fraction < 1.0 && entry.age() >= self.ttl.mul_f64(fraction)
+    }
+
+    /// 条目数和估算的内存占用，先处理挂起的淘汰
+    pub fn usage(&self) -> (u64, u64) {
+        self.entries.run_pending_tasks();
+        (self.entries.entry_count(), self.entries.weighted_size())
+    }
+
+    /// 条目数、命中统计和估算的内存占用（见 calculate_ipinfo_size），格式与其他缓存相同
+    pub fn stats(&self) -> CacheStats {
+        let (entries, memory_bytes) = self.usage();
+        self.counters.stats(entries as usize, memory_bytes as usize)
+    }
+
+    /// 清空缓存，返回移除前的条目数
+    pub fn clear(&self) -> u64 {
+        let (entries, _) = self.usage();
+        self.entries.invalidate_all();
+        self.entries.run_pending_tasks();
+        entries
+    }
+}
@@ -0,0 +1,73 @@
+use std::future::Future;
+use std::hash::Hash;
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use tokio::sync::watch;
+
+/// 合并同一个 key 的并发请求：第一个调用者执行，其余调用者等待并共享它的结果
+pub struct SingleFlight<K, V> {
+    inflight: DashMap<K, watch::Receiver<Option<V>>>,
+}
+
+// 领头请求结束（包括被取消）时移除条目，后续请求重新执行
+struct FlightGuard<'a, K: Eq + Hash, V> {
+    inflight: &'a DashMap<K, watch::Receiver<Option<V>>>,
+    key: &'a K,
+}
+
+impl<K: Eq + Hash, V> Drop for FlightGuard<'_, K, V> {
+    fn drop(&mut self) {
+        self.inflight.remove(self.key);
+    }
+}
+
+impl<K, V> Default for SingleFlight<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> SingleFlight<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    pub fn new() -> Self {
+        Self { inflight: DashMap::new() }
+    }
+
+    /// 返回结果以及它是否来自其他调用者
+    pub async fn run<F, Fut>(&self, key: K, f: F) -> (V, bool)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V>,
+    {
+        let tx = match self.inflight.entry(key.clone()) {
+            Entry::Occupied(entry) => {
+                let mut rx = entry.get().clone();
+                drop(entry);
+                if let Ok(value) = rx.wait_for(Option::is_some).await {
+                    if let Some(value) = value.clone() {
+                        return (value, true);
+                    }
+                }
+                // 领头请求被取消，自己执行
+                return (f().await, false);
+            }
+            Entry::Vacant(entry) => {
+                let (tx, rx) = watch::channel(None);
+                entry.insert(rx);
+                tx
+            }
+        };
+
+        let _guard = FlightGuard { inflight: &self.inflight, key: &key };
+        let value = f().await;
+        let _ = tx.send(Some(value.clone()));
+        (value, false)
+    }
+}
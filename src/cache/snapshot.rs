@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+use crate::config::Config;
+use crate::metrics::Metrics;
+use crate::models::{Detail, IpInfo, LookupOptions, NameLocales, RegionStyle};
+use super::results::{ResultCache, ResultKey};
+
+// 格式变化时加一，旧版本的快照被忽略
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// 结果缓存的快照：条目只在写入时的数据库构建时间下有效
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    version: u32,
+    /// 数据库类型到构建时间（Unix 秒），见 geo::database_epochs
+    epochs: BTreeMap<String, u64>,
+    entries: Vec<SnapshotEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotEntry {
+    ip: IpAddr,
+    options: StoredOptions,
+    info: IpInfo,
+    computed_at_ms: u64,
+    hits: u32,
+}
+
+// 规范化之后的查询选项；LookupOptions 按查询参数反序列化，不能直接写入快照
+#[derive(Serialize, Deserialize)]
+struct StoredOptions {
+    detail: Detail,
+    sources: bool,
+    precision: Option<u8>,
+    regions: RegionStyle,
+    rir: bool,
+    locales: Option<Vec<String>>,
+    compare: bool,
+}
+
+impl From<LookupOptions> for StoredOptions {
+    fn from(options: LookupOptions) -> Self {
+        Self {
+            detail: options.detail,
+            sources: options.sources,
+            precision: options.precision,
+            regions: options.regions,
+            rir: options.rir,
+            locales: options.locales.map(|locales| locales.as_slice().iter().map(|tag| tag.to_string()).collect()),
+            compare: options.compare,
+        }
+    }
+}
+
+impl StoredOptions {
+    fn into_options(self) -> Option<LookupOptions> {
+        let locales = match self.locales {
+            Some(tags) => Some(NameLocales::from_tags(tags.iter().map(String::as_str))?),
+            None => None,
+        };
+        Some(LookupOptions {
+            detail: self.detail,
+            sources: self.sources,
+            precision: self.precision,
+            regions: self.regions,
+            rir: self.rir,
+            locales,
+            compare: self.compare,
+            ..LookupOptions::default()
+        })
+    }
+}
+
+/// 把当前代数下命中最多的条目写入 path，条数和估算内存受 CACHE_SNAPSHOT_MAX_ENTRIES 与 RESULT_CACHE_MAX_BYTES 限制；
+/// 先写临时文件再改名，返回写入的条目数
+pub fn save_snapshot(cache: &ResultCache, path: &Path) -> std::io::Result<usize> {
+    let config = Config::global();
+    let generation = crate::geo::data_generation();
+    let entries: Vec<SnapshotEntry> = cache.hottest(generation, config.cache_snapshot_max_entries, config.result_cache_max_bytes)
+        .into_iter()
+        .map(|((ip, options), entry)| SnapshotEntry {
+            ip,
+            options: options.into(),
+            info: entry.info.clone(),
+            computed_at_ms: entry.computed_at.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+            hits: entry.hits(),
+        })
+        .collect();
+    let snapshot = Snapshot { version: SNAPSHOT_VERSION, epochs: crate::geo::database_epochs(), entries };
+    let bytes = rmp_serde::to_vec_named(&snapshot).map_err(std::io::Error::other)?;
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(snapshot.entries.len())
+}
+
+/// 载入 path 中的快照并返回恢复的条目数。文件缺失、损坏、版本不同或数据库构建时间已经变化时
+/// 不恢复任何条目，也不报错；已经过期的条目被跳过
+pub fn load_snapshot(cache: &ResultCache, path: &Path) -> usize {
+    let Ok(bytes) = std::fs::read(path) else {
+        return 0;
+    };
+    let snapshot: Snapshot = match rmp_serde::from_slice(&bytes) {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            debug!("Ignoring unreadable result cache snapshot {:?}: {}", path, e);
+            return 0;
+        }
+    };
+    // 先取代数再比较构建时间，期间替换了数据库时恢复的条目随代数一起作废
+    let generation = crate::geo::data_generation();
+    if snapshot.version != SNAPSHOT_VERSION || snapshot.epochs != crate::geo::database_epochs() {
+        debug!("Ignoring result cache snapshot {:?} from other databases", path);
+        return 0;
+    }
+    snapshot.entries.into_iter()
+        .filter_map(|entry| {
+            let key: ResultKey = (entry.ip, entry.options.into_options()?);
+            let computed_at = SystemTime::UNIX_EPOCH + Duration::from_millis(entry.computed_at_ms);
+            cache.insert_with_hits(key, entry.info, computed_at, generation, entry.hits).then_some(())
+        })
+        .count()
+}
+
+/// 开启 CACHE_SNAPSHOT 时在启动后载入快照，恢复的条目数记入指标
+pub fn restore_result_cache() {
+    let config = Config::global();
+    let Some(cache) = ResultCache::global().filter(|_| config.cache_snapshot) else {
+        return;
+    };
+    let path = config.cache_snapshot_file();
+    let restored = load_snapshot(cache, &path);
+    Metrics::global().result_cache_restored.store(restored as u64, Ordering::Relaxed);
+    if restored > 0 {
+        info!("Restored {} result cache entries from {:?}", restored, path);
+    }
+}
+
+/// 开启 CACHE_SNAPSHOT 时在正常关闭前写入快照；数据目录只读等写入失败的情况只记录警告
+pub fn persist_result_cache() {
+    let config = Config::global();
+    let Some(cache) = ResultCache::global().filter(|_| config.cache_snapshot) else {
+        return;
+    };
+    let path = config.cache_snapshot_file();
+    match save_snapshot(cache, &path) {
+        Ok(saved) => info!("Saved {} result cache entries to {:?}", saved, path),
+        Err(e) => warn!("Failed to save the result cache snapshot to {:?}: {}", path, e),
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use futures::stream::{self, StreamExt};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+use crate::config::Config;
+use crate::metrics::Metrics;
+use super::results::ResultCache;
+
+/// 数据目录中记录热点IP的文件，每行一个，按命中次数降序
+pub const HOT_IPS_FILE: &str = "hot_ips.txt";
+
+/// 写入热点IP的间隔
+pub const HOT_IPS_DUMP_INTERVAL: Duration = Duration::from_secs(300);
+
+fn hot_ips_path() -> PathBuf {
+    Config::global().data_dir.join(HOT_IPS_FILE)
+}
+
+/// 把结果缓存中命中最多的 limit 个IP写入 path（只有IP，不含查询结果），返回写入的个数。
+/// 同一IP的多种查询选项合并为一行
+pub fn save_hot_ips(cache: &ResultCache, path: &Path, limit: usize) -> std::io::Result<usize> {
+    let mut seen = HashSet::new();
+    let ips: Vec<IpAddr> = cache.hottest(crate::geo::data_generation(), usize::MAX, u64::MAX)
+        .into_iter()
+        .map(|((ip, _), _)| ip)
+        .filter(|ip| seen.insert(*ip))
+        .take(limit)
+        .collect();
+    let mut text = String::from("# most frequently looked up IPs, used to warm the result cache at startup\n");
+    for ip in &ips {
+        text.push_str(&ip.to_string());
+        text.push('\n');
+    }
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, text)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(ips.len())
+}
+
+/// 读取 path 中的热点IP，忽略空行、`#` 开头的注释和无法解析的行；文件不存在时为空
+pub fn read_hot_ips(path: &Path) -> Vec<IpAddr> {
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.parse().ok())
+        .collect()
+}
+
+/// 以最多 concurrency 个并发查询预先计算 ips 的结果，shutdown 取消时停止，返回完成的查询数
+pub async fn warm_up(ips: Vec<IpAddr>, concurrency: usize, shutdown: CancellationToken) -> usize {
+    let lookups = stream::iter(ips)
+        .map(|ip| async move { crate::geo::get_ip_info(&ip.to_string()).await.is_ok() })
+        .buffer_unordered(concurrency.max(1))
+        .take_until(shutdown.cancelled_owned());
+    lookups.filter(|ok| std::future::ready(*ok)).count().await
+}
+
+/// 关闭前写入一次热点IP，写入失败只记录警告
+pub fn dump_hot_ips() {
+    let config = Config::global();
+    let Some(cache) = ResultCache::global().filter(|_| config.cache_warmup) else {
+        return;
+    };
+    let path = hot_ips_path();
+    match save_hot_ips(cache, &path, config.cache_warmup_top_k) {
+        Ok(saved) => debug!("Saved {} hot IPs to {:?}", saved, path),
+        Err(e) => warn!("Failed to save hot IPs to {:?}: {}", path, e),
+    }
+}
+
+/// 开启 CACHE_WARMUP 时在后台预热结果缓存：先按 hot_ips.txt 预先计算，之后每隔
+/// HOT_IPS_DUMP_INTERVAL 写入一次当前的热点IP。不影响就绪状态，shutdown 取消时退出
+pub fn spawn_cache_warmup(shutdown: CancellationToken) -> Option<tokio::task::JoinHandle<()>> {
+    let config = Config::global();
+    if !config.cache_warmup {
+        return None;
+    }
+    ResultCache::global()?;
+    Some(tokio::spawn(async move {
+        let ips = read_hot_ips(&hot_ips_path());
+        if !ips.is_empty() {
+            let total = ips.len();
+            let warmed = warm_up(ips, config.cache_warmup_concurrency, shutdown.clone()).await;
+            Metrics::global().result_cache_warmed.store(warmed as u64, Ordering::Relaxed);
+            info!("Warmed the result cache with {} of {} hot IPs", warmed, total);
+        }
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + HOT_IPS_DUMP_INTERVAL, HOT_IPS_DUMP_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => tokio::task::spawn_blocking(dump_hot_ips).await.unwrap_or_default(),
+                _ = shutdown.cancelled() => return,
+            }
+        }
+    }))
+}
@@ -0,0 +1,146 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use crate::geo::{init_asn_data, lookup_resolved, resolve_host_with_name, DatabaseManager, UpdateOptions, UpdateStatus};
+use crate::models::LookupOptions;
+use crate::utils::{ipinfo_to_csv, CSV_HEADER};
+
+#[derive(Debug, Parser)]
+#[command(name = "ipgeo", version, about = "IP Geolocation Service")]
+pub struct Cli {
+    /// TOML 配置文件，环境变量和命令行参数优先于其中的设置
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// 检查配置，输出合并后的有效配置（隐去管理令牌）后退出，配置无效时以非零状态退出
+    #[arg(long)]
+    pub check_config: bool,
+
+    /// 数据库文件所在目录（覆盖 MMDB_PATH 和 IPGEO_DATA_DIR，都未设置时为 data）
+    #[arg(long, global = true)]
+    pub data_dir: Option<PathBuf>,
+
+    /// HTTP 监听地址，可以重复指定，如 `--bind 0.0.0.0:8080 --bind [::]:8080`（覆盖 BIND）
+    #[arg(long, global = true)]
+    pub bind: Vec<SocketAddr>,
+
+    /// 只提供 /healthz、/metrics 和管理接口的监听地址，如 127.0.0.1:8081（覆盖 ADMIN_BIND）
+    #[arg(long, global = true)]
+    pub admin_bind: Option<SocketAddr>,
+
+    /// gRPC 服务监听地址，如 0.0.0.0:50051（覆盖 GRPC_BIND）
+    #[arg(long, global = true)]
+    pub grpc_bind: Option<SocketAddr>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// 启动HTTP服务（默认）
+    Serve,
+    /// 离线查询，每行输出一个结果
+    Lookup {
+        /// IP或域名，`-` 表示从标准输入逐行读取
+        #[arg(required = true)]
+        hosts: Vec<String>,
+        /// 输出格式
+        #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+        format: OutputFormat,
+    },
+    /// 下载或刷新数据库后退出
+    Update {
+        /// 忽略文件新鲜度强制重新下载
+        #[arg(long)]
+        force: bool,
+        /// 只更新指定的数据库，逗号分隔；city 和 country 只下载 DB_PROFILE 对应的一个
+        #[arg(long, value_delimiter = ',', value_parser = ["city", "country", "asn", "geocn", "rir"])]
+        only: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Csv,
+}
+
+// 展开参数中的 `-` 为标准输入的每一行
+async fn collect_hosts(args: Vec<String>) -> std::io::Result<Vec<String>> {
+    let mut hosts = Vec::with_capacity(args.len());
+    for arg in args {
+        if arg == "-" {
+            let mut lines = BufReader::new(tokio::io::stdin()).lines();
+            while let Some(line) = lines.next_line().await? {
+                let line = line.trim();
+                if !line.is_empty() {
+                    hosts.push(line.to_string());
+                }
+            }
+        } else {
+            hosts.push(arg);
+        }
+    }
+    Ok(hosts)
+}
+
+pub async fn run_lookup(data_dir: PathBuf, hosts: Vec<String>, format: OutputFormat) -> std::io::Result<ExitCode> {
+    // City 和 Country 有一个即可
+    for names in [&["GeoLite2-City.mmdb", "GeoLite2-Country.mmdb"][..], &["GeoLite2-ASN.mmdb"], &["GeoCN.mmdb"]] {
+        if !names.iter().any(|name| data_dir.join(name).exists()) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Database not found at {:?}", data_dir.join(names[0]))
+            ));
+        }
+    }
+    init_asn_data(&DatabaseManager::new(data_dir))?;
+
+    let hosts = collect_hosts(hosts).await?;
+    if format == OutputFormat::Csv {
+        println!("{}", CSV_HEADER);
+    }
+
+    let mut failed = false;
+    for host in hosts {
+        let result = match resolve_host_with_name(&host).await {
+            Ok(resolved) => lookup_resolved(resolved, LookupOptions::default()).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(info) => match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string(&info)?),
+                OutputFormat::Csv => println!("{}", ipinfo_to_csv(&info)),
+            },
+            Err(e) => {
+                eprintln!("{}: {}", host, e);
+                failed = true;
+            }
+        }
+    }
+
+    Ok(if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+}
+
+pub async fn run_update(data_dir: PathBuf, options: UpdateOptions) -> std::io::Result<ExitCode> {
+    let db_manager = DatabaseManager::new(data_dir.clone());
+    let outcomes = db_manager.update_databases_with(&options).await?;
+
+    let mut missing = false;
+    for outcome in &outcomes {
+        match &outcome.status {
+            UpdateStatus::Downloaded => println!("{}: downloaded", outcome.name),
+            UpdateStatus::Cached => println!("{}: cached", outcome.name),
+            UpdateStatus::Failed(e) => println!("{}: failed ({})", outcome.name, e),
+        }
+        if !data_dir.join(outcome.name).exists() {
+            missing = true;
+        }
+    }
+
+    Ok(if missing { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+}
@@ -0,0 +1,626 @@
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+use ipnet::IpNet;
+use serde::Serialize;
+use thiserror::Error;
+use toml::Value;
+use super::file::{self, Settings};
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Invalid configuration: {0}")]
+    Invalid(String),
+}
+
+/// 日志输出格式，对应 LOG_FORMAT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+    Pretty,
+}
+
+impl FromStr for LogFormat {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "pretty" => Ok(Self::Pretty),
+            other => Err(ConfigError::Invalid(format!("unknown LOG_FORMAT '{}'", other))),
+        }
+    }
+}
+
+/// 日志与错误信息中IP地址的脱敏级别，对应 PRIVACY_MODE
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrivacyMode {
+    /// 原样记录
+    #[default]
+    Full,
+    /// IPv4 抹去最后一段，IPv6 抹去后80位
+    Truncated,
+    /// 完全不记录IP
+    None,
+}
+
+impl FromStr for PrivacyMode {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "full" => Ok(Self::Full),
+            "truncated" => Ok(Self::Truncated),
+            "none" => Ok(Self::None),
+            other => Err(ConfigError::Invalid(format!("unknown PRIVACY_MODE '{}'", other))),
+        }
+    }
+}
+
+/// 查询目标是私有或保留地址时的处理方式，对应 PRIVATE_TARGET_POLICY；不影响查询调用方自身
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrivateTargetPolicy {
+    /// 返回所属网段
+    #[default]
+    Allow,
+    /// 返回 403 PRIVATE_TARGET
+    Reject,
+    /// 只返回 ip 和 type，不暴露所属网段
+    Redact,
+}
+
+impl FromStr for PrivateTargetPolicy {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "allow" => Ok(Self::Allow),
+            "reject" => Ok(Self::Reject),
+            "redact" => Ok(Self::Redact),
+            other => Err(ConfigError::Invalid(format!("unknown PRIVATE_TARGET_POLICY '{}'", other))),
+        }
+    }
+}
+
+/// 下载哪一种地理位置数据库，对应 DB_PROFILE
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DbProfile {
+    /// GeoLite2-City，包含省市和坐标
+    #[default]
+    Full,
+    /// 只有国家的 GeoLite2-Country，内存占用小得多
+    Lite,
+}
+
+impl FromStr for DbProfile {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "full" => Ok(Self::Full),
+            "lite" => Ok(Self::Lite),
+            other => Err(ConfigError::Invalid(format!("unknown DB_PROFILE '{}'", other))),
+        }
+    }
+}
+
+/// 每日固定的更新时间（本地时间），对应 DB_UPDATE_AT，格式为 HH:MM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DailyTime {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl DailyTime {
+    /// 一天中的秒数
+    pub fn seconds_of_day(self) -> u64 {
+        u64::from(self.hour) * 3600 + u64::from(self.minute) * 60
+    }
+}
+
+impl fmt::Display for DailyTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+impl FromStr for DailyTime {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ConfigError::Invalid(format!("DB_UPDATE_AT must be HH:MM, got '{}'", s));
+        let (hour, minute) = s.trim().split_once(':').ok_or_else(invalid)?;
+        let hour: u8 = hour.parse().map_err(|_| invalid())?;
+        let minute: u8 = minute.parse().map_err(|_| invalid())?;
+        if hour > 23 || minute > 59 {
+            return Err(invalid());
+        }
+        Ok(Self { hour, minute })
+    }
+}
+
+/// 按客户端IP放行或拒绝请求的网段列表
+#[derive(Debug, Clone, Default)]
+pub struct AccessList {
+    pub allow: Vec<IpNet>,
+    pub deny: Vec<IpNet>,
+    /// 无法解析的条目，启动时报错
+    invalid: Vec<String>,
+}
+
+impl AccessList {
+    /// 条目为 CIDR 网段或单个IP；`@path` 从文件读取，每行一条，`#` 之后为注释
+    pub fn new<S: AsRef<str>>(allow: &[S], deny: &[S]) -> Self {
+        let mut invalid = Vec::new();
+        let allow = parse_networks(allow, &mut invalid);
+        let deny = parse_networks(deny, &mut invalid);
+        Self { allow, deny, invalid }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// 拒绝列表优先；配置了允许列表时，只放行其中的地址
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        if self.deny.iter().any(|net| net.contains(&ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|net| net.contains(&ip))
+    }
+
+    pub fn validate(&self, name: &str) -> Result<(), ConfigError> {
+        match self.invalid.as_slice() {
+            [] => Ok(()),
+            invalid => Err(ConfigError::Invalid(format!("{} has invalid entries: {}", name, invalid.join(", ")))),
+        }
+    }
+}
+
+fn parse_networks<S: AsRef<str>>(entries: &[S], invalid: &mut Vec<String>) -> Vec<IpNet> {
+    let mut lines = Vec::new();
+    for entry in entries.iter().map(|entry| entry.as_ref().trim()) {
+        match entry.strip_prefix('@') {
+            Some(path) => match std::fs::read_to_string(path) {
+                Ok(content) => lines.extend(content.lines()
+                    .map(|line| line.split('#').next().unwrap_or_default().trim().to_string())
+                    .filter(|line| !line.is_empty())),
+                Err(e) => invalid.push(format!("{} ({})", entry, e)),
+            },
+            None => lines.push(entry.to_string()),
+        }
+    }
+    lines.into_iter()
+        .filter_map(|entry| match entry.parse::<IpNet>().or_else(|_| entry.parse::<IpAddr>().map(IpNet::from)) {
+            Ok(network) => Some(network.trunc()),
+            Err(_) => {
+                invalid.push(entry);
+                None
+            }
+        })
+        .collect()
+}
+
+// 全局配置
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub data_dir: PathBuf,
+    /// 收到关闭信号后等待在途请求完成的最长时间
+    pub shutdown_timeout: Duration,
+    /// PEM格式的证书链，与私钥同时设置时启用HTTPS
+    pub tls_cert_path: Option<PathBuf>,
+    pub tls_key_path: Option<PathBuf>,
+    pub log_format: LogFormat,
+    pub privacy_mode: PrivacyMode,
+    /// 是否按 Accept-Encoding 压缩响应
+    pub compression: bool,
+    /// 小于该字节数的响应不压缩
+    pub compression_min_size: u16,
+    /// 允许跨域访问的来源，为空时不输出CORS头，`*` 表示任意来源
+    pub cors_allow_origins: Vec<String>,
+    /// 预检请求的缓存时间
+    pub cors_max_age: Duration,
+    /// 单次DNS解析的超时时间，超时后会重试一次
+    pub dns_timeout: Duration,
+    /// 指定的DNS服务器，为空时使用系统配置
+    pub dns_servers: Vec<IpAddr>,
+    /// 是否允许 localhost、intranet 这类不含点的主机名
+    pub allow_single_label_hosts: bool,
+    /// 同时处理的最大请求数，超出时直接返回 503
+    pub max_in_flight: usize,
+    /// 单个请求的最长处理时间，超时返回 504
+    pub request_timeout: Duration,
+    /// 批量查询单次最多包含的主机数
+    pub batch_max_size: usize,
+    /// 批量查询时同时处理的主机数
+    pub batch_parallelism: usize,
+    /// 流式批量查询单次最多处理的行数
+    pub stream_max_rows: usize,
+    /// /events/self 重新查询并推送的间隔
+    pub events_interval: Duration,
+    /// GET /stats 保留的统计时长，为零时不统计也不注册该路由
+    pub stats_window: Duration,
+    /// 管理接口令牌，与 admin_client_ca 都未设置时不注册 /admin 管理路由
+    pub admin_token: Option<String>,
+    /// 签发管理客户端证书的CA（PEM），启用 TLS 时出示其签发证书的连接无需令牌即可访问管理接口
+    pub admin_client_ca: Option<PathBuf>,
+    /// HTTP 监听地址，每个地址一个监听套接字
+    pub bind: Vec<SocketAddr>,
+    /// 单独的管理端口，设置后 /healthz、/metrics 和管理接口只在这里提供
+    pub admin_bind: Option<SocketAddr>,
+    /// gRPC 服务监听地址，未设置时不启动（需要 grpc 特性）
+    pub grpc_bind: Option<SocketAddr>,
+    /// 数据库自动更新间隔，为 0 时不自动更新
+    pub db_update_interval: Duration,
+    /// 设置后改为每天在该本地时间（前后随机 15 分钟）更新
+    pub db_update_at: Option<DailyTime>,
+    /// 更新数据库时保留的旧版本数量，可通过管理接口回滚
+    pub db_keep_generations: usize,
+    /// 监视数据目录，数据库文件被外部替换时自动重新加载
+    pub watch_data_dir: bool,
+    /// 为 false 时不下载也不定时更新数据库，由外部维护数据目录
+    pub db_auto_update: bool,
+    /// 下载 GeoLite2-City 还是 GeoLite2-Country
+    pub db_profile: DbProfile,
+    /// 在 /metrics 中按国家代码统计查询，注重隐私的部署可以关闭
+    pub metrics_country_breakdown: bool,
+    /// 为 false 时不补“省”“市”后缀、不生成 regions_short，地区名称与数据库一致
+    pub cn_localization: bool,
+    /// 为 true 时 `as` 中缺失的号码和说明直接省略，号码和名称都没有时不输出 `as`，而不是输出 0 或重复名称
+    pub asn_omit_unknown: bool,
+    /// 超过该耗时的请求输出一条带各阶段耗时的警告日志，为 0 时不输出
+    pub slow_request: Duration,
+    /// 查询和其他公开接口的客户端访问控制，对应 CLIENT_ALLOW / CLIENT_DENY
+    pub client_acl: AccessList,
+    /// 管理接口的访问控制，对应 ADMIN_ALLOW / ADMIN_DENY，不受 client_acl 影响
+    pub admin_acl: AccessList,
+    pub private_target_policy: PrivateTargetPolicy,
+    /// POST 请求体的最大字节数
+    pub max_body_bytes: usize,
+    /// 是否接受 HTTP/2：明文端口按前言识别，HTTPS 通过 ALPN 协商；默认关闭，只提供 HTTP/1.1
+    pub http2: bool,
+    /// HTTP/2 单个连接上同时处理的最大流数
+    pub http2_max_concurrent_streams: u32,
+    /// 接受连接后设置 TCP_NODELAY
+    pub tcp_nodelay: bool,
+    /// HTTP/1.1 长连接的空闲超时，为 0 时不限制
+    pub keep_alive_timeout: Duration,
+    /// 同一对端地址的最大并发连接数，为 0 时不限制
+    pub max_connections_per_ip: usize,
+    /// 经纬度保留的小数位数，为 None 时原样输出
+    pub coord_precision: Option<u8>,
+    /// 查询结果的缓存时长，从计算时算起；为 0 时不缓存
+    pub result_cache_ttl: Duration,
+    /// 结果缓存按估算内存占用的上限，超出时淘汰较少使用的条目
+    pub result_cache_max_bytes: u64,
+    /// 正常关闭时把命中最多的缓存结果写入快照，下次启动且数据库未变时恢复
+    pub cache_snapshot: bool,
+    /// 快照文件，未设置时为数据目录下的 result_cache.msgpack
+    pub cache_snapshot_path: Option<PathBuf>,
+    /// 快照最多保存的条目数
+    pub cache_snapshot_max_entries: usize,
+    /// 命中的条目超过 TTL 的这一比例后返回缓存的结果，同时在后台重新计算；为 0 时不提前刷新
+    pub result_cache_refresh_ahead: f64,
+    /// 等待后台刷新的最大条目数，队列已满时放弃刷新
+    pub result_cache_refresh_queue: usize,
+    /// 定期把命中最多的IP写入数据目录的 hot_ips.txt，启动时在后台预先查询这些IP
+    pub cache_warmup: bool,
+    /// hot_ips.txt 最多记录的IP数
+    pub cache_warmup_top_k: usize,
+    /// 启动预热时同时进行的查询数
+    pub cache_warmup_concurrency: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from(DEFAULT_DATA_DIR),
+            shutdown_timeout: Duration::from_secs(10),
+            tls_cert_path: None,
+            tls_key_path: None,
+            log_format: LogFormat::default(),
+            privacy_mode: PrivacyMode::default(),
+            compression: true,
+            compression_min_size: 1024,
+            cors_allow_origins: Vec::new(),
+            cors_max_age: Duration::from_secs(3600),
+            dns_timeout: Duration::from_millis(2000),
+            dns_servers: Vec::new(),
+            allow_single_label_hosts: false,
+            max_in_flight: 1024,
+            request_timeout: Duration::from_millis(5000),
+            batch_max_size: 100,
+            batch_parallelism: 16,
+            stream_max_rows: 1_000_000,
+            events_interval: Duration::from_secs(30),
+            stats_window: Duration::from_secs(24 * 3600),
+            admin_token: None,
+            admin_client_ca: None,
+            bind: vec![SocketAddr::from(([0, 0, 0, 0], 8080))],
+            admin_bind: None,
+            grpc_bind: None,
+            db_update_interval: Duration::from_secs(24 * 3600),
+            db_update_at: None,
+            db_keep_generations: 2,
+            watch_data_dir: false,
+            db_auto_update: true,
+            db_profile: DbProfile::default(),
+            metrics_country_breakdown: true,
+            cn_localization: true,
+            asn_omit_unknown: false,
+            slow_request: Duration::from_millis(500),
+            client_acl: AccessList::default(),
+            admin_acl: AccessList::default(),
+            private_target_policy: PrivateTargetPolicy::default(),
+            max_body_bytes: 64 * 1024,
+            http2: false,
+            http2_max_concurrent_streams: 200,
+            tcp_nodelay: false,
+            keep_alive_timeout: Duration::ZERO,
+            max_connections_per_ip: 0,
+            coord_precision: None,
+            result_cache_ttl: Duration::ZERO,
+            result_cache_max_bytes: 64 * 1024 * 1024,
+            cache_snapshot: false,
+            cache_snapshot_path: None,
+            cache_snapshot_max_entries: 10_000,
+            result_cache_refresh_ahead: 0.8,
+            result_cache_refresh_queue: 1024,
+            cache_warmup: false,
+            cache_warmup_top_k: 5000,
+            cache_warmup_concurrency: 4,
+        }
+    }
+}
+
+fn env_path(key: &str) -> Option<PathBuf> {
+    std::env::var_os(key)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+/// 默认的数据目录
+pub const DEFAULT_DATA_DIR: &str = "data";
+
+// 旧版本和其他部署方式常用的数据目录，只用于启动时提示数据库放错了位置
+const LEGACY_DATA_DIRS: [&str; 2] = ["../data", "/usr/local/share/ipgeo"];
+
+/// 按 MMDB_PATH（兼容旧版本）、IPGEO_DATA_DIR、默认值的顺序选择数据目录
+pub fn data_dir_from_env() -> PathBuf {
+    env_path("MMDB_PATH")
+        .or_else(|| env_path("IPGEO_DATA_DIR"))
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_DATA_DIR))
+}
+
+/// 所有可能存放数据库的目录：环境变量指定的目录、默认目录和旧版本的位置，已去重
+pub fn known_data_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    let candidates = [env_path("MMDB_PATH"), env_path("IPGEO_DATA_DIR")].into_iter().flatten()
+        .chain(std::iter::once(DEFAULT_DATA_DIR).chain(LEGACY_DATA_DIRS).map(PathBuf::from));
+    for dir in candidates {
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+// 全局单例
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+impl Config {
+    /// 从环境变量读取配置，未设置或无法解析的值使用默认值
+    pub fn from_env() -> Self {
+        Self::from_settings(&Settings::default())
+    }
+
+    /// 读取配置文件（可选）并以环境变量覆盖，任何一项无法解析或整体校验失败都返回错误，错误中给出文件行号或变量名
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let settings = match path {
+            Some(path) => Settings::from_file(path)?,
+            None => Settings::default(),
+        };
+        let config = Self::from_settings(&settings);
+        settings.check()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_settings(settings: &Settings) -> Self {
+        let default = Self::default();
+        Self {
+            data_dir: env_path("MMDB_PATH").unwrap_or_else(|| settings.parse("IPGEO_DATA_DIR", default.data_dir.clone())),
+            shutdown_timeout: Duration::from_secs(settings.parse("SHUTDOWN_TIMEOUT_SECS", default.shutdown_timeout.as_secs())),
+            tls_cert_path: settings.optional("TLS_CERT_PATH", None),
+            tls_key_path: settings.optional("TLS_KEY_PATH", None),
+            log_format: settings.parse("LOG_FORMAT", default.log_format),
+            privacy_mode: settings.parse("PRIVACY_MODE", default.privacy_mode),
+            compression: settings.flag("COMPRESSION", default.compression),
+            compression_min_size: settings.parse("COMPRESSION_MIN_SIZE", default.compression_min_size),
+            cors_allow_origins: settings.strings("CORS_ALLOW_ORIGINS"),
+            cors_max_age: Duration::from_secs(settings.parse("CORS_MAX_AGE_SECS", default.cors_max_age.as_secs())),
+            dns_timeout: Duration::from_millis(settings.parse("DNS_TIMEOUT_MS", default.dns_timeout.as_millis() as u64)),
+            dns_servers: settings.list("DNS_SERVERS", default.dns_servers),
+            allow_single_label_hosts: settings.flag("ALLOW_SINGLE_LABEL_HOSTS", default.allow_single_label_hosts),
+            max_in_flight: settings.parse("MAX_IN_FLIGHT", default.max_in_flight).max(1),
+            request_timeout: Duration::from_millis(settings.parse("REQUEST_TIMEOUT_MS", default.request_timeout.as_millis() as u64)),
+            batch_max_size: settings.parse("BATCH_MAX_SIZE", default.batch_max_size),
+            batch_parallelism: settings.parse("BATCH_PARALLELISM", default.batch_parallelism).max(1),
+            stream_max_rows: settings.parse("STREAM_MAX_ROWS", default.stream_max_rows),
+            events_interval: Duration::from_secs(settings.parse("EVENTS_INTERVAL_SECS", default.events_interval.as_secs()).max(1)),
+            stats_window: Duration::from_secs(settings.parse("STATS_WINDOW_HOURS", default.stats_window.as_secs() / 3600) * 3600),
+            admin_token: settings.optional("ADMIN_TOKEN", None),
+            admin_client_ca: settings.optional("ADMIN_CLIENT_CA", None),
+            bind: settings.list("BIND", default.bind),
+            admin_bind: settings.optional("ADMIN_BIND", None),
+            grpc_bind: settings.optional("GRPC_BIND", None),
+            db_update_interval: Duration::from_secs(settings.parse("DB_UPDATE_INTERVAL_HOURS", default.db_update_interval.as_secs() / 3600) * 3600),
+            db_update_at: settings.optional("DB_UPDATE_AT", None),
+            db_keep_generations: settings.parse("DB_KEEP_GENERATIONS", default.db_keep_generations),
+            watch_data_dir: settings.flag("WATCH_DATA_DIR", default.watch_data_dir),
+            db_auto_update: settings.flag("DB_AUTO_UPDATE", default.db_auto_update),
+            db_profile: settings.parse("DB_PROFILE", default.db_profile),
+            metrics_country_breakdown: settings.flag("METRICS_COUNTRY_BREAKDOWN", default.metrics_country_breakdown),
+            cn_localization: settings.flag("CN_LOCALIZATION", default.cn_localization),
+            asn_omit_unknown: settings.flag("ASN_OMIT_UNKNOWN", default.asn_omit_unknown),
+            slow_request: Duration::from_millis(settings.parse("SLOW_REQUEST_MS", default.slow_request.as_millis() as u64)),
+            client_acl: AccessList::new(&settings.strings("CLIENT_ALLOW"), &settings.strings("CLIENT_DENY")),
+            admin_acl: AccessList::new(&settings.strings("ADMIN_ALLOW"), &settings.strings("ADMIN_DENY")),
+            private_target_policy: settings.parse("PRIVATE_TARGET_POLICY", default.private_target_policy),
+            max_body_bytes: settings.parse("MAX_BODY_BYTES", default.max_body_bytes),
+            http2: settings.flag("HTTP2", default.http2),
+            http2_max_concurrent_streams: settings.parse("HTTP2_MAX_CONCURRENT_STREAMS", default.http2_max_concurrent_streams),
+            tcp_nodelay: settings.flag("TCP_NODELAY", default.tcp_nodelay),
+            keep_alive_timeout: Duration::from_secs(settings.parse("KEEP_ALIVE_TIMEOUT_SECS", default.keep_alive_timeout.as_secs())),
+            max_connections_per_ip: settings.parse("MAX_CONNECTIONS_PER_IP", default.max_connections_per_ip),
+            coord_precision: settings.optional("COORD_PRECISION", None),
+            result_cache_ttl: Duration::from_secs(settings.parse("RESULT_CACHE_TTL_SECS", default.result_cache_ttl.as_secs())),
+            result_cache_max_bytes: settings.parse("RESULT_CACHE_MAX_BYTES", default.result_cache_max_bytes),
+            cache_snapshot: settings.flag("CACHE_SNAPSHOT", default.cache_snapshot),
+            cache_snapshot_path: settings.optional("CACHE_SNAPSHOT_PATH", None),
+            cache_snapshot_max_entries: settings.parse("CACHE_SNAPSHOT_MAX_ENTRIES", default.cache_snapshot_max_entries),
+            result_cache_refresh_ahead: settings.parse("RESULT_CACHE_REFRESH_AHEAD", default.result_cache_refresh_ahead).clamp(0.0, 1.0),
+            result_cache_refresh_queue: settings.parse("RESULT_CACHE_REFRESH_QUEUE", default.result_cache_refresh_queue),
+            cache_warmup: settings.flag("CACHE_WARMUP", default.cache_warmup),
+            cache_warmup_top_k: settings.parse("CACHE_WARMUP_TOP_K", default.cache_warmup_top_k),
+            cache_warmup_concurrency: settings.parse("CACHE_WARMUP_CONCURRENCY", default.cache_warmup_concurrency).max(1),
+        }
+    }
+
+    /// 合并后的有效配置，格式与配置文件相同；管理令牌以 `<redacted>` 代替，未设置的可选项省略
+    pub fn to_toml(&self) -> String {
+        let int = |n: u64| Value::Integer(n as i64);
+        let strings = |items: Vec<String>| Value::Array(items.into_iter().map(Value::String).collect());
+        let networks = |nets: &[IpNet]| strings(nets.iter().map(IpNet::to_string).collect());
+        let mut values = vec![
+            ("BIND", strings(self.bind.iter().map(SocketAddr::to_string).collect())),
+            ("SHUTDOWN_TIMEOUT_SECS", int(self.shutdown_timeout.as_secs())),
+            ("MAX_IN_FLIGHT", int(self.max_in_flight as u64)),
+            ("REQUEST_TIMEOUT_MS", int(self.request_timeout.as_millis() as u64)),
+            ("MAX_BODY_BYTES", int(self.max_body_bytes as u64)),
+            ("HTTP2", Value::Boolean(self.http2)),
+            ("HTTP2_MAX_CONCURRENT_STREAMS", int(u64::from(self.http2_max_concurrent_streams))),
+            ("TCP_NODELAY", Value::Boolean(self.tcp_nodelay)),
+            ("KEEP_ALIVE_TIMEOUT_SECS", int(self.keep_alive_timeout.as_secs())),
+            ("MAX_CONNECTIONS_PER_IP", int(self.max_connections_per_ip as u64)),
+            ("COMPRESSION", Value::Boolean(self.compression)),
+            ("COMPRESSION_MIN_SIZE", int(u64::from(self.compression_min_size))),
+            ("CORS_ALLOW_ORIGINS", strings(self.cors_allow_origins.clone())),
+            ("CORS_MAX_AGE_SECS", int(self.cors_max_age.as_secs())),
+            ("SLOW_REQUEST_MS", int(self.slow_request.as_millis() as u64)),
+            ("IPGEO_DATA_DIR", Value::String(self.data_dir.display().to_string())),
+            ("DB_AUTO_UPDATE", Value::Boolean(self.db_auto_update)),
+            ("DB_UPDATE_INTERVAL_HOURS", int(self.db_update_interval.as_secs() / 3600)),
+            ("DB_KEEP_GENERATIONS", int(self.db_keep_generations as u64)),
+            ("WATCH_DATA_DIR", Value::Boolean(self.watch_data_dir)),
+            ("DNS_TIMEOUT_MS", int(self.dns_timeout.as_millis() as u64)),
+            ("DNS_SERVERS", strings(self.dns_servers.iter().map(IpAddr::to_string).collect())),
+            ("ALLOW_SINGLE_LABEL_HOSTS", Value::Boolean(self.allow_single_label_hosts)),
+            ("BATCH_MAX_SIZE", int(self.batch_max_size as u64)),
+            ("BATCH_PARALLELISM", int(self.batch_parallelism as u64)),
+            ("STREAM_MAX_ROWS", int(self.stream_max_rows as u64)),
+            ("EVENTS_INTERVAL_SECS", int(self.events_interval.as_secs())),
+            ("CN_LOCALIZATION", Value::Boolean(self.cn_localization)),
+            ("ASN_OMIT_UNKNOWN", Value::Boolean(self.asn_omit_unknown)),
+            ("CLIENT_ALLOW", networks(&self.client_acl.allow)),
+            ("CLIENT_DENY", networks(&self.client_acl.deny)),
+            ("ADMIN_ALLOW", networks(&self.admin_acl.allow)),
+            ("ADMIN_DENY", networks(&self.admin_acl.deny)),
+            ("STATS_WINDOW_HOURS", int(self.stats_window.as_secs() / 3600)),
+            ("METRICS_COUNTRY_BREAKDOWN", Value::Boolean(self.metrics_country_breakdown)),
+            ("RESULT_CACHE_TTL_SECS", int(self.result_cache_ttl.as_secs())),
+            ("RESULT_CACHE_MAX_BYTES", int(self.result_cache_max_bytes)),
+            ("CACHE_SNAPSHOT", Value::Boolean(self.cache_snapshot)),
+            ("CACHE_SNAPSHOT_MAX_ENTRIES", int(self.cache_snapshot_max_entries as u64)),
+            ("RESULT_CACHE_REFRESH_AHEAD", Value::Float(self.result_cache_refresh_ahead)),
+            ("RESULT_CACHE_REFRESH_QUEUE", int(self.result_cache_refresh_queue as u64)),
+            ("CACHE_WARMUP", Value::Boolean(self.cache_warmup)),
+            ("CACHE_WARMUP_TOP_K", int(self.cache_warmup_top_k as u64)),
+            ("CACHE_WARMUP_CONCURRENCY", int(self.cache_warmup_concurrency as u64)),
+        ];
+        values.extend([
+            // 枚举按 serde 的小写名称输出
+            ("DB_PROFILE", Value::try_from(self.db_profile).ok()),
+            ("PRIVATE_TARGET_POLICY", Value::try_from(self.private_target_policy).ok()),
+            ("LOG_FORMAT", Value::try_from(self.log_format).ok()),
+            ("PRIVACY_MODE", Value::try_from(self.privacy_mode).ok()),
+            ("ADMIN_BIND", self.admin_bind.map(|addr| Value::String(addr.to_string()))),
+            ("GRPC_BIND", self.grpc_bind.map(|addr| Value::String(addr.to_string()))),
+            ("TLS_CERT_PATH", self.tls_cert_path.as_ref().map(|path| Value::String(path.display().to_string()))),
+            ("TLS_KEY_PATH", self.tls_key_path.as_ref().map(|path| Value::String(path.display().to_string()))),
+            ("DB_UPDATE_AT", self.db_update_at.map(|at| Value::String(at.to_string()))),
+            ("COORD_PRECISION", self.coord_precision.map(|places| int(u64::from(places)))),
+            ("ADMIN_TOKEN", self.admin_token.as_ref().map(|_| Value::String("<redacted>".to_string()))),
+            ("ADMIN_CLIENT_CA", self.admin_client_ca.as_ref().map(|path| Value::String(path.display().to_string()))),
+            ("CACHE_SNAPSHOT_PATH", self.cache_snapshot_path.as_ref().map(|path| Value::String(path.display().to_string()))),
+        ].into_iter().filter_map(|(key, value)| Some((key, value?))));
+        file::render(values)
+    }
+
+    /// 结果缓存快照的实际路径
+    pub fn cache_snapshot_file(&self) -> PathBuf {
+        self.cache_snapshot_path.clone().unwrap_or_else(|| self.data_dir.join("result_cache.msgpack"))
+    }
+
+    /// 是否配置了管理接口的凭据，都没有时不注册任何管理路由
+    pub fn admin_enabled(&self) -> bool {
+        self.admin_token.is_some() || self.admin_client_ca.is_some()
+    }
+
+    /// 启动前的整体校验：TLS 证书和私钥成对、访问控制网段可以解析、超时为正且与长连接超时相容
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.tls_paths()?.is_none() && self.admin_client_ca.is_some() {
+            return Err(ConfigError::Invalid("ADMIN_CLIENT_CA requires TLS_CERT_PATH and TLS_KEY_PATH".into()));
+        }
+        self.client_acl.validate("CLIENT_ALLOW/CLIENT_DENY")?;
+        self.admin_acl.validate("ADMIN_ALLOW/ADMIN_DENY")?;
+        for (name, timeout) in [("REQUEST_TIMEOUT_MS", self.request_timeout), ("DNS_TIMEOUT_MS", self.dns_timeout)] {
+            if timeout.is_zero() {
+                return Err(ConfigError::Invalid(format!("{} must be greater than 0", name)));
+            }
+        }
+        self.validate_keep_alive()
+    }
+
+    /// 空闲计时不区分请求是否在处理中，必须长于单个请求的最长处理时间
+    pub fn validate_keep_alive(&self) -> Result<(), ConfigError> {
+        if !self.keep_alive_timeout.is_zero() && self.keep_alive_timeout <= self.request_timeout {
+            return Err(ConfigError::Invalid(format!(
+                "KEEP_ALIVE_TIMEOUT_SECS ({}s) must be longer than REQUEST_TIMEOUT_MS ({}ms)",
+                self.keep_alive_timeout.as_secs(),
+                self.request_timeout.as_millis(),
+            )));
+        }
+        Ok(())
+    }
+
+    /// 证书和私钥必须同时配置，只配置其中一个视为错误
+    pub fn tls_paths(&self) -> Result<Option<(&Path, &Path)>, ConfigError> {
+        match (&self.tls_cert_path, &self.tls_key_path) {
+            (Some(cert), Some(key)) => Ok(Some((cert, key))),
+            (None, None) => Ok(None),
+            (Some(_), None) => Err(ConfigError::Invalid("TLS_CERT_PATH is set but TLS_KEY_PATH is missing".into())),
+            (None, Some(_)) => Err(ConfigError::Invalid("TLS_KEY_PATH is set but TLS_CERT_PATH is missing".into())),
+        }
+    }
+
+    /// 在启动时设置全局配置，只有第一次调用生效
+    pub fn init(config: Config) -> &'static Config {
+        CONFIG.get_or_init(|| config)
+    }
+
+    pub fn global() -> &'static Config {
+        CONFIG.get_or_init(Config::from_env)
+    }
+}
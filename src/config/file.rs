@@ -0,0 +1,183 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::str::FromStr;
+use toml::{Spanned, Table, Value};
+use super::ConfigError;
+
+/// 配置文件的分区和其中的设置，按对应的环境变量列出。文件中的键名为环境变量的小写形式，数据目录为 `data_dir`
+pub const SECTIONS: &[(&str, &[&str])] = &[
+    ("server", &[
+        "BIND", "ADMIN_BIND", "GRPC_BIND", "SHUTDOWN_TIMEOUT_SECS", "TLS_CERT_PATH", "TLS_KEY_PATH",
+        "MAX_IN_FLIGHT", "REQUEST_TIMEOUT_MS", "MAX_BODY_BYTES", "HTTP2", "HTTP2_MAX_CONCURRENT_STREAMS",
+        "TCP_NODELAY", "KEEP_ALIVE_TIMEOUT_SECS", "MAX_CONNECTIONS_PER_IP", "COMPRESSION", "COMPRESSION_MIN_SIZE",
+        "CORS_ALLOW_ORIGINS", "CORS_MAX_AGE_SECS", "SLOW_REQUEST_MS",
+    ]),
+    ("database", &[
+        "IPGEO_DATA_DIR", "DB_AUTO_UPDATE", "DB_PROFILE", "DB_UPDATE_INTERVAL_HOURS", "DB_UPDATE_AT",
+        "DB_KEEP_GENERATIONS", "WATCH_DATA_DIR",
+    ]),
+    ("lookup", &[
+        "DNS_TIMEOUT_MS", "DNS_SERVERS", "ALLOW_SINGLE_LABEL_HOSTS", "BATCH_MAX_SIZE", "BATCH_PARALLELISM",
+        "STREAM_MAX_ROWS", "EVENTS_INTERVAL_SECS", "PRIVATE_TARGET_POLICY", "COORD_PRECISION", "CN_LOCALIZATION",
+        "ASN_OMIT_UNKNOWN",
+    ]),
+    ("cache", &[
+        "RESULT_CACHE_TTL_SECS", "RESULT_CACHE_MAX_BYTES", "CACHE_SNAPSHOT", "CACHE_SNAPSHOT_PATH", "CACHE_SNAPSHOT_MAX_ENTRIES",
+        "CACHE_WARMUP", "CACHE_WARMUP_TOP_K", "CACHE_WARMUP_CONCURRENCY", "RESULT_CACHE_REFRESH_AHEAD", "RESULT_CACHE_REFRESH_QUEUE",
+    ]),
+    ("access", &["ADMIN_TOKEN", "ADMIN_CLIENT_CA", "CLIENT_ALLOW", "CLIENT_DENY", "ADMIN_ALLOW", "ADMIN_DENY"]),
+    ("logging", &["LOG_FORMAT", "PRIVACY_MODE", "STATS_WINDOW_HOURS", "METRICS_COUNTRY_BREAKDOWN"]),
+];
+
+/// 环境变量在配置文件中的键名
+pub fn file_key(env: &str) -> String {
+    match env {
+        "IPGEO_DATA_DIR" => "data_dir".to_string(),
+        env => env.to_ascii_lowercase(),
+    }
+}
+
+// 配置文件中的一项设置和它的位置，如 "config.toml:7: [server] bind"
+struct FileEntry {
+    origin: String,
+    value: Value,
+}
+
+/// 环境变量和配置文件中的设置，环境变量优先。设置了但无法解析的值记录下来，由 `check` 统一报告
+#[derive(Default)]
+pub(crate) struct Settings {
+    /// 按环境变量名索引
+    file: HashMap<&'static str, FileEntry>,
+    invalid: RefCell<Vec<String>>,
+}
+
+impl Settings {
+    /// 读取配置文件；语法错误、未知的分区和键直接返回错误，并带上行号
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| ConfigError::Invalid(format!("cannot read {}: {}", path.display(), e)))?;
+        let sections: BTreeMap<Spanned<String>, BTreeMap<Spanned<String>, Value>> = toml::from_str(&content)
+            .map_err(|e| ConfigError::Invalid(format!("{}: {}", path.display(), e.to_string().trim_end())))?;
+        let line = |key: &Spanned<String>| content[..key.span().start].matches('\n').count() + 1;
+
+        let mut file = HashMap::new();
+        let mut errors = Vec::new();
+        for (section, entries) in &sections {
+            let Some((_, settings)) = SECTIONS.iter().find(|(name, _)| name == section.get_ref()) else {
+                errors.push(format!("{}:{}: unknown section [{}]", path.display(), line(section), section.get_ref()));
+                continue;
+            };
+            for (key, value) in entries {
+                let origin = format!("{}:{}: [{}] {}", path.display(), line(key), section.get_ref(), key.get_ref());
+                if let Some(env) = settings.iter().find(|env| file_key(env) == *key.get_ref()) {
+                    file.insert(*env, FileEntry { origin, value: value.clone() });
+                    continue;
+                }
+                // 键放错了分区时指出正确的分区
+                match SECTIONS.iter().find(|(_, settings)| settings.iter().any(|env| file_key(env) == *key.get_ref())) {
+                    Some((other, _)) => errors.push(format!("{}: belongs in [{}]", origin, other)),
+                    None => errors.push(format!("{}: unknown key", origin)),
+                }
+            }
+        }
+        if !errors.is_empty() {
+            return Err(ConfigError::Invalid(errors.join("; ")));
+        }
+        Ok(Self { file, invalid: RefCell::default() })
+    }
+
+    // 环境变量优先，空值视为未设置；返回出处和值
+    fn raw(&self, key: &str) -> Option<(String, String)> {
+        if let Some(value) = std::env::var(key).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) {
+            return Some((key.to_string(), value));
+        }
+        let entry = self.file.get(key)?;
+        match value_string(&entry.value) {
+            Some(value) => Some((entry.origin.clone(), value)),
+            None => {
+                self.invalid.borrow_mut().push(format!("{}: expected a value, not a table", entry.origin));
+                None
+            }
+        }
+    }
+
+    fn parse_with<T>(&self, key: &str, default: T, parse: impl FnOnce(&str) -> Option<T>) -> T {
+        let Some((origin, value)) = self.raw(key) else {
+            return default;
+        };
+        match parse(&value) {
+            Some(parsed) => parsed,
+            None => {
+                self.invalid.borrow_mut().push(format!("{}: invalid value '{}'", origin, value));
+                default
+            }
+        }
+    }
+
+    pub fn parse<T: FromStr>(&self, key: &str, default: T) -> T {
+        self.parse_with(key, default, |v| v.parse().ok())
+    }
+
+    /// 布尔开关，接受 1/0、true/false、on/off、yes/no
+    pub fn flag(&self, key: &str, default: bool) -> bool {
+        self.parse_with(key, default, |v| match v.to_ascii_lowercase().as_str() {
+            "1" | "true" | "on" | "yes" => Some(true),
+            "0" | "false" | "off" | "no" => Some(false),
+            _ => None,
+        })
+    }
+
+    pub fn optional<T: FromStr>(&self, key: &str, default: Option<T>) -> Option<T> {
+        self.parse_with(key, default, |v| v.parse().ok().map(Some))
+    }
+
+    /// 逗号分隔的列表（配置文件中为数组），其中一项无法解析时整个列表无效
+    pub fn list<T: FromStr>(&self, key: &str, default: Vec<T>) -> Vec<T> {
+        self.parse_with(key, default, |v| split_list(v).map(|item| item.parse().ok()).collect())
+    }
+
+    pub fn strings(&self, key: &str) -> Vec<String> {
+        self.raw(key)
+            .map(|(_, value)| split_list(&value).map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+
+    /// 报告所有设置了但无法解析的值
+    pub fn check(&self) -> Result<(), ConfigError> {
+        match self.invalid.borrow().as_slice() {
+            [] => Ok(()),
+            invalid => Err(ConfigError::Invalid(invalid.join("; "))),
+        }
+    }
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim).filter(|item| !item.is_empty())
+}
+
+// 配置文件中的值转换为与环境变量相同的写法，数组以逗号连接
+fn value_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.trim().to_string()),
+        Value::Array(items) => items.iter()
+            .map(value_string)
+            .collect::<Option<Vec<_>>>()
+            .map(|items| items.join(",")),
+        Value::Table(_) => None,
+        other => Some(other.to_string()),
+    }
+}
+
+/// 按分区输出配置文件，未列出的设置忽略
+pub fn render(values: Vec<(&str, Value)>) -> String {
+    let mut document = Table::new();
+    for (section, settings) in SECTIONS {
+        let table: Table = settings.iter()
+            .filter_map(|env| values.iter().find(|(key, _)| key == env))
+            .map(|(env, value)| (file_key(env), value.clone()))
+            .collect();
+        document.insert(section.to_string(), Value::Table(table));
+    }
+    toml::to_string(&document).unwrap_or_default()
+}
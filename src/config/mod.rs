@@ -0,0 +1,3 @@
+pub mod config;
+mod file;
+pub use config::*;
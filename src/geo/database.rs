@@ -1,83 +1,452 @@
 use std::path::{Path, PathBuf};
-use tokio::time::{Duration, interval};
-use std::time::SystemTime;
-use tracing::info;
+use futures::stream::{self, StreamExt};
+use once_cell::sync::Lazy;
+use tokio::time::Duration;
+use std::time::{SystemTime, UNIX_EPOCH};
+use rand::Rng;
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use crate::config::{Config, DailyTime, DbProfile};
+use crate::metrics::Metrics;
+use crate::utils::format_epoch_date;
+use super::rir::{encode_allocations, parse_delegated, DELEGATED_URLS, RIR_FILE};
 
-const UPDATE_INTERVAL: Duration = Duration::from_secs(86400); // 24小时
+const DAY_SECS: u64 = 86400;
+
+/// 编译进二进制的 asn_info.json，数据目录中没有该文件时使用
+pub const BUNDLED_ASN_INFO: &str = include_str!("../asn_info.json");
+
+// 只读挂载或没有写权限
+fn is_read_only(e: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    if e.raw_os_error() == Some(libc::EROFS) {
+        return true;
+    }
+    e.kind() == std::io::ErrorKind::PermissionDenied
+}
+
+// 固定时间更新时前后随机偏移的范围，避免多个实例同时请求镜像
+const UPDATE_JITTER_SECS: i64 = 15 * 60;
+
+// 同时下载的数据库数量
+const DOWNLOAD_CONCURRENCY: usize = 3;
+
+// 所有下载共用一个客户端，复用连接池
+static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(reqwest::Client::new);
 
 pub struct DatabaseManager {
     data_dir: PathBuf,
 }
 
 struct DatabaseUrl {
+    key: &'static str,
     name: &'static str,
     url: &'static str,
 }
 
-const DATABASE_URLS: [DatabaseUrl; 3] = [
+// 单个数据库的更新结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateStatus {
+    Downloaded,
+    Cached,
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct UpdateOutcome {
+    pub name: &'static str,
+    pub status: UpdateStatus,
+}
+
+/// 下载失败时的重试策略
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// 最多尝试的次数
+    pub attempts: u32,
+    /// 第一次重试前的等待时间，之后每次翻倍
+    pub backoff: Duration,
+    /// 单次下载的超时
+    pub attempt_timeout: Duration,
+    /// 所有尝试加起来的期限
+    pub deadline: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            backoff: Duration::from_secs(5),
+            attempt_timeout: Duration::from_secs(60),
+            deadline: Duration::from_secs(300),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // 第 attempt 次失败后的等待时间，带 ±25% 的随机抖动
+    fn delay_after(&self, attempt: u32) -> Duration {
+        let base = self.backoff.saturating_mul(1 << (attempt - 1).min(16));
+        base.mul_f64(rand::thread_rng().gen_range(0.75..=1.25))
+    }
+}
+
+// 单次下载失败的原因，retryable 表示可能是暂时性的问题
+#[derive(Debug)]
+struct DownloadError {
+    message: String,
+    retryable: bool,
+}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(e: reqwest::Error) -> Self {
+        // 超时、连接失败和传输中断可以重试；4xx 和无效地址重试也不会成功
+        let retryable = match e.status() {
+            Some(status) => status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS,
+            None => !e.is_builder() && !e.is_redirect(),
+        };
+        Self { message: e.to_string(), retryable }
+    }
+}
+
+impl From<std::io::Error> for DownloadError {
+    fn from(e: std::io::Error) -> Self {
+        Self { message: e.to_string(), retryable: false }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UpdateOptions {
+    /// 忽略文件新鲜度强制下载
+    pub force: bool,
+    /// 只更新指定的数据库（city、country、asn、geocn、rir），为空表示全部
+    pub only: Vec<String>,
+}
+
+// 由 RIR 统计文件生成的分配表，对应 UpdateOptions::only 中的键
+const RIR_KEY: &str = "rir";
+
+const DATABASE_URLS: [DatabaseUrl; 4] = [
     DatabaseUrl {
+        key: "city",
         name: "GeoLite2-City.mmdb",
         url: "https://github.com/P3TERX/GeoLite.mmdb/raw/download/GeoLite2-City.mmdb",
     },
     DatabaseUrl {
+        key: "country",
+        name: "GeoLite2-Country.mmdb",
+        url: "https://github.com/P3TERX/GeoLite.mmdb/raw/download/GeoLite2-Country.mmdb",
+    },
+    DatabaseUrl {
+        key: "asn",
         name: "GeoLite2-ASN.mmdb",
         url: "https://github.com/P3TERX/GeoLite.mmdb/raw/download/GeoLite2-ASN.mmdb",
     },
     DatabaseUrl {
+        key: "geocn",
         name: "GeoCN.mmdb",
         url: "http://github.com/ljxi/GeoCN/releases/download/Latest/GeoCN.mmdb",
     },
 ];
 
+// 判断文件是否新鲜的时间窗口：自动更新间隔，关闭自动更新或按固定时间更新时为一天
+fn freshness_window() -> Duration {
+    let config = Config::global();
+    if config.db_update_interval.is_zero() || config.db_update_at.is_some() {
+        Duration::from_secs(DAY_SECS)
+    } else {
+        config.db_update_interval
+    }
+}
+
+// 文件存在且距上次修改不超过更新间隔
+async fn is_fresh(path: &Path) -> bool {
+    match tokio::fs::metadata(path).await.and_then(|m| m.modified()) {
+        Ok(modified) => {
+            let elapsed = SystemTime::now().duration_since(modified)
+                .unwrap_or(Duration::from_secs(0));
+            elapsed <= freshness_window()
+        }
+        Err(_) => false,
+    }
+}
+
+// 本地时区相对 UTC 的偏移秒数
+#[cfg(unix)]
+fn local_utc_offset(epoch: i64) -> i64 {
+    let time = epoch as libc::time_t;
+    // SAFETY: localtime_r 只写入传入的 tm 结构
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            0
+        } else {
+            tm.tm_gmtoff as i64
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn local_utc_offset(_epoch: i64) -> i64 {
+    0
+}
+
+/// 距离下一次每日更新的秒数。`local_secs` 是本地时间的 Unix 秒数，`jitter` 为随机偏移；
+/// 不足两个偏移范围时顺延到第二天，避免偏移后同一天内更新两次
+pub fn seconds_until_daily(at: DailyTime, local_secs: u64, jitter: i64) -> u64 {
+    let target = (at.seconds_of_day() as i64 + jitter).rem_euclid(DAY_SECS as i64) as u64;
+    let now = local_secs % DAY_SECS;
+    let until = (target + DAY_SECS - now) % DAY_SECS;
+    if (until as i64) < 2 * UPDATE_JITTER_SECS {
+        until + DAY_SECS
+    } else {
+        until
+    }
+}
+
+// 下一次自动更新前的等待时间，关闭自动更新时为 None
+fn next_update_delay(config: &Config) -> Option<Duration> {
+    if config.db_update_interval.is_zero() {
+        return None;
+    }
+    let Some(at) = config.db_update_at else {
+        return Some(config.db_update_interval);
+    };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+    let local = (now + local_utc_offset(now)).max(0) as u64;
+    let jitter = rand::thread_rng().gen_range(-UPDATE_JITTER_SECS..=UPDATE_JITTER_SECS);
+    Some(Duration::from_secs(seconds_until_daily(at, local, jitter)))
+}
+
+// 以本地时间输出计划的更新时间，如 "2024-05-01 03:42"
+fn format_local_time(delay: Duration) -> String {
+    let at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64 + delay.as_secs() as i64;
+    let local = (at + local_utc_offset(at)).max(0) as u64;
+    format!("{} {:02}:{:02}", format_epoch_date(local), local % DAY_SECS / 3600, local % 3600 / 60)
+}
+
+async fn download_once(url: &str, path: &Path, timeout: Duration) -> Result<(), DownloadError> {
+    let bytes = HTTP_CLIENT.get(url).timeout(timeout).send().await?.error_for_status()?.bytes().await?;
+    // 先写临时文件再改名，下载中断时不会留下损坏的数据库
+    let partial = path.with_extension("partial");
+    tokio::fs::write(&partial, bytes).await?;
+    install_database(&partial, path, Config::global().db_keep_generations)?;
+    Ok(())
+}
+
+// 第 n 代旧版本的路径，如 GeoCN.mmdb.1
+fn generation_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}", n));
+    path.with_file_name(name)
+}
+
+/// 用 staged 替换 path，原文件依次后移为 .1、.2……，只保留 keep 代
+pub fn install_database(staged: &Path, path: &Path, keep: usize) -> std::io::Result<()> {
+    // 删掉超出保留数量的旧版本（包括调小保留数量后遗留的）
+    let mut n = keep.max(1);
+    while generation_path(path, n).exists() {
+        std::fs::remove_file(generation_path(path, n))?;
+        n += 1;
+    }
+    if keep > 0 {
+        for n in (1..keep).rev() {
+            let older = generation_path(path, n);
+            if older.exists() {
+                std::fs::rename(&older, generation_path(path, n + 1))?;
+            }
+        }
+        if path.exists() {
+            std::fs::rename(path, generation_path(path, 1))?;
+        }
+    }
+    std::fs::rename(staged, path)
+}
+
+/// 把上一代版本换回原位，其余旧版本依次前移；当前文件被丢弃
+pub fn rollback_database(path: &Path) -> std::io::Result<()> {
+    let previous = generation_path(path, 1);
+    if !previous.exists() {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("no previous generation of {:?}", path)));
+    }
+    std::fs::rename(&previous, path)?;
+    let mut n = 2;
+    while generation_path(path, n).exists() {
+        std::fs::rename(generation_path(path, n), generation_path(path, n - 1))?;
+        n += 1;
+    }
+    Ok(())
+}
+
+/// 数据库文件的一代版本，0 为当前使用的文件
+#[derive(Debug, Clone, Serialize)]
+pub struct Generation {
+    pub generation: usize,
+    pub file: String,
+    /// 数据库元数据中的构建时间，文件无法解析时为 None
+    pub build_epoch: Option<u64>,
+}
+
+/// 列出 path 当前及保留的旧版本
+pub fn list_generations(path: &Path) -> Vec<Generation> {
+    (0..)
+        .map(|n| if n == 0 { path.to_path_buf() } else { generation_path(path, n) })
+        .take_while(|file| file.exists())
+        .enumerate()
+        .map(|(generation, file)| Generation {
+            generation,
+            file: file.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            build_epoch: maxminddb::Reader::open_readfile(&file).ok().map(|r| r.metadata.build_epoch),
+        })
+        .collect()
+}
+
+// 当前 DB_PROFILE 下载的数据库：full 下载 City，lite 改为下载 Country
+fn profile_databases() -> impl Iterator<Item = &'static DatabaseUrl> {
+    let skipped = match Config::global().db_profile {
+        DbProfile::Full => "country",
+        DbProfile::Lite => "city",
+    };
+    DATABASE_URLS.iter().filter(move |d| d.key != skipped)
+}
+
+/// known 中除 selected 之外含有数据库文件的目录，同一目录的不同写法按规范化后的路径比较
+pub fn misplaced_data_dirs(selected: &Path, known: &[PathBuf]) -> Vec<PathBuf> {
+    let canonical = |path: &Path| std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let selected = canonical(selected);
+    known.iter()
+        .filter(|dir| canonical(dir) != selected)
+        .filter(|dir| DATABASE_URLS.iter().any(|db| dir.join(db.name).is_file()))
+        .cloned()
+        .collect()
+}
+
+/// 自动下载的数据库文件名
+pub fn downloadable_databases() -> impl Iterator<Item = &'static str> {
+    profile_databases().map(|d| d.name)
+}
+
+/// 按键名（city、country、asn、geocn）或类型（City、Country、ASN、GeoCN）查找可下载的数据库文件名，不区分大小写
+pub fn downloadable_database(db: &str) -> Option<&'static str> {
+    profile_databases()
+        .find(|d| d.key.eq_ignore_ascii_case(db) || database_type(d.name).is_some_and(|t| t.eq_ignore_ascii_case(db)))
+        .map(|d| d.name)
+}
+
+/// 下载文件到 path，暂时性错误按策略退避重试，超过总期限后放弃
+pub async fn download_with_retry(url: &str, path: &Path, policy: &RetryPolicy) -> std::io::Result<()> {
+    with_retry(url, policy, || download_once(url, path, policy.attempt_timeout)).await?;
+    info!("Successfully downloaded database to {:?}", path);
+    Ok(())
+}
+
+// 只读入内存，由调用方处理后再写入数据目录
+async fn fetch_once(url: &str, timeout: Duration) -> Result<Vec<u8>, DownloadError> {
+    Ok(HTTP_CLIENT.get(url).timeout(timeout).send().await?.error_for_status()?.bytes().await?.into())
+}
+
+// 下载到内存，重试策略与 download_with_retry 相同
+async fn fetch_with_retry(url: &'static str, policy: RetryPolicy) -> std::io::Result<Vec<u8>> {
+    let timeout = policy.attempt_timeout;
+    with_retry(url, &policy, || fetch_once(url, timeout)).await
+}
+
+// 重复执行 attempt 直到成功、遇到不可重试的错误或超过期限
+async fn with_retry<T, F, Fut>(url: &str, policy: &RetryPolicy, mut attempt_fn: F) -> std::io::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, DownloadError>>,
+{
+    let deadline = tokio::time::Instant::now() + policy.deadline;
+
+    let mut attempt = 1;
+    loop {
+        info!("Downloading database from {} (attempt {}/{})", url, attempt, policy.attempts);
+        let result = match tokio::time::timeout_at(deadline, attempt_fn()).await {
+            Ok(result) => result,
+            Err(_) => Err(DownloadError { message: "overall deadline exceeded".to_string(), retryable: false }),
+        };
+        let error = match result {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+
+        let delay = policy.delay_after(attempt);
+        let out_of_time = tokio::time::Instant::now() + delay >= deadline;
+        if !error.retryable || attempt >= policy.attempts || out_of_time {
+            let error = format!("Failed to download after {} attempt(s): {}", attempt, error.message);
+            return Err(std::io::Error::other(error));
+        }
+        warn!("Download attempt {}/{} for {} failed: {}; retrying in {:.1}s",
+            attempt, policy.attempts, url, error.message, delay.as_secs_f64());
+        Metrics::incr(&Metrics::global().db_download_retries);
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// 没有下载地址、放在数据目录中才启用的商业数据库：(文件名, reload_database 类型)
+pub const OPTIONAL_DATABASES: [(&str, &str); 2] = [
+    ("GeoIP2-ISP.mmdb", "ISP"),
+    ("GeoIP2-Domain.mmdb", "Domain"),
+];
+
+/// 根据文件名得到 reload_database 使用的数据库类型
+pub fn database_type(name: &str) -> Option<&'static str> {
+    match name {
+        "GeoLite2-ASN.mmdb" => Some("ASN"),
+        "GeoCN.mmdb" => Some("GeoCN"),
+        "GeoLite2-City.mmdb" => Some("City"),
+        "GeoLite2-Country.mmdb" => Some("Country"),
+        _ => OPTIONAL_DATABASES.iter()
+            .find(|(file, _)| *file == name)
+            .map(|(_, db_type)| *db_type),
+    }
+}
+
 impl DatabaseManager {
     pub fn new(data_dir: PathBuf) -> Self {
         Self { data_dir }
     }
 
     async fn download_database(&self, url: &str, path: &Path) -> std::io::Result<()> {
-        info!("Downloading database from {}", url);
-        let response = reqwest::get(url).await.map_err(|e| std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Failed to download: {}", e)
-        ))?;
-        
-        let bytes = response.bytes().await.map_err(|e| std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Failed to get bytes: {}", e)
-        ))?;
-        
-        tokio::fs::write(path, bytes).await?;
-        info!("Successfully downloaded database to {:?}", path);
-        Ok(())
+        download_with_retry(url, path, &RetryPolicy::default()).await
     }
 
     async fn copy_asn_info(&self) -> std::io::Result<()> {
         let target_path = self.data_dir.join("asn_info.json");
         if !target_path.exists() {
             info!("Copying asn_info.json to data directory");
-            // 首先尝试从源代码目录复制
+            // 首先尝试从源代码目录复制，找不到时使用编译进二进制的版本
             let source_paths = [
                 "src/asn_info.json",
                 "../src/asn_info.json",
                 "/usr/local/share/ipgeo/asn_info.json",
             ];
-
+            let mut content = None;
             for source_path in source_paths {
-                if let Ok(content) = tokio::fs::read_to_string(source_path).await {
-                    tokio::fs::write(&target_path, content).await?;
-                    info!("Successfully copied asn_info.json to {:?}", target_path);
-                    return Ok(());
+                if let Ok(found) = tokio::fs::read_to_string(source_path).await {
+                    content = Some(found);
+                    break;
                 }
             }
+            let content = content.unwrap_or_else(|| BUNDLED_ASN_INFO.to_string());
 
-            // 如果找不到源文件，创建一个空的JSON对象
-            info!("Could not find asn_info.json source, creating empty file");
-            tokio::fs::write(&target_path, "{}").await?;
+            // 只读的数据目录不算错误，init_asn_data 会直接使用内置的数据
+            match tokio::fs::write(&target_path, content).await {
+                Ok(()) => info!("Successfully copied asn_info.json to {:?}", target_path),
+                Err(e) if is_read_only(&e) => info!("Data directory is read-only, using the bundled asn_info.json"),
+                Err(e) => return Err(e),
+            }
         }
         Ok(())
     }
 
-    pub async fn update_databases(&self) -> std::io::Result<()> {
+    pub async fn update_databases(&self) -> std::io::Result<Vec<UpdateOutcome>> {
+        self.update_databases_with(&UpdateOptions::default()).await
+    }
+
+    pub async fn update_databases_with(&self, options: &UpdateOptions) -> std::io::Result<Vec<UpdateOutcome>> {
         if !self.data_dir.exists() {
             tokio::fs::create_dir_all(&self.data_dir).await?;
         }
@@ -85,74 +454,131 @@ impl DatabaseManager {
         // 确保 asn_info.json 存在
         self.copy_asn_info().await?;
 
-        for db in &DATABASE_URLS {
-            let db_path = self.data_dir.join(db.name);
-            let should_update = if db_path.exists() {
-                if let Ok(metadata) = tokio::fs::metadata(&db_path).await {
-                    if let Ok(modified) = metadata.modified() {
-                        let elapsed = SystemTime::now().duration_since(modified)
-                            .unwrap_or(Duration::from_secs(0));
-                        elapsed > UPDATE_INTERVAL
-                    } else {
-                        true
-                    }
-                } else {
-                    true
+        // 各数据库独立下载和重新加载，一个失败不影响其他数据库；buffered 保持结果顺序
+        // city 和 country 都指当前 DB_PROFILE 使用的那一个
+        let is_location = |key: &str| key == "city" || key == "country";
+        let tasks: Vec<_> = profile_databases()
+            .filter(|db| options.only.is_empty() || options.only.iter().any(|k| k == db.key || (is_location(k) && is_location(db.key))))
+            .map(|db| self.update_database(db, options.force))
+            .collect();
+        let mut outcomes: Vec<UpdateOutcome> = stream::iter(tasks)
+            .buffered(DOWNLOAD_CONCURRENCY)
+            .collect()
+            .await;
+        if options.only.is_empty() || options.only.iter().any(|k| k == RIR_KEY) {
+            outcomes.push(self.update_rir(options.force).await);
+        }
+
+        Ok(outcomes)
+    }
+
+    async fn update_rir(&self, force: bool) -> UpdateOutcome {
+        let path = self.data_dir.join(RIR_FILE);
+        let status = if !force && is_fresh(&path).await {
+            UpdateStatus::Cached
+        } else {
+            match self.build_rir_table(&path).await {
+                Ok(()) => UpdateStatus::Downloaded,
+                Err(e) => {
+                    warn!("Failed to build {}: {}", RIR_FILE, e);
+                    UpdateStatus::Failed(e.to_string())
                 }
-            } else {
-                true
-            };
+            }
+        };
+        Metrics::global().record_db_update(RIR_FILE, matches!(status, UpdateStatus::Failed(_)));
+        UpdateOutcome { name: RIR_FILE, status }
+    }
 
-            if should_update {
-                if let Ok(()) = self.download_database(db.url, &db_path).await {
+    // 下载五个统计文件，任何一个失败都保留原有的 rir.bin；解析和聚合在阻塞线程池中进行
+    async fn build_rir_table(&self, path: &Path) -> std::io::Result<()> {
+        let texts = futures::future::try_join_all(
+            DELEGATED_URLS.iter().map(|(_, url)| fetch_with_retry(url, RetryPolicy::default()))
+        ).await?;
+
+        let encoded = tokio::task::spawn_blocking(move || {
+            let mut allocations = Vec::new();
+            for ((registry, _), text) in DELEGATED_URLS.iter().zip(texts) {
+                let (parsed, skipped) = parse_delegated(&String::from_utf8_lossy(&text));
+                if parsed.is_empty() {
+                    return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("no allocations in the {} statistics file", registry)));
+                }
+                if skipped > 0 {
+                    warn!("Skipped {} unparseable lines in the {} statistics file", skipped, registry);
+                }
+                allocations.extend(parsed);
+            }
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            Ok(encode_allocations(allocations, now))
+        }).await.map_err(std::io::Error::other)??;
+
+        let partial = path.with_extension("partial");
+        tokio::fs::write(&partial, encoded).await?;
+        install_database(&partial, path, Config::global().db_keep_generations)?;
+        super::rir::load_rir_table(&self.data_dir)
+    }
+
+    async fn update_database(&self, db: &DatabaseUrl, force: bool) -> UpdateOutcome {
+        let db_path = self.data_dir.join(db.name);
+        let should_update = force || !is_fresh(&db_path).await;
+
+        let status = if !should_update {
+            UpdateStatus::Cached
+        } else {
+            match self.download_database(db.url, &db_path).await {
+                Ok(()) => {
                     // 下载成功后重新加载数据库
-                    let db_type = match db.name {
-                        "GeoLite2-ASN.mmdb" => "ASN",
-                        "GeoCN.mmdb" => "GeoCN",
-                        "GeoLite2-City.mmdb" => "City",
-                        _ => continue,
-                    };
-                    
-                    if let Err(e) = super::geo::reload_database(db_type, &db_path) {
-                        info!("Failed to reload {} database: {}", db_type, e);
+                    if let Some(db_type) = database_type(db.name) {
+                        if let Err(e) = super::geo::reload_database(db_type, &db_path) {
+                            info!("Failed to reload {} database: {}", db_type, e);
+                        }
                     }
-                } else {
-                    info!("Failed to download {}", db.name);
+                    UpdateStatus::Downloaded
+                }
+                Err(e) => {
+                    warn!("Failed to download {}: {}", db.name, e);
+                    UpdateStatus::Failed(e.to_string())
                 }
             }
-        }
-
-        Ok(())
+        };
+        Metrics::global().record_db_update(db.name, matches!(status, UpdateStatus::Failed(_)));
+        UpdateOutcome { name: db.name, status }
     }
 
+    /// 数据目录中的文件，读取、更新和重新加载都只使用这一个目录
     pub fn get_data_file_path(&self, filename: &str) -> PathBuf {
-        let data_paths = [
-            self.data_dir.as_path(),
-            Path::new("../data"),
-            Path::new("/usr/local/share/ipgeo"),
-        ];
-        
-        for base_path in data_paths.iter() {
-            let file_path = base_path.join(filename);
-            if file_path.exists() {
-                return file_path;
-            }
-        }
-        
         self.data_dir.join(filename)
     }
 
-    pub async fn start_auto_update(&self) {
+    pub async fn start_auto_update(&self, shutdown: CancellationToken) {
         let data_dir = self.data_dir.clone();
         let manager = DatabaseManager::new(data_dir);
         
         tokio::spawn(async move {
-            let mut interval = interval(UPDATE_INTERVAL);
             loop {
-                interval.tick().await;
+                // 每次按当前时间重新计算，系统休眠或改时间后不会累积偏差
+                let Some(delay) = next_update_delay(Config::global()) else {
+                    info!("Database auto-update is disabled");
+                    break;
+                };
+                info!("Next database update at {} (in {}m)", format_local_time(delay), delay.as_secs() / 60);
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {},
+                    _ = shutdown.cancelled() => {
+                        info!("Database auto-update task stopped");
+                        break;
+                    }
+                }
                 info!("Starting scheduled database update");
-                if let Err(e) = manager.update_databases().await {
-                    info!("Failed to update databases: {}", e);
+                tokio::select! {
+                    result = manager.update_databases() => {
+                        if let Err(e) = result {
+                            info!("Failed to update databases: {}", e);
+                        }
+                    }
+                    _ = shutdown.cancelled() => {
+                        info!("Database auto-update cancelled during shutdown");
+                        break;
+                    }
                 }
             }
         });
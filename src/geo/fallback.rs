@@ -0,0 +1,143 @@
+//! 内置的国家级 IP 段表，由 build.rs 从 `start,end,country[,name]` 格式的 CSV 生成，
+//! 启用 embedded-fallback 特性时编译进二进制。这个文件只依赖标准库，build.rs 通过 #[path] 复用。
+//!
+//! 格式：`IPFB1`，IPv4 段数、IPv6 段数、国家名数（u32 大端），
+//! 然后是按起始地址排序的段（起止地址 + 两字节国家代码），最后是国家代码、名称长度和名称。
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+const MAGIC: &[u8] = b"IPFB1";
+const V4_RECORD: usize = 4 + 4 + 2;
+const V6_RECORD: usize = 16 + 16 + 2;
+
+/// 解析后的段表，只引用原始字节
+#[derive(Debug, Clone, Copy)]
+pub struct FallbackTable<'a> {
+    v4: &'a [u8],
+    v6: &'a [u8],
+    names: &'a [u8],
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    bytes.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+impl<'a> FallbackTable<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Option<Self> {
+        let body = bytes.strip_prefix(MAGIC)?;
+        let v4_len = read_u32(body, 0)? as usize * V4_RECORD;
+        let v6_len = read_u32(body, 4)? as usize * V6_RECORD;
+        let rest = body.get(12..)?;
+        let v4 = rest.get(..v4_len)?;
+        let v6 = rest.get(v4_len..v4_len + v6_len)?;
+        let names = rest.get(v4_len + v6_len..)?;
+        Some(Self { v4, v6, names })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.v4.is_empty() && self.v6.is_empty()
+    }
+
+    /// ip 所在段的两字母国家代码
+    pub fn lookup(&self, ip: IpAddr) -> Option<&'a str> {
+        let (records, size, key) = match ip {
+            IpAddr::V4(ip) => (self.v4, V4_RECORD, u128::from(u32::from(ip))),
+            IpAddr::V6(ip) => (self.v6, V6_RECORD, u128::from(ip)),
+        };
+        let width = (size - 2) / 2;
+        let field = |record: &[u8], offset: usize| {
+            record[offset..offset + width].iter().fold(0u128, |acc, b| acc << 8 | u128::from(*b))
+        };
+        let record_at = |index: usize| &records[index * size..(index + 1) * size];
+        // 二分查找最后一个起始地址不大于 ip 的段
+        let (mut low, mut high) = (0, records.len() / size);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if field(record_at(mid), 0) <= key {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        let record = record_at(low.checked_sub(1)?);
+        if field(record, width) < key {
+            return None;
+        }
+        std::str::from_utf8(&record[size - 2..]).ok()
+    }
+
+    /// 国家代码对应的名称，CSV 中没有名称时为 None
+    pub fn name(&self, code: &str) -> Option<&'a str> {
+        let mut rest = self.names;
+        while rest.len() >= 3 {
+            let len = rest[2] as usize;
+            let name = rest.get(3..3 + len)?;
+            if &rest[..2] == code.as_bytes() {
+                return std::str::from_utf8(name).ok();
+            }
+            rest = &rest[3 + len..];
+        }
+        None
+    }
+}
+
+/// 把 CSV 编码为段表。无法解析的行被跳过，返回编码结果和跳过的行数
+pub fn encode_csv(csv: &str) -> (Vec<u8>, usize) {
+    let mut v4: Vec<(u32, u32, [u8; 2])> = Vec::new();
+    let mut v6: Vec<(u128, u128, [u8; 2])> = Vec::new();
+    let mut names: BTreeMap<[u8; 2], String> = BTreeMap::new();
+    let mut skipped = 0;
+
+    for line in csv.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim().trim_matches('"')).collect();
+        let parsed = match fields.as_slice() {
+            [start, end, code, rest @ ..] if code.len() == 2 && code.is_ascii() && !code.eq_ignore_ascii_case("ZZ") => {
+                let code = code.to_ascii_uppercase();
+                let code = [code.as_bytes()[0], code.as_bytes()[1]];
+                match (start.parse::<IpAddr>(), end.parse::<IpAddr>()) {
+                    (Ok(IpAddr::V4(start)), Ok(IpAddr::V4(end))) if start <= end => {
+                        v4.push((start.into(), end.into(), code));
+                        Some((code, rest.first()))
+                    }
+                    (Ok(IpAddr::V6(start)), Ok(IpAddr::V6(end))) if start <= end => {
+                        v6.push((start.into(), end.into(), code));
+                        Some((code, rest.first()))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        match parsed {
+            Some((code, Some(name))) if !name.is_empty() && name.len() <= u8::MAX as usize => {
+                names.entry(code).or_insert_with(|| name.to_string());
+            }
+            Some(_) => {}
+            None => skipped += 1,
+        }
+    }
+    v4.sort_unstable();
+    v6.sort_unstable();
+
+    let mut out = MAGIC.to_vec();
+    out.extend_from_slice(&(v4.len() as u32).to_be_bytes());
+    out.extend_from_slice(&(v6.len() as u32).to_be_bytes());
+    out.extend_from_slice(&(names.len() as u32).to_be_bytes());
+    for (start, end, code) in v4 {
+        out.extend_from_slice(&start.to_be_bytes());
+        out.extend_from_slice(&end.to_be_bytes());
+        out.extend_from_slice(&code);
+    }
+    for (start, end, code) in v6 {
+        out.extend_from_slice(&start.to_be_bytes());
+        out.extend_from_slice(&end.to_be_bytes());
+        out.extend_from_slice(&code);
+    }
+    for (code, name) in names {
+        out.extend_from_slice(&code);
+        out.push(name.len() as u8);
+        out.extend_from_slice(name.as_bytes());
+    }
+    (out, skipped)
+}
@@ -1,337 +1,1211 @@
-use std::sync::{Arc, RwLock};
-use maxminddb::{geoip2, MaxMindDBError};
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock, RwLock, RwLockReadGuard};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use maxminddb::geoip2;
 use std::net::IpAddr;
-use tokio::net::lookup_host;
 use std::path::Path;
-use crate::models::{IpInfo, AsnInfo as ModelAsnInfo, Location, CountryInfo, IpGeoError};
-use crate::utils::{get_country, get_short_name};
-use crate::cache::CacheManager;
-use tracing::info;
+use std::time::{Instant, SystemTime};
+use crate::models::{IpInfo, AsnFormat, AsnInfo as ModelAsnInfo, ComparedAnswer, GeoComparison, Location, CityInfo, ContinentInfo, CountryInfo, Detail, GeoCNInfo, IpGeoError, LookupOptions, NameLocales, NetworkCategory, RegionStyle, RirInfo, Traits};
+use crate::utils::{china_province_code, format_epoch_date, format_rfc3339, get_city, get_continent, get_country, get_des, china_isp, get_short_name, is_link_local, push_region_name, is_private_ip, isp_network_type, private_network, mask_input, network_for, normalize_host, parse_ip_lenient, round_coord, sanitize_echo};
+use crate::cache::{AsnType, CacheManager, RefreshQueue, ResultCache, SingleFlight};
+use crate::metrics::{timing, Metrics};
+use crate::config::{known_data_dirs, Config, DbProfile};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, debug_span, dispatcher, field, info, warn, Dispatch, Instrument};
 use once_cell::sync::Lazy;
+use serde::Serialize;
+use super::lock::{read_recovering, write_recovering};
 
-// 使用Arc<RwLock>替代OnceLock
-static ASN_READER: Lazy<Arc<RwLock<maxminddb::Reader<Vec<u8>>>>> = Lazy::new(|| {
-    Arc::new(RwLock::new(maxminddb::Reader::open_readfile("data/GeoLite2-ASN.mmdb")
-        .expect("Failed to open ASN database")))
-});
+// 数据库读取器，文件缺失时为 None，对应的查询阶段会被跳过
+type SharedReader = Arc<RwLock<Option<maxminddb::Reader<Vec<u8>>>>>;
 
-static GEOCN_READER: Lazy<Arc<RwLock<maxminddb::Reader<Vec<u8>>>>> = Lazy::new(|| {
-    Arc::new(RwLock::new(maxminddb::Reader::open_readfile("data/GeoCN.mmdb")
-        .expect("Failed to open GeoCN database")))
-});
+fn open_from_data_dir(name: &str) -> SharedReader {
+    let path = Config::global().data_dir.join(name);
+    let reader = if path.exists() {
+        match maxminddb::Reader::open_readfile(&path) {
+            Ok(reader) => Some(reader),
+            Err(e) => {
+                warn!("Failed to open database {:?}: {}", path, e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    Arc::new(RwLock::new(reader))
+}
 
-static CITY_READER: Lazy<Arc<RwLock<maxminddb::Reader<Vec<u8>>>>> = Lazy::new(|| {
-    Arc::new(RwLock::new(maxminddb::Reader::open_readfile("data/GeoLite2-City.mmdb")
-        .expect("Failed to open City database")))
-});
+static ASN_READER: Lazy<SharedReader> = Lazy::new(|| open_from_data_dir("GeoLite2-ASN.mmdb"));
+
+static GEOCN_READER: Lazy<SharedReader> = Lazy::new(|| open_from_data_dir("GeoCN.mmdb"));
+
+static CITY_READER: Lazy<SharedReader> = Lazy::new(|| open_from_data_dir("GeoLite2-City.mmdb"));
+
+// DB_PROFILE=lite 时代替 City 的国家数据库
+static COUNTRY_READER: Lazy<SharedReader> = Lazy::new(|| open_from_data_dir("GeoLite2-Country.mmdb"));
+
+// 可选的商业数据库
+static ISP_READER: Lazy<SharedReader> = Lazy::new(|| open_from_data_dir("GeoIP2-ISP.mmdb"));
+
+static DOMAIN_READER: Lazy<SharedReader> = Lazy::new(|| open_from_data_dir("GeoIP2-Domain.mmdb"));
+
+fn reader_slot(db_type: &str) -> Option<&'static SharedReader> {
+    match db_type {
+        "ASN" => Some(&ASN_READER),
+        "GeoCN" => Some(&GEOCN_READER),
+        "City" => Some(&CITY_READER),
+        "Country" => Some(&COUNTRY_READER),
+        "ISP" => Some(&ISP_READER),
+        "Domain" => Some(&DOMAIN_READER),
+        _ => None,
+    }
+}
+
+// 槽位对应的数据库类型，用于日志和指标
+fn slot_name(slot: &SharedReader) -> &'static str {
+    ["ASN", "GeoCN", "City", "Country", "ISP", "Domain"].into_iter()
+        .find(|name| reader_slot(name).is_some_and(|known| Arc::ptr_eq(known, slot)))
+        .unwrap_or("unknown")
+}
+
+/// 读取器的读锁；锁中毒时恢复并计数，不再静默跳过该数据库
+pub fn read_reader(slot: &SharedReader) -> RwLockReadGuard<'_, Option<maxminddb::Reader<Vec<u8>>>> {
+    read_recovering(slot, || slot_name(slot))
+}
+
+// 数据代数：每次替换数据库、ASN 信息或覆盖表时加一，之前算出的结果随之作废
+static DATA_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// 当前的数据代数
+pub fn data_generation() -> u64 {
+    DATA_GENERATION.load(Ordering::Acquire)
+}
+
+pub(crate) fn bump_data_generation() {
+    DATA_GENERATION.fetch_add(1, Ordering::AcqRel);
+}
+
+/// 已加载的各数据库的构建时间（Unix 秒），按数据库类型索引；用于判断进程重启前算出的结果是否仍然有效
+pub fn database_epochs() -> BTreeMap<String, u64> {
+    ["ASN", "GeoCN", "City", "Country", "ISP", "Domain"].into_iter()
+        .filter_map(|db_type| {
+            let slot = reader_slot(db_type)?;
+            let reader = read_reader(slot);
+            Some((db_type.to_string(), reader.as_ref()?.metadata.build_epoch))
+        })
+        .collect()
+}
 
 // 添加重新加载函数
 pub fn reload_database(db_type: &str, path: &Path) -> std::io::Result<()> {
-    match db_type {
-        "ASN" => {
-            let new_reader = maxminddb::Reader::open_readfile(path)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-            if let Ok(mut reader) = ASN_READER.write() {
-                *reader = new_reader;
-                info!("ASN database reloaded successfully");
-            }
-        }
-        "GeoCN" => {
-            let new_reader = maxminddb::Reader::open_readfile(path)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-            if let Ok(mut reader) = GEOCN_READER.write() {
-                *reader = new_reader;
-                info!("GeoCN database reloaded successfully");
-            }
-        }
-        "City" => {
-            let new_reader = maxminddb::Reader::open_readfile(path)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-            if let Ok(mut reader) = CITY_READER.write() {
-                *reader = new_reader;
-                info!("City database reloaded successfully");
-            }
+    let slot = reader_slot(db_type)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "Unknown database type"))?;
+    let new_reader = maxminddb::Reader::open_readfile(path)
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    *write_recovering(slot, || slot_name(slot)) = Some(new_reader);
+    bump_data_generation();
+    Metrics::incr(&Metrics::global().db_reloads);
+    info!("{} database reloaded successfully", db_type);
+    Ok(())
+}
+
+/// 从指定目录加载所有已知的数据库文件，不存在的跳过；用于测试和离线环境
+pub fn load_databases_from(dir: &Path) -> std::io::Result<()> {
+    let names = ["GeoLite2-ASN.mmdb", "GeoCN.mmdb", "GeoLite2-City.mmdb", "GeoLite2-Country.mmdb"]
+        .into_iter()
+        .chain(super::database::OPTIONAL_DATABASES.iter().map(|(name, _)| *name));
+    for name in names {
+        let path = dir.join(name);
+        if let (true, Some(db_type)) = (path.exists(), super::database::database_type(name)) {
+            reload_database(db_type, &path)?;
         }
-        _ => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Unknown database type")),
     }
-    Ok(())
+    super::rir::load_rir_table(dir)
 }
 
 // 修改获取读取器的函数
-pub fn get_asn_reader() -> Arc<RwLock<maxminddb::Reader<Vec<u8>>>> {
+pub fn get_asn_reader() -> SharedReader {
     ASN_READER.clone()
 }
 
-pub fn get_geocn_reader() -> Arc<RwLock<maxminddb::Reader<Vec<u8>>>> {
+pub fn get_geocn_reader() -> SharedReader {
     GEOCN_READER.clone()
 }
 
-pub fn get_city_reader() -> Arc<RwLock<maxminddb::Reader<Vec<u8>>>> {
+pub fn get_city_reader() -> SharedReader {
     CITY_READER.clone()
 }
 
-pub async fn init_mmdb_readers() -> std::io::Result<()> {
-    let data_dir = Path::new("data");
-    let db_manager = super::database::DatabaseManager::new(data_dir.to_path_buf());
-    
-    // 初始化ASN数据
-    let asn_data = {
-        let path = db_manager.get_data_file_path("asn_info.json");
-        let data = std::fs::read_to_string(&path)
-            .unwrap_or_else(|_| panic!("Failed to read ASN info at {:?}", path));
-        serde_json::from_str(&data)
-            .unwrap_or_else(|_| panic!("Failed to parse ASN info at {:?}", path))
+pub fn get_country_reader() -> SharedReader {
+    COUNTRY_READER.clone()
+}
+
+// DB_PROFILE 对应的地理位置数据库文件名，用于错误信息
+fn location_database() -> &'static str {
+    match Config::global().db_profile {
+        DbProfile::Full => "GeoLite2-City.mmdb",
+        DbProfile::Lite => "GeoLite2-Country.mmdb",
+    }
+}
+
+/// 地理位置查询使用的读取器：City 已加载时用 City，否则用 Country，都没有时为空的 City 槽位
+pub fn get_location_reader() -> SharedReader {
+    if !is_loaded(&CITY_READER) && is_loaded(&COUNTRY_READER) {
+        get_country_reader()
+    } else {
+        get_city_reader()
+    }
+}
+
+pub fn get_isp_reader() -> SharedReader {
+    ISP_READER.clone()
+}
+
+pub fn get_domain_reader() -> SharedReader {
+    DOMAIN_READER.clone()
+}
+
+/// 加载 asn_info.json 到缓存，不触发数据库下载
+pub fn init_asn_data(db_manager: &super::database::DatabaseManager) -> std::io::Result<()> {
+    let path = db_manager.get_data_file_path("asn_info.json");
+    let data = match std::fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            info!("No asn_info.json in the data directory, using the bundled copy");
+            super::database::BUNDLED_ASN_INFO.to_string()
+        }
+        Err(e) => return Err(std::io::Error::new(
+            e.kind(),
+            format!("Failed to read ASN info at {:?}: {}", path, e)
+        )),
     };
-    
-    // 初始化缓存
+    let asn_data = serde_json::from_str(&data).map_err(|e| std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("Failed to parse ASN info at {:?}: {}", path, e)
+    ))?;
+
     CacheManager::global().init_asn_data(&asn_data);
-    
-    // 初始更新数据库
-    db_manager.update_databases().await?;
-    
-    // 启动自动更新任务
-    db_manager.start_auto_update().await;
-    
+    bump_data_generation();
+    Ok(())
+}
+
+/// 数据库的加载状态，用于健康检查
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseState {
+    /// 首次下载尚未完成，部分数据库还不可用
+    Initializing,
+    /// City（或 Country）、ASN 和 GeoCN 都已加载
+    Ready,
+    /// 首次下载已结束但仍有数据库缺失，只能返回部分结果
+    Degraded,
+}
+
+// 启动时的首次数据库更新是否已经结束（无论成功与否）
+static INITIAL_UPDATE_DONE: AtomicBool = AtomicBool::new(false);
+
+fn is_loaded(slot: &SharedReader) -> bool {
+    read_reader(slot).is_some()
+}
+
+pub fn database_state() -> DatabaseState {
+    let location_loaded = is_loaded(&CITY_READER) || is_loaded(&COUNTRY_READER);
+    if location_loaded && [&*ASN_READER, &*GEOCN_READER].into_iter().all(is_loaded) {
+        DatabaseState::Ready
+    } else if INITIAL_UPDATE_DONE.load(Ordering::Acquire) {
+        DatabaseState::Degraded
+    } else {
+        DatabaseState::Initializing
+    }
+}
+
+/// 加载已有的数据，并在后台下载缺失或过期的数据库；不等待下载完成，
+/// 数据目录为空时服务照常启动，下载完成后数据库自动生效
+pub async fn init_mmdb_readers(shutdown: CancellationToken) -> std::io::Result<()> {
+    let data_dir = Config::global().data_dir.clone();
+    let db_manager = super::database::DatabaseManager::new(data_dir.clone());
+    info!("Using data directory {:?}", data_dir);
+    for dir in super::database::misplaced_data_dirs(&data_dir, &known_data_dirs()) {
+        warn!(
+            "Found databases in {:?}, but the data directory is {:?}; set MMDB_PATH, IPGEO_DATA_DIR or --data-dir to use them",
+            dir, data_dir
+        );
+    }
+
+    // 文件损坏时先跳过，下载之后再试一次
+    let asn_loaded = match init_asn_data(&db_manager) {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("ASN info not loaded yet: {}", e);
+            false
+        }
+    };
+    if let Err(e) = super::overrides::load_overrides(&data_dir) {
+        warn!("Ignoring geolocation overrides: {}", e);
+    }
+    if let Err(e) = super::rir::load_rir_table(&data_dir) {
+        warn!("Ignoring RIR allocation table: {}", e);
+    }
+    info!("Database state at startup: {:?}", database_state());
+    if Config::global().watch_data_dir {
+        if let Err(e) = super::watcher::watch_data_dir(&data_dir, super::watcher::WATCH_DEBOUNCE, shutdown.clone()) {
+            warn!("Failed to watch {:?} for database changes: {}", data_dir, e);
+        }
+    }
+
+    // 数据目录由外部维护（如只读挂载）时不创建目录、不下载，也不启动定时更新
+    if !Config::global().db_auto_update {
+        info!("Database auto-update is disabled, databases are managed externally");
+        INITIAL_UPDATE_DONE.store(true, Ordering::Release);
+        return Ok(());
+    }
+
+    spawn_updater(db_manager, asn_loaded, shutdown);
     Ok(())
 }
 
+/// 后台下载缺失或过期的数据库，完成后按配置定时更新，shutdown 取消时退出
+pub(crate) fn spawn_updater(db_manager: super::database::DatabaseManager, asn_loaded: bool, shutdown: CancellationToken) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        tokio::select! {
+            result = db_manager.update_databases() => {
+                if let Err(e) = result {
+                    warn!("Initial database update failed: {}", e);
+                }
+            }
+            _ = shutdown.cancelled() => return,
+        }
+        INITIAL_UPDATE_DONE.store(true, Ordering::Release);
+        if !asn_loaded {
+            if let Err(e) = init_asn_data(&db_manager) {
+                warn!("Failed to load ASN info: {}", e);
+            }
+        }
+        info!("Initial database update finished: {:?}", database_state());
+
+        // 启动自动更新任务
+        db_manager.start_auto_update(shutdown).await;
+    })
+}
+
+// 键中带上数据代数，重新加载之后开始的查询不会共享旧数据算出的结果
+static IP_FLIGHTS: Lazy<SingleFlight<(IpAddr, LookupOptions, u64), Option<Computed>>> = Lazy::new(SingleFlight::new);
+
+// 查询结果和计算时间，共享给其他调用者时 timestamp 仍是最初的计算时间
+type Computed = (IpInfo, SystemTime);
+
+// 后台提前刷新同时重新计算的条目数
+const REFRESH_CONCURRENCY: usize = 4;
+
+// 结果缓存的刷新队列，首次需要刷新时在当前运行时中启动
+static REFRESH_QUEUE: OnceLock<RefreshQueue> = OnceLock::new();
+
+fn refresh_queue() -> &'static RefreshQueue {
+    REFRESH_QUEUE.get_or_init(|| {
+        RefreshQueue::spawn(Config::global().result_cache_refresh_queue, REFRESH_CONCURRENCY, |(ip, options)| async move {
+            compute_ip_info(ip, options).await;
+        })
+    })
+}
+
+/// 按默认的详细程度查询IP信息
 pub async fn get_ip_info(ip_str: &str) -> Result<IpInfo, IpGeoError> {
-    let ip: IpAddr = ip_str.parse()?;
-    
-    // 查询ASN信息
-    let (asn, asn_type) = if let Ok(reader) = get_asn_reader().read() {
-        if let Ok(asn) = reader.lookup::<geoip2::Asn>(ip) {
-            let number = asn.autonomous_system_number.unwrap_or(0);
-            let org_name = asn.autonomous_system_organization.unwrap_or("").to_string();
-            
-            // 从缓存获取ASN详细信息
-            let (name, asn_type) = if let Some((name, type_info)) = CacheManager::global().get_asn_info(number) {
-                (name.into_string(), Some(type_info))
-            } else {
-                (org_name, None)
-            };
-            
-            (Some(ModelAsnInfo {
-                number,
-                name: name.clone(),
-                info: name,
-            }), asn_type)
-        } else {
-            (None, None)
+    get_ip_info_with(ip_str, LookupOptions::default()).await
+}
+
+/// 查询IP信息，同一IP、同样选项的并发查询共享一次结果
+pub async fn get_ip_info_with(ip_str: &str, options: LookupOptions) -> Result<IpInfo, IpGeoError> {
+    let ip = parse_ip_lenient(ip_str)?;
+    let echo = options.echo();
+    // 先换算成实际精度并去掉只影响输出写法的选项，结果相同的请求共享同一次查询
+    let options = LookupOptions {
+        asn_format: AsnFormat::default(),
+        echo: None,
+        ..options.with_precision_limit(Config::global().coord_precision)
+    };
+    // City（或 Country）和 ASN 都没有加载时结果没有意义，通常是首次启动还在下载
+    let location_loaded = is_loaded(&CITY_READER) || is_loaded(&COUNTRY_READER);
+    if !location_loaded && !is_loaded(&ASN_READER) && embedded_table().is_none() {
+        return Err(IpGeoError::DatabaseUnavailable(location_database()));
+    }
+    let metrics = Metrics::global();
+    Metrics::incr(&metrics.ip_lookups);
+
+    let cached = ResultCache::global()
+        .and_then(|cache| Some((cache, cache.get(&(ip, options), data_generation())?)));
+    let (computed, shared) = match cached {
+        Some((cache, hit)) => {
+            // 接近过期的条目照常返回，同时在后台重新计算
+            if cache.needs_refresh(&hit, Config::global().result_cache_refresh_ahead) {
+                refresh_queue().enqueue((ip, options));
+            }
+            (Some((hit.info.clone(), hit.computed_at)), true)
         }
-    } else {
-        (None, None)
+        None => compute_ip_info(ip, options).await,
     };
+    // 数据库已加载但查询时全部出错，与未加载同样处理
+    let (mut info, computed_at) = computed.ok_or(IpGeoError::DatabaseUnavailable(location_database()))?;
+    // 输出规范的小写形式，不带 zone 后缀
+    info.ip = ip.to_string();
+    info.asn = info.asn.and_then(|asn| asn.for_output(Config::global().asn_omit_unknown));
+    if echo {
+        info.timestamp = Some(format_rfc3339(computed_at));
+        info.cached = shared;
+    }
+    Ok(info)
+}
 
-    // 构建IP信息
-    let mut info = IpInfo {
-        ip: ip_str.to_string(),
-        asn,
-        addr: String::new(),
-        location: None,
-        country: None,
-        registered_country: None,
-        regions: None,
-        regions_short: None,
-        r#type: None,
+// 查询并写入结果缓存。同一IP、同样选项的并发查询（包括后台刷新）共享一次结果，返回结果以及它是否来自其他调用者
+async fn compute_ip_info(ip: IpAddr, options: LookupOptions) -> (Option<Computed>, bool) {
+    let generation = data_generation();
+    let span = debug_span!("get_ip_info", ip.family = ip_family(ip), shared = field::Empty);
+    let lookup = || async move {
+        let computed = lookup_ip_info(ip, options).await.map(|info| (info, SystemTime::now()));
+        // 只缓存完整的结果，有阶段出错时下次重新查询
+        if let (Some(cache), Some((info, computed_at))) = (ResultCache::global(), &computed) {
+            if info.warnings.is_empty() {
+                cache.insert((ip, options), info.clone(), *computed_at, generation);
+            }
+        }
+        computed
     };
+    let (computed, shared) = IP_FLIGHTS.run((ip, options, generation), lookup)
+        .instrument(span.clone())
+        .await;
+    span.record("shared", shared);
+    if shared {
+        timing::mark_shared();
+        Metrics::incr(&Metrics::global().ip_dedup_hits);
+    }
+    (computed, shared)
+}
+
+// 数据库类型和构建日期，如 "GeoLite2-City 2024-05-01"，用于标注字段来源
+fn source_label(reader: &maxminddb::Reader<Vec<u8>>) -> String {
+    format!("{} {}", reader.metadata.database_type, format_epoch_date(reader.metadata.build_epoch))
+}
+
+// asn_info.json 中的网络类型没有构建日期
+const ASN_INFO_SOURCE: &str = "asn_info.json";
+
+/// 按字段组记录数据来源，后写入的来源覆盖先前的
+#[derive(Debug, Default)]
+struct Provenance(BTreeMap<String, String>);
+
+impl Provenance {
+    fn record(&mut self, group: &str, present: bool, source: Option<&str>) {
+        if let (true, Some(source)) = (present, source) {
+            self.0.insert(group.to_string(), source.to_string());
+        }
+    }
+}
+
+fn ip_family(ip: IpAddr) -> &'static str {
+    if ip.is_ipv4() { "ipv4" } else { "ipv6" }
+}
+
+// 在阻塞线程池中查询一个数据库，span 挂在调用方的 span 下。
+// 阻塞线程上没有调用方的 dispatcher，进入 span 前先装上，否则退出时释放不了 span
+async fn db_lookup<T: Send + 'static>(
+    db: &'static str,
+    stage: &'static str,
+    answered: fn(&T) -> bool,
+    lookup: impl FnOnce() -> T + Send + 'static,
+) -> T {
+    let span = debug_span!("mmdb_lookup", db, answered = field::Empty);
+    let traced = (!span.is_disabled()).then(|| (span.clone(), dispatcher::get_default(Dispatch::clone)));
+    let start = Instant::now();
+    let task = tokio::task::spawn_blocking(move || match traced {
+        Some((span, dispatch)) => dispatcher::with_default(&dispatch, || span.in_scope(lookup)),
+        None => lookup(),
+    });
+    let result = task.await.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+    timing::record_stage(stage, start.elapsed());
+    if !span.is_disabled() {
+        span.record("answered", answered(&result));
+    }
+    result
+}
+
+/// 单个数据库查询阶段的失败原因，地址不在库中不算失败
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StageFailure {
+    /// 数据库没有加载
+    Unavailable,
+    /// 查询时读取或解码出错，通常是文件损坏
+    Error,
+}
+
+impl StageFailure {
+    fn label(self) -> &'static str {
+        match self {
+            StageFailure::Unavailable => "unavailable",
+            StageFailure::Error => "error",
+        }
+    }
+}
+
+// 地址不在库中时为 None，其余错误说明数据库本身有问题
+fn found<T>(result: Result<T, maxminddb::MaxMindDBError>) -> Result<Option<T>, StageFailure> {
+    match result {
+        Ok(record) => Ok(Some(record)),
+        Err(maxminddb::MaxMindDBError::AddressNotFoundError(_)) => Ok(None),
+        Err(e) => {
+            debug!("Database lookup failed: {}", e);
+            Err(StageFailure::Error)
+        }
+    }
+}
+
+/// 查询过程中失败的数据库，转换为响应中的 warnings 并计入指标
+#[derive(Debug, Default)]
+struct StageFailures(Vec<(&'static str, StageFailure)>);
+
+impl StageFailures {
+    // 记录失败并返回空结果，其余阶段的数据照常合并
+    fn settle<T: Default>(&mut self, db: &'static str, result: Result<T, StageFailure>) -> T {
+        result.unwrap_or_else(|failure| {
+            self.0.push((db, failure));
+            T::default()
+        })
+    }
+
+    fn failed(&self, db: &str) -> bool {
+        self.0.iter().any(|(name, _)| *name == db)
+    }
+
+    fn record_metrics(&self) {
+        for (db, failure) in &self.0 {
+            Metrics::global().record_stage_failure(db, failure.label());
+        }
+    }
+
+    fn into_warnings(self) -> Vec<String> {
+        self.0.into_iter().map(|(db, failure)| match failure {
+            StageFailure::Unavailable => format!("{} database unavailable", db),
+            StageFailure::Error => format!("{} lookup error", db),
+        }).collect()
+    }
+}
+
+// 各数据库互不依赖，分别在阻塞线程池中查询后再合并；minimal 只查 ASN 和国家。
+// 个别数据库失败时返回其余数据并附带 warnings，City 和 ASN 都失败时返回 None
+async fn lookup_ip_info(ip: IpAddr, options: LookupOptions) -> Option<IpInfo> {
+    let LookupOptions { detail, sources: with_sources, regions: style, locales, .. } = options;
+    let locales = locales.unwrap_or_default();
+    let (asn, extra, city, cn) = tokio::join!(
+        db_lookup("ASN", "asn_lookup", |asn: &Result<AsnLookup, _>| asn.as_ref().is_ok_and(|asn| asn.asn.is_some()), move || lookup_asn(ip, with_sources)),
+        db_lookup("ISP", "isp_lookup", |extra: &IspDomain| extra.isp.is_some() || extra.domain.is_some(), move || match detail {
+            Detail::Minimal => IspDomain::default(),
+            _ => lookup_isp_domain(ip, with_sources),
+        }),
+        db_lookup("City", "city_lookup", |city: &Result<(IpInfo, _), _>| city.as_ref().is_ok_and(|(info, _)| info.country.is_some()), move || lookup_city(ip, detail, style, locales, with_sources)),
+        db_lookup("GeoCN", "geocn_lookup", |cn: &Result<Option<_>, _>| cn.as_ref().is_ok_and(Option::is_some), move || match detail {
+            Detail::Minimal => Ok(None),
+            _ => lookup_geocn(ip, with_sources),
+        }),
+    );
+    let mut failures = StageFailures::default();
+    let asn = failures.settle("ASN", asn);
+    let (mut info, city_source) = failures.settle("City", city);
+    let cn = failures.settle("GeoCN", cn);
+    failures.0.extend(extra.failed.iter().map(|db| (*db, StageFailure::Error)));
+    failures.record_metrics();
+    if failures.failed("City") && failures.failed("ASN") && embedded_table().is_none() {
+        return None;
+    }
+
+    let mut sources = Provenance::default();
+    let city_source = city_source.as_deref();
+    sources.record("country", info.country.is_some() || info.registered_country.is_some(), city_source);
+    sources.record("continent", info.continent.is_some(), city_source);
+    sources.record("location", info.location.is_some() || info.accuracy_radius.is_some(), city_source);
+    sources.record("postal", info.postal.is_some(), city_source);
+    sources.record("traits", info.traits.is_some(), city_source);
+    sources.record("city", info.city.is_some(), city_source);
+    sources.record("regions", info.regions.is_some(), city_source);
+
+    info.ip = ip.to_string();
+    (info.asn, info.r#type) = (asn.asn, asn.network_type);
+    sources.record("asn", info.asn.is_some(), asn.source.as_deref());
+    sources.record("type", info.r#type.is_some(), Some(ASN_INFO_SOURCE));
+
+    info.isp = extra.isp;
+    info.organization = extra.organization;
+    info.domain = extra.domain;
+    sources.record("isp", info.isp.is_some(), extra.isp_source.as_deref());
+    sources.record("organization", info.organization.is_some(), extra.isp_source.as_deref());
+    sources.record("domain", info.domain.is_some(), extra.domain_source.as_deref());
+
+    // 国内IP优先使用 GeoCN 的省市区与运营商信息，没有记录时保留 GeoLite2 的结果
+    let mut geocn_isp = None;
+    if let Some((cn, cn_source)) = cn {
+        geocn_isp = cn.isp.clone().filter(|isp| !isp.is_empty()).map(|isp| (isp, cn_source.clone()));
+        info.comparison = options.compare.then(|| compare_with_geolite2(&info, &cn));
+        apply_geocn(&mut info, cn, style, locales, &mut sources, cn_source.as_deref());
+    }
+    reconcile_china_isp(&mut info, geocn_isp, asn.source.as_deref(), &mut sources);
     
-    // 设置网络类型
-    if let Some(asn_type) = asn_type {
-        use crate::cache::AsnType;
-        info.r#type = Some(match asn_type {
-            AsnType::Type(t) => t.into_string(),
-            AsnType::Other => "其他网络".to_string(),
+    if failures.failed("City") && !is_loaded(&GEOCN_READER) {
+        apply_embedded_fallback(ip, &mut info, &mut sources);
+    }
+
+    // 本地修正优先于所有数据库
+    for group in super::overrides::apply_overrides(ip, &mut info) {
+        sources.record(group, true, Some(super::overrides::OVERRIDE_SOURCE));
+    }
+    // 覆盖的地区只有全称
+    if !style.full() {
+        info.regions = None;
+    }
+
+    info.category = info.r#type.as_deref().and_then(NetworkCategory::from_type);
+
+    // 数据中心的判断依据写入 sources，便于排查误判
+    let hosting_asn = info.asn.as_ref().is_some_and(|asn| asn.number.is_some_and(|number| CacheManager::global().is_hosting_asn(number)));
+    let hosting_type = info.category == Some(NetworkCategory::Hosting);
+    let datacenter_reason = if info.is_datacenter {
+        city_source.map(|source| format!("{} is_hosting_provider", source))
+    } else if hosting_asn {
+        Some(format!("{} hosting_asns", ASN_INFO_SOURCE))
+    } else if hosting_type {
+        Some(format!("{} type", ASN_INFO_SOURCE))
+    } else {
+        None
+    };
+    info.is_datacenter |= hosting_asn || hosting_type;
+    sources.record("is_datacenter", info.is_datacenter, datacenter_reason.as_deref());
+    sources.record("is_anonymous", info.is_anonymous, city_source);
+
+    // 覆盖的坐标同样按精度处理
+    if let (Some(places), Some(location)) = (options.precision, info.location.as_mut()) {
+        location.latitude = location.latitude.map(|v| round_coord(v, places));
+        location.longitude = location.longitude.map(|v| round_coord(v, places));
+    }
+
+    // 设置地址信息：IPv4 取 /16，IPv6 取 /32
+    if info.asn.is_some() {
+        let prefix_len = if ip.is_ipv4() { 16 } else { 32 };
+        info.addr = network_for(ip, prefix_len).to_string();
+    }
+
+    if options.rir || detail == Detail::Full {
+        let rir = super::rir::rir_lookup(ip);
+        sources.record("rir", rir.is_some(), rir.as_ref().map(|(_, source)| source.as_str()));
+        info.rir = rir.map(|(record, _)| RirInfo {
+            registry: record.registry.to_string(),
+            allocated: record.allocated,
+            cidr: record.cidr.to_string(),
         });
     }
 
-    // 查询地理位置信息
-    if let Ok(reader) = get_city_reader().read() {
-        if let Ok(city) = reader.lookup::<geoip2::City>(ip) {
-            // 处理位置信息
-            if let (Some(lat), Some(lon)) = (
-                city.location.as_ref().and_then(|l| l.latitude),
-                city.location.as_ref().and_then(|l| l.longitude)
-            ) {
-                info.location = Some(Location {
-                    latitude: Some(lat),
-                    longitude: Some(lon),
-                });
+    if detail == Detail::Full {
+        info.rdns = super::resolver::reverse_lookup(ip).await;
+        sources.record("rdns", info.rdns.is_some(), Some("DNS PTR"));
+    }
+
+    if with_sources {
+        info.sources = Some(sources.0);
+    }
+    info.warnings = failures.into_warnings();
+    Some(info)
+}
+
+// 内置国家表的来源和网络类型标记
+const EMBEDDED_FALLBACK: &str = "embedded-fallback";
+
+#[cfg(feature = "embedded-fallback")]
+static EMBEDDED_TABLE: Lazy<Option<super::fallback::FallbackTable<'static>>> = Lazy::new(|| {
+    super::fallback::FallbackTable::parse(include_bytes!(concat!(env!("OUT_DIR"), "/fallback.bin")))
+        .filter(|table| !table.is_empty())
+});
+
+#[cfg(feature = "embedded-fallback")]
+fn embedded_table() -> Option<super::fallback::FallbackTable<'static>> {
+    *EMBEDDED_TABLE
+}
+
+#[cfg(not(feature = "embedded-fallback"))]
+fn embedded_table() -> Option<super::fallback::FallbackTable<'static>> {
+    None
+}
+
+// City 和 GeoCN 都不可用时，用内置表给出国家和粗略的网段
+fn apply_embedded_fallback(ip: IpAddr, info: &mut IpInfo, sources: &mut Provenance) {
+    let Some(table) = embedded_table() else {
+        return;
+    };
+    let Some(code) = table.lookup(ip) else {
+        return;
+    };
+    info.country = Some(CountryInfo {
+        code: code.to_string(),
+        name: table.name(code).unwrap_or(code).to_string(),
+        is_eu: false,
+        confidence: None,
+    });
+    let prefix_len = if ip.is_ipv4() { 16 } else { 32 };
+    info.addr = network_for(ip, prefix_len).to_string();
+    info.r#type = Some(EMBEDDED_FALLBACK.to_string());
+    sources.record("country", true, Some(EMBEDDED_FALLBACK));
+    sources.record("type", true, Some(EMBEDDED_FALLBACK));
+}
+
+/// ASN 数据库的查询结果
+#[derive(Debug, Default)]
+struct AsnLookup {
+    asn: Option<ModelAsnInfo>,
+    /// 来自 asn_info.json 的网络类型
+    network_type: Option<String>,
+    source: Option<String>,
+}
+
+// 查询ASN编号、名称和网络类型
+fn lookup_asn(ip: IpAddr, with_source: bool) -> Result<AsnLookup, StageFailure> {
+    let reader = get_asn_reader();
+    let reader = read_reader(&reader);
+    let reader = reader.as_ref().ok_or(StageFailure::Unavailable)?;
+    let Some((asn, prefix_len)) = found(reader.lookup_prefix::<geoip2::Asn>(ip))? else {
+        return Ok(AsnLookup::default());
+    };
+    let route = Some(network_for(ip, prefix_len as u8).to_string());
+
+    let number = asn.autonomous_system_number;
+    let org_name = asn.autonomous_system_organization.unwrap_or("").to_string();
+    
+    // 从缓存获取ASN详细信息，不在列表中的按组织名称的关键词推断类型
+    let curated = number.and_then(|number| CacheManager::global().get_asn_info(number));
+    let (name, info, asn_type) = if let Some((name, type_info)) = curated {
+        let name = name.into_string();
+        (name.clone(), Some(name), Some(type_info))
+    } else {
+        let asn_type = CacheManager::global().classify_org(&org_name);
+        (org_name, None, asn_type)
+    };
+    
+    // 设置网络类型
+    let network_type = asn_type.map(|asn_type| match asn_type {
+        AsnType::Type(t) => t.into_string(),
+        AsnType::Other => "其他网络".to_string(),
+    });
+
+    Ok(AsnLookup {
+        asn: Some(ModelAsnInfo { number, name, info, route }),
+        network_type,
+        source: with_source.then(|| source_label(reader)),
+    })
+}
+
+/// 可选的 ISP / Domain 数据库的查询结果
+#[derive(Debug, Default)]
+struct IspDomain {
+    isp: Option<String>,
+    organization: Option<String>,
+    domain: Option<String>,
+    isp_source: Option<String>,
+    domain_source: Option<String>,
+    /// 查询出错的数据库；这两个数据库是可选的，未加载不算失败
+    failed: Vec<&'static str>,
+}
+
+// 查询可选的 ISP / Domain 数据库
+fn lookup_isp_domain(ip: IpAddr, with_source: bool) -> IspDomain {
+    let mut result = IspDomain::default();
+    if let Some(reader) = read_reader(&get_isp_reader()).as_ref() {
+        match found(reader.lookup::<geoip2::Isp>(ip)) {
+            Ok(Some(isp)) => {
+                result.isp = isp.isp.map(str::to_string);
+                result.organization = isp.organization.map(str::to_string);
+                result.isp_source = with_source.then(|| source_label(reader));
             }
-            
-            // 处理国家信息
-            if let Some(country) = city.country {
-                let name = get_country(&country);
-                if !name.is_empty() {
-                    info.country = Some(CountryInfo {
-                        code: country.iso_code.unwrap_or_default().to_string(),
-                        name,
-                    });
-                }
+            Ok(None) => {}
+            Err(_) => result.failed.push("ISP"),
+        }
+    }
+    if let Some(reader) = read_reader(&get_domain_reader()).as_ref() {
+        match found(reader.lookup::<geoip2::Domain>(ip)) {
+            Ok(Some(domain)) => {
+                result.domain = domain.domain.map(str::to_string);
+                result.domain_source = with_source.then(|| source_label(reader));
             }
-            
-            // 处理注册国家信息
-            if let Some(registered_country) = city.registered_country {
-                let name = get_country(&registered_country);
-                if !name.is_empty() {
-                    info.registered_country = Some(CountryInfo {
-                        code: registered_country.iso_code.unwrap_or_default().to_string(),
-                        name,
-                    });
+            Ok(None) => {}
+            Err(_) => result.failed.push("Domain"),
+        }
+    }
+    result
+}
+
+// 查询地理位置信息，结果只包含 City 数据库提供的字段，同时返回数据库的来源标注。
+// 按 GeoIP2 商业版的结构解码，GeoLite2 缺少的可信度等字段为空；
+// 只有 Country 数据库时同样解码，省市和坐标都不存在，结果中自然省略
+fn lookup_city(ip: IpAddr, detail: Detail, style: RegionStyle, locales: NameLocales, with_source: bool) -> Result<(IpInfo, Option<String>), StageFailure> {
+    let mut info = IpInfo::default();
+    let reader = get_location_reader();
+    let reader = read_reader(&reader);
+    let reader = reader.as_ref().ok_or(StageFailure::Unavailable)?;
+    let Some(city) = found(reader.lookup::<geoip2::Enterprise>(ip))? else {
+        return Ok((info, None));
+    };
+    let source = with_source.then(|| source_label(reader));
+
+    // 处理国家信息
+    if let Some(country) = city.country {
+        let name = get_country(&country, locales.as_slice());
+        if !name.is_empty() {
+            info.country = Some(CountryInfo {
+                code: country.iso_code.unwrap_or_default().to_string(),
+                name,
+                is_eu: country.is_in_european_union.unwrap_or(false),
+                confidence: country.confidence,
+            });
+        }
+    }
+    
+    // 处理注册国家信息
+    if let Some(registered_country) = city.registered_country {
+        let name = get_country(&registered_country, locales.as_slice());
+        if !name.is_empty() {
+            info.registered_country = Some(CountryInfo {
+                code: registered_country.iso_code.unwrap_or_default().to_string(),
+                name,
+                is_eu: registered_country.is_in_european_union.unwrap_or(false),
+                confidence: None,
+            });
+        }
+    }
+    
+    if detail == Detail::Minimal {
+        return Ok((info, source));
+    }
+
+    // 处理位置信息
+    if let (Some(lat), Some(lon)) = (
+        city.location.as_ref().and_then(|l| l.latitude),
+        city.location.as_ref().and_then(|l| l.longitude)
+    ) {
+        info.location = Some(Location {
+            latitude: Some(lat),
+            longitude: Some(lon),
+            time_zone: city.location.as_ref()
+                .and_then(|l| l.time_zone)
+                .filter(|_| detail == Detail::Full)
+                .map(str::to_string),
+            metro_code: city.location.as_ref().and_then(|l| l.metro_code),
+        });
+    }
+    info.accuracy_radius = city.location.as_ref().and_then(|l| l.accuracy_radius);
+    if let Some(postal) = city.postal {
+        info.postal = postal.code.map(str::to_string);
+        info.postal_confidence = postal.confidence.filter(|_| info.postal.is_some());
+    }
+    
+    // 处理大洲信息
+    if let Some(continent) = city.continent {
+        if let Some(code) = continent.code {
+            info.continent = Some(ContinentInfo {
+                code: code.to_string(),
+                name: get_continent(&continent, locales.as_slice()),
+            });
+        }
+    }
+    
+    // 处理代表国家信息
+    if let Some(represented_country) = city.represented_country {
+        let name = get_des(&represented_country.names, locales.as_slice());
+        if !name.is_empty() {
+            info.represented_country = Some(CountryInfo {
+                code: represented_country.iso_code.unwrap_or_default().to_string(),
+                name,
+                is_eu: represented_country.is_in_european_union.unwrap_or(false),
+                confidence: None,
+            });
+        }
+    }
+    
+    // 处理网络特征
+    if let Some(traits) = city.traits {
+        info.is_anonymous = traits.is_anonymous.unwrap_or(false) || traits.is_anonymous_proxy.unwrap_or(false);
+        info.is_datacenter = traits.is_hosting_provider.unwrap_or(false);
+        let traits = Traits {
+            is_anonymous_proxy: traits.is_anonymous_proxy.unwrap_or(false),
+            is_satellite_provider: traits.is_satellite_provider.unwrap_or(false),
+            is_anycast: traits.is_anycast.unwrap_or(false),
+        };
+        if !traits.is_empty() {
+            info.traits = Some(traits);
+        }
+    }
+    
+    // 处理地区信息，regions=none 时不生成；codes 与 regions 逐项对齐
+    let mut regions = Vec::with_capacity(2);
+    let mut codes = Vec::with_capacity(2);
+    let localize = Config::global().cn_localization;
+    
+    // 添加省级信息
+    if let Some(subdivisions) = city.subdivisions.filter(|_| style != RegionStyle::None) {
+        if let Some(province) = subdivisions.first() {
+            let code = match (info.country.as_ref(), province.iso_code) {
+                (Some(country), Some(iso_code)) if !country.code.is_empty() => format!("{}-{}", country.code, iso_code),
+                _ => String::new(),
+            };
+            if !localize {
+                let name = get_des(&province.names, locales.as_slice());
+                if push_raw_region(&mut regions, name) {
+                    codes.push(code);
+                    info.region_confidence = province.confidence;
                 }
-            }
-            
-            // 处理地区信息
-            let mut regions = Vec::with_capacity(2);
-            let mut regions_short = Vec::with_capacity(2);
-            
-            // 添加省级信息
-            if let Some(subdivisions) = city.subdivisions {
-                if let Some(province) = subdivisions.first() {
-                    if let Some(names) = &province.names {
-                        if let Some(name) = names.get("zh-CN") {
-                            let province_name = if !name.ends_with("省") 
-                                && !name.ends_with("自治区") 
-                                && !name.ends_with("特别行政区") {
-                                format!("{}省", name)
-                            } else {
-                                name.to_string()
-                            };
-                            regions.push(province_name);
-                            regions_short.push(get_short_name(name).to_string());
-                        }
+            } else if let Some(names) = &province.names {
+                if let Some(name) = names.get("zh-CN") {
+                    // 直辖市的省级名称本身以“市”结尾
+                    let province_name = if !name.ends_with("省") 
+                        && !name.ends_with("市") 
+                        && !name.ends_with("自治区") 
+                        && !name.ends_with("特别行政区") {
+                        format!("{}省", name)
+                    } else {
+                        name.to_string()
+                    };
+                    if push_region_name(&mut regions, province_name) {
+                        codes.push(code);
                     }
+                    info.region_confidence = province.confidence;
                 }
             }
-            
-            // 添加市级信息
-            if let Some(city_info) = city.city {
-                if let Some(names) = city_info.names {
-                    if let Some(name) = names.get("zh-CN") {
-                        let city_name = if !name.ends_with("市") {
-                            format!("{}市", name)
-                        } else {
-                            name.to_string()
-                        };
-                        regions.push(city_name);
-                        regions_short.push(get_short_name(name).to_string());
-                    }
-                }
+        }
+    }
+    
+    // 添加市级信息，城市没有 ISO 3166-2 代码
+    if let Some(city_info) = city.city {
+        let name = get_city(&city_info, locales.as_slice());
+        if !name.is_empty() {
+            info.city = Some(CityInfo {
+                name: name.clone(),
+                geoname_id: city_info.geoname_id,
+                confidence: city_info.confidence,
+            });
+        }
+
+        if style != RegionStyle::None && !localize {
+            if push_raw_region(&mut regions, name) {
+                codes.push(String::new());
             }
-            
-            if !regions.is_empty() {
-                info.regions = Some(regions);
-                info.regions_short = Some(regions_short);
+        } else if let Some(names) = city_info.names.filter(|_| style != RegionStyle::None) {
+            if let Some(name) = names.get("zh-CN") {
+                let city_name = if !name.ends_with("市") {
+                    format!("{}市", name)
+                } else {
+                    name.to_string()
+                };
+                if push_region_name(&mut regions, city_name) {
+                    codes.push(String::new());
+                }
             }
         }
     }
     
-    // 设置地址信息
-    if info.asn.is_some() {
-        match ip {
-            IpAddr::V4(ipv4) => {
-                let octets = ipv4.octets();
-                info.addr = format!("{}.{}.0.0/16", octets[0], octets[1]);
+    if !regions.is_empty() {
+        set_regions(&mut info, regions, codes, style);
+    }
+
+    Ok((info, source))
+}
+
+fn lookup_geocn(ip: IpAddr, with_source: bool) -> Result<Option<(GeoCNInfo, Option<String>)>, StageFailure> {
+    let reader = get_geocn_reader();
+    let reader = read_reader(&reader);
+    let reader = reader.as_ref().ok_or(StageFailure::Unavailable)?;
+    let record = found(reader.lookup::<GeoCNInfo>(ip))?;
+    Ok(record.map(|record| (record, with_source.then(|| source_label(reader)))))
+}
+
+// 关闭 CN_LOCALIZATION 时按数据库原样追加，只跳过空名称和与上一级完全相同的名称，返回是否追加
+fn push_raw_region(regions: &mut Vec<String>, name: String) -> bool {
+    if name.is_empty() || regions.last() == Some(&name) {
+        return false;
+    }
+    regions.push(name);
+    true
+}
+
+// 按 regions 参数写入地区全称、简称和代码，简称只在需要时生成；关闭 CN_LOCALIZATION 时没有简称
+fn set_regions(info: &mut IpInfo, regions: Vec<String>, codes: Vec<String>, style: RegionStyle) {
+    info.regions_short = (style.short() && Config::global().cn_localization)
+        .then(|| regions.iter().map(|name| get_short_name(name)).collect());
+    info.region_codes = codes.iter().any(|code| !code.is_empty()).then_some(codes);
+    info.regions = style.full().then_some(regions);
+}
+
+fn non_empty(field: Option<&String>) -> Option<String> {
+    field.filter(|v| !v.is_empty()).cloned()
+}
+
+// GeoCN 的省市区和与之对齐的代码；GeoCN 没有地区代码，省级按内置的名称表换算
+fn geocn_regions(cn: &GeoCNInfo) -> (Vec<String>, Vec<String>) {
+    let mut regions = Vec::with_capacity(3);
+    let mut codes = Vec::with_capacity(3);
+    let province = non_empty(cn.province.as_ref());
+    let province_code = province.as_deref().and_then(china_province_code).unwrap_or_default();
+    for (name, code) in [(province, province_code), (non_empty(cn.city.as_ref()), ""), (non_empty(cn.districts.as_ref()), "")] {
+        if let Some(name) = name {
+            if push_region_name(&mut regions, name) {
+                codes.push(code.to_string());
             }
-            IpAddr::V6(ipv6) => {
-                let segments = ipv6.segments();
-                info.addr = format!("{:x}:{:x}::/32", segments[0], segments[1]);
+        }
+    }
+    (regions, codes)
+}
+
+fn apply_geocn(info: &mut IpInfo, cn: GeoCNInfo, style: RegionStyle, locales: NameLocales, sources: &mut Provenance, source: Option<&str>) {
+    let city = non_empty(cn.city.as_ref());
+    let (regions, codes) = match style {
+        RegionStyle::None => Default::default(),
+        _ => geocn_regions(&cn),
+    };
+    if !regions.is_empty() {
+        set_regions(info, regions, codes, style);
+        // 可信度是 GeoIP2 对自己结果的判断，换成 GeoCN 的数据后不再适用
+        info.region_confidence = None;
+        sources.record("regions", true, source);
+    }
+
+    // GeoCN 只有中文名称，首选其他语言时保留 City 数据库的城市名
+    if let Some(city) = city.filter(|_| locales.preferred() == "zh-CN") {
+        sources.record("city", true, source);
+        match info.city.as_mut() {
+            Some(city_info) => {
+                city_info.name = city;
+                city_info.confidence = None;
             }
+            None => info.city = Some(CityInfo {
+                name: city,
+                geoname_id: None,
+                confidence: None,
+            }),
         }
     }
-    
+
+    let isp = non_empty(cn.isp.as_ref());
+    if info.r#type.is_none() {
+        info.r#type = isp.as_deref()
+            .and_then(isp_network_type)
+            .map(str::to_string)
+            .or(non_empty(cn.net.as_ref()));
+        sources.record("type", info.r#type.is_some(), source);
+    }
+    if info.isp.is_none() {
+        info.isp = isp;
+        sources.record("isp", info.isp.is_some(), source);
+    }
+}
+
+// 省级地区的 ISO 3166-2 代码，两个数据库都按代码比较，避免名称写法不同
+fn province_code(codes: Option<&Vec<String>>) -> Option<&str> {
+    codes.and_then(|codes| codes.first()).map(String::as_str).filter(|code| !code.is_empty())
+}
+
+// 在写入 GeoCN 之前取出 GeoLite2-City 的回答，与 GeoCN 记录对比；对比结果计入指标
+fn compare_with_geolite2(info: &IpInfo, cn: &GeoCNInfo) -> GeoComparison {
+    let (regions, codes) = geocn_regions(cn);
+    let geocn = ComparedAnswer {
+        regions: (!regions.is_empty()).then_some(regions),
+        region_codes: codes.iter().any(|code| !code.is_empty()).then_some(codes),
+        city: non_empty(cn.city.as_ref()),
+        location: None,
+    };
+    let geolite2 = ComparedAnswer {
+        regions: info.regions.clone(),
+        region_codes: info.region_codes.clone(),
+        city: info.city.as_ref().map(|city| city.name.clone()),
+        location: info.location.clone(),
+    };
+    let (result, divergent) = match (province_code(geocn.region_codes.as_ref()), province_code(geolite2.region_codes.as_ref())) {
+        (Some(cn), Some(lite)) if cn == lite => ("agree", false),
+        (Some(_), Some(_)) => ("divergent", true),
+        _ => ("incomplete", false),
+    };
+    Metrics::global().record_geocn_comparison(result);
+    GeoComparison { geocn, geolite2, divergent }
+}
+
+// 国内IP的运营商统一为规范代码和名称：优先 GeoCN，没有时按 ASN 名称推断，两者不一致时记录调试日志
+fn reconcile_china_isp(info: &mut IpInfo, geocn: Option<(String, Option<String>)>, asn_source: Option<&str>, sources: &mut Provenance) {
+    let in_china = geocn.is_some() || info.country.as_ref().is_some_and(|country| country.code == "CN");
+    if !in_china {
+        return;
+    }
+    let asn_name = info.asn.as_ref().map(|asn| asn.name.as_str());
+    let from_asn = asn_name.and_then(china_isp);
+    let from_geocn = geocn.as_ref().and_then(|(isp, source)| Some((isp.as_str(), china_isp(isp)?, source.as_deref())));
+    if let (Some((geocn_isp, cn, _)), Some(asn)) = (from_geocn, from_asn) {
+        if cn != asn {
+            debug!(geocn = geocn_isp, asn = asn_name.unwrap_or_default(), "GeoCN and ASN disagree on the ISP");
+        }
+    }
+    let chosen = from_geocn
+        .map(|(_, isp, source)| (isp, source))
+        .or(from_asn.map(|isp| (isp, asn_source)));
+    if let Some((isp, source)) = chosen {
+        info.isp = Some(isp.name.to_string());
+        info.isp_code = Some(isp.code.to_string());
+        sources.record("isp", true, source);
+    }
+}
+
+/// 查询已解析的地址，私有地址不查数据库，只返回所属网段
+pub async fn lookup_resolved(resolved: ResolvedHost, options: LookupOptions) -> Result<IpInfo, IpGeoError> {
+    let ip = resolved.ip;
+    let mut info = if is_private_ip(ip) {
+        IpInfo {
+            ip: ip.to_string(),
+            addr: private_network(ip).map_or_else(|| "private".to_string(), |net| net.to_string()),
+            r#type: is_link_local(ip).then(|| "链路本地地址".to_string()),
+            timestamp: options.echo().then(|| format_rfc3339(SystemTime::now())),
+            ..IpInfo::default()
+        }
+    } else {
+        get_ip_info_with(&ip.to_string(), options).await?
+    };
+    resolved.apply_to(&mut info);
     Ok(info)
 }
 
 pub async fn resolve_host(host: &str) -> Result<IpAddr, IpGeoError> {
-    // 首先验证是否为有效的IP地址格式
-    if let Ok(ip) = host.parse() {
-        // 验证IP地址的有效性
-        match ip {
-            IpAddr::V4(ipv4) => {
-                let octets = ipv4.octets();
-                // 检查是否为有效的公网IP地址
-                if octets[0] == 0 || // 0.0.0.0/8
-                   octets == [255, 255, 255, 255] || // 广播地址
-                   octets == [0, 0, 0, 0] || // 未指定地址
-                   (octets[0] == 192 && octets[1] == 0 && octets[2] == 2) || // 文档地址
-                   (octets[0] == 198 && octets[1] == 51 && octets[2] == 100) || // 文档地址
-                   (octets[0] == 203 && octets[1] == 0 && octets[2] == 113) // 文档地址
-                {
-                    return Err(IpGeoError::InvalidIp(format!("无效的IPv4地址: {}", host)));
-                }
-            },
-            IpAddr::V6(ipv6) => {
-                let segments = ipv6.segments();
-                if segments == [0, 0, 0, 0, 0, 0, 0, 0] || // 未指定地址
-                   (segments[0] == 0x2001 && segments[1] == 0xdb8) // 文档地址
-                {
-                    return Err(IpGeoError::InvalidIp(format!("无效的IPv6地址: {}", host)));
-                }
-            }
+    resolve_host_with_name(host).await.map(|resolved| resolved.ip)
+}
+
+/// 一次查询实际解析的对象
+#[derive(Debug, Clone)]
+pub struct ResolvedHost {
+    /// 规范化后的主机名（保留用户输入的 Unicode 或 xn-- 形式），输入是IP时为 None
+    pub host: Option<String>,
+    pub ip: IpAddr,
+    pub cnames: Vec<String>,
+    pub cnames_truncated: bool,
+}
+
+impl ResolvedHost {
+    pub fn from_ip(ip: IpAddr) -> Self {
+        Self {
+            host: None,
+            ip,
+            cnames: Vec::new(),
+            cnames_truncated: false,
         }
-        return Ok(ip);
     }
-    
-    // 验证域名格式
-    if !is_valid_domain(host) {
-        return Err(IpGeoError::ResolveError);
+
+    /// 把主机名和 CNAME 链写入查询结果，IP查询不输出这些字段
+    pub fn apply_to(self, info: &mut IpInfo) {
+        if self.host.is_some() {
+            info.host = self.host;
+            info.cnames = Some(self.cnames);
+            info.cnames_truncated = self.cnames_truncated;
+        }
     }
-    
-    // 如果是有效域名，尝试解析
-    match tokio::time::timeout(
-        std::time::Duration::from_secs(1), // 设置1秒超时
-        lookup_host(format!("{}:0", host))
-    ).await {
-        Ok(Ok(mut addrs)) => {
-            // 优先返回IPv4地址
-            while let Some(addr) = addrs.next() {
-                match addr.ip() {
-                    IpAddr::V4(_) => return Ok(addr.ip()),
-                    _ => continue,
-                }
+}
+
+/// 域名的最大长度（RFC 1035）
+pub const MAX_HOST_LEN: usize = 253;
+
+/// 规范化输入后解析，同时返回实际解析的主机名和 CNAME 链
+pub async fn resolve_host_with_name(input: &str) -> Result<ResolvedHost, IpGeoError> {
+    let host = normalize_host(input);
+    if host.len() > MAX_HOST_LEN {
+        return Err(IpGeoError::HostTooLong(MAX_HOST_LEN));
+    }
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        validate_ip(ip, &host)?;
+        return Ok(ResolvedHost::from_ip(ip));
+    }
+
+    let name = parse_domain(&host, Config::global().allow_single_label_hosts)?;
+    let span = debug_span!("resolve_host", ip.family = field::Empty);
+    let answer = timing::timed("resolution", super::resolver::lookup_ips(&name.ascii)).instrument(span.clone()).await?;
+    // 优先返回IPv4地址，如果没有IPv4地址，返回第一个IPv6地址
+    let ip = answer.ips.iter()
+        .find(|ip| ip.is_ipv4())
+        .or_else(|| answer.ips.first())
+        .copied()
+        .ok_or(IpGeoError::ResolveError)?;
+    span.record("ip.family", ip_family(ip));
+    Ok(ResolvedHost {
+        host: Some(name.echo),
+        ip,
+        cnames: answer.cnames,
+        cnames_truncated: answer.cnames_truncated,
+    })
+}
+
+// 拒绝未指定、广播和文档地址
+fn validate_ip(ip: IpAddr, host: &str) -> Result<(), IpGeoError> {
+    match ip {
+        IpAddr::V4(ipv4) => {
+            let octets = ipv4.octets();
+            // 检查是否为有效的公网IP地址
+            if octets[0] == 0 || // 0.0.0.0/8
+               octets == [255, 255, 255, 255] || // 广播地址
+               octets == [0, 0, 0, 0] || // 未指定地址
+               (octets[0] == 192 && octets[1] == 0 && octets[2] == 2) || // 文档地址
+               (octets[0] == 198 && octets[1] == 51 && octets[2] == 100) || // 文档地址
+               (octets[0] == 203 && octets[1] == 0 && octets[2] == 113) // 文档地址
+            {
+                return Err(IpGeoError::InvalidIp(mask_input(host)));
             }
-            // 如果没有IPv4地址，返回第一个IPv6地址
-            addrs.next()
-                .map(|addr| addr.ip())
-                .ok_or(IpGeoError::ResolveError)
         },
-        Ok(Err(_)) => Err(IpGeoError::ResolveError),
-        Err(_) => Err(IpGeoError::TimeoutError),
+        IpAddr::V6(ipv6) => {
+            let segments = ipv6.segments();
+            if segments == [0, 0, 0, 0, 0, 0, 0, 0] || // 未指定地址
+               (segments[0] == 0x2001 && segments[1] == 0xdb8) // 文档地址
+            {
+                return Err(IpGeoError::InvalidIp(mask_input(host)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 待解析的域名：回显给用户的形式和实际解析的 ASCII 形式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomainName {
+    /// 保留用户输入的 Unicode 或 xn-- 形式
+    pub echo: String,
+    pub ascii: String,
+}
+
+/// 把规范化后的主机名转换为 punycode 并验证格式，已是 xn-- 形式的保持不变
+pub fn parse_domain(host: &str, allow_single_label: bool) -> Result<DomainName, IpGeoError> {
+    let ascii = idna::domain_to_ascii(host).map_err(|_| IpGeoError::ResolveError)?;
+    if !is_valid_domain(&ascii, allow_single_label) {
+        return Err(IpGeoError::ResolveError);
     }
+    Ok(DomainName { echo: sanitize_echo(host), ascii })
 }
 
-/// 检查是否为有效的域名格式
-fn is_valid_domain(host: &str) -> bool {
+/// 检查是否为有效的域名格式，allow_single_label 对应 ALLOW_SINGLE_LABEL_HOSTS
+pub fn is_valid_domain(host: &str, allow_single_label: bool) -> bool {
     // 域名的基本验证规则
     // 1. 长度在1-253之间
-    if host.len() < 1 || host.len() > 253 {
+    if host.is_empty() || host.len() > 253 {
         return false;
     }
     
-    // 2. 只包含字母、数字、点和连字符
-    if !host.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-') {
+    // 2. 只包含字母、数字、点、连字符和下划线
+    if !host.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_') {
         return false;
     }
     
-    // 3. 不能以点或连字符开始或结束
-    if host.starts_with('.') || host.starts_with('-') || 
-       host.ends_with('.') || host.ends_with('-') {
+    // 3. 排除纯数字的情况；单标签名称（如 localhost）需要显式开启
+    if host.chars().all(|c| c.is_ascii_digit() || c == '.') {
         return false;
     }
-    
-    // 4. 至少包含一个点（排除纯数字的情况）
-    if !host.contains('.') || host.chars().all(|c| c.is_ascii_digit() || c == '.') {
+    if !host.contains('.') && !allow_single_label {
         return false;
     }
     
-    // 5. 每个标签（点之间的部分）长度在1-63之间
-    let labels: Vec<&str> = host.split('.').collect();
-    for label in labels {
-        if label.len() < 1 || label.len() > 63 {
-            return false;
-        }
-    }
-    
-    true
-} 
\ No newline at end of file
+    // 4. 每个标签（点之间的部分）长度在1-63之间，且不能以连字符开始或结束
+    host.split('.').all(|label| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+    })
+}
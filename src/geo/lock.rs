@@ -0,0 +1,26 @@
+//! 数据库读取器、RIR 分配表和覆盖表的读写锁。
+//!
+//! 持有写锁时 panic 会使锁中毒，之后的 `read()` 全部返回错误，对应的数据会被一直跳过。
+//! 写入方总是整体替换锁中的值，中毒时数据本身仍然完整，因此清除中毒标记后继续使用，
+//! 同时记录错误日志、计入 `ipgeo_lock_recoveries_total` 并在 /healthz 中列出。
+
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tracing::error;
+use crate::metrics::Metrics;
+
+fn recover<T, G>(lock: &RwLock<T>, poisoned: PoisonError<G>, name: &'static str) -> G {
+    error!(database = name, "Lock was poisoned by a panic while it was held; recovered the data and cleared the poison");
+    Metrics::global().record_lock_recovery(name);
+    lock.clear_poison();
+    poisoned.into_inner()
+}
+
+/// 读锁，锁中毒时恢复；name 只在中毒时才计算
+pub(crate) fn read_recovering<T>(lock: &RwLock<T>, name: impl FnOnce() -> &'static str) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(|poisoned| recover(lock, poisoned, name()))
+}
+
+/// 写锁，锁中毒时恢复，保证重新加载不会被静默跳过
+pub(crate) fn write_recovering<T>(lock: &RwLock<T>, name: impl FnOnce() -> &'static str) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(|poisoned| recover(lock, poisoned, name()))
+}
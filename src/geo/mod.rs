@@ -1,5 +1,18 @@
 mod geo;
 mod database;
+mod resolver;
+mod overrides;
+mod fallback;
+mod watcher;
+mod service;
+mod rir;
+mod lock;
 
 pub use geo::*;
-pub use database::*; 
\ No newline at end of file
+pub use database::*;
+pub use resolver::*;
+pub use overrides::*;
+pub use fallback::*;
+pub use watcher::*;
+pub use service::*;
+pub use rir::*;
@@ -0,0 +1,173 @@
+//! 数据目录中的 overrides.json：按网段修正数据库中的地理位置，在查询的最后一步覆盖。
+//!
+//! ```json
+//! { "203.0.113.0/24": { "country": { "code": "CN", "name": "中国" }, "regions": ["上海市"] } }
+//! ```
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::RwLock;
+use ipnet::IpNet;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use tracing::{info, warn};
+use crate::models::{AsnInfo, CountryInfo, IpInfo, Location};
+use super::lock::{read_recovering, write_recovering};
+
+pub const OVERRIDES_FILE: &str = "overrides.json";
+
+/// 字段来源中标记被覆盖的字段组
+pub const OVERRIDE_SOURCE: &str = "override";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AsnPatch {
+    /// 原结果没有 ASN 时必须提供
+    pub number: Option<u32>,
+    pub name: Option<String>,
+}
+
+/// 覆盖到匹配网段查询结果上的字段，未出现的字段保留数据库的值
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OverridePatch {
+    pub country: Option<CountryInfo>,
+    pub regions: Option<Vec<String>>,
+    pub location: Option<Location>,
+    pub asn: Option<AsnPatch>,
+}
+
+impl OverridePatch {
+    /// 应用到查询结果，返回被覆盖的字段组
+    pub fn apply(&self, info: &mut IpInfo) -> Vec<&'static str> {
+        let mut groups = Vec::new();
+        if let Some(country) = &self.country {
+            info.country = Some(country.clone());
+            groups.push("country");
+        }
+        if let Some(regions) = &self.regions {
+            info.regions = Some(regions.clone());
+            // 简称和代码来自数据库，与覆盖后的地区对不上
+            info.regions_short = None;
+            info.region_codes = None;
+            info.region_confidence = None;
+            groups.push("regions");
+        }
+        if let Some(location) = &self.location {
+            info.location = Some(location.clone());
+            groups.push("location");
+        }
+        if let Some(patch) = &self.asn {
+            info.asn = match (info.asn.take(), patch.number) {
+                // 改了号码时数据库中的网段不再属于该 ASN
+                (Some(asn), number) => Some(AsnInfo {
+                    route: asn.route.filter(|_| number.is_none() || number == asn.number),
+                    number: number.or(asn.number),
+                    name: patch.name.clone().unwrap_or(asn.name),
+                    info: asn.info,
+                }),
+                (None, Some(number)) => Some(AsnInfo {
+                    number: Some(number),
+                    name: patch.name.clone().unwrap_or_default(),
+                    info: None,
+                    route: None,
+                }),
+                (None, None) => None,
+            };
+            if info.asn.is_some() {
+                groups.push("asn");
+            }
+        }
+        groups
+    }
+}
+
+/// 按网段排序的覆盖表，查询时取最长的匹配前缀
+#[derive(Debug, Default)]
+pub struct OverrideTable {
+    entries: Vec<(IpNet, OverridePatch)>,
+    /// 表中出现过的前缀长度，从长到短
+    prefix_lens: Vec<u8>,
+}
+
+impl OverrideTable {
+    /// 解析 overrides.json 的内容，无法解析的条目记录警告后跳过
+    pub fn parse(content: &str) -> Result<Self, serde_json::Error> {
+        let raw: BTreeMap<String, serde_json::Value> = serde_json::from_str(content)?;
+        let mut entries = Vec::with_capacity(raw.len());
+        for (key, value) in raw {
+            let network = match key.parse::<IpNet>().or_else(|_| key.parse::<IpAddr>().map(IpNet::from)) {
+                Ok(network) => network.trunc(),
+                Err(_) => {
+                    warn!("Skipping override '{}': not a CIDR prefix", key);
+                    continue;
+                }
+            };
+            match serde_json::from_value::<OverridePatch>(value) {
+                Ok(patch) => entries.push((network, patch)),
+                Err(e) => warn!("Skipping override '{}': {}", key, e),
+            }
+        }
+        entries.sort_by_key(|(network, _)| *network);
+        entries.dedup_by(|later, earlier| {
+            let duplicate = later.0 == earlier.0;
+            if duplicate {
+                warn!("Skipping duplicate override for {}", later.0);
+            }
+            duplicate
+        });
+
+        let mut prefix_lens: Vec<u8> = entries.iter().map(|(network, _)| network.prefix_len()).collect();
+        prefix_lens.sort_unstable_by(|a, b| b.cmp(a));
+        prefix_lens.dedup();
+        Ok(Self { entries, prefix_lens })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 包含 ip 的最长前缀对应的修正
+    pub fn lookup(&self, ip: IpAddr) -> Option<&OverridePatch> {
+        self.prefix_lens.iter().find_map(|&len| {
+            let network = IpNet::new(ip, len).ok()?.trunc();
+            let index = self.entries.binary_search_by(|(entry, _)| entry.cmp(&network)).ok()?;
+            Some(&self.entries[index].1)
+        })
+    }
+}
+
+static OVERRIDES: Lazy<RwLock<OverrideTable>> = Lazy::new(RwLock::default);
+
+/// 从数据目录加载 overrides.json，替换当前的覆盖表；文件不存在时清空，返回加载的条目数
+pub fn load_overrides(dir: &Path) -> std::io::Result<usize> {
+    let path = dir.join(OVERRIDES_FILE);
+    let table = match std::fs::read_to_string(&path) {
+        Ok(content) => OverrideTable::parse(&content).map_err(|e| std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Failed to parse {:?}: {}", path, e),
+        ))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => OverrideTable::default(),
+        Err(e) => return Err(e),
+    };
+    let count = table.len();
+    if count > 0 {
+        info!("Loaded {} geolocation overrides from {:?}", count, path);
+    }
+    *write_recovering(&OVERRIDES, || "overrides") = table;
+    super::geo::bump_data_generation();
+    Ok(count)
+}
+
+/// 对匹配的网段应用修正，返回被覆盖的字段组
+pub fn apply_overrides(ip: IpAddr, info: &mut IpInfo) -> Vec<&'static str> {
+    read_recovering(&OVERRIDES, || "overrides")
+        .lookup(ip)
+        .map(|patch| patch.apply(info))
+        .unwrap_or_default()
+}
@@ -0,0 +1,144 @@
+use std::net::IpAddr;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+use hickory_resolver::TokioAsyncResolver;
+use hickory_resolver::config::{LookupIpStrategy, NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::error::ResolveErrorKind;
+use hickory_resolver::proto::rr::RData;
+use once_cell::sync::Lazy;
+use tracing::{debug, info, warn};
+use crate::cache::SingleFlight;
+use crate::config::Config;
+use crate::metrics::Metrics;
+use crate::models::IpGeoError;
+
+// 解析器缓存条目数，正向和否定结果共用
+const DNS_CACHE_SIZE: usize = 4096;
+
+// CNAME 链最多保留的条目数
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// 一次解析得到的全部地址以及经过的 CNAME 链
+#[derive(Debug, Clone, Default)]
+pub struct DnsAnswer {
+    pub ips: Vec<IpAddr>,
+    pub cnames: Vec<String>,
+    /// CNAME 链出现循环或超过 MAX_CNAME_CHAIN 时被截断
+    pub cnames_truncated: bool,
+}
+
+// 配置了 DNS_SERVERS 时只使用这些服务器，否则读取系统配置
+static RESOLVER: Lazy<TokioAsyncResolver> = Lazy::new(|| {
+    let config = Config::global();
+    let (resolver_config, mut opts) = if config.dns_servers.is_empty() {
+        hickory_resolver::system_conf::read_system_conf().unwrap_or_else(|e| {
+            warn!("Failed to read system DNS configuration, using defaults: {}", e);
+            (ResolverConfig::default(), ResolverOpts::default())
+        })
+    } else {
+        info!("Using DNS servers {:?}", config.dns_servers);
+        let servers = NameServerConfigGroup::from_ips_clear(&config.dns_servers, 53, true);
+        (ResolverConfig::from_parts(None, Vec::new(), servers), ResolverOpts::default())
+    };
+
+    // 超时后重试一次
+    opts.timeout = config.dns_timeout;
+    opts.attempts = 2;
+    opts.ip_strategy = LookupIpStrategy::Ipv4AndIpv6;
+    opts.cache_size = DNS_CACHE_SIZE;
+    Metrics::global().dns_cache_capacity.store(DNS_CACHE_SIZE as u64, Ordering::Relaxed);
+
+    TokioAsyncResolver::tokio(resolver_config, opts)
+});
+
+/// 解析器缓存的容量
+pub fn dns_cache_capacity() -> usize {
+    DNS_CACHE_SIZE
+}
+
+/// 清空解析器缓存，之后的解析重新向服务器查询
+pub fn clear_dns_cache() {
+    RESOLVER.clear_cache();
+}
+
+// 可在并发请求之间共享的解析失败原因
+#[derive(Debug, Clone, Copy)]
+enum LookupFailure {
+    NotFound,
+    Timeout,
+}
+
+impl From<LookupFailure> for IpGeoError {
+    fn from(failure: LookupFailure) -> Self {
+        match failure {
+            LookupFailure::NotFound => IpGeoError::ResolveError,
+            LookupFailure::Timeout => IpGeoError::TimeoutError,
+        }
+    }
+}
+
+static DNS_FLIGHTS: Lazy<SingleFlight<String, Result<DnsAnswer, LookupFailure>>> = Lazy::new(SingleFlight::new);
+
+/// 解析域名的全部 A 和 AAAA 记录，同一域名的并发解析只发出一次查询
+pub async fn lookup_ips(host: &str) -> Result<DnsAnswer, IpGeoError> {
+    let (result, shared) = DNS_FLIGHTS.run(host.to_string(), || lookup_uncached(host)).await;
+    if shared {
+        Metrics::incr(&Metrics::global().dns_dedup_hits);
+    }
+    result.map_err(IpGeoError::from)
+}
+
+async fn lookup_uncached(host: &str) -> Result<DnsAnswer, LookupFailure> {
+    let metrics = Metrics::global();
+    Metrics::incr(&metrics.dns_lookups);
+    let started = Instant::now();
+    let result = RESOLVER.lookup_ip(host).await;
+    metrics.dns_lookup_micros.fetch_add(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+
+    match result {
+        Ok(lookup) => {
+            let ips: Vec<IpAddr> = lookup.iter().collect();
+            if ips.is_empty() {
+                Metrics::incr(&metrics.dns_failures);
+                return Err(LookupFailure::NotFound);
+            }
+
+            let mut answer = DnsAnswer { ips, ..DnsAnswer::default() };
+            for record in lookup.as_lookup().records() {
+                let Some(RData::CNAME(cname)) = record.data() else {
+                    continue;
+                };
+                let name = cname.0.to_string().trim_end_matches('.').to_string();
+                if answer.cnames.len() >= MAX_CNAME_CHAIN || answer.cnames.contains(&name) {
+                    answer.cnames_truncated = true;
+                    break;
+                }
+                answer.cnames.push(name);
+            }
+            Ok(answer)
+        }
+        Err(e) => match e.kind() {
+            ResolveErrorKind::Timeout => {
+                Metrics::incr(&metrics.dns_timeouts);
+                Err(LookupFailure::Timeout)
+            }
+            _ => {
+                Metrics::incr(&metrics.dns_failures);
+                Err(LookupFailure::NotFound)
+            }
+        },
+    }
+}
+
+/// 反向解析IP得到的第一个主机名，失败或超时时返回 None
+pub async fn reverse_lookup(ip: IpAddr) -> Option<String> {
+    match RESOLVER.reverse_lookup(ip).await {
+        Ok(names) => names.iter()
+            .next()
+            .map(|name| name.to_utf8().trim_end_matches('.').to_string()),
+        Err(e) => {
+            debug!("Reverse lookup failed: {}", e);
+            None
+        }
+    }
+}
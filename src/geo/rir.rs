@@ -0,0 +1,295 @@
+//! 五个区域互联网注册机构（RIR）的 delegated-extended 统计文件合并成的分配表。
+//! 下载时解析、聚合后写成 rir.bin，启动时直接读取，不再解析文本。
+//!
+//! 格式：`IPRIR1`，生成时间（u64 大端），IPv4 段数、IPv6 段数（u32 大端），
+//! 然后是按起始地址排序的段：起止地址、注册机构序号（u8）、分配日期（YYYYMMDD，u32，0 为未知）。
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use ipnet::IpNet;
+use once_cell::sync::Lazy;
+use tracing::info;
+use crate::utils::{format_epoch_date, network_for};
+use super::lock::{read_recovering, write_recovering};
+
+/// 数据目录中的分配表文件名
+pub const RIR_FILE: &str = "rir.bin";
+
+/// 各注册机构的统计文件，键为文件中的 registry 字段
+pub const DELEGATED_URLS: [(&str, &str); 5] = [
+    ("afrinic", "https://ftp.afrinic.net/pub/stats/afrinic/delegated-afrinic-extended-latest"),
+    ("apnic", "https://ftp.apnic.net/stats/apnic/delegated-apnic-extended-latest"),
+    ("arin", "https://ftp.arin.net/pub/stats/arin/delegated-arin-extended-latest"),
+    ("lacnic", "https://ftp.lacnic.net/pub/stats/lacnic/delegated-lacnic-extended-latest"),
+    ("ripencc", "https://ftp.ripe.net/pub/stats/ripencc/delegated-ripencc-extended-latest"),
+];
+
+// 序号即 rir.bin 中保存的值，与 DELEGATED_URLS 的顺序一致
+const REGISTRY_NAMES: [&str; 5] = ["AFRINIC", "APNIC", "ARIN", "LACNIC", "RIPE NCC"];
+
+const MAGIC: &[u8] = b"IPRIR1";
+const HEADER: usize = 6 + 8 + 4 + 4;
+const V4_RECORD: usize = 4 + 4 + 1 + 4;
+const V6_RECORD: usize = 16 + 16 + 1 + 4;
+
+/// 统计文件中的一条已分配记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allocation {
+    pub start: IpAddr,
+    pub end: IpAddr,
+    /// REGISTRY_NAMES 中的序号
+    registry: u8,
+    /// YYYYMMDD，0 表示文件中没有日期
+    pub date: u32,
+    /// extended 格式中的持有者标识，相同标识的相邻记录才会合并
+    pub holder: Option<String>,
+}
+
+impl Allocation {
+    pub fn registry(&self) -> &'static str {
+        REGISTRY_NAMES[self.registry as usize]
+    }
+}
+
+// 前 n 位之后全为 1 的掩码
+fn host_mask(host_bits: u32) -> u128 {
+    if host_bits >= 128 { u128::MAX } else { (1u128 << host_bits) - 1 }
+}
+
+fn to_u128(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(ip) => u128::from(u32::from(ip)),
+        IpAddr::V6(ip) => u128::from(ip),
+    }
+}
+
+fn from_u128(value: u128, v6: bool) -> IpAddr {
+    if v6 {
+        IpAddr::V6(Ipv6Addr::from(value))
+    } else {
+        IpAddr::V4(Ipv4Addr::from(value as u32))
+    }
+}
+
+// 日期字段只接受 8 位数字，00000000 等同于没有日期
+fn parse_date(field: &str) -> Option<u32> {
+    match field {
+        "" => Some(0),
+        field if field.len() == 8 => field.parse().ok(),
+        _ => None,
+    }
+}
+
+// registry|cc|type|start|value|date|status[|opaque-id]，IPv4 的 value 是地址数，IPv6 的是前缀长度
+fn parse_line(line: &str) -> Option<Option<Allocation>> {
+    let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+    let [registry, _cc, kind, start, value, date, status, rest @ ..] = fields.as_slice() else {
+        // 版本行和汇总行的字段数不同
+        return Some(None);
+    };
+    if !matches!(*kind, "ipv4" | "ipv6") || *start == "*" || !matches!(*status, "allocated" | "assigned") {
+        return Some(None);
+    }
+    let registry = DELEGATED_URLS.iter().position(|(key, _)| key.eq_ignore_ascii_case(registry))? as u8;
+    let start: IpAddr = start.parse().ok()?;
+    let value: u128 = value.parse().ok()?;
+    let first = to_u128(start);
+    let end = match (start, *kind) {
+        (IpAddr::V4(_), "ipv4") if value > 0 => first.checked_add(value - 1).filter(|end| *end <= u128::from(u32::MAX))?,
+        (IpAddr::V6(_), "ipv6") if value <= 128 => first | host_mask(128 - value as u32),
+        _ => return None,
+    };
+    Some(Some(Allocation {
+        start,
+        end: from_u128(end, start.is_ipv6()),
+        registry,
+        date: parse_date(date)?,
+        holder: rest.first().filter(|id| !id.is_empty()).map(|id| id.to_string()),
+    }))
+}
+
+/// 解析一个统计文件，只保留 allocated 和 assigned 的地址段。无法解析的地址记录被跳过，返回记录和跳过的行数
+pub fn parse_delegated(text: &str) -> (Vec<Allocation>, usize) {
+    let mut allocations = Vec::new();
+    let mut skipped = 0;
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        match parse_line(line) {
+            Some(Some(allocation)) => allocations.push(allocation),
+            Some(None) => {}
+            None => skipped += 1,
+        }
+    }
+    (allocations, skipped)
+}
+
+// 按起始地址排序，丢弃与前一段重叠的记录，合并同一注册机构、同一日期、同一持有者的相邻段
+fn aggregate(mut allocations: Vec<Allocation>) -> Vec<Allocation> {
+    allocations.sort_by_key(|a| to_u128(a.start));
+    let mut merged: Vec<Allocation> = Vec::with_capacity(allocations.len());
+    for allocation in allocations {
+        if let Some(last) = merged.last_mut() {
+            let last_end = to_u128(last.end);
+            if to_u128(allocation.start) <= last_end {
+                continue;
+            }
+            let adjacent = last_end.checked_add(1) == Some(to_u128(allocation.start));
+            if adjacent && last.registry == allocation.registry && last.date == allocation.date
+                && last.holder.is_some() && last.holder == allocation.holder
+            {
+                last.end = allocation.end;
+                continue;
+            }
+        }
+        merged.push(allocation);
+    }
+    merged
+}
+
+/// 把各注册机构的记录聚合后编码为 rir.bin
+pub fn encode_allocations(allocations: Vec<Allocation>, build_epoch: u64) -> Vec<u8> {
+    let (v6, v4): (Vec<_>, Vec<_>) = allocations.into_iter().partition(|a| a.start.is_ipv6());
+    let (v4, v6) = (aggregate(v4), aggregate(v6));
+
+    let mut out = Vec::with_capacity(HEADER + v4.len() * V4_RECORD + v6.len() * V6_RECORD);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&build_epoch.to_be_bytes());
+    out.extend_from_slice(&(v4.len() as u32).to_be_bytes());
+    out.extend_from_slice(&(v6.len() as u32).to_be_bytes());
+    for (records, width) in [(v4, 4), (v6, 16)] {
+        for allocation in records {
+            out.extend_from_slice(&to_u128(allocation.start).to_be_bytes()[16 - width..]);
+            out.extend_from_slice(&to_u128(allocation.end).to_be_bytes()[16 - width..]);
+            out.push(allocation.registry);
+            out.extend_from_slice(&allocation.date.to_be_bytes());
+        }
+    }
+    out
+}
+
+/// ip 所在的分配记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RirRecord {
+    pub registry: &'static str,
+    /// 如 2011-08-11
+    pub allocated: Option<String>,
+    /// 分配段中包含 ip 的 CIDR；不是 2 的幂的 IPv4 段会拆成多个 CIDR
+    pub cidr: IpNet,
+}
+
+/// 读入内存的 rir.bin
+#[derive(Debug)]
+pub struct RirTable {
+    bytes: Vec<u8>,
+    v4_count: usize,
+    v6_count: usize,
+}
+
+fn read_be(bytes: &[u8]) -> u128 {
+    bytes.iter().fold(0u128, |acc, b| acc << 8 | u128::from(*b))
+}
+
+// [start, end] 拆成对齐的 CIDR 后，包含 key 的那一个的前缀长度
+fn covering_prefix(start: u128, end: u128, key: u128, bits: u32) -> u8 {
+    let mut cursor = start;
+    loop {
+        let mut host_bits = if cursor == 0 { bits } else { cursor.trailing_zeros().min(bits) };
+        while cursor | host_mask(host_bits) > end {
+            host_bits -= 1;
+        }
+        let last = cursor | host_mask(host_bits);
+        if key <= last {
+            return (bits - host_bits) as u8;
+        }
+        cursor = last + 1;
+    }
+}
+
+fn format_date(date: u32) -> Option<String> {
+    (date != 0).then(|| format!("{:04}-{:02}-{:02}", date / 10000, date / 100 % 100, date % 100))
+}
+
+impl RirTable {
+    pub fn parse(bytes: Vec<u8>) -> Option<Self> {
+        if !bytes.starts_with(MAGIC) || bytes.len() < HEADER {
+            return None;
+        }
+        let v4_count = read_be(&bytes[14..18]) as usize;
+        let v6_count = read_be(&bytes[18..22]) as usize;
+        if bytes.len() != HEADER + v4_count * V4_RECORD + v6_count * V6_RECORD {
+            return None;
+        }
+        Some(Self { bytes, v4_count, v6_count })
+    }
+
+    /// 生成时间（Unix 秒）
+    pub fn build_epoch(&self) -> u64 {
+        read_be(&self.bytes[6..14]) as u64
+    }
+
+    pub fn len(&self) -> usize {
+        self.v4_count + self.v6_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn lookup(&self, ip: IpAddr) -> Option<RirRecord> {
+        let v4_bytes = self.v4_count * V4_RECORD;
+        let (records, size, width) = match ip {
+            IpAddr::V4(_) => (&self.bytes[HEADER..HEADER + v4_bytes], V4_RECORD, 4),
+            IpAddr::V6(_) => (&self.bytes[HEADER + v4_bytes..], V6_RECORD, 16),
+        };
+        let key = to_u128(ip);
+        let record_at = |index: usize| &records[index * size..(index + 1) * size];
+        // 二分查找最后一个起始地址不大于 ip 的段
+        let (mut low, mut high) = (0, records.len() / size);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if read_be(&record_at(mid)[..width]) <= key {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        let record = record_at(low.checked_sub(1)?);
+        let (start, end) = (read_be(&record[..width]), read_be(&record[width..2 * width]));
+        if end < key {
+            return None;
+        }
+        let prefix_len = covering_prefix(start, end, key, width as u32 * 8);
+        Some(RirRecord {
+            registry: REGISTRY_NAMES.get(record[2 * width] as usize)?,
+            allocated: format_date(read_be(&record[2 * width + 1..]) as u32),
+            cidr: network_for(ip, prefix_len),
+        })
+    }
+}
+
+static RIR_TABLE: Lazy<RwLock<Option<Arc<RirTable>>>> = Lazy::new(|| RwLock::new(None));
+
+/// 读取数据目录中的 rir.bin，文件不存在时保持未加载
+pub fn load_rir_table(dir: &Path) -> std::io::Result<()> {
+    let path = dir.join(RIR_FILE);
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let table = RirTable::parse(bytes).ok_or_else(|| std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("{:?} is not a valid RIR allocation table", path),
+    ))?;
+    info!("Loaded {} RIR allocations from {:?}", table.len(), path);
+    *write_recovering(&RIR_TABLE, || "RIR") = Some(Arc::new(table));
+    super::geo::bump_data_generation();
+    Ok(())
+}
+
+/// 查询 ip 的分配记录和数据来源，分配表未加载时为 None
+pub fn rir_lookup(ip: IpAddr) -> Option<(RirRecord, String)> {
+    let table = read_recovering(&RIR_TABLE, || "RIR").clone()?;
+    let record = table.lookup(ip)?;
+    Some((record, format!("RIR delegated {}", format_epoch_date(table.build_epoch()))))
+}
@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use crate::config::Config;
+use crate::models::{IpGeoError, IpInfo, LookupOptions};
+use super::database::DatabaseManager;
+
+/// 嵌入到其他应用时的查询入口。创建时只加载数据目录中已有的文件，
+/// 不启动任何后台任务；需要下载和定时更新时调用 spawn_updater
+pub struct GeoResolver {
+    data_dir: PathBuf,
+    shutdown: CancellationToken,
+}
+
+impl GeoResolver {
+    /// 用 config 初始化全局配置（已经初始化过时沿用已有的配置），并从其 data_dir 加载数据库、
+    /// asn_info.json 和覆盖表。缺少的数据库跳过，查询时返回 DATABASE_UNAVAILABLE
+    pub fn new(config: Config) -> std::io::Result<Self> {
+        let data_dir = Config::init(config).data_dir.clone();
+        super::load_databases_from(&data_dir)?;
+        super::init_asn_data(&DatabaseManager::new(data_dir.clone()))?;
+        super::load_overrides(&data_dir)?;
+        Ok(Self { data_dir, shutdown: CancellationToken::new() })
+    }
+
+    /// 在后台下载缺失或过期的数据库，之后按 DB_UPDATE_INTERVAL_HOURS 或 DB_UPDATE_AT 定时更新
+    pub fn spawn_updater(&self) -> JoinHandle<()> {
+        super::spawn_updater(DatabaseManager::new(self.data_dir.clone()), true, self.shutdown.clone())
+    }
+
+    /// 停止 spawn_updater 启动的任务
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// 不经过 HTTP 直接查询
+    pub async fn lookup(&self, ip: &str, options: LookupOptions) -> Result<IpInfo, IpGeoError> {
+        super::get_ip_info_with(ip, options).await
+    }
+}
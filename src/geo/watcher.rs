@@ -0,0 +1,79 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+use super::database::database_type;
+
+/// 最后一次文件变化之后等待的时间，rsync 等工具连续写入期间只重新加载一次
+pub const WATCH_DEBOUNCE: Duration = Duration::from_secs(2);
+
+// 只关心最终落到已知数据库文件名上的写入和改名，临时文件被忽略
+fn changed_database(kind: &EventKind, path: &Path) -> Option<String> {
+    if !matches!(kind, EventKind::Create(_) | EventKind::Modify(_)) {
+        return None;
+    }
+    let name = path.file_name()?.to_str()?;
+    database_type(name).map(|_| name.to_string())
+}
+
+fn reload_changed(dir: &Path, names: BTreeSet<String>) {
+    for name in names {
+        let Some(db_type) = database_type(&name) else {
+            continue;
+        };
+        // 新文件无法打开时 reload_database 不会替换，旧的读取器继续使用
+        match super::geo::reload_database(db_type, &dir.join(&name)) {
+            Ok(()) => info!("Reloaded {} after it changed on disk", name),
+            Err(e) => error!("Keeping the previous {} database, new file is unusable: {}", name, e),
+        }
+    }
+}
+
+/// 在后台监视数据目录，已知的数据库文件变化后去抖并重新加载，随 shutdown 停止
+pub fn watch_data_dir(
+    dir: &Path,
+    debounce: Duration,
+    shutdown: CancellationToken,
+) -> notify::Result<tokio::task::JoinHandle<()>> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let _ = tx.send(event);
+    })?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    let dir: PathBuf = dir.to_path_buf();
+    info!("Watching {:?} for database changes", dir);
+
+    Ok(tokio::spawn(async move {
+        // watcher 被丢弃时停止监视，所以要留在任务里
+        let _watcher = watcher;
+        let mut pending = BTreeSet::new();
+        let mut deadline = Instant::now();
+        loop {
+            tokio::select! {
+                event = rx.recv() => match event {
+                    Some(Ok(event)) => {
+                        let changed: Vec<_> = event.paths.iter()
+                            .filter_map(|path| changed_database(&event.kind, path))
+                            .collect();
+                        if !changed.is_empty() {
+                            pending.extend(changed);
+                            deadline = Instant::now() + debounce;
+                        }
+                    }
+                    Some(Err(e)) => warn!("Data directory watcher error: {}", e),
+                    None => break,
+                },
+                _ = tokio::time::sleep_until(deadline), if !pending.is_empty() => {
+                    let dir = dir.clone();
+                    let names = std::mem::take(&mut pending);
+                    let _ = tokio::task::spawn_blocking(move || reload_changed(&dir, names)).await;
+                }
+                _ = shutdown.cancelled() => break,
+            }
+        }
+        info!("Data directory watcher stopped");
+    }))
+}
@@ -0,0 +1,199 @@
+use std::net::SocketAddr;
+use std::pin::Pin;
+use futures::stream::{self, Stream, StreamExt};
+use tokio_util::sync::CancellationToken;
+use tonic::metadata::MetadataMap;
+use tonic::{Code, Request, Response, Status};
+use tracing::info;
+use crate::config::Config;
+use crate::geo::{lookup_resolved, resolve_host_with_name};
+use crate::models::{self, Detail, IpGeoError, IpInfo, Lang, LookupOptions, ParamError};
+
+/// 由 proto/ipgeo.proto 生成的消息和服务定义
+#[allow(clippy::large_enum_variant)]
+pub mod pb {
+    tonic::include_proto!("ipgeo.v1");
+}
+
+use pb::batch_lookup_reply::Result as BatchResult;
+use pb::ip_geo_server::{IpGeo, IpGeoServer};
+
+fn lookup_options(detail: i32) -> LookupOptions {
+    let detail = match pb::Detail::try_from(detail).unwrap_or_default() {
+        pb::Detail::Standard => Detail::Standard,
+        pb::Detail::Minimal => Detail::Minimal,
+        pb::Detail::Full => Detail::Full,
+    };
+    LookupOptions { detail, ..LookupOptions::default() }
+}
+
+fn country(country: models::CountryInfo) -> pb::CountryInfo {
+    pb::CountryInfo {
+        code: country.code,
+        name: country.name,
+        is_eu: country.is_eu,
+        confidence: country.confidence.map(u32::from),
+    }
+}
+
+impl From<IpInfo> for pb::IpInfoReply {
+    fn from(info: IpInfo) -> Self {
+        Self {
+            ip: info.ip,
+            r#as: info.asn.map(|asn| pb::AsnInfo {
+                number: asn.number_or_zero(),
+                info: asn.info_or_name().to_string(),
+                name: asn.name,
+            }),
+            addr: info.addr,
+            location: info.location.map(|location| pb::Location {
+                latitude: location.latitude.unwrap_or_default(),
+                longitude: location.longitude.unwrap_or_default(),
+                time_zone: location.time_zone,
+                metro_code: location.metro_code.map(u32::from),
+            }),
+            accuracy_radius: info.accuracy_radius.map(u32::from),
+            postal: info.postal,
+            continent: info.continent.map(|continent| pb::ContinentInfo {
+                code: continent.code,
+                name: continent.name,
+            }),
+            country: info.country.map(country),
+            registered_country: info.registered_country.map(country),
+            represented_country: info.represented_country.map(country),
+            regions: info.regions.unwrap_or_default(),
+            regions_short: info.regions_short.unwrap_or_default(),
+            region_codes: info.region_codes.unwrap_or_default(),
+            city: info.city.map(|city| pb::CityInfo {
+                name: city.name,
+                geoname_id: city.geoname_id,
+                confidence: city.confidence.map(u32::from),
+            }),
+            r#type: info.r#type,
+            traits: info.traits.map(|traits| pb::Traits {
+                is_anonymous_proxy: traits.is_anonymous_proxy,
+                is_satellite_provider: traits.is_satellite_provider,
+                is_anycast: traits.is_anycast,
+            }),
+            isp: info.isp,
+            isp_code: info.isp_code,
+            organization: info.organization,
+            domain: info.domain,
+            rdns: info.rdns,
+            host: info.host,
+            cnames: info.cnames.unwrap_or_default(),
+            cnames_truncated: info.cnames_truncated,
+            warnings: info.warnings,
+            postal_confidence: info.postal_confidence.map(u32::from),
+            region_confidence: info.region_confidence.map(u32::from),
+            category: info.category.map(|category| category.code().to_string()),
+            is_datacenter: info.is_datacenter,
+            is_anonymous: info.is_anonymous,
+            rir: info.rir.map(|rir| pb::RirInfo {
+                registry: rir.registry,
+                allocated: rir.allocated,
+                cidr: rir.cidr,
+            }),
+        }
+    }
+}
+
+/// IpGeoError 对应的 gRPC 状态码
+pub fn status_code(error: &IpGeoError) -> Code {
+    match error {
+        IpGeoError::InvalidIp(_)
+        | IpGeoError::ParseError(_)
+        | IpGeoError::PrivateIp(_)
+        | IpGeoError::InvalidParameter(_)
+        | IpGeoError::HostTooLong(_) => Code::InvalidArgument,
+        IpGeoError::ResolveError | IpGeoError::NotFound(_) => Code::NotFound,
+        IpGeoError::TimeoutError | IpGeoError::RequestTimeout => Code::DeadlineExceeded,
+        IpGeoError::Unauthorized => Code::Unauthenticated,
+        IpGeoError::Forbidden | IpGeoError::AdminForbidden | IpGeoError::PrivateTarget(_) => Code::PermissionDenied,
+        IpGeoError::Overloaded => Code::ResourceExhausted,
+        IpGeoError::DatabaseUnavailable(_) => Code::Unavailable,
+        IpGeoError::MethodNotAllowed => Code::Unimplemented,
+        IpGeoError::IoError(_) | IpGeoError::InternalPanic => Code::Internal,
+        IpGeoError::Rejected(status, _) if status.is_client_error() => Code::InvalidArgument,
+        IpGeoError::Rejected(..) => Code::Internal,
+    }
+}
+
+impl From<IpGeoError> for Status {
+    fn from(error: IpGeoError) -> Self {
+        Status::new(status_code(&error), error.message(Lang::current()))
+    }
+}
+
+fn error_reply(error: &IpGeoError, lang: Lang) -> pb::ErrorReply {
+    let (status, error_type) = error.parts();
+    pb::ErrorReply {
+        code: u32::from(status.as_u16()),
+        error: error_type.to_string(),
+        message: error.message(lang),
+    }
+}
+
+// 与 HTTP 接口相同，按 accept-language 元数据选择错误说明的语言
+fn request_lang(metadata: &MetadataMap) -> Lang {
+    metadata.get("accept-language")
+        .and_then(|v| v.to_str().ok())
+        .and_then(Lang::from_accept_language)
+        .unwrap_or_default()
+}
+
+async fn lookup_host(host: &str, options: LookupOptions) -> Result<IpInfo, IpGeoError> {
+    let resolved = resolve_host_with_name(host).await?;
+    lookup_resolved(resolved, options).await
+}
+
+/// 与 HTTP 接口共用数据库读取器和缓存的 gRPC 服务
+#[derive(Debug, Default)]
+pub struct GrpcService;
+
+#[tonic::async_trait]
+impl IpGeo for GrpcService {
+    async fn lookup(&self, request: Request<pb::LookupRequest>) -> Result<Response<pb::IpInfoReply>, Status> {
+        let lang = request_lang(request.metadata());
+        let request = request.into_inner();
+        lang.scope(async {
+            let info = lookup_host(&request.host, lookup_options(request.detail)).await?;
+            Ok(Response::new(info.into()))
+        }).await
+    }
+
+    type BatchLookupStream = Pin<Box<dyn Stream<Item = Result<pb::BatchLookupReply, Status>> + Send>>;
+
+    async fn batch_lookup(&self, request: Request<pb::BatchLookupRequest>) -> Result<Response<Self::BatchLookupStream>, Status> {
+        let config = Config::global();
+        let lang = request_lang(request.metadata());
+        let request = request.into_inner();
+        if request.hosts.len() > config.batch_max_size {
+            let error = IpGeoError::InvalidParameter(ParamError::BatchTooLarge(config.batch_max_size));
+            return Err(Status::new(status_code(&error), error.message(lang)));
+        }
+
+        let options = lookup_options(request.detail);
+        // buffered 保持输入顺序，同时最多并发 batch_parallelism 个查询
+        let replies = stream::iter(request.hosts)
+            .map(move |host| async move {
+                let result = match lookup_host(&host, options).await {
+                    Ok(info) => BatchResult::Info(info.into()),
+                    Err(e) => BatchResult::Error(error_reply(&e, lang)),
+                };
+                Ok(pb::BatchLookupReply { query: host, result: Some(result) })
+            })
+            .buffered(config.batch_parallelism);
+        Ok(Response::new(Box::pin(replies)))
+    }
+}
+
+/// 在指定地址上提供 gRPC 服务，直到 shutdown 被取消
+pub async fn serve_grpc(addr: SocketAddr, shutdown: CancellationToken) -> Result<(), tonic::transport::Error> {
+    info!("Listening on grpc://{}", addr);
+    tonic::transport::Server::builder()
+        .timeout(Config::global().request_timeout)
+        .add_service(IpGeoServer::new(GrpcService))
+        .serve_with_shutdown(addr, async move { shutdown.cancelled().await })
+        .await
+}
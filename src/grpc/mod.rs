@@ -0,0 +1,3 @@
+mod grpc;
+
+pub use grpc::*;
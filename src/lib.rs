@@ -0,0 +1,13 @@
+#![allow(clippy::module_inception)]
+
+pub mod models;
+pub mod utils;
+pub mod geo;
+pub mod api;
+pub mod cache;
+pub mod config;
+pub mod cli;
+pub mod server;
+pub mod metrics;
+#[cfg(feature = "grpc")]
+pub mod grpc;
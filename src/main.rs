@@ -1,72 +1,102 @@
-pub mod models;
-pub mod utils;
-pub mod geo;
-pub mod api;
-pub mod cache;
-
-use std::net::SocketAddr;
-use tracing::info;
+use std::process::ExitCode;
+use clap::Parser;
+use tracing::Subscriber;
 use tracing_subscriber::EnvFilter;
-use tokio::signal;
-
-async fn shutdown_signal() {
-    let ctrl_c = async {
-        signal::ctrl_c()
-            .await
-            .expect("Failed to install Ctrl+C handler");
-    };
-
-    #[cfg(unix)]
-    let terminate = async {
-        signal::unix::signal(signal::unix::SignalKind::terminate())
-            .expect("Failed to install signal handler")
-            .recv()
-            .await;
-    };
-
-    #[cfg(not(unix))]
-    let terminate = std::future::pending::<()>();
-
-    tokio::select! {
-        _ = ctrl_c => {},
-        _ = terminate => {},
-    }
-
-    info!("Signal received, starting graceful shutdown");
-}
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+#[cfg(feature = "otlp")]
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use ipgeo::{api, cli, geo, server};
+use ipgeo::cli::{Cli, Command};
+use ipgeo::config::{Config, LogFormat};
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize logging
+fn init_logging(writer: BoxMakeWriter) {
     if std::env::var("RUST_LOG").is_err() {
         std::env::set_var("RUST_LOG", "info");
     }
+    let default_filter = std::env::var("RUST_LOG").unwrap_or_default();
     
-    tracing_subscriber::fmt()
+    let builder = tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env())
         .with_target(false)
         .with_thread_ids(true)
         .with_thread_names(true)
         .with_file(true)
-        .init();
+        .with_writer(writer);
 
-    info!("Initializing IP Geo Service");
-    
-    // Initialize MaxMind databases
-    geo::init_mmdb_readers().await?;
-    
-    // Create the router
-    let app = api::create_router();
-    
-    // Start the server
-    let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
-    info!("Listening on {}", addr);
-    
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
-    
-    info!("Server shutdown completed");
-    Ok(())
-}
\ No newline at end of file
+    // 过滤规则可以通过 /admin/log_level 和 SIGUSR1 在运行时切换
+    match Config::global().log_format {
+        LogFormat::Json => {
+            let builder = builder.json().with_current_span(true).with_span_list(false).with_filter_reloading();
+            server::install_log_filter(&default_filter, builder.reload_handle());
+            install_subscriber(builder.finish())
+        }
+        LogFormat::Pretty => {
+            let builder = builder.pretty().with_filter_reloading();
+            server::install_log_filter(&default_filter, builder.reload_handle());
+            install_subscriber(builder.finish())
+        }
+        LogFormat::Text => {
+            let builder = builder.with_filter_reloading();
+            server::install_log_filter(&default_filter, builder.reload_handle());
+            install_subscriber(builder.finish())
+        }
+    }
+}
+
+// 启用 otlp 特性且设置了 OTEL_EXPORTER_OTLP_ENDPOINT 时，通过同一个过滤规则的 span 同时导出
+fn install_subscriber<S>(subscriber: S)
+where
+    S: Subscriber + for<'a> LookupSpan<'a> + Send + Sync + 'static,
+{
+    #[cfg(feature = "otlp")]
+    let subscriber = subscriber.with(server::otlp_layer());
+    subscriber.init()
+}
+
+#[tokio::main]
+async fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    // 配置无效时直接退出，不回退到默认值
+    let config = match Config::load(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let config = Config {
+        data_dir: cli.data_dir.clone().unwrap_or_else(|| config.data_dir.clone()),
+        bind: if cli.bind.is_empty() { config.bind.clone() } else { cli.bind.clone() },
+        admin_bind: cli.admin_bind.or(config.admin_bind),
+        grpc_bind: cli.grpc_bind.or(config.grpc_bind),
+        ..config
+    };
+    if cli.check_config {
+        print!("{}", config.to_toml());
+        return Ok(ExitCode::SUCCESS);
+    }
+    Config::init(config);
+
+    let data_dir = Config::global().data_dir.clone();
+    let result = match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => {
+            // Initialize logging
+            init_logging(BoxMakeWriter::new(std::io::stdout));
+            server::serve(api::AppState::new()).await
+        }
+        Command::Lookup { hosts, format } => {
+            // stdout 留给查询结果，日志写到 stderr
+            init_logging(BoxMakeWriter::new(std::io::stderr));
+            Ok(cli::run_lookup(data_dir, hosts, format).await?)
+        }
+        Command::Update { force, only } => {
+            init_logging(BoxMakeWriter::new(std::io::stderr));
+            Ok(cli::run_update(data_dir, geo::UpdateOptions { force, only }).await?)
+        }
+    };
+    #[cfg(feature = "otlp")]
+    server::shutdown_otlp();
+    result
+}
@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 允许作为标签值的网络类型，来自内置 asn_info.json 和 ISP 名称的分类；其余归为 other
+pub const NETWORK_TYPE_LABELS: &[&str] = &[
+    "电信网络", "联通网络", "移动网络", "铁通网络", "广电网络", "教育网络", "科技网",
+    "数据中心", "长城宽带", "鹏博士", "政务网络", "互联网交换中心", "其他网络", "embedded-fallback",
+];
+
+// 国家代码只接受两位大写字母，其余归为 other，最多 26×26 个取值
+pub(crate) fn country_label(code: Option<&str>) -> &str {
+    match code {
+        None | Some("") => "unknown",
+        Some(code) if code.len() == 2 && code.bytes().all(|b| b.is_ascii_uppercase()) => code,
+        Some(_) => "other",
+    }
+}
+
+pub(crate) fn network_type_label(network_type: Option<&str>) -> &'static str {
+    match network_type {
+        None => "unknown",
+        Some(network_type) => NETWORK_TYPE_LABELS.iter()
+            .find(|label| **label == network_type)
+            .copied()
+            .unwrap_or("other"),
+    }
+}
+
+fn bump(counts: &Mutex<BTreeMap<String, u64>>, label: &str) {
+    if let Ok(mut counts) = counts.lock() {
+        match counts.get_mut(label) {
+            Some(count) => *count += 1,
+            None => {
+                counts.insert(label.to_string(), 1);
+            }
+        }
+    }
+}
+
+// 进程内计数器，以 Prometheus 文本格式输出
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub dns_lookups: AtomicU64,
+    pub dns_failures: AtomicU64,
+    pub dns_timeouts: AtomicU64,
+    /// DNS 解析累计耗时（微秒）
+    pub dns_lookup_micros: AtomicU64,
+    /// 解析器缓存容量，启动时写入
+    pub dns_cache_capacity: AtomicU64,
+    /// 共享了其他并发请求结果的DNS解析
+    pub dns_dedup_hits: AtomicU64,
+    pub ip_lookups: AtomicU64,
+    /// 共享了其他并发请求结果的IP查询
+    pub ip_dedup_hits: AtomicU64,
+    /// 启动时从快照恢复的结果缓存条目
+    pub result_cache_restored: AtomicU64,
+    /// 启动时按 hot_ips.txt 预先完成的查询
+    pub result_cache_warmed: AtomicU64,
+    /// 完成的结果缓存提前刷新
+    pub result_cache_refreshes: AtomicU64,
+    /// 刷新队列已满而放弃的提前刷新
+    pub result_cache_refresh_drops: AtomicU64,
+    /// 超过 MAX_IN_FLIGHT 被拒绝的请求
+    pub requests_rejected: AtomicU64,
+    /// 超过 REQUEST_TIMEOUT_MS 的请求
+    pub requests_timed_out: AtomicU64,
+    /// 数据库下载的重试次数
+    pub db_download_retries: AtomicU64,
+    /// 重试后仍然失败的数据库更新
+    pub db_update_failures: AtomicU64,
+    /// 成功重新加载数据库读取器的次数
+    pub db_reloads: AtomicU64,
+    /// 每个数据库最近一次更新是否失败
+    db_update_failed: Mutex<BTreeMap<&'static str, bool>>,
+    /// 按国家代码统计的查询，标签值见 country_label
+    lookups_by_country: Mutex<BTreeMap<String, u64>>,
+    /// 按网络类型统计的查询，标签值见 NETWORK_TYPE_LABELS
+    lookups_by_type: Mutex<BTreeMap<String, u64>>,
+    /// 按 get_real_ip 采用的头部统计的请求
+    client_ip_sources: Mutex<BTreeMap<String, u64>>,
+    /// 按数据库和原因统计的查询阶段失败
+    db_stage_failures: Mutex<BTreeMap<(&'static str, &'static str), u64>>,
+    /// compare=1 时 GeoCN 与 GeoLite2-City 的省级对比结果
+    geocn_comparisons: Mutex<BTreeMap<String, u64>>,
+    /// 按数据统计的中毒锁恢复次数
+    lock_recoveries: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+impl Metrics {
+    pub fn global() -> &'static Metrics {
+        METRICS.get_or_init(Metrics::default)
+    }
+
+    #[inline]
+    pub fn incr(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录一次数据库更新的结果
+    pub fn record_db_update(&self, name: &'static str, failed: bool) {
+        if failed {
+            Metrics::incr(&self.db_update_failures);
+        }
+        if let Ok(mut status) = self.db_update_failed.lock() {
+            status.insert(name, failed);
+        }
+    }
+
+    /// 记录一次查询结果的国家代码
+    pub fn record_country(&self, code: Option<&str>) {
+        bump(&self.lookups_by_country, country_label(code));
+    }
+
+    /// 记录一次查询结果的网络类型
+    pub fn record_network_type(&self, network_type: Option<&str>) {
+        bump(&self.lookups_by_type, network_type_label(network_type));
+    }
+
+    /// 记录客户端IP来自哪个头部，source 只能是固定的头部名或 "socket"
+    pub fn record_client_ip_source(&self, source: &'static str) {
+        bump(&self.client_ip_sources, source);
+    }
+
+    /// 记录一次数据库查询阶段的失败，reason 为 "unavailable" 或 "error"
+    pub fn record_stage_failure(&self, database: &'static str, reason: &'static str) {
+        if let Ok(mut counts) = self.db_stage_failures.lock() {
+            *counts.entry((database, reason)).or_insert(0) += 1;
+        }
+    }
+
+    /// 记录一次中毒锁的恢复，database 为数据库类型、"RIR" 或 "overrides"
+    pub fn record_lock_recovery(&self, database: &'static str) {
+        if let Ok(mut counts) = self.lock_recoveries.lock() {
+            *counts.entry(database).or_insert(0) += 1;
+        }
+    }
+
+    /// 各数据恢复过中毒锁的次数，用于健康检查
+    pub fn lock_recoveries(&self) -> BTreeMap<&'static str, u64> {
+        self.lock_recoveries.lock().map(|counts| counts.clone()).unwrap_or_default()
+    }
+
+    /// 记录一次 GeoCN 与 GeoLite2-City 的对比，result 为 "agree"、"divergent" 或 "incomplete"
+    pub fn record_geocn_comparison(&self, result: &'static str) {
+        bump(&self.geocn_comparisons, result);
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::with_capacity(1024);
+        let mut metric = |name: &str, kind: &str, help: &str, value: f64| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value);
+        };
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed) as f64;
+
+        metric("ipgeo_dns_lookups_total", "counter", "DNS lookups sent to the resolver.", load(&self.dns_lookups));
+        metric("ipgeo_dns_failures_total", "counter", "DNS lookups that returned an error or no records.", load(&self.dns_failures));
+        metric("ipgeo_dns_timeouts_total", "counter", "DNS lookups that timed out after all attempts.", load(&self.dns_timeouts));
+        metric("ipgeo_dns_lookup_seconds_sum", "counter", "Total time spent in DNS lookups.", load(&self.dns_lookup_micros) / 1_000_000.0);
+        metric("ipgeo_dns_cache_capacity", "gauge", "Maximum number of entries in the resolver cache.", load(&self.dns_cache_capacity));
+        metric("ipgeo_dns_dedup_hits_total", "counter", "DNS lookups that shared the result of a concurrent identical lookup.", load(&self.dns_dedup_hits));
+        metric("ipgeo_ip_lookups_total", "counter", "IP geolocation lookups.", load(&self.ip_lookups));
+        metric("ipgeo_requests_rejected_total", "counter", "Requests rejected because the concurrency limit was reached.", load(&self.requests_rejected));
+        metric("ipgeo_requests_timed_out_total", "counter", "Requests that exceeded the request timeout.", load(&self.requests_timed_out));
+        metric("ipgeo_ip_dedup_hits_total", "counter", "IP lookups that shared the result of a concurrent identical lookup.", load(&self.ip_dedup_hits));
+        metric("ipgeo_result_cache_restored", "gauge", "Result cache entries restored from the snapshot at startup.", load(&self.result_cache_restored));
+        metric("ipgeo_result_cache_warmed", "gauge", "Hot IPs looked up in the background at startup to warm the result cache.", load(&self.result_cache_warmed));
+        metric("ipgeo_result_cache_refreshes_total", "counter", "Result cache entries recomputed in the background before they expired.", load(&self.result_cache_refreshes));
+        metric("ipgeo_result_cache_refresh_drops_total", "counter", "Refresh-ahead requests dropped because the refresh queue was full.", load(&self.result_cache_refresh_drops));
+        metric("ipgeo_db_download_retries_total", "counter", "Database download attempts that were retried.", load(&self.db_download_retries));
+        metric("ipgeo_db_update_failures_total", "counter", "Database updates that failed after all retries.", load(&self.db_update_failures));
+        metric("ipgeo_db_reloads_total", "counter", "Database readers swapped in after a download, rollback or file change.", load(&self.db_reloads));
+        metric("ipgeo_data_generation", "gauge", "Incremented whenever databases, ASN info or overrides are replaced.", crate::geo::data_generation() as f64);
+
+        if let Ok(status) = self.db_update_failed.lock() {
+            if !status.is_empty() {
+                let _ = writeln!(out, "# HELP ipgeo_db_update_failed Whether the last update of each database failed.");
+                let _ = writeln!(out, "# TYPE ipgeo_db_update_failed gauge");
+                for (name, failed) in status.iter() {
+                    let _ = writeln!(out, "ipgeo_db_update_failed{{database=\"{}\"}} {}", name, u8::from(*failed));
+                }
+            }
+        }
+        if let Ok(counts) = self.db_stage_failures.lock() {
+            if !counts.is_empty() {
+                let _ = writeln!(out, "# HELP ipgeo_db_stage_failures_total Lookups where a database was unavailable or returned an error.");
+                let _ = writeln!(out, "# TYPE ipgeo_db_stage_failures_total counter");
+                for ((database, reason), count) in counts.iter() {
+                    let _ = writeln!(out, "ipgeo_db_stage_failures_total{{database=\"{}\",reason=\"{}\"}} {}", database, reason, count);
+                }
+            }
+        }
+        let recoveries = self.lock_recoveries();
+        if !recoveries.is_empty() {
+            let _ = writeln!(out, "# HELP ipgeo_lock_recoveries_total Poisoned database locks that were recovered after a panic.");
+            let _ = writeln!(out, "# TYPE ipgeo_lock_recoveries_total counter");
+            for (database, count) in recoveries {
+                let _ = writeln!(out, "ipgeo_lock_recoveries_total{{database=\"{}\"}} {}", database, count);
+            }
+        }
+        let mut labeled = |name: &str, help: &str, label: &str, counts: &Mutex<BTreeMap<String, u64>>| {
+            let Ok(counts) = counts.lock() else {
+                return;
+            };
+            if counts.is_empty() {
+                return;
+            }
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            for (value, count) in counts.iter() {
+                let _ = writeln!(out, "{}{{{}=\"{}\"}} {}", name, label, value, count);
+            }
+        };
+        labeled("ipgeo_lookups_by_country_total", "IP lookups by resolved country code.", "country", &self.lookups_by_country);
+        labeled("ipgeo_lookups_by_type_total", "IP lookups by network type.", "type", &self.lookups_by_type);
+        labeled("ipgeo_client_ip_source_total", "Requests by the header the client IP was taken from.", "source", &self.client_ip_sources);
+        labeled("ipgeo_geocn_comparisons_total", "GeoCN answers compared with GeoLite2-City, by province-level agreement.", "result", &self.geocn_comparisons);
+        out
+    }
+}
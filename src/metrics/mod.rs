@@ -0,0 +1,4 @@
+pub mod metrics;
+pub mod stats;
+pub mod timing;
+pub use metrics::*;
@@ -0,0 +1,155 @@
+//! 最近一段时间查询的国家和网络类型分布，供 `GET /stats` 使用。
+//! 每分钟一个桶，环形复用；只记录归类后的标签（见 country_label 和 network_type_label），
+//! 不保存IP，内存上限为窗口分钟数 ×（国家代码 + 网络类型）的取值个数。
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use utoipa::ToSchema;
+use crate::config::Config;
+use super::metrics::{country_label, network_type_label};
+
+// 一分钟内的计数，minute 为 Unix 纪元以来的分钟数
+#[derive(Debug, Default)]
+struct Bucket {
+    minute: u64,
+    total: u64,
+    countries: BTreeMap<String, u64>,
+    types: BTreeMap<&'static str, u64>,
+}
+
+/// 按分钟滚动的查询分布
+#[derive(Debug)]
+pub struct TrafficStats {
+    buckets: Mutex<Vec<Bucket>>,
+}
+
+/// 单个国家代码的查询数
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct CountryCount {
+    pub code: String,
+    pub count: u64,
+}
+
+/// 单个网络类型的查询数
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct TypeCount {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub count: u64,
+}
+
+/// `GET /stats` 的响应
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct StatsSummary {
+    /// 统计窗口（分钟）
+    pub window_minutes: u64,
+    pub total: u64,
+    /// 按查询数降序，最多 top 个
+    pub countries: Vec<CountryCount>,
+    pub types: Vec<TypeCount>,
+}
+
+static STATS: OnceLock<Option<TrafficStats>> = OnceLock::new();
+
+fn now_minute() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / 60
+}
+
+// 按数量降序、标签升序取前 top 个
+fn top_n<K: Ord + ToString>(counts: BTreeMap<K, u64>, top: usize) -> Vec<(String, u64)> {
+    let mut counts: Vec<(K, u64)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.into_iter().take(top).map(|(label, count)| (label.to_string(), count)).collect()
+}
+
+impl TrafficStats {
+    /// 保留 window 内的统计，不足一分钟按一分钟计
+    pub fn new(window: Duration) -> Self {
+        let minutes = window.as_secs().div_ceil(60).max(1) as usize;
+        Self { buckets: Mutex::new((0..minutes).map(|_| Bucket::default()).collect()) }
+    }
+
+    /// 进程内的实例，STATS_WINDOW_HOURS=0 时为 None
+    pub fn global() -> Option<&'static TrafficStats> {
+        STATS.get_or_init(|| {
+            let window = Config::global().stats_window;
+            (!window.is_zero()).then(|| TrafficStats::new(window))
+        }).as_ref()
+    }
+
+    /// 保留的分钟数，也是 summary 可用的最大窗口
+    pub fn window_minutes(&self) -> u64 {
+        self.buckets.lock().map(|buckets| buckets.len() as u64).unwrap_or(0)
+    }
+
+    /// 记录一次成功的查询
+    pub fn record(&self, country: Option<&str>, network_type: Option<&str>) {
+        self.record_at(now_minute(), country, network_type);
+    }
+
+    /// 在指定分钟记录一次查询，桶里是更早的数据时先清空
+    pub fn record_at(&self, minute: u64, country: Option<&str>, network_type: Option<&str>) {
+        let Ok(mut buckets) = self.buckets.lock() else { return };
+        let len = buckets.len() as u64;
+        let bucket = &mut buckets[(minute % len) as usize];
+        if bucket.minute != minute {
+            *bucket = Bucket { minute, ..Bucket::default() };
+        }
+        bucket.total += 1;
+        let country = country_label(country);
+        match bucket.countries.get_mut(country) {
+            Some(count) => *count += 1,
+            None => {
+                bucket.countries.insert(country.to_string(), 1);
+            }
+        }
+        *bucket.types.entry(network_type_label(network_type)).or_insert(0) += 1;
+    }
+
+    /// 最近 window_minutes 分钟（含当前分钟）的汇总
+    pub fn summary(&self, window_minutes: u64, top: usize) -> StatsSummary {
+        self.summary_at(now_minute(), window_minutes, top)
+    }
+
+    /// 以 now 为当前分钟汇总，窗口超过保留时长时按保留时长计算
+    pub fn summary_at(&self, now: u64, window_minutes: u64, top: usize) -> StatsSummary {
+        let mut total = 0;
+        let mut countries: BTreeMap<String, u64> = BTreeMap::new();
+        let mut types: BTreeMap<&'static str, u64> = BTreeMap::new();
+        let mut window_minutes = window_minutes.max(1);
+        if let Ok(buckets) = self.buckets.lock() {
+            window_minutes = window_minutes.min(buckets.len() as u64);
+            for bucket in buckets.iter().filter(|b| b.total > 0 && b.minute <= now && now - b.minute < window_minutes) {
+                total += bucket.total;
+                for (code, count) in &bucket.countries {
+                    *countries.entry(code.clone()).or_insert(0) += count;
+                }
+                for (kind, count) in &bucket.types {
+                    *types.entry(kind).or_insert(0) += count;
+                }
+            }
+        }
+        StatsSummary {
+            window_minutes,
+            total,
+            countries: top_n(countries, top).into_iter().map(|(code, count)| CountryCount { code, count }).collect(),
+            types: top_n(types, top).into_iter().map(|(kind, count)| TypeCount { kind, count }).collect(),
+        }
+    }
+}
+
+/// 解析 `30m`、`1h`、`2d` 形式的窗口，返回分钟数
+pub fn parse_window(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let split = value.len().checked_sub(1)?;
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number.parse().ok().filter(|n| *n > 0)?;
+    match unit {
+        "m" => Some(number),
+        "h" => number.checked_mul(60),
+        "d" => number.checked_mul(24 * 60),
+        _ => None,
+    }
+}
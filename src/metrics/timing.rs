@@ -0,0 +1,103 @@
+//! 请求各阶段的耗时，用于慢请求日志和 `debug_timing=1`。
+//! 访问日志中间件为每个请求建立 task-local 的计时表，查询路径上的各阶段各自记录；
+//! 不在请求中调用（如 gRPC、测试）时记录被忽略。
+
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+tokio::task_local! {
+    static TIMINGS: Arc<RequestTimings>;
+}
+
+/// 一个请求的计时表
+#[derive(Debug, Default)]
+pub struct RequestTimings {
+    stages: Mutex<Vec<(&'static str, Duration)>>,
+    /// 查询结果来自并发的相同查询
+    shared: AtomicBool,
+    /// 在响应中输出 timing 对象
+    expose: bool,
+}
+
+/// 单个阶段的累计耗时
+#[derive(Debug, Clone, Serialize)]
+pub struct StageTiming {
+    pub stage: &'static str,
+    pub ms: f64,
+}
+
+/// 各阶段的耗时，同名阶段（如批量查询中的多次解析）累加，按首次出现的顺序排列
+#[derive(Debug, Clone, Serialize)]
+pub struct TimingBreakdown {
+    pub stages: Vec<StageTiming>,
+    pub shared: bool,
+}
+
+impl fmt::Display for TimingBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, stage) in self.stages.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}={:.3}ms", stage.stage, stage.ms)?;
+        }
+        Ok(())
+    }
+}
+
+impl RequestTimings {
+    pub fn new(expose: bool) -> Arc<Self> {
+        Arc::new(Self { expose, ..Self::default() })
+    }
+
+    pub fn breakdown(&self) -> TimingBreakdown {
+        let mut stages: Vec<StageTiming> = Vec::new();
+        if let Ok(recorded) = self.stages.lock() {
+            for (stage, elapsed) in recorded.iter() {
+                let ms = elapsed.as_secs_f64() * 1000.0;
+                match stages.iter_mut().find(|timing| timing.stage == *stage) {
+                    Some(timing) => timing.ms += ms,
+                    None => stages.push(StageTiming { stage, ms }),
+                }
+            }
+        }
+        TimingBreakdown {
+            stages,
+            shared: self.shared.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 在计时表的作用域中运行 fut
+pub async fn with_timings<F: Future>(timings: Arc<RequestTimings>, fut: F) -> F::Output {
+    TIMINGS.scope(timings, fut).await
+}
+
+pub fn record_stage(stage: &'static str, elapsed: Duration) {
+    let _ = TIMINGS.try_with(|timings| {
+        if let Ok(mut stages) = timings.stages.lock() {
+            stages.push((stage, elapsed));
+        }
+    });
+}
+
+/// 运行 fut 并把耗时记为 stage
+pub async fn timed<F: Future>(stage: &'static str, fut: F) -> F::Output {
+    let start = Instant::now();
+    let output = fut.await;
+    record_stage(stage, start.elapsed());
+    output
+}
+
+pub fn mark_shared() {
+    let _ = TIMINGS.try_with(|timings| timings.shared.store(true, Ordering::Relaxed));
+}
+
+/// 请求要求输出计时时返回目前为止的各阶段耗时
+pub fn exposed_breakdown() -> Option<TimingBreakdown> {
+    TIMINGS.try_with(|timings| timings.expose.then(|| timings.breakdown())).ok().flatten()
+}
@@ -1,50 +1,509 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::net::AddrParseError;
 use thiserror::Error;
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct AsnInfo {
-    pub number: u32,
+    /// 数据库中没有号码时为 None
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number: Option<u32>,
+    /// asn_info.json 中整理的名称，没有时为数据库中的组织名称
     pub name: String,
-    pub info: String,
+    /// asn_info.json 中整理的说明，没有时为 None
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub info: Option<String>,
+    /// ASN 数据库中匹配记录的网段，即该 ASN 宣告的前缀，如 `8.8.8.0/24`；与固定取 /16（IPv4）或 /32（IPv6）的 addr 无关
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route: Option<String>,
+}
+
+/// `AS15169` 形式的 ASN，各处输出字符串形式时都用它
+pub fn format_asn(number: u32) -> String {
+    format!("AS{}", number)
+}
+
+impl AsnInfo {
+    pub fn label(&self) -> Option<String> {
+        self.number.map(format_asn)
+    }
+
+    /// 号码和名称都没有时，`as` 对象没有意义
+    pub fn is_empty(&self) -> bool {
+        self.number.is_none() && self.name.is_empty()
+    }
+
+    /// 旧的写法：没有号码时为 0
+    pub fn number_or_zero(&self) -> u32 {
+        self.number.unwrap_or(0)
+    }
+
+    /// 旧的写法：没有整理的说明时重复 name
+    pub fn info_or_name(&self) -> &str {
+        self.info.as_deref().unwrap_or(&self.name)
+    }
+
+    /// 按 ASN_OMIT_UNKNOWN 输出：开启时原样省略缺失的字段，没有意义时整个去掉；关闭时补上旧的占位值
+    pub fn for_output(self, omit_unknown: bool) -> Option<Self> {
+        match omit_unknown {
+            true => (!self.is_empty()).then_some(self),
+            false => Some(Self {
+                number: Some(self.number_or_zero()),
+                info: Some(self.info_or_name().to_string()),
+                name: self.name,
+                route: self.route,
+            }),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Clone)]
+/// GeoCN.mmdb 中的记录，字段均为中文全称
+#[derive(Debug, Deserialize, Clone)]
+pub struct GeoCNInfo {
+    pub province: Option<String>,
+    pub city: Option<String>,
+    pub districts: Option<String>,
+    pub isp: Option<String>,
+    pub net: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct Location {
     pub latitude: Option<f64>,
     pub longitude: Option<f64>,
+    /// IANA 时区，只在 detail=full 时输出
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<String>,
+    /// 美国的 DMA 都市区代码，其他国家没有
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metro_code: Option<u16>,
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct CountryInfo {
     pub code: String,
     pub name: String,
+    /// 是否为欧盟成员国。只在为 true 时输出，false 或未知时省略
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_eu: bool,
+    /// 国家判断的可信度（0-100），只有商业版 GeoIP2-City 提供
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<u8>,
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct CityInfo {
+    pub name: String,
+    /// GeoNames ID，只有来自 GeoLite2-City 的城市才有
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geoname_id: Option<u32>,
+    /// 城市判断的可信度（0-100），只有商业版 GeoIP2-City 提供
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ContinentInfo {
+    pub code: String,
+    pub name: String,
+}
+
+/// 网络类型的分类，输出为稳定的英文代码，与中文的 `type` 一起给程序判断用
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkCategory {
+    /// 电信、联通、移动等接入运营商
+    Isp,
+    /// 数据中心和云服务商
+    Hosting,
+    /// 教育网和科研网
+    Education,
+    /// 政府机构的网络
+    Government,
+    /// 互联网交换中心
+    Ixp,
+    Other,
+}
+
+impl NetworkCategory {
+    /// 按 asn_info.json 和 ISP 名称分类得到的中文类型归类，私有地址等特殊类型没有分类
+    pub fn from_type(network_type: &str) -> Option<Self> {
+        match network_type {
+            "电信网络" | "联通网络" | "移动网络" | "铁通网络" | "广电网络" | "长城宽带" | "鹏博士" => Some(Self::Isp),
+            "数据中心" => Some(Self::Hosting),
+            "教育网络" | "科技网" => Some(Self::Education),
+            "政务网络" => Some(Self::Government),
+            "互联网交换中心" => Some(Self::Ixp),
+            "其他网络" => Some(Self::Other),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Isp => "isp",
+            Self::Hosting => "hosting",
+            Self::Education => "education",
+            Self::Government => "government",
+            Self::Ixp => "ixp",
+            Self::Other => "other",
+        }
+    }
+}
+
+/// 区域互联网注册机构的分配记录，来自各 RIR 的 delegated-extended 统计文件
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct RirInfo {
+    /// AFRINIC、APNIC、ARIN、LACNIC 或 RIPE NCC
+    pub registry: String,
+    /// 分配日期，如 `2011-08-11`，统计文件中没有日期时省略
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allocated: Option<String>,
+    /// 分配段中包含该IP的网段
+    pub cidr: String,
+}
+
+/// GeoIP2 网络特征，只输出为 true 的标记
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
+pub struct Traits {
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_anonymous_proxy: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_satellite_provider: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_anycast: bool,
+}
+
+impl Traits {
+    pub fn is_empty(&self) -> bool {
+        !(self.is_anonymous_proxy || self.is_satellite_provider || self.is_anycast)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
 pub struct IpInfo {
+    /// 提交的原始输入（规范化之前），只在批量查询或 echo=1 时输出
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    /// 按域名查询时为规范化后实际解析的主机名
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// 解析域名时经过的 CNAME 链
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cnames: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cnames_truncated: bool,
     pub ip: String,
     #[serde(rename = "as", skip_serializing_if = "Option::is_none")]
     pub asn: Option<AsnInfo>,
     pub addr: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub location: Option<Location>,
+    /// 坐标的精度半径（公里）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accuracy_radius: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub postal: Option<String>,
+    /// 邮编的可信度（0-100），只有商业版 GeoIP2-City 提供
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub postal_confidence: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continent: Option<ContinentInfo>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub country: Option<CountryInfo>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub registered_country: Option<CountryInfo>,
+    /// 与所在地不同的代表国家，例如驻外军事基地
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub represented_country: Option<CountryInfo>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub regions: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub regions_short: Option<Vec<String>>,
+    /// 与 regions 逐项对齐的 ISO 3166-2 代码，如 `CN-ZJ`、`US-CA`，无法确定的一级为空字符串；一个都没有时省略
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region_codes: Option<Vec<String>>,
+    /// regions 中省级地区的可信度（0-100），只有商业版 GeoIP2-City 提供
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region_confidence: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<CityInfo>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub r#type: Option<String>,
+    /// type 对应的英文分类代码，如 `education`、`ixp`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<NetworkCategory>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub traits: Option<Traits>,
+    /// 推测为数据中心或云服务的地址，只在为 true 时输出；sources=1 时 sources 中给出判断依据
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_datacenter: bool,
+    /// GeoIP2 标记的匿名网络（代理、VPN 等），只在为 true 时输出
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_anonymous: bool,
+    /// 以下字段来自可选的 GeoIP2-ISP / GeoIP2-Domain 数据库
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub isp: Option<String>,
+    /// 国内运营商的规范代码，如 `CT`、`CU`、`CM`，此时 isp 为对应的规范名称
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub isp_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    /// 所属地址块在 RIR 的分配信息，只在 rir=1 或 detail=full 时输出
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rir: Option<RirInfo>,
+    /// 反向解析得到的主机名，只在 detail=full 时查询
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rdns: Option<String>,
+    /// 字段组到数据来源的映射，如 `"regions": "GeoCN 2024-04-28"`，只在 sources=1 时输出
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sources: Option<BTreeMap<String, String>>,
+    /// 结果的计算时间（RFC3339，UTC），只在批量查询或 echo=1 时输出
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    /// 结果不是为本次请求计算的，timestamp 为最初的计算时间；只在 timestamp 输出且为 true 时输出
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+    /// 部分数据库查询失败时的说明，如 `"ASN database unavailable"`，其余字段仍然有效
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// GeoCN 与 GeoLite2-City 各自的回答，只在 compare=1 且 GeoCN 有记录时输出
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comparison: Option<GeoComparison>,
+}
+
+/// 单个数据库对地区和位置的回答
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
+pub struct ComparedAnswer {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regions: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region_codes: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    /// GeoCN 没有坐标
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
 }
 
-#[derive(Debug, Serialize, Clone)]
-pub struct IpResponse {
-    pub host: String,
-    pub ips: Vec<IpInfo>,
+/// compare=1 时同一地址在 GeoCN 和 GeoLite2-City 中的回答，主结果仍以 GeoCN 为准
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
+pub struct GeoComparison {
+    pub geocn: ComparedAnswer,
+    pub geolite2: ComparedAnswer,
+    /// 两个数据库都给出了省级地区且不一致
+    pub divergent: bool,
+}
+
+/// 查询的详细程度，对应 `detail` 参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Detail {
+    /// 只查询 ASN 和国家，跳过 GeoCN、省市和 ISP 数据库
+    Minimal,
+    #[default]
+    Standard,
+    /// 额外输出时区并做反向解析
+    Full,
+}
+
+/// `as` 对象中 ASN 的写法，对应 `asn_format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AsnFormat {
+    /// `"number": 15169`
+    #[default]
+    Number,
+    /// `"asn": "AS15169"`，不输出 number
+    String,
+    /// 同时输出 number 和 asn
+    Both,
+}
+
+/// 输出哪些地区字段，对应 `regions` 参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RegionStyle {
+    /// 只输出 regions
+    Full,
+    /// 只输出 regions_short
+    Short,
+    #[default]
+    Both,
+    /// 两者都不输出
+    None,
+}
+
+impl RegionStyle {
+    pub fn full(self) -> bool {
+        matches!(self, Self::Full | Self::Both)
+    }
+
+    pub fn short(self) -> bool {
+        matches!(self, Self::Short | Self::Both)
+    }
+}
+
+/// 单次查询的选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize)]
+pub struct LookupOptions {
+    #[serde(default)]
+    pub detail: Detail,
+    /// 是否输出各字段的数据来源，对应 `sources=1`
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub sources: bool,
+    /// 坐标保留的小数位数，对应 `precision`，只能比服务端的 COORD_PRECISION 更粗
+    #[serde(default)]
+    pub precision: Option<u8>,
+    #[serde(default)]
+    pub asn_format: AsnFormat,
+    #[serde(default)]
+    pub regions: RegionStyle,
+    /// 输出 RIR 分配信息，对应 `rir=1`；detail=full 时总是输出
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub rir: bool,
+    /// 国家、城市等名称的语言，来自 `lang` 参数，未指定时由处理函数按 Accept-Language 填写
+    #[serde(default, rename = "lang", deserialize_with = "deserialize_locales")]
+    pub locales: Option<NameLocales>,
+    /// 输出 query、timestamp 和 cached，对应 `echo`；未指定时 /v2 的批量接口开启，单个查询关闭
+    #[serde(default, deserialize_with = "deserialize_optional_flag")]
+    pub echo: Option<bool>,
+    /// 同时输出 GeoLite2-City 的地区和位置，与 GeoCN 的结果对比，对应 `compare=1`；需要管理凭据
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub compare: bool,
+}
+
+impl LookupOptions {
+    /// 按服务端配置限制坐标精度：请求只能要求更少的小数位，不能更多
+    pub fn with_precision_limit(self, limit: Option<u8>) -> Self {
+        let precision = match (self.precision, limit) {
+            (Some(requested), Some(limit)) => Some(requested.min(limit)),
+            (requested, limit) => requested.or(limit),
+        };
+        Self { precision, ..self }
+    }
+
+    /// 未指定 echo 时按接口的默认值处理
+    pub fn with_echo_default(self, default: bool) -> Self {
+        Self { echo: Some(self.echo.unwrap_or(default)), ..self }
+    }
+
+    pub fn echo(&self) -> bool {
+        self.echo == Some(true)
+    }
+}
+
+// `?sources`、`?sources=1`、`?sources=true` 等都视为开启
+fn deserialize_flag<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    Ok(crate::utils::is_truthy(&value))
+}
+
+// 与 deserialize_flag 相同，但保留未指定的情况，由接口决定默认值
+fn deserialize_optional_flag<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<bool>, D::Error> {
+    deserialize_flag(deserializer).map(Some)
+}
+
+// `lang` 参数可以是单个语言或逗号分隔的列表，都不认识时视为未指定
+fn deserialize_locales<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<NameLocales>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    Ok(NameLocales::from_tags(value.split(',')))
+}
+
+/// GeoLite2 提供名称的语言
+pub const NAME_LOCALES: &[&str] = &["en", "zh-CN", "ja", "ru", "de", "es", "fr", "pt-BR"];
+
+/// 名称语言的优先顺序，最后总是回退到 en
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NameLocales {
+    order: [&'static str; NAME_LOCALES.len()],
+    len: usize,
+}
+
+impl Default for NameLocales {
+    fn default() -> Self {
+        Self::from_tags(["zh-CN"]).expect("zh-CN is a name locale")
+    }
+}
+
+impl NameLocales {
+    /// 按主语言匹配 GeoLite2 的名称语言。数据库只有简体中文，zh-TW、zh-Hant 等也取 zh-CN，
+    /// 比回退到英文更贴近用户；pt-PT 同理取 pt-BR
+    pub fn match_tag(tag: &str) -> Option<&'static str> {
+        let primary = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        NAME_LOCALES.iter()
+            .find(|locale| locale.split('-').next().is_some_and(|p| p.eq_ignore_ascii_case(&primary)))
+            .copied()
+    }
+
+    /// 按给定顺序去重，忽略不认识的语言；一个都不认识时返回 None
+    pub fn from_tags<'a>(tags: impl IntoIterator<Item = &'a str>) -> Option<Self> {
+        let mut locales = Self { order: [""; NAME_LOCALES.len()], len: 0 };
+        for locale in tags.into_iter().filter_map(Self::match_tag) {
+            locales.push(locale);
+        }
+        if locales.len == 0 {
+            return None;
+        }
+        locales.push("en");
+        Some(locales)
+    }
+
+    /// 按 q 值排列 Accept-Language 中的语言
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        Self::from_tags(weighted_tags(header))
+    }
+
+    fn push(&mut self, locale: &'static str) {
+        if !self.as_slice().contains(&locale) {
+            self.order[self.len] = locale;
+            self.len += 1;
+        }
+    }
+
+    /// 依次尝试的语言，如 `["zh-CN", "en"]`
+    pub fn as_slice(&self) -> &[&'static str] {
+        &self.order[..self.len]
+    }
+
+    /// 首选语言
+    pub fn preferred(&self) -> &'static str {
+        self.order[0]
+    }
+}
+
+// Accept-Language 中的语言标签，按 q 值降序，q 相同时保留原顺序；q=0 和写错的项被忽略
+fn weighted_tags(header: &str) -> Vec<&str> {
+    let mut tags: Vec<(&str, f32)> = header
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let tag = parts.next()?.trim();
+            let q = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (q > 0.0 && !tag.is_empty()).then_some((tag, q))
+        })
+        .collect();
+    tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+    tags.into_iter().map(|(tag, _)| tag).collect()
+}
+
+/// 统一的错误信封
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ErrorBody {
+    /// 与响应相同的HTTP状态码
+    pub code: u16,
+    /// 机器可读的错误类型，如 INVALID_IP
+    pub error: String,
+    /// 按 lang / Accept-Language 选择语言的说明
+    pub message: String,
+    /// 与 X-Request-Id 响应头相同的请求ID，批量结果中的单项错误不带
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
 #[derive(Debug, Error)]
@@ -59,44 +518,268 @@ pub enum IpGeoError {
     ParseError(#[from] AddrParseError),
     #[error("DNS resolution timeout")]
     TimeoutError,
+    #[error("Private IP address: {0}")]
+    PrivateIp(String),
+    #[error("Invalid parameter: {0}")]
+    InvalidParameter(ParamError),
+    #[error("Unauthorized")]
+    Unauthorized,
+    #[error("Admin credentials rejected")]
+    AdminForbidden,
+    #[error("Forbidden")]
+    Forbidden,
+    #[error("Private target: {0}")]
+    PrivateTarget(String),
+    #[error("Host longer than {0} characters")]
+    HostTooLong(usize),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Server overloaded")]
+    Overloaded,
+    #[error("Request timeout")]
+    RequestTimeout,
+    #[error("Database not loaded: {0}")]
+    DatabaseUnavailable(&'static str),
+    #[error("Method not allowed")]
+    MethodNotAllowed,
+    #[error("Handler panicked")]
+    InternalPanic,
+    /// axum 提取器等框架层返回的非JSON错误，保留原状态码和说明
+    #[error("{1}")]
+    Rejected(axum::http::StatusCode, String),
+}
+
+/// 参数错误的具体原因，说明与其他错误一样随请求语言变化
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamError {
+    /// detail、precision、asn_format 或 regions 的取值不合法
+    LookupOptions,
+    /// 批量查询的请求体不是字符串数组，附带 JSON 解析器的说明
+    BatchBody(String),
+    /// 超过单次批量查询的主机数上限
+    BatchTooLarge(usize),
+    /// 流式批量查询超过行数上限
+    TooManyRows(usize),
+    /// 流式请求体读取失败
+    UnreadableBody(String),
+    /// ip 与 host 同时指定且取值不同
+    HostConflict,
+    /// /stats 的 window 或 top 不合法
+    StatsWindow,
+    /// 调试查询不认识的数据库名
+    UnknownDebugDatabase(String),
+    /// 回滚不认识的数据库名
+    UnknownRollbackDatabase(String),
+    /// 日志过滤规则无法解析：规则和解析器的说明
+    LogFilter(String, String),
+    /// 切换日志规则的请求体格式错误，附带 JSON 解析器的说明
+    LogLevelBody(String),
+}
+
+impl ParamError {
+    pub fn message(&self, lang: Lang) -> String {
+        match (self, lang) {
+            (ParamError::LookupOptions, Lang::Zh) => "detail 只能是 minimal、standard 或 full，precision 必须是非负整数，asn_format 只能是 number、string 或 both，regions 只能是 full、short、both 或 none".to_string(),
+            (ParamError::LookupOptions, Lang::En) => "detail must be minimal, standard or full, precision must be a non-negative integer, asn_format must be number, string or both, and regions must be full, short, both or none".to_string(),
+            (ParamError::BatchBody(detail), Lang::Zh) => format!("请求体必须是字符串数组: {}", detail),
+            (ParamError::BatchBody(detail), Lang::En) => format!("The request body must be an array of strings: {}", detail),
+            (ParamError::BatchTooLarge(max), Lang::Zh) => format!("单次最多查询 {} 个主机", max),
+            (ParamError::BatchTooLarge(max), Lang::En) => format!("At most {} hosts can be looked up at once", max),
+            (ParamError::TooManyRows(max), Lang::Zh) => format!("单次最多查询 {} 行", max),
+            (ParamError::TooManyRows(max), Lang::En) => format!("At most {} lines can be looked up at once", max),
+            (ParamError::UnreadableBody(err), Lang::Zh) => format!("无法读取请求体: {}", err),
+            (ParamError::UnreadableBody(err), Lang::En) => format!("Failed to read the request body: {}", err),
+            (ParamError::HostConflict, Lang::Zh) => "ip 是 host 的别名，不能同时指定不同的值".to_string(),
+            (ParamError::HostConflict, Lang::En) => "ip is an alias of host, they cannot be given different values".to_string(),
+            (ParamError::StatsWindow, Lang::Zh) => "window 必须是正整数加 m、h 或 d，如 30m、1h，top 必须是非负整数".to_string(),
+            (ParamError::StatsWindow, Lang::En) => "window must be a positive integer followed by m, h or d, such as 30m or 1h, and top must be a non-negative integer".to_string(),
+            (ParamError::UnknownDebugDatabase(db), Lang::Zh) => format!("未知的数据库 '{}'，可选 city、country、asn、geocn", db),
+            (ParamError::UnknownDebugDatabase(db), Lang::En) => format!("Unknown database '{}', expected city, country, asn or geocn", db),
+            (ParamError::UnknownRollbackDatabase(db), Lang::Zh) => format!("未知的数据库 '{}'，可选 City（或 Country）、ASN、GeoCN", db),
+            (ParamError::UnknownRollbackDatabase(db), Lang::En) => format!("Unknown database '{}', expected City (or Country), ASN or GeoCN", db),
+            (ParamError::LogFilter(filter, err), Lang::Zh) => format!("无法解析日志过滤规则 '{}': {}", filter, err),
+            (ParamError::LogFilter(filter, err), Lang::En) => format!("Invalid log filter '{}': {}", filter, err),
+            (ParamError::LogLevelBody(detail), Lang::Zh) => format!("请求体必须是 {{\"filter\": \"...\"}}: {}", detail),
+            (ParamError::LogLevelBody(detail), Lang::En) => format!("The request body must be {{\"filter\": \"...\"}}: {}", detail),
+        }
+    }
+}
+
+impl std::fmt::Display for ParamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message(Lang::En))
+    }
+}
+
+// 框架层错误按状态码给出错误类型
+fn rejection_code(status: axum::http::StatusCode) -> &'static str {
+    match status.as_u16() {
+        400 => "BAD_REQUEST",
+        404 => "NOT_FOUND",
+        405 => "METHOD_NOT_ALLOWED",
+        408 => "REQUEST_TIMEOUT",
+        413 => "PAYLOAD_TOO_LARGE",
+        415 => "UNSUPPORTED_MEDIA_TYPE",
+        422 => "UNPROCESSABLE_ENTITY",
+        429 => "RATE_LIMITED",
+        500..=599 => "INTERNAL_ERROR",
+        _ => "HTTP_ERROR",
+    }
+}
+
+/// 错误说明使用的语言，`error` 类型码不受影响
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    Zh,
+    En,
+}
+
+tokio::task_local! {
+    static REQUEST_LANG: Lang;
+}
+
+impl Lang {
+    /// 解析 `zh`、`zh-CN`、`en-US` 这类语言标签，不认识的返回 None
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        match primary.as_str() {
+            "zh" => Some(Self::Zh),
+            "en" => Some(Self::En),
+            _ => None,
+        }
+    }
+
+    /// 按 q 值选出 Accept-Language 中优先级最高的已支持语言
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        weighted_tags(header).into_iter().find_map(Self::from_tag)
+    }
+
+    /// 当前请求协商出的语言，不在请求上下文中时为中文
+    pub fn current() -> Self {
+        REQUEST_LANG.try_with(|lang| *lang).unwrap_or_default()
+    }
+
+    /// 在指定语言下执行 future，期间生成的错误响应使用该语言
+    pub async fn scope<F: std::future::Future>(self, future: F) -> F::Output {
+        REQUEST_LANG.scope(self, future).await
+    }
+}
+
+tokio::task_local! {
+    static REQUEST_ID: std::sync::Arc<str>;
+}
+
+/// 当前请求的ID，由访问日志中间件设置
+pub struct RequestId;
+
+impl RequestId {
+    /// 当前请求的ID，不在请求上下文中时为 None
+    pub fn current() -> Option<String> {
+        REQUEST_ID.try_with(|id| id.to_string()).ok()
+    }
+
+    /// 在指定请求ID下执行 future，期间生成的错误响应带上该ID
+    pub async fn scope<F: std::future::Future>(id: &str, future: F) -> F::Output {
+        REQUEST_ID.scope(id.into(), future).await
+    }
+}
+
+impl IpGeoError {
+    /// HTTP状态码和机器可读的错误类型
+    pub fn parts(&self) -> (axum::http::StatusCode, &'static str) {
+        use axum::http::StatusCode;
+        match self {
+            IpGeoError::InvalidIp(_) => (StatusCode::BAD_REQUEST, "INVALID_IP"),
+            IpGeoError::ResolveError => (StatusCode::BAD_REQUEST, "RESOLVE_ERROR"),
+            IpGeoError::IoError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "IO_ERROR"),
+            IpGeoError::ParseError(_) => (StatusCode::BAD_REQUEST, "PARSE_ERROR"),
+            IpGeoError::TimeoutError => (StatusCode::GATEWAY_TIMEOUT, "TIMEOUT"),
+            IpGeoError::PrivateIp(_) => (StatusCode::BAD_REQUEST, "PRIVATE_IP"),
+            IpGeoError::InvalidParameter(_) => (StatusCode::BAD_REQUEST, "INVALID_PARAMETER"),
+            IpGeoError::Unauthorized => (StatusCode::UNAUTHORIZED, "UNAUTHORIZED"),
+            IpGeoError::AdminForbidden => (StatusCode::FORBIDDEN, "ADMIN_FORBIDDEN"),
+            IpGeoError::Forbidden => (StatusCode::FORBIDDEN, "FORBIDDEN"),
+            IpGeoError::PrivateTarget(_) => (StatusCode::FORBIDDEN, "PRIVATE_TARGET"),
+            IpGeoError::HostTooLong(_) => (StatusCode::BAD_REQUEST, "HOST_TOO_LONG"),
+            IpGeoError::NotFound(_) => (StatusCode::NOT_FOUND, "NOT_FOUND"),
+            IpGeoError::Overloaded => (StatusCode::SERVICE_UNAVAILABLE, "OVERLOADED"),
+            IpGeoError::RequestTimeout => (StatusCode::GATEWAY_TIMEOUT, "REQUEST_TIMEOUT"),
+            IpGeoError::DatabaseUnavailable(_) => (StatusCode::SERVICE_UNAVAILABLE, "DB_UNAVAILABLE"),
+            IpGeoError::MethodNotAllowed => (StatusCode::METHOD_NOT_ALLOWED, "METHOD_NOT_ALLOWED"),
+            IpGeoError::InternalPanic => (StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_PANIC"),
+            IpGeoError::Rejected(status, _) => (*status, rejection_code(*status)),
+        }
+    }
+
+    /// 面向用户的错误说明
+    pub fn message(&self, lang: Lang) -> String {
+        match (self, lang) {
+            (IpGeoError::InvalidIp(ip), Lang::Zh) => format!("无效的IP地址: {}", ip),
+            (IpGeoError::InvalidIp(ip), Lang::En) => format!("Invalid IP address: {}", ip),
+            (IpGeoError::ResolveError, Lang::Zh) => "无法解析域名，请检查域名是否正确".to_string(),
+            (IpGeoError::ResolveError, Lang::En) => "Unable to resolve the domain, please check that it is correct".to_string(),
+            (IpGeoError::IoError(err), Lang::Zh) => format!("IO错误: {}", err),
+            (IpGeoError::IoError(err), Lang::En) => format!("IO error: {}", err),
+            (IpGeoError::ParseError(err), Lang::Zh) => format!("IP解析错误: {}", err),
+            (IpGeoError::ParseError(err), Lang::En) => format!("Failed to parse IP: {}", err),
+            (IpGeoError::TimeoutError, Lang::Zh) => "域名解析超时，请稍后重试".to_string(),
+            (IpGeoError::TimeoutError, Lang::En) => "DNS resolution timed out, please try again later".to_string(),
+            (IpGeoError::PrivateIp(ip), Lang::Zh) => format!("私有IP地址不在数据库中: {}", ip),
+            (IpGeoError::PrivateIp(ip), Lang::En) => format!("Private IP addresses are not in the database: {}", ip),
+            (IpGeoError::InvalidParameter(param), Lang::Zh) => format!("参数错误: {}", param.message(lang)),
+            (IpGeoError::InvalidParameter(param), Lang::En) => format!("Invalid parameter: {}", param.message(lang)),
+            (IpGeoError::Unauthorized, Lang::Zh) => "缺少管理令牌或客户端证书".to_string(),
+            (IpGeoError::Unauthorized, Lang::En) => "Missing admin token or client certificate".to_string(),
+            (IpGeoError::AdminForbidden, Lang::Zh) => "管理令牌错误".to_string(),
+            (IpGeoError::AdminForbidden, Lang::En) => "Invalid admin token".to_string(),
+            (IpGeoError::Forbidden, Lang::Zh) => "客户端IP不允许访问该服务".to_string(),
+            (IpGeoError::Forbidden, Lang::En) => "Client IP is not allowed to access this service".to_string(),
+            (IpGeoError::PrivateTarget(ip), Lang::Zh) => format!("不允许查询私有或保留地址: {}", ip),
+            (IpGeoError::PrivateTarget(ip), Lang::En) => format!("Looking up private or reserved addresses is not allowed: {}", ip),
+            (IpGeoError::HostTooLong(max), Lang::Zh) => format!("主机名不能超过 {} 个字符", max),
+            (IpGeoError::HostTooLong(max), Lang::En) => format!("Host must not be longer than {} characters", max),
+            (IpGeoError::NotFound(path), Lang::Zh) => format!("资源不存在: {}", path),
+            (IpGeoError::NotFound(path), Lang::En) => format!("Not found: {}", path),
+            (IpGeoError::Overloaded, Lang::Zh) => "服务繁忙，请稍后重试".to_string(),
+            (IpGeoError::Overloaded, Lang::En) => "Service is busy, please try again later".to_string(),
+            (IpGeoError::RequestTimeout, Lang::Zh) => "请求处理超时，请稍后重试".to_string(),
+            (IpGeoError::RequestTimeout, Lang::En) => "Request timed out, please try again later".to_string(),
+            (IpGeoError::DatabaseUnavailable(name), Lang::Zh) => format!("数据库未加载: {}", name),
+            (IpGeoError::DatabaseUnavailable(name), Lang::En) => format!("Database not loaded: {}", name),
+            (IpGeoError::MethodNotAllowed, Lang::Zh) => "不支持该请求方法".to_string(),
+            (IpGeoError::MethodNotAllowed, Lang::En) => "Method not allowed".to_string(),
+            (IpGeoError::InternalPanic, Lang::Zh) => "服务器内部错误，请稍后重试".to_string(),
+            (IpGeoError::InternalPanic, Lang::En) => "Internal server error, please try again later".to_string(),
+            // 框架给出的说明本身是英文，两种语言下原样返回
+            (IpGeoError::Rejected(_, message), _) => message.clone(),
+        }
+    }
+
+    /// 统一的错误信封 `{code, error, message}`，说明使用当前请求的语言，不含请求ID
+    pub fn to_json(&self) -> serde_json::Value {
+        self.to_json_in(Lang::current())
+    }
+
+    pub fn to_json_in(&self, lang: Lang) -> serde_json::Value {
+        let (status, error_type) = self.parts();
+        serde_json::json!(ErrorBody {
+            code: status.as_u16(),
+            error: error_type.to_string(),
+            message: self.message(lang),
+            request_id: None,
+        })
+    }
 }
 
 impl axum::response::IntoResponse for IpGeoError {
     fn into_response(self) -> axum::response::Response {
-        let (status, error_type, message) = match self {
-            IpGeoError::InvalidIp(ip) => (
-                axum::http::StatusCode::BAD_REQUEST,
-                "INVALID_IP",
-                format!("无效的IP地址: {}", ip),
-            ),
-            IpGeoError::ResolveError => (
-                axum::http::StatusCode::BAD_REQUEST,
-                "RESOLVE_ERROR",
-                "无法解析域名，请检查域名是否正确".to_string(),
-            ),
-            IpGeoError::IoError(err) => (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                "IO_ERROR",
-                format!("IO错误: {}", err),
-            ),
-            IpGeoError::ParseError(err) => (
-                axum::http::StatusCode::BAD_REQUEST,
-                "PARSE_ERROR",
-                format!("IP解析错误: {}", err),
-            ),
-            IpGeoError::TimeoutError => (
-                axum::http::StatusCode::REQUEST_TIMEOUT,
-                "TIMEOUT_ERROR",
-                "域名解析超时，请稍后重试".to_string(),
-            ),
-        };
-        
-        let body = serde_json::json!({
-            "code": status.as_u16(),
-            "error": error_type,
-            "message": message
-        });
-        
+        let status = self.parts().0;
+        let mut body = self.to_json();
+        if let Some(request_id) = RequestId::current() {
+            body["request_id"] = request_id.into();
+        }
+
         (
             status,
             [(axum::http::header::CONTENT_TYPE, "application/json; charset=utf-8")],
@@ -0,0 +1,30 @@
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use socket2::{Domain, Protocol, Socket, Type};
+
+/// 绑定单个地址。`v6_only` 让 IPv6 套接字不再接收 v4 映射地址，
+/// 这样 `0.0.0.0:8080` 和 `[::]:8080` 可以同时绑定
+pub fn bind_listener(addr: SocketAddr, v6_only: bool) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    // 与 std::net::TcpListener::bind 一致
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(v6_only)?;
+    }
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    Ok(socket.into())
+}
+
+/// 依次绑定所有地址，任何一个失败都返回带地址的错误。
+/// 同时配置了 IPv4 地址时 IPv6 套接字只接收 IPv6 连接
+pub fn bind_all(addrs: &[SocketAddr]) -> io::Result<Vec<TcpListener>> {
+    let v6_only = addrs.iter().any(SocketAddr::is_ipv4);
+    addrs.iter()
+        .map(|&addr| bind_listener(addr, v6_only).map_err(|e| io::Error::new(
+            e.kind(),
+            format!("Failed to bind {}: {}", addr, e),
+        )))
+        .collect()
+}
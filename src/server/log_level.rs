@@ -0,0 +1,100 @@
+//! 运行时切换日志过滤规则：`PUT /admin/log_level` 设置任意规则，SIGUSR1 在启动时的规则和 debug 之间切换。
+//! main 用 reload 层安装日志订阅者后调用 install_log_filter；未安装时（如嵌入到其他应用）不支持切换。
+
+use std::sync::{Mutex, OnceLock};
+use thiserror::Error;
+use tracing::{info, warn};
+use tracing_subscriber::reload;
+use tracing_subscriber::EnvFilter;
+
+/// SIGUSR1 切换到的规则
+pub const DEBUG_FILTER: &str = "debug";
+
+type ApplyFilter = Box<dyn Fn(EnvFilter) -> Result<(), reload::Error> + Send + Sync>;
+
+struct LogFilter {
+    /// 启动时的规则，来自 RUST_LOG
+    default: String,
+    current: Mutex<String>,
+    apply: ApplyFilter,
+}
+
+static LOG_FILTER: OnceLock<LogFilter> = OnceLock::new();
+
+/// 日志规则无法切换的原因
+#[derive(Debug, Error)]
+pub enum LogFilterError {
+    #[error("invalid log filter '{0}': {1}")]
+    Invalid(String, String),
+    #[error("log filter reloading is not enabled")]
+    Unavailable,
+}
+
+/// 当前和启动时的日志规则
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct LogFilterState {
+    pub filter: String,
+    pub default: String,
+}
+
+/// 登记 reload 层的句柄，default 为启动时的规则；重复调用时保留第一次的句柄
+pub fn install_log_filter<S: 'static>(default: &str, handle: reload::Handle<EnvFilter, S>) {
+    let _ = LOG_FILTER.set(LogFilter {
+        default: default.to_string(),
+        current: Mutex::new(default.to_string()),
+        apply: Box::new(move |filter| handle.reload(filter)),
+    });
+}
+
+/// 当前生效的规则，未安装 reload 层时为 None
+pub fn log_filter() -> Option<LogFilterState> {
+    let state = LOG_FILTER.get()?;
+    let filter = state.current.lock().map(|current| current.clone()).unwrap_or_else(|e| e.into_inner().clone());
+    Some(LogFilterState { filter, default: state.default.clone() })
+}
+
+/// 换成新的规则；规则无法解析时返回错误，当前规则保持不变
+pub fn set_log_filter(filter: &str) -> Result<LogFilterState, LogFilterError> {
+    let state = LOG_FILTER.get().ok_or(LogFilterError::Unavailable)?;
+    let filter = filter.trim();
+    if filter.is_empty() {
+        return Err(LogFilterError::Invalid(filter.to_string(), "empty filter".to_string()));
+    }
+    let parsed = EnvFilter::builder()
+        .parse(filter)
+        .map_err(|e| LogFilterError::Invalid(filter.to_string(), e.to_string()))?;
+    let mut current = state.current.lock().unwrap_or_else(|e| e.into_inner());
+    (state.apply)(parsed).map_err(|_| LogFilterError::Unavailable)?;
+    *current = filter.to_string();
+    drop(current);
+    info!("Log filter changed to '{}'", filter);
+    Ok(log_filter().expect("log filter is installed"))
+}
+
+/// 当前是启动时的规则则换成 debug，否则换回启动时的规则
+pub fn toggle_debug_log_filter() -> Result<LogFilterState, LogFilterError> {
+    let state = log_filter().ok_or(LogFilterError::Unavailable)?;
+    let next = if state.filter == state.default { DEBUG_FILTER } else { state.default.as_str() };
+    set_log_filter(next)
+}
+
+/// 收到 SIGUSR1 时切换日志规则，shutdown 取消时退出
+#[cfg(unix)]
+pub fn spawn_sigusr1_toggle(shutdown: tokio_util::sync::CancellationToken) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut user1 = signal(SignalKind::user_defined1())?;
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = user1.recv() => {
+                    if let Err(e) = toggle_debug_log_filter() {
+                        warn!("Failed to toggle the log filter: {}", e);
+                    }
+                }
+                _ = shutdown.cancelled() => break,
+            }
+        }
+    });
+    Ok(())
+}
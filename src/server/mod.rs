@@ -0,0 +1,17 @@
+mod bind;
+mod log_level;
+#[cfg(feature = "otlp")]
+mod otlp;
+mod server;
+mod systemd;
+mod tls;
+mod tuning;
+
+pub use bind::*;
+pub use log_level::*;
+#[cfg(feature = "otlp")]
+pub use otlp::*;
+pub use server::*;
+pub use systemd::*;
+pub use tls::*;
+pub use tuning::*;
@@ -0,0 +1,189 @@
+use std::future::Future;
+use std::net::{SocketAddr, TcpListener};
+use std::process::ExitCode;
+use axum::Router;
+use axum_server::Handle;
+use tokio::signal;
+use tracing::{info, warn};
+use crate::api::AppState;
+use crate::config::Config;
+
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Failed to install signal handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    info!("Signal received, starting graceful shutdown");
+}
+
+/// 运行服务直到 `signal` 完成（通常是 shutdown_signal），然后在 drain 超时内等待在途请求完成。
+/// `begin_shutdown` 负责通知服务停止接受新连接。
+pub async fn run_until_shutdown<F>(
+    server: F,
+    state: &AppState,
+    signal: impl Future<Output = ()>,
+    begin_shutdown: impl FnOnce(),
+) -> std::io::Result<ExitCode>
+where
+    F: Future<Output = std::io::Result<()>>,
+{
+    tokio::pin!(server);
+
+    tokio::select! {
+        result = &mut server => result?,
+        _ = signal => {
+            super::notify("STOPPING=1");
+            let drain_timeout = Config::global().shutdown_timeout;
+            let deadline = state.drain_deadline(drain_timeout);
+            info!("{} requests in flight, draining for up to {:?}", state.in_flight(), drain_timeout);
+            state.shutdown.cancel();
+            begin_shutdown();
+
+            match tokio::time::timeout_at(deadline, &mut server).await {
+                Ok(result) => result?,
+                Err(_) => {
+                    warn!("Drain timeout elapsed, aborting {} remaining requests", state.in_flight());
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+        }
+    }
+
+    info!("Server shutdown completed");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// 同时运行所有监听套接字上的服务，任何一个出错都会结束整体
+pub(crate) async fn join_servers<F>(servers: Vec<F>) -> std::io::Result<()>
+where
+    F: Future<Output = std::io::Result<()>>,
+{
+    futures::future::try_join_all(servers).await.map(drop)
+}
+
+/// 通知所有服务停止接受新连接，已有连接继续处理到 drain 超时
+pub(crate) fn graceful_shutdown_all(handles: &[Handle]) {
+    for handle in handles {
+        handle.graceful_shutdown(None);
+    }
+}
+
+/// 在每个监听套接字上运行对应的路由，共用同一个 state 和关闭流程
+pub async fn serve_plain(listeners: Vec<(TcpListener, Router)>, state: &AppState) -> std::io::Result<ExitCode> {
+    let mut handles = Vec::with_capacity(listeners.len());
+    let mut servers = Vec::with_capacity(listeners.len());
+    for (listener, app) in listeners {
+        info!("Listening on http://{}", listener.local_addr()?);
+        let handle = Handle::new();
+        servers.push(super::tune(axum_server::from_tcp(listener), Config::global(), false)
+            .handle(handle.clone())
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>()));
+        handles.push(handle);
+    }
+    super::notify_ready(state.shutdown.clone());
+
+    run_until_shutdown(join_servers(servers), state, shutdown_signal(), move || graceful_shutdown_all(&handles)).await
+}
+
+pub async fn serve(state: AppState) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let config = Config::global();
+    // 先校验配置，避免下载数据库之后才报错
+    config.validate()?;
+    let tls = config.tls_paths()?;
+
+    // 由 systemd 套接字激活时沿用传入的监听套接字，否则绑定所有配置的地址
+    let listeners = match super::listen_fd()? {
+        Some(listener) => vec![listener],
+        None => super::bind_all(&config.bind)?,
+    };
+    let admin_listener = match config.admin_bind {
+        Some(addr) => super::bind_all(&[addr])?.pop(),
+        None => None,
+    };
+
+    info!("Initializing IP Geo Service");
+    
+    // 数据库在后台下载，服务立即开始监听，期间 /healthz 报告 initializing
+    crate::geo::init_mmdb_readers(state.shutdown.clone()).await?;
+    // 快照可能较大，在阻塞线程池中恢复，不推迟开始监听
+    tokio::task::spawn_blocking(crate::cache::restore_result_cache);
+    crate::cache::spawn_cache_warmup(state.shutdown.clone());
+    #[cfg(unix)]
+    if super::log_filter().is_some() {
+        if let Err(e) = super::spawn_sigusr1_toggle(state.shutdown.clone()) {
+            warn!("Failed to install the SIGUSR1 handler: {}", e);
+        }
+    }
+    
+    // Create the router
+    let app = crate::api::create_router(state.clone());
+    let mut listeners: Vec<_> = listeners.into_iter().map(|listener| (listener, app.clone())).collect();
+    if let Some(listener) = admin_listener {
+        listeners.push((listener, crate::api::create_admin_router(state.clone())));
+    }
+    
+    let grpc = spawn_grpc(&state);
+
+    // Start the server
+    let result = match tls {
+        Some((cert, key)) => super::serve_tls(listeners, &state, cert, key).await,
+        None => serve_plain(listeners, &state).await,
+    };
+    // HTTP 服务异常退出时同样让 gRPC 服务停止
+    state.shutdown.cancel();
+    let mut code = result?;
+    if let Some(handle) = grpc {
+        // 与 HTTP 共用收到信号时确定的截止时间，HTTP 异常退出时从现在开始计算
+        match tokio::time::timeout_at(state.drain_deadline(config.shutdown_timeout), handle).await {
+            Ok(Ok(Ok(()))) => {}
+            Ok(Ok(Err(e))) => {
+                warn!("gRPC server error: {}", e);
+                code = ExitCode::FAILURE;
+            }
+            Ok(Err(e)) => {
+                warn!("gRPC server task failed: {}", e);
+                code = ExitCode::FAILURE;
+            }
+            Err(_) => {
+                warn!("Drain timeout elapsed, aborting gRPC server");
+                code = ExitCode::FAILURE;
+            }
+        }
+    }
+    crate::cache::persist_result_cache();
+    crate::cache::dump_hot_ips();
+    Ok(code)
+}
+
+/// 配置了 grpc_bind 时在后台启动 gRPC 服务，随 state.shutdown 一起关闭
+#[cfg(feature = "grpc")]
+fn spawn_grpc(state: &AppState) -> Option<tokio::task::JoinHandle<Result<(), tonic::transport::Error>>> {
+    let addr = Config::global().grpc_bind?;
+    Some(tokio::spawn(crate::grpc::serve_grpc(addr, state.shutdown.clone())))
+}
+
+#[cfg(not(feature = "grpc"))]
+fn spawn_grpc(_state: &AppState) -> Option<tokio::task::JoinHandle<Result<(), std::convert::Infallible>>> {
+    if let Some(addr) = Config::global().grpc_bind {
+        warn!("GRPC_BIND={} is ignored: built without the grpc feature", addr);
+    }
+    None
+}
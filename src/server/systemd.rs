@@ -0,0 +1,109 @@
+use std::io;
+use std::net::TcpListener;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+use crate::geo::{database_state, DatabaseState};
+
+/// 取出 systemd 通过 LISTEN_FDS 传入的第一个监听套接字。
+/// 没有传入、LISTEN_PID 不是本进程或非 unix 平台时返回 None
+#[cfg(unix)]
+pub fn listen_fd() -> io::Result<Option<TcpListener>> {
+    use std::os::fd::FromRawFd;
+
+    // sd_listen_fds 约定的第一个描述符
+    const SD_LISTEN_FDS_START: libc::c_int = 3;
+
+    let pid = std::env::var("LISTEN_PID").ok().and_then(|v| v.parse::<u32>().ok());
+    let fds = std::env::var("LISTEN_FDS").ok().and_then(|v| v.parse::<u32>().ok()).unwrap_or(0);
+    // 不让子进程再次继承
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+    if pid != Some(std::process::id()) || fds == 0 {
+        return Ok(None);
+    }
+    if fds > 1 {
+        info!("systemd passed {} sockets, only the first one is used", fds);
+    }
+
+    let fd = SD_LISTEN_FDS_START;
+    // SAFETY: 只读取描述符标志
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: 同上，只追加 FD_CLOEXEC
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: systemd 把该描述符交给本进程，此后只由返回的监听器持有
+    let listener = unsafe { TcpListener::from_raw_fd(fd) };
+    listener.local_addr().map_err(|e| io::Error::new(
+        e.kind(),
+        format!("LISTEN_FDS descriptor {} is not a TCP socket: {}", fd, e),
+    ))?;
+    Ok(Some(listener))
+}
+
+#[cfg(not(unix))]
+pub fn listen_fd() -> io::Result<Option<TcpListener>> {
+    Ok(None)
+}
+
+/// 向 NOTIFY_SOCKET 发送 sd_notify 状态，例如 `READY=1`。未由 systemd 启动时什么都不做
+#[cfg(unix)]
+pub fn notify(state: &str) {
+    use std::os::unix::net::UnixDatagram;
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let result = UnixDatagram::unbound().and_then(|socket| {
+        // 以 @ 开头的是 Linux 抽象命名空间地址
+        #[cfg(target_os = "linux")]
+        if let Some(name) = path.as_encoded_bytes().strip_prefix(b"@") {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            return socket.send_to_addr(state.as_bytes(), &addr);
+        }
+        socket.send_to(state.as_bytes(), &path)
+    });
+    if let Err(e) = result {
+        debug!("Failed to notify systemd: {}", e);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn notify(_state: &str) {}
+
+fn status(state: DatabaseState) -> &'static str {
+    match state {
+        DatabaseState::Initializing => "STATUS=Serving, databases are still initializing",
+        DatabaseState::Ready => "STATUS=Serving, all databases loaded",
+        DatabaseState::Degraded => "STATUS=Serving in degraded mode, some databases are missing",
+    }
+}
+
+/// 开始接受连接后通知 systemd 就绪，并在首次下载结束前持续更新 STATUS
+pub fn notify_ready(shutdown: CancellationToken) {
+    let state = database_state();
+    notify(&format!("READY=1\n{}", status(state)));
+    if state != DatabaseState::Initializing {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => return,
+            }
+            let state = database_state();
+            if state != DatabaseState::Initializing {
+                notify(status(state));
+                return;
+            }
+        }
+    });
+}
@@ -0,0 +1,178 @@
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, OnceLock};
+use axum::{Extension, Router};
+use axum::middleware::AddExtension;
+use axum_server::accept::Accept;
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
+use futures::future::BoxFuture;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::server::TlsStream;
+use tower::Layer;
+use tracing::{info, warn};
+use crate::api::AppState;
+use crate::config::Config;
+use super::{graceful_shutdown_all, join_servers, run_until_shutdown, shutdown_signal};
+
+struct TlsState {
+    config: RustlsConfig,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+}
+
+// 当前生效的证书配置，供 SIGHUP 和管理接口重新加载
+static TLS_STATE: OnceLock<TlsState> = OnceLock::new();
+
+/// 从磁盘重新加载证书链和私钥。未启用 TLS 时返回 Ok(false)
+pub async fn reload_certificates() -> io::Result<bool> {
+    let Some(tls) = TLS_STATE.get() else {
+        return Ok(false);
+    };
+    tls.config.reload_from_config(Arc::new(server_config(&tls.cert_path, &tls.key_path)?));
+    info!("TLS certificate reloaded from {:?}", tls.cert_path);
+    Ok(true)
+}
+
+fn invalid_pem(path: &Path, e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{:?}: {}", path, e))
+}
+
+fn read_certificates(path: &Path) -> io::Result<Vec<CertificateDer<'static>>> {
+    CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid_pem(path, e))
+}
+
+// 公开接口不要求客户端证书；出示了证书时必须由 ADMIN_CLIENT_CA 签发，否则握手失败
+fn client_verifier(ca_path: &Path) -> io::Result<Arc<dyn rustls::server::danger::ClientCertVerifier>> {
+    let mut roots = RootCertStore::empty();
+    for cert in read_certificates(ca_path)? {
+        roots.add(cert).map_err(|e| invalid_pem(ca_path, e))?;
+    }
+    WebPkiClientVerifier::builder(Arc::new(roots))
+        .allow_unauthenticated()
+        .build()
+        .map_err(|e| invalid_pem(ca_path, e))
+}
+
+/// 证书链、私钥和可选的客户端证书校验。关闭 HTTP2 时 ALPN 只提供 http/1.1
+fn server_config(cert_path: &Path, key_path: &Path) -> io::Result<ServerConfig> {
+    let config = Config::global();
+    let certs = read_certificates(cert_path)?;
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|e| invalid_pem(key_path, e))?;
+    let builder = ServerConfig::builder();
+    let builder = match &config.admin_client_ca {
+        Some(ca_path) => builder.with_client_cert_verifier(client_verifier(ca_path)?),
+        None => builder.with_no_client_auth(),
+    };
+    let mut server_config = builder.with_single_cert(certs, key).map_err(io::Error::other)?;
+    server_config.alpn_protocols = if config.http2 {
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    } else {
+        vec![b"http/1.1".to_vec()]
+    };
+    Ok(server_config)
+}
+
+/// TLS 连接上客户端证书的校验结果，作为请求扩展交给管理接口的认证
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClientCertificate {
+    /// 客户端出示了由 ADMIN_CLIENT_CA 签发的证书
+    pub verified: bool,
+}
+
+/// 完成 TLS 握手后把客户端证书的校验结果放进该连接上每个请求的扩展
+#[derive(Clone)]
+pub struct ClientCertAcceptor {
+    inner: RustlsAcceptor,
+}
+
+impl ClientCertAcceptor {
+    pub fn new(config: RustlsConfig) -> Self {
+        Self { inner: RustlsAcceptor::new(config) }
+    }
+}
+
+impl<I, S> Accept<I, S> for ClientCertAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = TlsStream<I>;
+    type Service = AddExtension<S, ClientCertificate>;
+    type Future = BoxFuture<'static, io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let accept = self.inner.accept(stream, service);
+        Box::pin(async move {
+            let (stream, service) = accept.await?;
+            // 握手已经校验过证书链，这里只需要知道是否出示了证书
+            let verified = stream.get_ref().1.peer_certificates().is_some_and(|certs| !certs.is_empty());
+            Ok((stream, Extension(ClientCertificate { verified }).layer(service)))
+        })
+    }
+}
+
+#[cfg(unix)]
+fn spawn_sighup_reload(shutdown: tokio_util::sync::CancellationToken) -> io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = hangup.recv() => {
+                    if let Err(e) = reload_certificates().await {
+                        warn!("Failed to reload TLS certificate, keeping the previous one: {}", e);
+                    }
+                }
+                _ = shutdown.cancelled() => break,
+            }
+        }
+    });
+    Ok(())
+}
+
+pub async fn serve_tls(
+    listeners: Vec<(TcpListener, Router)>,
+    state: &AppState,
+    cert_path: &Path,
+    key_path: &Path,
+) -> io::Result<ExitCode> {
+    // 进程内只使用 ring 作为加密后端
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let config = server_config(cert_path, key_path).map_err(|e| io::Error::new(
+        e.kind(),
+        format!("Failed to load TLS certificate {:?} / key {:?}: {}", cert_path, key_path, e)
+    ))?;
+    let config = RustlsConfig::from_config(Arc::new(config));
+    let _ = TLS_STATE.set(TlsState {
+        config: config.clone(),
+        cert_path: cert_path.to_path_buf(),
+        key_path: key_path.to_path_buf(),
+    });
+
+    #[cfg(unix)]
+    spawn_sighup_reload(state.shutdown.clone())?;
+
+    let mut handles = Vec::with_capacity(listeners.len());
+    let mut servers = Vec::with_capacity(listeners.len());
+    for (listener, app) in listeners {
+        info!("Listening on https://{}", listener.local_addr()?);
+        let handle = axum_server::Handle::new();
+        servers.push(super::tune(axum_server::from_tcp(listener).acceptor(ClientCertAcceptor::new(config.clone())), Config::global(), true)
+            .handle(handle.clone())
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>()));
+        handles.push(handle);
+    }
+    super::notify_ready(state.shutdown.clone());
+
+    run_until_shutdown(join_servers(servers), state, shutdown_signal(), move || graceful_shutdown_all(&handles)).await
+}
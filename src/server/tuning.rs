@@ -0,0 +1,201 @@
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use axum_server::accept::Accept;
+use axum_server::Server;
+use dashmap::DashMap;
+use futures::future::{self, Either, Ready};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::time::{Instant, Sleep};
+use tracing::debug;
+use crate::config::Config;
+
+// HTTP/2 明文连接的客户端前言
+const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// 按配置调整协议参数，并在接受连接时设置 TCP_NODELAY、空闲超时和按对端地址的连接数上限。
+/// `tls` 为 true 时 HTTP/2 由 ALPN 协商，否则关闭 HTTP2 时直接拒绝带 h2 前言的连接
+pub fn tune<A>(mut server: Server<A>, config: &Config, tls: bool) -> Server<ConnectionAcceptor<A>> {
+    server.http_builder().http2().max_concurrent_streams(config.http2_max_concurrent_streams);
+    server.map(|inner| ConnectionAcceptor {
+        inner,
+        tcp_nodelay: config.tcp_nodelay,
+        idle_timeout: config.keep_alive_timeout,
+        reject_h2: !config.http2 && !tls,
+        max_per_ip: config.max_connections_per_ip,
+        connections: Arc::default(),
+    })
+}
+
+/// 包在 TLS 等内层 acceptor 之外，直接处理 TCP 连接
+#[derive(Clone)]
+pub struct ConnectionAcceptor<A> {
+    inner: A,
+    tcp_nodelay: bool,
+    idle_timeout: Duration,
+    reject_h2: bool,
+    max_per_ip: usize,
+    connections: Arc<DashMap<IpAddr, usize>>,
+}
+
+impl<A> ConnectionAcceptor<A> {
+    // 占用对端地址的一个连接名额，未设置上限时不计数
+    fn acquire(&self, peer: SocketAddr) -> io::Result<Option<ConnectionSlot>> {
+        if self.max_per_ip == 0 {
+            return Ok(None);
+        }
+        let ip = peer.ip().to_canonical();
+        let mut count = self.connections.entry(ip).or_insert(0);
+        if *count >= self.max_per_ip {
+            debug!("Refusing connection from {}: {} connections already open", ip, self.max_per_ip);
+            return Err(io::Error::other("too many connections from peer"));
+        }
+        *count += 1;
+        Ok(Some(ConnectionSlot { ip, connections: self.connections.clone() }))
+    }
+
+    fn track(&self, stream: TcpStream) -> io::Result<TrackedStream> {
+        let slot = self.acquire(stream.peer_addr()?)?;
+        if self.tcp_nodelay {
+            stream.set_nodelay(true)?;
+        }
+        let idle = (!self.idle_timeout.is_zero()).then(|| IdleTimer {
+            timeout: self.idle_timeout,
+            sleep: Box::pin(tokio::time::sleep(self.idle_timeout)),
+        });
+        Ok(TrackedStream {
+            stream,
+            idle,
+            preface_matched: self.reject_h2.then_some(0),
+            _slot: slot,
+        })
+    }
+}
+
+impl<A, S> Accept<TcpStream, S> for ConnectionAcceptor<A>
+where
+    A: Accept<TrackedStream, S>,
+{
+    type Stream = A::Stream;
+    type Service = A::Service;
+    type Future = Either<Ready<io::Result<(A::Stream, A::Service)>>, A::Future>;
+
+    fn accept(&self, stream: TcpStream, service: S) -> Self::Future {
+        match self.track(stream) {
+            Ok(stream) => Either::Right(self.inner.accept(stream, service)),
+            Err(e) => Either::Left(future::ready(Err(e))),
+        }
+    }
+}
+
+// 连接关闭时归还名额
+struct ConnectionSlot {
+    ip: IpAddr,
+    connections: Arc<DashMap<IpAddr, usize>>,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        if let Some(mut count) = self.connections.get_mut(&self.ip) {
+            *count -= 1;
+        }
+        self.connections.remove_if(&self.ip, |_, count| *count == 0);
+    }
+}
+
+// 连接上没有任何读写超过 timeout 后让读取失败，由 hyper 关闭连接
+struct IdleTimer {
+    timeout: Duration,
+    sleep: Pin<Box<Sleep>>,
+}
+
+impl IdleTimer {
+    fn reset(&mut self) {
+        self.sleep.as_mut().reset(Instant::now() + self.timeout);
+    }
+}
+
+/// 带连接名额和空闲计时的 TCP 流
+pub struct TrackedStream {
+    stream: TcpStream,
+    idle: Option<IdleTimer>,
+    /// 已读到的 h2 前言字节数，None 表示不检查或已确认不是 h2
+    preface_matched: Option<usize>,
+    _slot: Option<ConnectionSlot>,
+}
+
+impl TrackedStream {
+    fn check_preface(&mut self, read: &[u8]) -> io::Result<()> {
+        let Some(matched) = self.preface_matched else {
+            return Ok(());
+        };
+        let expected = &H2_PREFACE[matched..];
+        let len = read.len().min(expected.len());
+        if read[..len] != expected[..len] {
+            self.preface_matched = None;
+        } else if matched + len == H2_PREFACE.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "HTTP/2 is disabled"));
+        } else {
+            self.preface_matched = Some(matched + len);
+        }
+        Ok(())
+    }
+}
+
+impl AsyncRead for TrackedStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let before = buf.filled().len();
+        match Pin::new(&mut this.stream).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) => {
+                if let Some(idle) = &mut this.idle {
+                    idle.reset();
+                }
+                Poll::Ready(this.check_preface(&buf.filled()[before..]))
+            }
+            Poll::Pending => {
+                let expired = this.idle.as_mut().is_some_and(|idle| idle.sleep.as_mut().poll(cx).is_ready());
+                if expired {
+                    return Poll::Ready(Err(io::Error::new(io::ErrorKind::TimedOut, "idle connection timed out")));
+                }
+                Poll::Pending
+            }
+            other => other,
+        }
+    }
+}
+
+impl AsyncWrite for TrackedStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.stream).poll_write(cx, buf);
+        if let (Poll::Ready(Ok(_)), Some(idle)) = (&result, &mut self.idle) {
+            idle.reset();
+        }
+        result
+    }
+
+    fn poll_write_vectored(mut self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[io::IoSlice<'_>]) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.stream).poll_write_vectored(cx, bufs);
+        if let (Poll::Ready(Ok(_)), Some(idle)) = (&result, &mut self.idle) {
+            idle.reset();
+        }
+        result
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
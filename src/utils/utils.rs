@@ -1,7 +1,44 @@
-use crate::models::IpInfo;
+use crate::config::{Config, PrivacyMode};
+use crate::models::{AsnInfo, IpInfo};
 use maxminddb::geoip2;
 use std::collections::BTreeMap;
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use ipnet::IpNet;
+use once_cell::sync::Lazy;
+
+/// 开关类查询参数的取值，与环境变量的布尔开关保持一致，空值（`?pretty`）视为开启
+pub fn is_truthy(value: &str) -> bool {
+    matches!(value.trim().to_ascii_lowercase().as_str(), "" | "1" | "true" | "on" | "yes")
+}
+
+/// Unix 时间戳对应的 UTC 日期，格式为 YYYY-MM-DD
+pub fn format_epoch_date(epoch: u64) -> String {
+    // 按公历把天数换算为年月日，历法以 3 月为年首以便处理闰日
+    let days = (epoch / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// RFC3339 格式的 UTC 时间，精确到毫秒，如 2024-05-01T08:30:00.123Z
+pub fn format_rfc3339(time: std::time::SystemTime) -> String {
+    let since_epoch = time.duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    format!(
+        "{}T{:02}:{:02}:{:02}.{:03}Z",
+        format_epoch_date(secs),
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        since_epoch.subsec_millis()
+    )
+}
 
 pub fn get_des(names: &Option<BTreeMap<&str, &str>>, lang: &[&str]) -> String {
     if let Some(names) = names {
@@ -14,9 +51,84 @@ pub fn get_des(names: &Option<BTreeMap<&str, &str>>, lang: &[&str]) -> String {
     String::new()
 }
 
-pub fn get_country(country: &geoip2::country::Country) -> String {
-    let lang = &["zh-CN", "en"];
-    get_des(&country.names, lang)
+pub fn get_country(country: &geoip2::enterprise::Country, locales: &[&str]) -> String {
+    get_des(&country.names, locales)
+}
+
+pub fn get_city(city: &geoip2::enterprise::City, locales: &[&str]) -> String {
+    get_des(&city.names, locales)
+}
+
+pub fn get_continent(continent: &geoip2::enterprise::Continent, locales: &[&str]) -> String {
+    get_des(&continent.names, locales)
+}
+
+/// 根据运营商名称推断网络类型，与 asn_info.json 中的类型名称保持一致
+pub fn isp_network_type(isp: &str) -> Option<&'static str> {
+    [
+        ("移动", "移动网络"),
+        ("联通", "联通网络"),
+        ("电信", "电信网络"),
+        ("广电", "广电网络"),
+        ("教育网", "教育网络"),
+        ("政务", "政务网络"),
+    ]
+    .iter()
+    .find(|(keyword, _)| isp.contains(keyword))
+    .map(|(_, network)| *network)
+}
+
+/// 国内运营商的规范代码和中文名称
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChinaIsp {
+    pub code: &'static str,
+    pub name: &'static str,
+}
+
+// 中文关键词按子串匹配，英文关键词按整词匹配 ASN 组织名称，如 CHINANET-BACKBONE；铁通排在电信之前
+const CHINA_ISPS: &[(ChinaIsp, &[&str])] = &[
+    (ChinaIsp { code: "CTT", name: "中国铁通" }, &["铁通", "cttnet", "crtc"]),
+    (ChinaIsp { code: "CT", name: "中国电信" }, &["电信", "chinanet", "telecom", "ctcc"]),
+    (ChinaIsp { code: "CU", name: "中国联通" }, &["联通", "unicom", "cnc", "cncgroup", "uninet", "cucc"]),
+    (ChinaIsp { code: "CM", name: "中国移动" }, &["移动", "cmnet", "cmcc", "china mobile"]),
+    (ChinaIsp { code: "CBN", name: "中国广电" }, &["广电", "bttnet", "cbn"]),
+    (ChinaIsp { code: "CERNET", name: "中国教育网" }, &["教育网", "cernet"]),
+    (ChinaIsp { code: "GWBN", name: "长城宽带" }, &["长城宽带", "gwbn", "gwbnet"]),
+];
+
+/// 把 GeoCN、asn_info.json 或 ASN 数据库中的运营商名称归一为规范的代码和名称
+pub fn china_isp(name: &str) -> Option<ChinaIsp> {
+    let words: Vec<String> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let words = format!(" {} ", words.join(" "));
+    CHINA_ISPS.iter()
+        .find(|(_, keywords)| keywords.iter().any(|keyword| match keyword.is_ascii() {
+            true => words.contains(&format!(" {} ", keyword)),
+            false => name.contains(keyword),
+        }))
+        .map(|(isp, _)| *isp)
+}
+
+// 省级行政区名称的开头和 ISO 3166-2:CN 代码，没有哪个开头是另一个的前缀
+const CHINA_PROVINCE_CODES: &[(&str, &str)] = &[
+    ("北京", "CN-BJ"), ("天津", "CN-TJ"), ("河北", "CN-HE"), ("山西", "CN-SX"), ("内蒙古", "CN-NM"),
+    ("辽宁", "CN-LN"), ("吉林", "CN-JL"), ("黑龙江", "CN-HL"), ("上海", "CN-SH"), ("江苏", "CN-JS"),
+    ("浙江", "CN-ZJ"), ("安徽", "CN-AH"), ("福建", "CN-FJ"), ("江西", "CN-JX"), ("山东", "CN-SD"),
+    ("河南", "CN-HA"), ("湖北", "CN-HB"), ("湖南", "CN-HN"), ("广东", "CN-GD"), ("广西", "CN-GX"),
+    ("海南", "CN-HI"), ("重庆", "CN-CQ"), ("四川", "CN-SC"), ("贵州", "CN-GZ"), ("云南", "CN-YN"),
+    ("西藏", "CN-XZ"), ("陕西", "CN-SN"), ("甘肃", "CN-GS"), ("青海", "CN-QH"), ("宁夏", "CN-NX"),
+    ("新疆", "CN-XJ"), ("台湾", "CN-TW"), ("香港", "CN-HK"), ("澳门", "CN-MO"),
+];
+
+/// 中文省级名称（如“浙江省”“广西壮族自治区”）对应的 ISO 3166-2 代码，GeoCN 的地区没有代码时使用
+pub fn china_province_code(name: &str) -> Option<&'static str> {
+    let name = name.trim();
+    CHINA_PROVINCE_CODES.iter()
+        .find(|(prefix, _)| name.starts_with(prefix))
+        .map(|(_, code)| *code)
 }
 
 pub fn get_short_name(name: &str) -> String {
@@ -49,20 +161,59 @@ pub fn get_short_name(name: &str) -> String {
     name.chars().take(2).collect()
 }
 
+/// 追加一级地区及其简称。与上一级只差“市”后缀或完全相同时跳过，
+/// 例如直辖市的省级和市级都是“北京市”；吉林省吉林市这样的省市同名不受影响
+pub fn push_region(regions: &mut Vec<String>, regions_short: &mut Vec<String>, name: String) {
+    if push_region_name(regions, name) {
+        regions_short.extend(regions.last().map(|name| get_short_name(name)));
+    }
+}
+
+/// 只追加地区全称，去重规则与 push_region 相同，返回是否追加
+pub fn push_region_name(regions: &mut Vec<String>, name: String) -> bool {
+    let normalize = |name: &str| name.trim().trim_end_matches('市').to_string();
+    if regions.last().is_some_and(|last| normalize(last) == normalize(&name)) {
+        return false;
+    }
+    regions.push(name);
+    true
+}
+
 pub fn calculate_ipinfo_size(info: &IpInfo) -> usize {
     let mut size = std::mem::size_of::<IpInfo>();
 
     size += info.ip.capacity();
     size += info.addr.capacity();
+    if let Some(host) = &info.host {
+        size += host.capacity();
+    }
+    if let Some(cnames) = &info.cnames {
+        size += cnames.iter().map(|c| c.capacity()).sum::<usize>();
+    }
+    if let Some(rdns) = &info.rdns {
+        size += rdns.capacity();
+    }
+    size += info.warnings.iter().map(|w| w.capacity()).sum::<usize>();
 
     if let Some(asn) = &info.asn {
         size += std::mem::size_of::<crate::models::AsnInfo>();
         size += asn.name.capacity();
-        size += asn.info.capacity();
+        size += asn.info.as_ref().map_or(0, String::capacity);
     }
 
-    if let Some(_location) = &info.location {
+    if let Some(location) = &info.location {
         size += std::mem::size_of::<crate::models::Location>();
+        size += location.time_zone.as_ref().map_or(0, String::capacity);
+    }
+
+    if let Some(postal) = &info.postal {
+        size += postal.capacity();
+    }
+
+    if let Some(continent) = &info.continent {
+        size += std::mem::size_of::<crate::models::ContinentInfo>();
+        size += continent.code.capacity();
+        size += continent.name.capacity();
     }
 
     if let Some(country) = &info.country {
@@ -77,6 +228,16 @@ pub fn calculate_ipinfo_size(info: &IpInfo) -> usize {
         size += registered_country.name.capacity();
     }
 
+    if let Some(represented_country) = &info.represented_country {
+        size += std::mem::size_of::<crate::models::CountryInfo>();
+        size += represented_country.code.capacity();
+        size += represented_country.name.capacity();
+    }
+
+    if info.traits.is_some() {
+        size += std::mem::size_of::<crate::models::Traits>();
+    }
+
     if let Some(regions) = &info.regions {
         size += std::mem::size_of::<Vec<String>>();
         for region in regions {
@@ -90,14 +251,117 @@ pub fn calculate_ipinfo_size(info: &IpInfo) -> usize {
             size += region.capacity();
         }
     }
+    if let Some(region_codes) = &info.region_codes {
+        size += std::mem::size_of::<Vec<String>>();
+        for code in region_codes {
+            size += code.capacity();
+        }
+    }
+
+    if let Some(city) = &info.city {
+        size += std::mem::size_of::<crate::models::CityInfo>();
+        size += city.name.capacity();
+    }
 
     if let Some(r#type) = &info.r#type {
         size += r#type.capacity();
     }
 
+    for field in [&info.isp, &info.organization, &info.domain].into_iter().flatten() {
+        size += field.capacity();
+    }
+
     size
 }
 
+/// 把坐标四舍五入到指定的小数位数。经十进制文本转换，输出不会出现 39.900000000000006 这样的尾数
+pub fn round_coord(value: f64, places: u8) -> f64 {
+    format!("{:.*}", usize::from(places), value).parse().unwrap_or(value)
+}
+
+/// 按前缀长度得到IP所在网段，例如 1.2.3.4 与 24 得到 1.2.3.0/24，超出位数的前缀按最大值处理
+pub fn network_for(ip: IpAddr, prefix_len: u8) -> IpNet {
+    let max = match ip {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+    IpNet::new(ip, prefix_len.min(max))
+        .expect("prefix length is clamped")
+        .trunc()
+}
+
+// 私有和链路本地网段，按从具体到宽泛的顺序匹配
+static PRIVATE_NETWORKS: Lazy<[IpNet; 7]> = Lazy::new(|| [
+    "127.0.0.0/8", "10.0.0.0/8", "172.16.0.0/12", "192.168.0.0/16", "169.254.0.0/16",
+    "fe80::/10", "fc00::/7",
+].map(|net| net.parse().expect("valid network")));
+
+/// 私有地址所属的网段，不在已知网段内（如广播地址、::1）时返回 None
+pub fn private_network(ip: IpAddr) -> Option<IpNet> {
+    PRIVATE_NETWORKS.iter().find(|net| net.contains(&ip)).copied()
+}
+
+/// 是否是链路本地地址（169.254.0.0/16、fe80::/10）
+pub fn is_link_local(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_link_local(),
+        IpAddr::V6(ip) => ip.segments()[0] & 0xffc0 == 0xfe80,
+    }
+}
+
+/// 从用户粘贴的URL或 host:port 中取出主机名：
+/// 去掉协议、用户信息、路径/查询/片段、端口和末尾的点，`[::1]` 形式的IPv6去掉方括号
+pub fn normalize_host(input: &str) -> String {
+    let mut host = input.trim();
+    if let Some((_, rest)) = host.split_once("://") {
+        host = rest;
+    }
+    if let Some(end) = host.find(['/', '?', '#']) {
+        host = &host[..end];
+    }
+    if let Some((_, rest)) = host.rsplit_once('@') {
+        host = rest;
+    }
+
+    if let Some(rest) = host.strip_prefix('[') {
+        // [IPv6] 或 [IPv6]:port
+        host = rest.split_once(']').map(|(inner, _)| inner).unwrap_or(rest);
+    } else if let Some((name, port)) = host.split_once(':') {
+        // 只有一个冒号时才是 host:port，多个冒号是未加括号的IPv6
+        if !port.contains(':') && port.chars().all(|c| c.is_ascii_digit()) {
+            host = name;
+        }
+    }
+
+    strip_zone_id(host.trim_end_matches('.')).to_ascii_lowercase()
+}
+
+/// 去掉 `fe80::1%eth0`、`fe80::1%25eth0` 这类IPv6地址的 zone 后缀，其他输入原样返回
+pub fn strip_zone_id(host: &str) -> &str {
+    match host.split_once('%') {
+        Some((addr, _)) if addr.parse::<Ipv6Addr>().is_ok() => addr,
+        _ => host,
+    }
+}
+
+/// 容忍首尾空白和 zone 后缀的IP解析
+pub fn parse_ip_lenient(input: &str) -> Result<IpAddr, std::net::AddrParseError> {
+    strip_zone_id(input.trim()).parse()
+}
+
+// 常见的静态文件扩展名，均不是有效的顶级域名
+const FILE_EXTENSIONS: [&str; 20] = [
+    "ico", "png", "jpg", "jpeg", "gif", "svg", "webp", "txt", "xml", "html",
+    "htm", "js", "css", "map", "json", "php", "asp", "aspx", "woff", "woff2",
+];
+
+/// 路径看起来是文件而不是域名，例如 favicon.ico、sitemap.xml
+pub fn looks_like_file(path: &str) -> bool {
+    path.rsplit_once('.')
+        .map(|(_, ext)| FILE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
 pub fn is_private_ip(ip: IpAddr) -> bool {
     match ip {
         IpAddr::V4(ip) => {
@@ -118,4 +382,127 @@ pub fn is_private_ip(ip: IpAddr) -> bool {
             ip.segments()[0] & 0xfe00 == 0xfc00 // fc00::/7
         }
     }
-} 
\ No newline at end of file
+} 
+pub const CSV_HEADER: &str = "ip,as_number,as_name,as_info,addr,latitude,longitude,accuracy_radius,postal,continent_code,continent_name,country_code,country_name,country_is_eu,registered_country_code,registered_country_name,represented_country_code,represented_country_name,regions,regions_short,city_name,city_geoname_id,type,is_anonymous_proxy,is_satellite_provider,isp,organization,domain,host,cnames,time_zone,metro_code,asn";
+
+// CSV字段转义：包含逗号、引号或换行时用双引号包裹
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// 将IpInfo序列化为一行CSV，字段顺序与 CSV_HEADER 一致，多值地区用 `;` 连接
+pub fn ipinfo_to_csv(info: &IpInfo) -> String {
+    let asn = info.asn.as_ref();
+    let location = info.location.as_ref();
+    let fields = [
+        info.ip.clone(),
+        asn.and_then(|a| a.number).map(|n| n.to_string()).unwrap_or_default(),
+        asn.map(|a| a.name.clone()).unwrap_or_default(),
+        asn.and_then(|a| a.info.clone()).unwrap_or_default(),
+        info.addr.clone(),
+        location.and_then(|l| l.latitude).map(|v| v.to_string()).unwrap_or_default(),
+        location.and_then(|l| l.longitude).map(|v| v.to_string()).unwrap_or_default(),
+        info.accuracy_radius.map(|v| v.to_string()).unwrap_or_default(),
+        info.postal.clone().unwrap_or_default(),
+        info.continent.as_ref().map(|c| c.code.clone()).unwrap_or_default(),
+        info.continent.as_ref().map(|c| c.name.clone()).unwrap_or_default(),
+        info.country.as_ref().map(|c| c.code.clone()).unwrap_or_default(),
+        info.country.as_ref().map(|c| c.name.clone()).unwrap_or_default(),
+        info.country.as_ref().map(|c| c.is_eu.to_string()).unwrap_or_default(),
+        info.registered_country.as_ref().map(|c| c.code.clone()).unwrap_or_default(),
+        info.registered_country.as_ref().map(|c| c.name.clone()).unwrap_or_default(),
+        info.represented_country.as_ref().map(|c| c.code.clone()).unwrap_or_default(),
+        info.represented_country.as_ref().map(|c| c.name.clone()).unwrap_or_default(),
+        info.regions.as_ref().map(|r| r.join(";")).unwrap_or_default(),
+        info.regions_short.as_ref().map(|r| r.join(";")).unwrap_or_default(),
+        info.city.as_ref().map(|c| c.name.clone()).unwrap_or_default(),
+        info.city.as_ref().and_then(|c| c.geoname_id).map(|id| id.to_string()).unwrap_or_default(),
+        info.r#type.clone().unwrap_or_default(),
+        info.traits.as_ref().map(|t| t.is_anonymous_proxy).unwrap_or(false).to_string(),
+        info.traits.as_ref().map(|t| t.is_satellite_provider).unwrap_or(false).to_string(),
+        info.isp.clone().unwrap_or_default(),
+        info.organization.clone().unwrap_or_default(),
+        info.domain.clone().unwrap_or_default(),
+        info.host.clone().unwrap_or_default(),
+        info.cnames.as_ref().map(|c| c.join(";")).unwrap_or_default(),
+        location.and_then(|l| l.time_zone.clone()).unwrap_or_default(),
+        location.and_then(|l| l.metro_code).map(|v| v.to_string()).unwrap_or_default(),
+        asn.and_then(AsnInfo::label).unwrap_or_default(),
+    ];
+
+    fields.iter()
+        .map(|f| csv_escape(f))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+const REDACTED: &str = "[redacted]";
+
+/// 按 PRIVACY_MODE 处理将要写入日志的IP地址
+pub fn mask_ip(ip: IpAddr) -> String {
+    match Config::global().privacy_mode {
+        PrivacyMode::Full => ip.to_string(),
+        PrivacyMode::Truncated => match ip {
+            IpAddr::V4(ip) => {
+                let [a, b, c, _] = ip.octets();
+                Ipv4Addr::new(a, b, c, 0).to_string()
+            }
+            IpAddr::V6(ip) => {
+                let s = ip.segments();
+                Ipv6Addr::new(s[0], s[1], s[2], 0, 0, 0, 0, 0).to_string()
+            }
+        },
+        PrivacyMode::None => REDACTED.to_string(),
+    }
+}
+
+// 回显用户输入时最多保留的字符数
+const ECHO_MAX_CHARS: usize = 64;
+
+// 零宽字符、双向文本控制符等在终端和日志中不可见的字符
+fn is_invisible(c: char) -> bool {
+    matches!(c,
+        '\u{00AD}' | '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}'
+        | '\u{2060}'..='\u{206F}' | '\u{FEFF}' | '\u{FFF9}'..='\u{FFFB}')
+        || (c.is_whitespace() && c != ' ')
+}
+
+/// 清理要回显的用户输入：去掉控制字符，不可见字符按UTF-8百分号编码，
+/// 超过64个字符时截断并加省略号，防止日志注入和超长回显
+pub fn sanitize_echo(input: &str) -> String {
+    let mut output = String::with_capacity(input.len().min(ECHO_MAX_CHARS * 4));
+    let mut count = 0;
+    for c in input.chars().filter(|c| !c.is_control()) {
+        let mut buf = [0u8; 4];
+        let piece = if is_invisible(c) {
+            c.encode_utf8(&mut buf).bytes().map(|b| format!("%{:02X}", b)).collect()
+        } else {
+            c.to_string()
+        };
+        let len = piece.chars().count();
+        if count + len > ECHO_MAX_CHARS {
+            output.push('…');
+            break;
+        }
+        output.push_str(&piece);
+        count += len;
+    }
+    output
+}
+
+/// 按 PRIVACY_MODE 处理要回显给客户端或写入日志的用户输入，
+/// full 模式下做 sanitize_echo 清理，其他模式只保留能解析为IP的输入（并做截断）
+pub fn mask_input(input: &str) -> String {
+    match Config::global().privacy_mode {
+        PrivacyMode::Full => sanitize_echo(input),
+        PrivacyMode::Truncated => input.trim()
+            .parse::<IpAddr>()
+            .map(mask_ip)
+            .unwrap_or_else(|_| REDACTED.to_string()),
+        PrivacyMode::None => REDACTED.to_string(),
+    }
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{assert_error, send_from, setup_with, TestResponse, ADMIN_TOKEN};
+use ipgeo::config::{AccessList, Config};
+
+fn restricted(config: Config) -> Config {
+    Config {
+        client_acl: AccessList::new(&["8.8.8.0/24", "2001:db8::/32"], &["8.8.8.9"]),
+        admin_acl: AccessList::new(&["10.0.0.0/8"], &[]),
+        ..config
+    }
+}
+
+async fn get_from(uri: &str, peer: &str, headers: &[(&str, &str)]) -> TestResponse {
+    setup_with(restricted);
+    let mut request = Request::get(uri);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    send_from(request.body(Body::empty()).unwrap(), peer.parse().unwrap()).await
+}
+
+#[tokio::test]
+async fn client_lists_allow_deny_and_follow_proxy_headers() {
+    for peer in ["8.8.8.8:1000", "[2001:db8::1]:1000", "[::ffff:8.8.8.8]:1000"] {
+        assert_eq!(get_from("/api?host=1.0.0.1", peer, &[]).await.status, StatusCode::OK, "{}", peer);
+    }
+    for peer in ["8.8.8.9:1000", "1.1.1.1:1000", "[2001:db9::1]:1000"] {
+        assert_error(&get_from("/api?host=1.0.0.1", peer, &[]).await, StatusCode::FORBIDDEN, "FORBIDDEN");
+    }
+
+    // 经过代理时按识别出的客户端IP判断
+    let via_proxy = get_from("/", "1.1.1.1:1000", &[("cf-connecting-ip", "8.8.8.1")]).await;
+    assert_eq!(via_proxy.status, StatusCode::OK);
+    let denied = get_from("/", "8.8.8.1:1000", &[("cf-connecting-ip", "8.8.8.9")]).await;
+    assert_error(&denied, StatusCode::FORBIDDEN, "FORBIDDEN");
+}
+
+#[tokio::test]
+async fn admin_routes_use_their_own_lists() {
+    let bearer = format!("Bearer {}", ADMIN_TOKEN);
+    let auth = [("authorization", bearer.as_str())];
+
+    // 公开接口放行的客户端不能访问管理接口
+    let response = get_from("/admin/generations", "8.8.8.8:1000", &auth).await;
+    assert_error(&response, StatusCode::FORBIDDEN, "FORBIDDEN");
+
+    // 管理列表中的地址不受公开接口列表影响
+    assert_eq!(get_from("/admin/generations", "10.1.2.3:1000", &auth).await.status, StatusCode::OK);
+    assert_error(&get_from("/api?host=1.0.0.1", "10.1.2.3:1000", &[]).await, StatusCode::FORBIDDEN, "FORBIDDEN");
+}
+
+#[test]
+fn invalid_entries_are_reported_and_files_are_read() {
+    let list = AccessList::new(&["10.0.0.0/8", "not-a-network", "300.1.1.1"], &[]);
+    let error = list.validate("CLIENT_ALLOW").unwrap_err().to_string();
+    assert!(error.contains("not-a-network") && error.contains("300.1.1.1"), "{}", error);
+
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("deny.txt");
+    std::fs::write(&path, "# 扫描器\n203.0.113.0/24\n\n2001:db8::1  # 单个地址\n").unwrap();
+    let entry = format!("@{}", path.display());
+    let list = AccessList::new(&[], &[entry.as_str()]);
+    list.validate("CLIENT_DENY").expect("valid file");
+    assert_eq!(list.deny.len(), 2);
+    assert!(!list.permits("203.0.113.7".parse().unwrap()));
+    assert!(!list.permits("2001:db8::1".parse().unwrap()));
+    assert!(list.permits("2001:db8::2".parse().unwrap()));
+
+    let missing = AccessList::new(&["@/nonexistent/allow.txt"], &[]);
+    assert!(missing.validate("CLIENT_ALLOW").is_err());
+}
@@ -0,0 +1,54 @@
+//! /admin 下的管理接口：统一的凭据检查、错误码和客户端证书
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{assert_error, get, get_admin, send};
+use ipgeo::server::ClientCertificate;
+
+fn admin_request(uri: &str, token: Option<&str>, certificate: Option<ClientCertificate>) -> Request<Body> {
+    let mut request = Request::get(uri);
+    if let Some(token) = token {
+        request = request.header("authorization", format!("Bearer {}", token));
+    }
+    let mut request = request.body(Body::empty()).unwrap();
+    if let Some(certificate) = certificate {
+        request.extensions_mut().insert(certificate);
+    }
+    request
+}
+
+#[tokio::test]
+async fn every_admin_route_is_nested_under_admin() {
+    for uri in ["/admin/debug/headers", "/admin/debug/8.8.8.8", "/admin/generations", "/admin/cache"] {
+        let response = get_admin(uri).await;
+        assert_eq!(response.status, StatusCode::OK, "{}: {}", uri, response.body);
+    }
+    // 测试进程没有安装可切换的日志过滤器，能到达处理函数即可
+    assert_ne!(get_admin("/admin/log_level").await.status, StatusCode::NOT_FOUND);
+    // 旧的 /debug 路径不再提供
+    assert_error(&get_admin("/debug/headers").await, StatusCode::NOT_FOUND, "NOT_FOUND");
+}
+
+#[tokio::test]
+async fn missing_and_wrong_credentials_have_distinct_codes() {
+    let response = get("/admin/generations").await;
+    assert_error(&response, StatusCode::UNAUTHORIZED, "UNAUTHORIZED");
+    assert!(response.body["request_id"].is_string());
+
+    let response = send(admin_request("/admin/generations", Some("wrong-token"), None)).await;
+    assert_error(&response, StatusCode::FORBIDDEN, "ADMIN_FORBIDDEN");
+    let response = send(admin_request("/admin/debug/headers", Some(""), None)).await;
+    assert_error(&response, StatusCode::FORBIDDEN, "ADMIN_FORBIDDEN");
+}
+
+#[tokio::test]
+async fn verified_client_certificate_replaces_the_token() {
+    let response = send(admin_request("/admin/generations", None, Some(ClientCertificate { verified: true }))).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    // TLS 连接但没有出示证书时仍然需要令牌
+    let response = send(admin_request("/admin/generations", None, Some(ClientCertificate::default()))).await;
+    assert_error(&response, StatusCode::UNAUTHORIZED, "UNAUTHORIZED");
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use std::net::SocketAddr;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{get, get_admin, send_to, setup_with, ADMIN_TOKEN, PEER};
+use ipgeo::api::{create_admin_router, AppState};
+use ipgeo::config::Config;
+use ipgeo::server::bind_all;
+
+fn admin_port(config: Config) -> Config {
+    Config { admin_bind: Some("127.0.0.1:8081".parse().unwrap()), ..config }
+}
+
+async fn get_admin_port(uri: &str, token: bool) -> common::TestResponse {
+    setup_with(admin_port);
+    let mut request = Request::get(uri);
+    if token {
+        request = request.header("authorization", format!("Bearer {}", ADMIN_TOKEN));
+    }
+    send_to(create_admin_router(AppState::new()), request.body(Body::empty()).unwrap(), PEER.parse().unwrap()).await
+}
+
+#[tokio::test]
+async fn ops_routes_move_to_admin_port() {
+    setup_with(admin_port);
+    // 公开端口上这两个路径落到 /{host} 查询，不再返回指标或健康状态
+    for uri in ["/metrics", "/healthz"] {
+        let response = get(uri).await;
+        assert_ne!(response.status, StatusCode::OK, "{} on the public port", uri);
+        assert!(response.body["error"].is_string(), "{}", response.body);
+    }
+    assert_eq!(get_admin("/admin/cache").await.status, StatusCode::NOT_FOUND);
+    assert_eq!(get("/api/8.8.8.8").await.status, StatusCode::OK);
+
+    assert_eq!(get_admin_port("/healthz", false).await.status, StatusCode::OK);
+    let metrics = get_admin_port("/metrics", false).await;
+    assert_eq!(metrics.status, StatusCode::OK);
+    assert!(metrics.text().contains("ipgeo_"));
+    assert_eq!(get_admin_port("/admin/cache", true).await.status, StatusCode::OK);
+    assert_eq!(get_admin_port("/admin/cache", false).await.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn admin_port_serves_no_lookups() {
+    let response = get_admin_port("/api/8.8.8.8", false).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    assert_eq!(response.body["error"], "NOT_FOUND");
+}
+
+// 先占用一个端口，再取同一端口的 IPv4 和 IPv6 地址
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+#[test]
+fn binds_every_address() {
+    let addrs: Vec<SocketAddr> = vec!["127.0.0.1:0".parse().unwrap(), "127.0.0.1:0".parse().unwrap()];
+    let listeners = bind_all(&addrs).unwrap();
+    assert_eq!(listeners.len(), 2);
+    assert_ne!(listeners[0].local_addr().unwrap(), listeners[1].local_addr().unwrap());
+}
+
+#[test]
+fn dual_stack_binds_the_same_port() {
+    if std::net::TcpListener::bind("[::1]:0").is_err() {
+        return; // 没有 IPv6 的环境
+    }
+    let port = free_port();
+    let addrs: Vec<SocketAddr> = vec![
+        format!("0.0.0.0:{}", port).parse().unwrap(),
+        format!("[::]:{}", port).parse().unwrap(),
+    ];
+    let listeners = bind_all(&addrs).unwrap();
+    assert_eq!(listeners.len(), 2);
+}
+
+#[test]
+fn bind_error_names_the_address() {
+    let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = taken.local_addr().unwrap();
+    let error = bind_all(&["127.0.0.1:0".parse().unwrap(), addr]).unwrap_err();
+    assert!(error.to_string().contains(&addr.to_string()), "{}", error);
+}
@@ -0,0 +1,22 @@
+//! 没有配置管理凭据时管理路由完全不存在
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{assert_error, get, get_admin, setup_with};
+use ipgeo::config::Config;
+
+fn no_admin(config: Config) -> Config {
+    Config { admin_token: None, admin_client_ca: None, ..config }
+}
+
+#[tokio::test]
+async fn admin_paths_are_plain_not_found() {
+    setup_with(no_admin);
+    for uri in ["/admin", "/admin/cache", "/admin/generations", "/admin/log_level", "/admin/debug/headers", "/admin/debug/8.8.8.8"] {
+        // 带不带令牌都一样，不会出现 401 这类暴露接口存在的响应
+        assert_error(&get(uri).await, StatusCode::NOT_FOUND, "NOT_FOUND");
+        assert_error(&get_admin(uri).await, StatusCode::NOT_FOUND, "NOT_FOUND");
+    }
+    assert_eq!(get("/api/8.8.8.8").await.status, StatusCode::OK);
+}
@@ -0,0 +1,258 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{assert_error, get, post_json, send, send_from};
+use serde_json::json;
+
+#[tokio::test]
+async fn root_uses_peer_address() {
+    let response = get("/").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["ip"], "8.8.8.8");
+    assert_eq!(response.body["country"]["code"], "US");
+    assert_eq!(response.body["country"]["name"], "美国");
+    assert_eq!(response.body["as"]["number"], 15169);
+}
+
+#[tokio::test]
+async fn root_prefers_forwarded_header() {
+    let response = send(
+        Request::get("/")
+            .header("x-forwarded-for", "1.0.0.1")
+            .body(Body::empty())
+            .unwrap(),
+    ).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["ip"], "1.0.0.1");
+    assert_eq!(response.body["country"]["code"], "AU");
+}
+
+#[tokio::test]
+async fn api_queries_host_parameter() {
+    let response = get("/api?host=8.8.8.8").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["ip"], "8.8.8.8");
+    assert_eq!(response.body["addr"], "8.8.0.0/16");
+    assert_eq!(response.body["as"]["name"], "谷歌");
+    assert_eq!(response.body["type"], "数据中心");
+    assert!(response.body.get("host").is_none());
+}
+
+#[tokio::test]
+async fn path_lookup_merges_geocn() {
+    let response = get("/114.114.114.114").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["country"]["code"], "CN");
+    assert_eq!(response.body["regions"], json!(["江苏省", "南京市", "玄武区"]));
+    assert_eq!(response.body["city"]["name"], "南京市");
+    assert_eq!(response.body["type"], "电信网络");
+    assert_eq!(response.body["isp"], "中国电信");
+}
+
+#[tokio::test]
+async fn path_lookup_without_asn_record() {
+    let response = get("/api/1.0.0.1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["country"]["code"], "AU");
+    assert!(response.body.get("as").is_none());
+}
+
+#[tokio::test]
+async fn private_ip_returns_network_only() {
+    let response = get("/10.1.2.3").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body, json!({ "ip": "10.1.2.3", "addr": "10.0.0.0/8" }));
+
+    let response = get("/api?host=fd00::1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body, json!({ "ip": "fd00::1", "addr": "fc00::/7" }));
+}
+
+#[tokio::test]
+async fn invalid_ip_error_envelope() {
+    let response = get("/0.0.0.0").await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_IP");
+}
+
+#[tokio::test]
+async fn invalid_host_error_envelope() {
+    let response = get("/api?host=bad..host").await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "RESOLVE_ERROR");
+}
+
+#[tokio::test]
+async fn static_files_are_not_resolved() {
+    let response = get("/favicon.ico").await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let response = get("/wp-login.php").await;
+    assert_error(&response, StatusCode::NOT_FOUND, "NOT_FOUND");
+}
+
+#[tokio::test]
+async fn batch_keeps_input_order() {
+    let response = post_json("/api/batch", &json!(["114.114.114.114", "0.0.0.0", "8.8.8.8"])).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let results = response.body.as_array().expect("array body");
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0]["ip"], "114.114.114.114");
+    assert_eq!(results[1]["error"], "INVALID_IP");
+    assert_eq!(results[1]["query"], "0.0.0.0");
+    assert_eq!(results[2]["ip"], "8.8.8.8");
+}
+
+#[tokio::test]
+async fn batch_rejects_non_array_body() {
+    let response = post_json("/api/batch", &json!({ "host": "8.8.8.8" })).await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_PARAMETER");
+}
+
+#[tokio::test]
+async fn ipv6_zone_id_is_stripped() {
+    let response = get("/fe80::1%25eth0").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["ip"], "fe80::1");
+    assert_eq!(response.body["addr"], "fe80::/10");
+    assert_eq!(response.body["type"], "链路本地地址");
+
+    let response = get("/api?host=%20FE80::ABCD%25en0%20").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["ip"], "fe80::abcd");
+}
+
+#[tokio::test]
+async fn ipv4_link_local_is_tagged() {
+    let response = get("/169.254.10.20").await;
+    assert_eq!(response.body["addr"], "169.254.0.0/16");
+    assert_eq!(response.body["type"], "链路本地地址");
+}
+
+#[tokio::test]
+async fn ip_field_is_canonical() {
+    let response = get("/api?host=2001:4860:4860:0:0:0:0:8888").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["ip"], "2001:4860:4860::8888");
+}
+
+#[tokio::test]
+async fn self_aliases_resolve_to_caller() {
+    for uri in ["/me", "/api/me", "/api?host=me", "/self", "/api/SELF", "/api?host=self", "/v1/me"] {
+        let response = send(
+            Request::get(uri)
+                .header("x-forwarded-for", "1.0.0.1, 10.0.0.1")
+                .body(Body::empty())
+                .unwrap(),
+        ).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", uri);
+        assert_eq!(response.body["ip"], "1.0.0.1", "{}", uri);
+        assert_eq!(response.body["country"]["code"], "AU", "{}", uri);
+        let alias = uri.rsplit(['/', '=']).next().unwrap();
+        assert_eq!(response.body["host"], alias, "{}", uri);
+        assert!(response.body.get("cnames").is_none(), "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn self_alias_from_private_peer_returns_network() {
+    let response = send_from(
+        Request::get("/api/me").body(Body::empty()).unwrap(),
+        "10.1.2.3:5000".parse().unwrap(),
+    ).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body, json!({ "host": "me", "ip": "10.1.2.3", "addr": "10.0.0.0/8" }));
+
+    let root = send_from(Request::get("/").body(Body::empty()).unwrap(), "10.1.2.3:5000".parse().unwrap()).await;
+    assert_eq!(root.body["addr"], response.body["addr"]);
+}
+
+#[tokio::test]
+async fn batch_accepts_self_alias() {
+    let response = post_json("/api/batch", &json!(["me", "1.0.0.1"])).await;
+    let results = response.body.as_array().expect("array body");
+    assert_eq!(results[0]["ip"], "8.8.8.8");
+    assert_eq!(results[0]["host"], "me");
+    assert_eq!(results[1]["ip"], "1.0.0.1");
+}
+
+#[tokio::test]
+async fn api_accepts_ip_alias() {
+    let response = get("/api?ip=1.0.0.1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["ip"], "1.0.0.1");
+
+    let response = get("/api?ip=1.0.0.1&host=1.0.0.1").await;
+    assert_eq!(response.body["ip"], "1.0.0.1");
+}
+
+#[tokio::test]
+async fn api_rejects_conflicting_ip_and_host() {
+    let response = get("/api?ip=1.0.0.1&host=8.8.8.8").await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_PARAMETER");
+}
+
+#[tokio::test]
+async fn api_multiple_hosts_return_array() {
+    for uri in ["/api?host=114.114.114.114&host=0.0.0.0&host=8.8.8.8", "/api?host=114.114.114.114,%200.0.0.0,8.8.8.8"] {
+        let response = get(uri).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", uri);
+        let results = response.body.as_array().expect("array body");
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["ip"], "114.114.114.114");
+        assert_eq!(results[1]["error"], "INVALID_IP");
+        assert_eq!(results[1]["query"], "0.0.0.0");
+        assert_eq!(results[2]["ip"], "8.8.8.8");
+    }
+}
+
+#[tokio::test]
+async fn api_multiple_hosts_share_batch_cap() {
+    let hosts = vec!["8.8.8.8"; 101].join(",");
+    let response = get(&format!("/api?host={}", hosts)).await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_PARAMETER");
+}
+
+#[tokio::test]
+async fn empty_host_falls_back_to_caller() {
+    let peer = "1.0.0.1:40000".parse().unwrap();
+    for uri in ["/api?host=", "/api?host=%20%20", "/api?ip=", "/api/%20", "/%20%20"] {
+        let response = send_from(Request::get(uri).body(Body::empty()).unwrap(), peer).await;
+        assert_eq!(response.status, StatusCode::OK, "{}: {}", uri, response.body);
+        assert_eq!(response.body["ip"], "1.0.0.1", "{}", uri);
+    }
+
+    // 空的 host 不与 ip 冲突
+    let response = get("/api?host=&ip=1.0.0.1").await;
+    assert_eq!(response.body["ip"], "1.0.0.1");
+}
+
+#[tokio::test]
+async fn padded_host_is_trimmed() {
+    for uri in ["/api?host=%208.8.8.8%20", "/api/8.8.8.8%20%20", "/%208.8.8.8", "/api?host=%20me%20"] {
+        let response = get(uri).await;
+        assert_eq!(response.status, StatusCode::OK, "{}: {}", uri, response.body);
+        assert_eq!(response.body["ip"], "8.8.8.8", "{}", uri);
+    }
+
+    // 去掉空白后仍按保留路径处理
+    let response = get("/metrics%20").await;
+    assert_error(&response, StatusCode::NOT_FOUND, "NOT_FOUND");
+}
+
+#[tokio::test]
+async fn trailing_slashes_are_ignored() {
+    let response = get("/api/?host=1.1.1.1").await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["ip"], "1.1.1.1");
+
+    for uri in ["/1.0.0.1/", "/api/1.0.0.1/", "/v1/1.0.0.1//", "/1.0.0.1/?detail=minimal"] {
+        let response = get(uri).await;
+        assert_eq!(response.status, StatusCode::OK, "{}: {}", uri, response.body);
+        assert_eq!(response.body["country"]["code"], "AU", "{}", uri);
+    }
+    let response = get("/1.0.0.1/?detail=minimal").await;
+    assert!(response.body.get("continent").is_none());
+
+    let response = get("/healthz/").await;
+    assert_eq!(response.status, StatusCode::OK);
+}
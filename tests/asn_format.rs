@@ -0,0 +1,60 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{assert_error, get, post_json, setup};
+use ipgeo::utils::{ipinfo_to_csv, CSV_HEADER};
+use serde_json::json;
+
+#[tokio::test]
+async fn number_is_the_default() {
+    let default = get("/8.8.8.8").await;
+    let number = get("/8.8.8.8?asn_format=number").await;
+    assert_eq!(default.body, number.body);
+    assert_eq!(number.body["as"], json!({ "number": 15169, "name": "谷歌", "info": "谷歌", "route": "8.8.8.0/24" }));
+}
+
+#[tokio::test]
+async fn string_replaces_the_number() {
+    let response = get("/8.8.8.8?asn_format=string").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["as"], json!({ "asn": "AS15169", "name": "谷歌", "info": "谷歌", "route": "8.8.8.0/24" }));
+}
+
+#[tokio::test]
+async fn both_outputs_number_and_string() {
+    let response = post_json("/api/batch?asn_format=both", &json!(["8.8.8.8"])).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body[0]["as"]["number"], 15169);
+    assert_eq!(response.body[0]["as"]["asn"], "AS15169");
+}
+
+#[tokio::test]
+async fn unknown_format_is_rejected() {
+    let response = get("/8.8.8.8?asn_format=hex").await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_PARAMETER");
+}
+
+#[tokio::test]
+async fn csv_includes_asn_string() {
+    setup();
+    let info = ipgeo::geo::get_ip_info("8.8.8.8").await.unwrap();
+    let header: Vec<_> = CSV_HEADER.split(',').collect();
+    let row = ipinfo_to_csv(&info);
+    let row: Vec<_> = row.split(',').collect();
+    assert_eq!(header.len(), row.len());
+    let column = header.iter().position(|name| *name == "asn").unwrap();
+    assert_eq!(row[column], "AS15169");
+}
+
+#[tokio::test]
+async fn missing_values_keep_the_legacy_placeholders_by_default() {
+    // 没有号码时输出 0，没有整理的说明时 info 重复 name
+    let response = get("/45.33.1.1").await;
+    assert_eq!(response.body["as"], json!({ "number": 0, "name": "Example Org Without Number", "info": "Example Org Without Number", "route": "45.33.1.0/24" }));
+    let response = get("/45.33.2.1").await;
+    assert_eq!(response.body["as"], json!({ "number": 64501, "name": "", "info": "", "route": "45.33.2.0/24" }));
+    let response = get("/45.33.3.1").await;
+    assert_eq!(response.body["as"], json!({ "number": 0, "name": "", "info": "", "route": "45.33.3.0/24" }));
+    let response = get("/114.114.114.114").await;
+    assert_eq!(response.body["as"], json!({ "number": 21859, "name": "ZEN-ECN", "info": "ZEN-ECN", "route": "114.114.114.0/24" }));
+}
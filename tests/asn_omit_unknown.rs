@@ -0,0 +1,42 @@
+//! ASN_OMIT_UNKNOWN=on 时 `as` 不再输出占位值
+
+mod common;
+
+use axum::http::StatusCode;
+use common::get;
+use ipgeo::config::Config;
+use serde_json::json;
+
+fn omit_unknown(config: Config) -> Config {
+    Config { asn_omit_unknown: true, ..config }
+}
+
+#[tokio::test]
+async fn curated_asn_is_unchanged() {
+    common::setup_with(omit_unknown);
+    let response = get("/8.8.8.8").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["as"], json!({ "number": 15169, "name": "谷歌", "info": "谷歌", "route": "8.8.8.0/24" }));
+}
+
+#[tokio::test]
+async fn info_is_omitted_without_curated_data() {
+    common::setup_with(omit_unknown);
+    let response = get("/114.114.114.114").await;
+    assert_eq!(response.body["as"], json!({ "number": 21859, "name": "ZEN-ECN", "route": "114.114.114.0/24" }));
+}
+
+#[tokio::test]
+async fn missing_number_and_name_are_omitted() {
+    common::setup_with(omit_unknown);
+    let response = get("/45.33.1.1").await;
+    assert_eq!(response.body["as"], json!({ "name": "Example Org Without Number", "route": "45.33.1.0/24" }));
+
+    let response = get("/45.33.2.1?asn_format=both").await;
+    assert_eq!(response.body["as"], json!({ "number": 64501, "asn": "AS64501", "route": "45.33.2.0/24" }));
+
+    // 号码和名称都没有时不输出 as
+    let response = get("/45.33.3.1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.get("as").is_none(), "{}", response.body);
+}
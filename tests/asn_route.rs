@@ -0,0 +1,37 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::get;
+
+#[tokio::test]
+async fn route_is_the_matched_asn_record_prefix() {
+    let response = get("/8.8.8.8").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["as"]["route"], "8.8.8.0/24", "{}", response.body);
+    // addr 固定为 IPv4 的 /16，route 只来自 ASN 数据库
+    assert_eq!(response.body["addr"], "8.8.0.0/16", "{}", response.body);
+
+    let response = get("/v2/8.8.8.8").await;
+    assert_eq!(response.body["as"]["route"], "8.8.8.0/24", "{}", response.body);
+
+    // /v1 保持首次发布时的字段
+    let response = get("/v1/8.8.8.8").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["as"]["number"], 15169, "{}", response.body);
+    assert!(response.body["as"].get("route").is_none(), "{}", response.body);
+}
+
+#[tokio::test]
+async fn route_covers_ipv6_records() {
+    let response = get("/2a03:2880::1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["as"]["number"], 32934, "{}", response.body);
+    assert_eq!(response.body["as"]["route"], "2a03:2880::/29", "{}", response.body);
+}
+
+#[tokio::test]
+async fn route_is_omitted_without_an_asn_record() {
+    let response = get("/1.0.0.1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.get("as").is_none(), "{}", response.body);
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{assert_error, post_json, setup_with};
+use ipgeo::config::Config;
+use serde_json::json;
+
+// 并发度远小于条数，结果仍须按输入顺序返回
+fn batch_limits(config: Config) -> Config {
+    Config { batch_max_size: 100, batch_parallelism: 3, ..config }
+}
+
+fn hosts() -> Vec<String> {
+    (0..100)
+        .map(|i| match i % 4 {
+            0 => format!("8.8.8.{}", i),
+            1 => format!("114.114.114.{}", i),
+            2 => format!("1.0.0.{}", i),
+            _ => "bad..host".to_string(),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn results_follow_input_order_with_bounded_parallelism() {
+    setup_with(batch_limits);
+    let hosts = hosts();
+    let response = post_json("/api/batch", &json!(hosts)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let results = response.body.as_array().expect("array body");
+    assert_eq!(results.len(), hosts.len());
+    for (host, result) in hosts.iter().zip(results) {
+        if host == "bad..host" {
+            assert_eq!(result["error"], "RESOLVE_ERROR", "{}", result);
+            assert_eq!(result["query"], "bad..host");
+        } else {
+            assert_eq!(result["ip"], host.as_str(), "{}", result);
+        }
+    }
+    assert_eq!(results[1]["regions"][0], "江苏省");
+    assert_eq!(results[2]["country"]["code"], "AU");
+}
+
+#[tokio::test]
+async fn batch_max_size_is_inclusive() {
+    setup_with(batch_limits);
+    let response = post_json("/api/batch", &json!(vec!["8.8.8.8"; 100])).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body.as_array().unwrap().len(), 100);
+
+    let response = post_json("/api/batch", &json!(vec!["8.8.8.8"; 101])).await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_PARAMETER");
+}
+
+#[tokio::test]
+async fn duplicate_hosts_each_get_a_result() {
+    setup_with(batch_limits);
+    let response = post_json("/api/batch", &json!(["8.8.8.8", "8.8.8.8", "1.0.0.1", "8.8.8.8"])).await;
+    let results = response.body.as_array().unwrap();
+    let ips: Vec<_> = results.iter().map(|result| result["ip"].as_str().unwrap()).collect();
+    assert_eq!(ips, ["8.8.8.8", "8.8.8.8", "1.0.0.1", "8.8.8.8"]);
+}
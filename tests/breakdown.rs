@@ -0,0 +1,39 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{get, send};
+use ipgeo::metrics::Metrics;
+
+#[tokio::test]
+async fn lookups_are_broken_down_by_country_type_and_ip_source() {
+    let request = Request::get("/api?host=8.8.8.8")
+        .header("cf-connecting-ip", "1.2.3.4")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(request).await.status, StatusCode::OK);
+
+    let metrics = String::from_utf8(get("/metrics").await.bytes).unwrap();
+    assert!(metrics.contains("ipgeo_lookups_by_country_total{country=\"US\"}"), "{}", metrics);
+    assert!(metrics.contains("ipgeo_lookups_by_type_total{type="), "{}", metrics);
+    assert!(metrics.contains("ipgeo_client_ip_source_total{source=\"cf-connecting-ip\"}"), "{}", metrics);
+}
+
+#[test]
+fn label_values_outside_the_whitelist_are_collapsed() {
+    let metrics = Metrics::default();
+    metrics.record_country(Some("CN"));
+    metrics.record_country(Some("\"}evil"));
+    metrics.record_country(Some("cn"));
+    metrics.record_country(None);
+    metrics.record_network_type(Some("数据中心"));
+    metrics.record_network_type(Some("自定义类型"));
+
+    let text = metrics.render();
+    assert!(text.contains("ipgeo_lookups_by_country_total{country=\"CN\"} 1"));
+    assert!(text.contains("ipgeo_lookups_by_country_total{country=\"other\"} 2"));
+    assert!(text.contains("ipgeo_lookups_by_country_total{country=\"unknown\"} 1"));
+    assert!(text.contains("ipgeo_lookups_by_type_total{type=\"数据中心\"} 1"));
+    assert!(text.contains("ipgeo_lookups_by_type_total{type=\"other\"} 1"));
+    assert!(!text.contains("evil"));
+}
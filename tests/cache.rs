@@ -0,0 +1,61 @@
+mod common;
+
+use std::time::Duration;
+use axum::http::StatusCode;
+use common::{delete_admin, get, get_admin, setup_with};
+use ipgeo::config::Config;
+
+fn with_result_cache(config: Config) -> Config {
+    Config { result_cache_ttl: Duration::from_secs(3600), ..config }
+}
+
+#[tokio::test]
+async fn cache_stats_count_lookups_and_flush_refills_asn_data() {
+    setup_with(with_result_cache);
+    assert_eq!(get("/api?host=8.8.8.8").await.status, StatusCode::OK);
+
+    let response = get_admin("/admin/cache").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let asn = &response.body["caches"]["asn"];
+    assert!(asn["entries"].as_u64().unwrap() > 0);
+    assert!(asn["hits"].as_u64().unwrap() + asn["misses"].as_u64().unwrap() > 0);
+    assert!(asn["memory_bytes"].as_u64().unwrap() > 0);
+    assert!(response.body["caches"]["keyword"]["entries"].is_u64());
+    assert!(response.body["dns"]["capacity"].as_u64().unwrap() > 0);
+    let entries = asn["entries"].as_u64().unwrap();
+
+    let response = delete_admin("/admin/cache").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let asn = &response.body["caches"]["asn"];
+    assert!(asn["evictions"].as_u64().unwrap() >= entries);
+    // ASN 类型数据随后从 asn_info.json 重新加载
+    assert_eq!(asn["entries"].as_u64().unwrap(), entries);
+}
+
+#[tokio::test]
+async fn result_cache_is_reported_and_flushed() {
+    setup_with(with_result_cache);
+    // 其他测试可能同时查询，只比较本测试的键带来的变化
+    assert_eq!(get("/api?host=1.0.0.7").await.status, StatusCode::OK);
+    let before = get_admin("/admin/cache").await.body["caches"]["results"].clone();
+    assert_eq!(get("/api?host=1.0.0.7").await.status, StatusCode::OK);
+
+    let results = &get_admin("/admin/cache").await.body["caches"]["results"];
+    assert!(results["entries"].as_u64().unwrap() > 0, "{}", results);
+    assert!(results["hits"].as_u64().unwrap() > before["hits"].as_u64().unwrap(), "{} {}", before, results);
+    assert!(results["misses"].as_u64().unwrap() > 0, "{}", results);
+    assert!(results["memory_bytes"].as_u64().unwrap() > 0, "{}", results);
+
+    let response = delete_admin("/admin/cache").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let results = &response.body["caches"]["results"];
+    assert!(results["evictions"].as_u64().unwrap() > 0, "{}", results);
+    let response = get("/api?host=1.0.0.7&echo=1").await;
+    assert!(response.body.get("cached").is_none(), "{}", response.body);
+}
+
+#[tokio::test]
+async fn cache_endpoint_requires_admin_token() {
+    setup_with(with_result_cache);
+    assert_eq!(get("/admin/cache").await.status, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::routing::get;
+use axum::{middleware, Router};
+use common::{assert_error, send_to, PEER};
+use ipgeo::api::access_log::access_log;
+use ipgeo::api::errors::{catch_panic, negotiate_lang};
+
+async fn explode() -> &'static str {
+    panic!("fixture handler bug")
+}
+
+async fn explode_formatted() -> &'static str {
+    panic!("bad record at {}", 42)
+}
+
+// 与 create_router 相同的顺序：catch_panic 在语言协商和访问日志之内
+fn panicking_router() -> Router {
+    Router::new()
+        .route("/explode", get(explode))
+        .route("/explode/formatted", get(explode_formatted))
+        .layer(middleware::from_fn(catch_panic))
+        .layer(middleware::from_fn(negotiate_lang))
+        .layer(middleware::from_fn(access_log))
+}
+
+#[tokio::test]
+async fn handler_panic_returns_the_error_envelope() {
+    for uri in ["/explode", "/explode/formatted"] {
+        let request = Request::get(uri)
+            .header("x-request-id", "panic-test-1")
+            .body(Body::empty())
+            .unwrap();
+        let response = send_to(panicking_router(), request, PEER.parse().unwrap()).await;
+        assert_error(&response, StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_PANIC");
+        assert_eq!(response.body["request_id"], "panic-test-1");
+        assert_eq!(response.headers["x-request-id"], "panic-test-1");
+        // panic 的内容只写入日志，不返回给客户端
+        assert!(!response.text().contains("bad record") && !response.text().contains("fixture"), "{}", response.text());
+    }
+}
+
+#[tokio::test]
+async fn panic_message_follows_the_request_language() {
+    let response = send_to(panicking_router(), Request::get("/explode?lang=en").body(Body::empty()).unwrap(), PEER.parse().unwrap()).await;
+    assert_eq!(response.body["message"], "Internal server error, please try again later");
+    let response = send_to(panicking_router(), Request::get("/explode").body(Body::empty()).unwrap(), PEER.parse().unwrap()).await;
+    assert_eq!(response.body["message"], "服务器内部错误，请稍后重试");
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::get;
+
+#[tokio::test]
+async fn cernet_is_education() {
+    let response = get("/202.112.0.1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["as"]["number"], 4538);
+    assert_eq!(response.body["type"], "教育网络");
+    assert_eq!(response.body["category"], "education");
+}
+
+#[tokio::test]
+async fn curated_ixp_asn() {
+    let response = get("/80.81.192.1").await;
+    assert_eq!(response.body["as"]["name"], "DE-CIX");
+    assert_eq!(response.body["type"], "互联网交换中心");
+    assert_eq!(response.body["category"], "ixp");
+}
+
+#[tokio::test]
+async fn gov_keyword_in_organization() {
+    let response = get("/159.142.0.1").await;
+    assert_eq!(response.body["as"]["name"], "General Services Administration (GSA.GOV)");
+    assert_eq!(response.body["type"], "政务网络");
+    assert_eq!(response.body["category"], "government");
+}
+
+#[tokio::test]
+async fn university_keyword_in_organization() {
+    let response = get("/171.64.0.1").await;
+    assert_eq!(response.body["category"], "education");
+}
+
+#[tokio::test]
+async fn keywords_match_whole_words() {
+    // "Reduced" 中含有 "edu"，但不是独立的单词
+    let response = get("/185.1.0.1").await;
+    assert!(response.body.get("type").is_none(), "{}", response.body);
+    assert!(response.body.get("category").is_none());
+}
+
+#[tokio::test]
+async fn existing_types_carry_a_category() {
+    assert_eq!(get("/8.8.8.8").await.body["category"], "hosting");
+    assert_eq!(get("/114.114.114.114").await.body["category"], "isp");
+}
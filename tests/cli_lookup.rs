@@ -0,0 +1,127 @@
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Command, Output, Stdio};
+use common::{fixtures_dir, setup};
+use ipgeo::utils::CSV_HEADER;
+use serde_json::Value;
+
+fn ipgeo() -> Command {
+    setup();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_ipgeo"));
+    command.env("RUST_LOG", "warn").arg("--data-dir").arg(fixtures_dir());
+    command
+}
+
+fn lookup(args: &[&str], stdin: &str) -> Output {
+    let mut child = ipgeo()
+        .arg("lookup")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("spawn ipgeo");
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().expect("wait for ipgeo")
+}
+
+fn stdout_lines(output: &Output) -> Vec<String> {
+    String::from_utf8(output.stdout.clone()).unwrap().lines().map(str::to_string).collect()
+}
+
+#[test]
+fn prints_one_json_line_per_host() {
+    let output = lookup(&["8.8.8.8", "114.114.114.114"], "");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let lines = stdout_lines(&output);
+    assert_eq!(lines.len(), 2);
+    let first: Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(first["ip"], "8.8.8.8");
+    assert_eq!(first["country"]["code"], "US");
+    let second: Value = serde_json::from_str(&lines[1]).unwrap();
+    assert_eq!(second["regions"][0], "江苏省");
+}
+
+#[test]
+fn csv_format_starts_with_the_header() {
+    let output = lookup(&["--format", "csv", "8.8.8.8", "1.2.7.3"], "");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let lines = stdout_lines(&output);
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], CSV_HEADER);
+    assert!(lines[1].starts_with("8.8.8.8,15169,"), "{}", lines[1]);
+    assert!(lines[1].ends_with(",AS15169"), "{}", lines[1]);
+    assert!(lines[2].starts_with("1.2.7.3,"), "{}", lines[2]);
+    assert_eq!(lines[2].split(',').count(), CSV_HEADER.split(',').count());
+}
+
+#[test]
+fn dash_reads_hosts_from_stdin() {
+    let output = lookup(&["1.0.0.1", "-"], "8.8.8.8\n\n  114.114.114.114  \n");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let ips: Vec<Value> = stdout_lines(&output).iter()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap()["ip"].clone())
+        .collect();
+    assert_eq!(ips, ["1.0.0.1", "8.8.8.8", "114.114.114.114"]);
+}
+
+#[test]
+fn failed_lookups_exit_non_zero_after_printing_the_rest() {
+    let output = lookup(&["192.0.2.1", "8.8.8.8"], "");
+    assert!(!output.status.success());
+    // 失败的输入写到 stderr，其余结果照常输出
+    assert_eq!(stdout_lines(&output).len(), 1);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("192.0.2.1: "), "{}", stderr);
+}
+
+#[test]
+fn missing_databases_are_reported() {
+    let empty = fixtures_dir().with_file_name("cli-empty");
+    std::fs::create_dir_all(&empty).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_ipgeo"))
+        .arg("--data-dir").arg(&empty)
+        .args(["lookup", "8.8.8.8"])
+        .output()
+        .expect("run ipgeo");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Database not found"));
+}
+
+#[test]
+fn bare_invocation_serves_http() {
+    let mut child = ipgeo()
+        .env("RUST_LOG", "info")
+        .env("BIND", "127.0.0.1:0")
+        .env("DB_AUTO_UPDATE", "false")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("spawn ipgeo");
+
+    // 服务日志写到 stdout，从中取出实际监听的端口
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    let addr = loop {
+        line.clear();
+        if stdout.read_line(&mut line).unwrap() == 0 {
+            let _ = child.kill();
+            panic!("ipgeo exited before listening");
+        }
+        if let Some((_, rest)) = line.split_once("Listening on http://") {
+            break rest.split(|c: char| c.is_whitespace() || c == '\u{1b}').next().unwrap().to_string();
+        }
+    };
+
+    let mut stream = TcpStream::connect(&addr).expect("connect to ipgeo");
+    stream.write_all(b"GET /8.8.8.8 HTTP/1.0\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(response.starts_with("HTTP/1.0 200"), "{}", response);
+    assert!(response.contains("\"ip\":\"8.8.8.8\""), "{}", response);
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use common::{fixtures_dir, setup};
+
+// 关闭的端口：下载立即失败，不会访问真实的下载地址
+const UNREACHABLE_PROXY: &str = "http://127.0.0.1:9";
+
+// 每个测试使用独立的数据目录，复制后的文件修改时间为当前时间
+fn data_dir(name: &str, with_fixtures: bool) -> PathBuf {
+    setup();
+    let dir = fixtures_dir().with_file_name(format!("cli-update-{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    if with_fixtures {
+        for entry in std::fs::read_dir(fixtures_dir()).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_file() {
+                std::fs::copy(&path, dir.join(path.file_name().unwrap())).unwrap();
+            }
+        }
+    }
+    dir
+}
+
+fn update(dir: &PathBuf, args: &[&str]) -> (Output, Vec<String>) {
+    let output = Command::new(env!("CARGO_BIN_EXE_ipgeo"))
+        .env("RUST_LOG", "warn")
+        .env("DB_PROFILE", "full")
+        .env("HTTP_PROXY", UNREACHABLE_PROXY)
+        .env("HTTPS_PROXY", UNREACHABLE_PROXY)
+        .env("ALL_PROXY", UNREACHABLE_PROXY)
+        .env_remove("NO_PROXY")
+        .arg("--data-dir").arg(dir)
+        .arg("update")
+        .args(args)
+        .output()
+        .expect("run ipgeo");
+    let summary = String::from_utf8(output.stdout.clone()).unwrap().lines().map(str::to_string).collect();
+    (output, summary)
+}
+
+#[test]
+fn fresh_databases_are_reported_as_cached() {
+    let dir = data_dir("cached", true);
+    let (output, summary) = update(&dir, &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(summary, [
+        "GeoLite2-City.mmdb: cached",
+        "GeoLite2-ASN.mmdb: cached",
+        "GeoCN.mmdb: cached",
+        "rir.bin: cached",
+    ]);
+}
+
+#[test]
+fn only_limits_the_databases() {
+    let dir = data_dir("only", true);
+    let (output, summary) = update(&dir, &["--only", "asn,geocn"]);
+    assert!(output.status.success());
+    assert_eq!(summary, ["GeoLite2-ASN.mmdb: cached", "GeoCN.mmdb: cached"]);
+
+    // country 指当前 DB_PROFILE 使用的地理位置数据库
+    let (_, summary) = update(&dir, &["--only", "country"]);
+    assert_eq!(summary, ["GeoLite2-City.mmdb: cached"]);
+
+    let (output, _) = update(&dir, &["--only", "isp"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("isp"));
+}
+
+#[test]
+fn force_downloads_fresh_databases_and_keeps_the_old_file_on_failure() {
+    let dir = data_dir("force", true);
+    let (output, summary) = update(&dir, &["--force", "--only", "asn"]);
+    assert_eq!(summary.len(), 1);
+    assert!(summary[0].starts_with("GeoLite2-ASN.mmdb: failed ("), "{:?}", summary);
+    // 下载失败但原有文件还在，不算缺少数据库
+    assert!(output.status.success());
+    assert!(dir.join("GeoLite2-ASN.mmdb").exists());
+}
+
+#[test]
+fn missing_required_database_exits_with_1() {
+    let dir = data_dir("missing", false);
+    let (output, summary) = update(&dir, &["--only", "asn"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(summary.len(), 1);
+    assert!(summary[0].starts_with("GeoLite2-ASN.mmdb: failed ("), "{:?}", summary);
+    assert!(!dir.join("GeoLite2-ASN.mmdb").exists());
+}
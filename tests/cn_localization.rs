@@ -0,0 +1,55 @@
+//! CN_LOCALIZATION=off 时地区名称与数据库一致：不补后缀、不生成简称。
+//! 快照位于 tests/snapshots/raw，可用 `UPDATE_SNAPSHOTS=1 cargo test --test cn_localization` 重新生成。
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{assert_snapshot, get, setup_with};
+use ipgeo::config::Config;
+use serde_json::{json, Value};
+
+fn raw_names(config: Config) -> Config {
+    Config { cn_localization: false, ..config }
+}
+
+async fn lookup(uri: &str) -> Value {
+    setup_with(raw_names);
+    let response = get(uri).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    response.body
+}
+
+#[tokio::test]
+async fn city_database_names_are_not_suffixed() {
+    let body = lookup("/v2/api/1.2.4.3").await;
+    assert_eq!(body["regions"], json!(["北京市", "北京"]));
+    assert!(body.get("regions_short").is_none());
+    assert_snapshot("raw", "lookup_1.2.4.3", &body);
+
+    let body = lookup("/v2/api/1.2.6.3").await;
+    assert_eq!(body["regions"], json!(["广东", "广州"]));
+    assert_eq!(body["city"]["name"], "广州");
+}
+
+#[tokio::test]
+async fn selected_language_is_used_for_regions() {
+    let body = lookup("/v2/api/1.2.7.3?lang=en").await;
+    assert_eq!(body["regions"], json!(["Land Berlin", "Berlin"]));
+    assert_eq!(body["region_confidence"], 80);
+    assert_snapshot("raw", "lookup_1.2.7.3_en", &body);
+}
+
+#[tokio::test]
+async fn geocn_names_are_passed_through() {
+    let body = lookup("/v2/api/114.114.114.114").await;
+    assert_eq!(body["regions"], json!(["江苏省", "南京市", "玄武区"]));
+    assert!(body.get("regions_short").is_none());
+    assert_snapshot("raw", "lookup_114.114.114.114", &body);
+}
+
+#[tokio::test]
+async fn short_regions_are_omitted_even_when_requested() {
+    let body = lookup("/v2/api/1.2.4.3?regions=short").await;
+    assert!(body.get("regions").is_none());
+    assert!(body.get("regions_short").is_none());
+}
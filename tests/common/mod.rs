@@ -0,0 +1,387 @@
+//! 集成测试共用的夹具：用 mmdb-writer 生成的小型数据库和组装好的路由
+
+#![allow(dead_code)]
+
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Once};
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use ipgeo::api::{create_router, AppState};
+use ipgeo::config::{Config, DbProfile};
+use ipgeo::geo::{encode_allocations, init_asn_data, load_databases_from, load_overrides, parse_delegated, DatabaseManager, RIR_FILE};
+use ipnet::IpNet;
+use mmdb_writer::Writer;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+use tracing::subscriber::DefaultGuard;
+
+/// 测试请求默认的对端地址
+pub const PEER: &str = "8.8.8.8:40000";
+
+/// 夹具 rir.bin 的生成时间，2024-05-01
+pub const RIR_BUILD_EPOCH: u64 = 1_714_521_600;
+
+/// 夹具配置中的管理令牌
+pub const ADMIN_TOKEN: &str = "test-admin-token";
+
+static SETUP: Once = Once::new();
+
+/// 夹具数据库所在目录，setup 之后才存在
+pub fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join("fixtures")
+}
+
+fn write_mmdb(dir: &Path, name: &str, database_type: &str, records: &[(&str, Value)]) {
+    let mut writer = Writer::new(database_type);
+    for (network, record) in records {
+        let network: IpNet = network.parse().expect("fixture network");
+        writer.insert(network, record).expect("fixture record");
+    }
+    std::fs::write(dir.join(name), writer.to_bytes().expect("fixture database"))
+        .expect("write fixture database");
+}
+
+fn names(en: &str, zh: &str) -> Value {
+    json!({ "en": en, "zh-CN": zh })
+}
+
+// 8.8.8.0/24 为美国的普通记录，114.114.114.0/24 为带 GeoCN 省市区的国内记录，
+// 1.0.0.0/24 只在 City 数据库中出现；9.9.9.0/24 和 8.8.8.128/25 由 overrides.json 修正。
+// 1.2.4.0/24（北京）和 1.2.6.0/24（广州）只在 City 中，1.2.5.0/24（重庆）只在 GeoCN 中，用于地区去重。
+// 1.2.7.0/24（柏林，另有日文和德文名称）和 1.2.8.0/24（杭州）带有商业版 GeoIP2-City 的可信度，后者同时出现在 GeoCN 中，
+// 且 GeoCN 的运营商（联通）与 ASN（CHINANET）不一致。
+// 202.112.0.0/24、80.81.192.0/24 等只在 ASN 中，用于网络类型分类；1.2.9.0/24 带有商业版的匿名和托管标记。
+// 1.2.10.0/24 在 City 中为上海、在 GeoCN 中为江苏苏州，用于 compare=1 的省级不一致。
+// 1.2.11.0/24 为驻德美军的网络，带 represented_country 以及匿名代理和卫星网络标记
+
+fn build_fixtures(dir: &Path, profile: DbProfile) {
+    std::fs::create_dir_all(dir).expect("create fixtures dir");
+
+    let north_america = json!({ "code": "NA", "geoname_id": 6255149, "names": names("North America", "北美洲") });
+    let asia = json!({ "code": "AS", "geoname_id": 6255147, "names": names("Asia", "亚洲") });
+    let us = json!({ "geoname_id": 6252001, "iso_code": "US", "names": names("United States", "美国") });
+    let cn = json!({ "geoname_id": 1814991, "iso_code": "CN", "names": names("China", "中国") });
+    let au = json!({ "geoname_id": 2077456, "iso_code": "AU", "names": names("Australia", "澳大利亚") });
+
+    // 精简配置只有国家级的 GeoLite2-Country，记录与 City 中的同名字段相同
+    if profile == DbProfile::Lite {
+        write_mmdb(dir, "GeoLite2-Country.mmdb", "GeoLite2-Country", &[
+            ("8.8.8.0/24", json!({ "continent": north_america, "country": us, "registered_country": us })),
+            ("114.114.114.0/24", json!({ "continent": asia, "country": cn, "registered_country": cn })),
+            ("1.2.7.0/24", json!({
+                "continent": { "code": "EU", "geoname_id": 6255148, "names": names("Europe", "欧洲") },
+                "country": { "geoname_id": 2921044, "is_in_european_union": true, "iso_code": "DE", "names": names("Germany", "德国") },
+                "registered_country": { "geoname_id": 2921044, "is_in_european_union": true, "iso_code": "DE", "names": names("Germany", "德国") },
+            })),
+        ]);
+    }
+
+    if profile == DbProfile::Full {
+        write_mmdb(dir, "GeoLite2-City.mmdb", "GeoLite2-City", &[
+            ("8.8.8.0/24", json!({
+                "continent": north_america,
+                "country": us,
+                "registered_country": us,
+                "location": { "latitude": 37.751, "longitude": -97.822, "accuracy_radius": 1000, "metro_code": 807, "time_zone": "America/Chicago" },
+            })),
+            ("114.114.114.0/24", json!({
+                "city": { "geoname_id": 1799962, "names": names("Nanjing", "南京") },
+                "continent": asia,
+                "country": cn,
+                "registered_country": cn,
+                "location": { "latitude": 32.0617, "longitude": 118.7778, "accuracy_radius": 50, "time_zone": "Asia/Shanghai" },
+                "subdivisions": [{ "geoname_id": 1806260, "iso_code": "JS", "names": names("Jiangsu", "江苏") }],
+            })),
+            ("1.0.0.0/24", json!({
+                "continent": { "code": "OC", "geoname_id": 6255151, "names": names("Oceania", "大洋洲") },
+                "country": au,
+                "registered_country": au,
+            })),
+            ("1.2.4.0/24", json!({
+                "city": { "geoname_id": 1816670, "names": names("Beijing", "北京") },
+                "continent": asia,
+                "country": cn,
+                "subdivisions": [{ "geoname_id": 2038349, "iso_code": "BJ", "names": names("Beijing", "北京市") }],
+            })),
+            ("1.2.6.0/24", json!({
+                "city": { "geoname_id": 1809858, "names": names("Guangzhou", "广州") },
+                "continent": asia,
+                "country": cn,
+                "subdivisions": [{ "geoname_id": 1809935, "iso_code": "GD", "names": names("Guangdong", "广东") }],
+            })),
+            ("1.2.7.0/24", json!({
+                "city": { "confidence": 60, "geoname_id": 2950159, "names": { "en": "Berlin", "zh-CN": "柏林", "ja": "ベルリン" } },
+                "continent": { "code": "EU", "geoname_id": 6255148, "names": names("Europe", "欧洲") },
+                "country": { "confidence": 99, "geoname_id": 2921044, "is_in_european_union": true, "iso_code": "DE", "names": { "en": "Germany", "zh-CN": "德国", "ja": "ドイツ", "de": "Deutschland" } },
+                "registered_country": { "geoname_id": 2921044, "is_in_european_union": true, "iso_code": "DE", "names": names("Germany", "德国") },
+                "postal": { "code": "10115", "confidence": 20 },
+                "subdivisions": [{ "confidence": 80, "geoname_id": 2950157, "iso_code": "BE", "names": names("Land Berlin", "柏林") }],
+            })),
+            ("1.2.8.0/24", json!({
+                "city": { "confidence": 50, "geoname_id": 1808926, "names": names("Hangzhou", "杭州") },
+                "country": { "confidence": 95, "geoname_id": 1814991, "iso_code": "CN", "names": names("China", "中国") },
+                "subdivisions": [{ "confidence": 70, "geoname_id": 1784764, "iso_code": "ZJ", "names": names("Zhejiang", "浙江") }],
+            })),
+            ("1.2.9.0/24", json!({
+                "country": us,
+                "traits": { "is_anonymous": true, "is_anonymous_vpn": true, "is_hosting_provider": true },
+            })),
+            ("1.2.10.0/24", json!({
+                "city": { "geoname_id": 1796236, "names": names("Shanghai", "上海") },
+                "continent": asia,
+                "country": cn,
+                "location": { "latitude": 31.2222, "longitude": 121.4581, "accuracy_radius": 100, "time_zone": "Asia/Shanghai" },
+                "subdivisions": [{ "geoname_id": 1796231, "iso_code": "SH", "names": names("Shanghai", "上海市") }],
+            })),
+            ("1.2.11.0/24", json!({
+                "continent": { "code": "EU", "geoname_id": 6255148, "names": names("Europe", "欧洲") },
+                "country": { "geoname_id": 2921044, "is_in_european_union": true, "iso_code": "DE", "names": names("Germany", "德国") },
+                "registered_country": us,
+                "represented_country": { "geoname_id": 6252001, "iso_code": "US", "names": names("United States", "美国"), "type": "military" },
+                "traits": { "is_anonymous_proxy": true, "is_satellite_provider": true },
+            })),
+        ]);
+    }
+
+    write_mmdb(dir, "GeoLite2-ASN.mmdb", "GeoLite2-ASN", &[
+        ("8.8.8.0/24", json!({ "autonomous_system_number": 15169, "autonomous_system_organization": "GOOGLE" })),
+        ("114.114.114.0/24", json!({ "autonomous_system_number": 21859, "autonomous_system_organization": "ZEN-ECN" })),
+        ("202.112.0.0/24", json!({ "autonomous_system_number": 4538, "autonomous_system_organization": "China Education and Research Network Center" })),
+        ("80.81.192.0/24", json!({ "autonomous_system_number": 6695, "autonomous_system_organization": "DE-CIX Management GmbH" })),
+        ("159.142.0.0/24", json!({ "autonomous_system_number": 64496, "autonomous_system_organization": "General Services Administration (GSA.GOV)" })),
+        ("171.64.0.0/24", json!({ "autonomous_system_number": 64497, "autonomous_system_organization": "Example State University" })),
+        ("185.1.0.0/24", json!({ "autonomous_system_number": 64498, "autonomous_system_organization": "Reduced Latency Networks" })),
+        ("5.161.0.0/24", json!({ "autonomous_system_number": 64499, "autonomous_system_organization": "Example Hosting" })),
+        ("1.2.8.0/24", json!({ "autonomous_system_number": 4134, "autonomous_system_organization": "CHINANET-BACKBONE" })),
+        ("1.2.4.0/24", json!({ "autonomous_system_number": 4837, "autonomous_system_organization": "CHINA UNICOM China169 Backbone" })),
+        // IPv6 的 ASN 记录，用于 as.route
+        ("2a03:2880::/29", json!({ "autonomous_system_number": 32934, "autonomous_system_organization": "FACEBOOK" })),
+        // 只有组织名称、只有号码和两者都没有的记录
+        ("45.33.1.0/24", json!({ "autonomous_system_organization": "Example Org Without Number" })),
+        ("45.33.2.0/24", json!({ "autonomous_system_number": 64501 })),
+        ("45.33.3.0/24", json!({})),
+    ]);
+
+    write_mmdb(dir, "GeoCN.mmdb", "GeoCN", &[
+        ("114.114.114.0/24", json!({
+            "province": "江苏省",
+            "city": "南京市",
+            "districts": "玄武区",
+            "isp": "中国电信",
+            "net": "",
+        })),
+        ("1.2.8.0/24", json!({
+            "province": "浙江省",
+            "city": "杭州市",
+            "districts": "西湖区",
+            "isp": "中国联通",
+            "net": "",
+        })),
+        ("1.2.10.0/24", json!({
+            "province": "江苏省",
+            "city": "苏州市",
+            "districts": "",
+            "isp": "中国电信",
+            "net": "",
+        })),
+        ("1.2.5.0/24", json!({
+            "province": "重庆市",
+            "city": "重庆市",
+            "districts": "渝中区",
+            "isp": "中国联通",
+            "net": "",
+        })),
+    ]);
+
+    std::fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/asn_info.json"),
+        dir.join("asn_info.json"),
+    ).expect("copy asn_info.json");
+    std::fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/overrides.json"),
+        dir.join("overrides.json"),
+    ).expect("copy overrides.json");
+
+    let delegated = std::fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/delegated-sample.txt"))
+        .expect("read delegated-sample.txt");
+    let (allocations, _) = parse_delegated(&delegated);
+    std::fs::write(dir.join(RIR_FILE), encode_allocations(allocations, RIR_BUILD_EPOCH)).expect("write rir.bin");
+}
+
+/// 生成夹具数据库并加载到全局读取器，多次调用只执行一次
+pub fn setup() {
+    setup_with(|config| config);
+}
+
+/// 同 setup，但先用 customize 调整夹具配置；同一个测试程序中只有第一次调用生效。
+/// DB_PROFILE=lite 时使用只有 GeoLite2-Country、没有 City 的单独目录
+pub fn setup_with(customize: fn(Config) -> Config) {
+    SETUP.call_once(|| {
+        let mut config = customize(Config {
+            data_dir: fixtures_dir(),
+            admin_token: Some(ADMIN_TOKEN.to_string()),
+            ..Config::default()
+        });
+        if config.db_profile == DbProfile::Lite {
+            config.data_dir = fixtures_dir().with_file_name("fixtures-lite");
+        }
+        let dir = config.data_dir.clone();
+        build_fixtures(&dir, config.db_profile);
+        Config::init(config);
+        load_databases_from(&dir).expect("load fixture databases");
+        load_overrides(&dir).expect("load fixture overrides.json");
+        init_asn_data(&DatabaseManager::new(dir)).expect("load fixture asn_info.json");
+    });
+}
+
+/// 响应状态码和解析后的JSON（非JSON响应体为 Null）
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: axum::http::HeaderMap,
+    pub body: Value,
+    /// 原始响应体
+    pub bytes: Vec<u8>,
+}
+
+impl TestResponse {
+    pub fn text(&self) -> &str {
+        std::str::from_utf8(&self.bytes).expect("utf-8 body")
+    }
+}
+
+/// 通过 oneshot 把请求交给完整的路由，附带对端地址
+pub async fn send(request: Request<Body>) -> TestResponse {
+    send_from(request, PEER.parse().unwrap()).await
+}
+
+/// 同 send，但使用指定的对端地址
+pub async fn send_from(request: Request<Body>, peer: SocketAddr) -> TestResponse {
+    setup();
+    send_to(create_router(AppState::new()), request, peer).await
+}
+
+/// 把请求交给指定的路由，例如管理端口的路由
+pub async fn send_to(router: Router, mut request: Request<Body>, peer: SocketAddr) -> TestResponse {
+    setup();
+    request.extensions_mut().insert(ConnectInfo(peer));
+
+    let response = router
+        .oneshot(request)
+        .await
+        .expect("router is infallible");
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("read response body");
+    let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+    TestResponse { status, headers, body, bytes: bytes.to_vec() }
+}
+
+pub async fn get(uri: &str) -> TestResponse {
+    send(Request::get(uri).body(Body::empty()).unwrap()).await
+}
+
+pub async fn get_admin(uri: &str) -> TestResponse {
+    send(
+        Request::get(uri)
+            .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+            .body(Body::empty())
+            .unwrap(),
+    ).await
+}
+
+pub async fn post_admin(uri: &str) -> TestResponse {
+    send(
+        Request::post(uri)
+            .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+            .body(Body::empty())
+            .unwrap(),
+    ).await
+}
+
+pub async fn delete_admin(uri: &str) -> TestResponse {
+    send(
+        Request::delete(uri)
+            .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+            .body(Body::empty())
+            .unwrap(),
+    ).await
+}
+
+pub async fn post_json(uri: &str, body: &Value) -> TestResponse {
+    send(
+        Request::post(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap(),
+    ).await
+}
+
+/// 断言统一的错误信封 `{code, error, message}`
+pub fn assert_error(response: &TestResponse, status: StatusCode, error: &str) {
+    assert_eq!(response.status, status, "unexpected status, body: {}", response.body);
+    assert_eq!(response.body["code"], status.as_u16());
+    assert_eq!(response.body["error"], error);
+    assert!(response.body["message"].is_string(), "missing message: {}", response.body);
+}
+
+/// 与 tests/snapshots/{dir}/{name}.json 逐字段比较，设置 UPDATE_SNAPSHOTS 时改为重新生成快照
+pub fn assert_snapshot(dir: &str, name: &str, actual: &Value) {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(dir);
+    let path = dir.join(format!("{}.json", name));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        let text = serde_json::to_string_pretty(actual).unwrap() + "\n";
+        std::fs::create_dir_all(&dir).expect("create snapshot dir");
+        std::fs::write(&path, text).expect("write snapshot");
+        return;
+    }
+    let expected: Value = std::fs::read(&path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_else(|| panic!("missing snapshot {:?}", path));
+    assert_eq!(
+        actual, &expected,
+        "response for {} changed:\n{}",
+        name,
+        serde_json::to_string_pretty(actual).unwrap(),
+    );
+}
+
+/// 收集日志输出的 MakeWriter
+#[derive(Clone, Default)]
+pub struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Captured {
+    pub fn output(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).expect("utf-8 log output")
+    }
+}
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 在当前线程安装 DEBUG 级别、输出到 Captured 的订阅者，guard 释放前一直生效
+pub fn capture_logs() -> (Captured, DefaultGuard) {
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    (captured, tracing::subscriber::set_default(subscriber))
+}
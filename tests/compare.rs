@@ -0,0 +1,53 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{assert_error, get, get_admin, send};
+
+#[tokio::test]
+async fn compare_requires_admin_credentials() {
+    assert_error(&get("/114.114.114.114?compare=1").await, StatusCode::UNAUTHORIZED, "UNAUTHORIZED");
+    let request = Request::get("/api?host=114.114.114.114&compare=1")
+        .header("x-admin-token", "wrong")
+        .body(Body::empty())
+        .unwrap();
+    assert_error(&send(request).await, StatusCode::FORBIDDEN, "ADMIN_FORBIDDEN");
+    // 不带 compare 的查询不受影响
+    let response = get("/114.114.114.114?compare=0").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.get("comparison").is_none());
+}
+
+#[tokio::test]
+async fn both_answers_are_returned_for_geocn_records() {
+    let response = get_admin("/v2/114.114.114.114?compare=1").await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let comparison = &response.body["comparison"];
+    assert_eq!(comparison["geocn"]["regions"], serde_json::json!(["江苏省", "南京市", "玄武区"]));
+    assert_eq!(comparison["geocn"]["region_codes"][0], "CN-JS");
+    assert!(comparison["geocn"].get("location").is_none());
+    assert_eq!(comparison["geolite2"]["region_codes"][0], "CN-JS");
+    assert_eq!(comparison["geolite2"]["city"], "南京");
+    assert_eq!(comparison["geolite2"]["location"]["latitude"], 32.0617);
+    assert_eq!(comparison["divergent"], false);
+    // 主结果仍以 GeoCN 为准
+    assert_eq!(response.body["regions"], comparison["geocn"]["regions"]);
+
+    // 没有 GeoCN 记录的地址不输出对比
+    let response = get_admin("/8.8.8.8?compare=1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.get("comparison").is_none());
+}
+
+#[tokio::test]
+async fn province_disagreement_is_flagged_and_counted() {
+    let response = get_admin("/1.2.10.1?compare=1").await;
+    let comparison = &response.body["comparison"];
+    assert_eq!(comparison["divergent"], true, "{}", response.body);
+    assert_eq!(comparison["geocn"]["region_codes"][0], "CN-JS");
+    assert_eq!(comparison["geolite2"]["region_codes"][0], "CN-SH");
+    assert_eq!(response.body["regions"][0], "江苏省");
+
+    let metrics = String::from_utf8(get("/metrics").await.bytes).unwrap();
+    assert!(metrics.contains("ipgeo_geocn_comparisons_total{result=\"divergent\"}"), "{}", metrics);
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use std::io::Read;
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use common::{send, setup_with, TestResponse};
+use ipgeo::config::Config;
+use serde_json::Value;
+
+// 查询结果约 400 字节、错误信封约 120 字节，/healthz 不到 100 字节
+fn small_threshold(config: Config) -> Config {
+    Config { compression_min_size: 100, ..config }
+}
+
+async fn get_encoded(uri: &str, accept_encoding: &str) -> TestResponse {
+    setup_with(small_threshold);
+    send(Request::get(uri).header(header::ACCEPT_ENCODING, accept_encoding).body(Body::empty()).unwrap()).await
+}
+
+fn decode(response: &TestResponse) -> Value {
+    let mut text = String::new();
+    match response.headers[header::CONTENT_ENCODING].to_str().unwrap() {
+        "gzip" => flate2::read::GzDecoder::new(response.bytes.as_slice()).read_to_string(&mut text),
+        "br" => brotli_decompressor::Decompressor::new(response.bytes.as_slice(), 4096).read_to_string(&mut text),
+        other => panic!("unexpected encoding {}", other),
+    }.expect("decompress body");
+    serde_json::from_str(&text).expect("JSON body")
+}
+
+#[tokio::test]
+async fn gzip_and_brotli_are_negotiated() {
+    let plain = get_encoded("/8.8.8.8", "identity").await;
+    assert!(plain.headers.get(header::CONTENT_ENCODING).is_none());
+
+    for encoding in ["gzip", "br"] {
+        let response = get_encoded("/8.8.8.8", encoding).await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.headers[header::CONTENT_ENCODING], encoding);
+        assert_eq!(response.headers[header::CONTENT_TYPE], "application/json; charset=utf-8");
+        assert!(response.headers[header::VARY].to_str().unwrap().contains("accept-encoding"));
+        assert_eq!(decode(&response)["ip"], plain.body["ip"]);
+        assert_eq!(decode(&response)["as"], plain.body["as"]);
+    }
+
+    // 两者都接受时按 q 值选择
+    let response = get_encoded("/8.8.8.8", "gzip;q=0.5, br").await;
+    assert_eq!(response.headers[header::CONTENT_ENCODING], "br");
+}
+
+#[tokio::test]
+async fn small_responses_are_not_compressed() {
+    let response = get_encoded("/healthz", "gzip, br").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.bytes.len() < 100);
+    assert!(response.headers.get(header::CONTENT_ENCODING).is_none());
+    assert!(response.body.is_object());
+}
+
+#[tokio::test]
+async fn error_envelopes_are_compressed() {
+    for encoding in ["gzip", "br"] {
+        let response = get_encoded("/api/192.0.2.1", encoding).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers[header::CONTENT_ENCODING], encoding);
+        assert_eq!(response.headers[header::CONTENT_TYPE], "application/json; charset=utf-8");
+        let body = decode(&response);
+        assert_eq!(body["error"], "INVALID_IP");
+        assert_eq!(body["code"], 400);
+    }
+}
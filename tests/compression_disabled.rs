@@ -0,0 +1,21 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use common::{send, setup_with};
+use ipgeo::config::Config;
+
+fn disabled(config: Config) -> Config {
+    Config { compression: false, compression_min_size: 0, ..config }
+}
+
+#[tokio::test]
+async fn compression_false_sends_identity_bodies() {
+    setup_with(disabled);
+    for uri in ["/8.8.8.8", "/api/192.0.2.1"] {
+        let response = send(Request::get(uri).header(header::ACCEPT_ENCODING, "gzip, br").body(Body::empty()).unwrap()).await;
+        assert_ne!(response.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(response.headers.get(header::CONTENT_ENCODING).is_none(), "{}", uri);
+        assert!(response.body.is_object(), "{}", uri);
+    }
+}
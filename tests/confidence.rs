@@ -0,0 +1,38 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::get;
+use serde_json::json;
+
+#[tokio::test]
+async fn commercial_confidence_is_reported() {
+    let response = get("/1.2.7.7").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["country"]["code"], "DE");
+    assert_eq!(response.body["country"]["confidence"], 99);
+    assert_eq!(response.body["city"]["confidence"], 60);
+    assert_eq!(response.body["postal"], "10115");
+    assert_eq!(response.body["postal_confidence"], 20);
+    assert_eq!(response.body["region_confidence"], 80);
+}
+
+#[tokio::test]
+async fn geolite_records_have_no_confidence() {
+    let response = get("/8.8.8.8").await;
+    assert!(response.body["country"].get("confidence").is_none());
+    assert!(response.body["registered_country"].get("confidence").is_none());
+    for field in ["postal_confidence", "region_confidence"] {
+        assert!(response.body.get(field).is_none(), "{}", field);
+    }
+}
+
+#[tokio::test]
+async fn geocn_regions_drop_geoip2_confidence() {
+    // 地区和城市名换成 GeoCN 的结果后，GeoIP2 的可信度不再适用；国家仍来自 GeoIP2
+    let response = get("/1.2.8.8").await;
+    assert_eq!(response.body["regions"], json!(["浙江省", "杭州市", "西湖区"]));
+    assert_eq!(response.body["city"]["name"], "杭州市");
+    assert!(response.body["city"].get("confidence").is_none());
+    assert!(response.body.get("region_confidence").is_none());
+    assert_eq!(response.body["country"]["confidence"], 95);
+}
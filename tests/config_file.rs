@@ -0,0 +1,127 @@
+//! config.toml 的读取、环境变量覆盖、校验和 --check-config 的输出
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use ipgeo::config::{Config, DbProfile};
+
+// 环境变量是进程级的，读取配置和修改环境变量的测试依次执行
+static ENV: Mutex<()> = Mutex::new(());
+
+fn write_config(name: &str, content: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("config-file");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+fn load(name: &str, content: &str) -> Result<Config, String> {
+    let path = write_config(name, content);
+    let _guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    Config::load(Some(&path)).map_err(|e| e.to_string())
+}
+
+#[test]
+fn file_values_are_applied() {
+    let config = load("values.toml", r#"
+[server]
+bind = ["127.0.0.1:9000", "[::1]:9000"]
+request_timeout_ms = 3000
+
+[database]
+data_dir = "/srv/ipgeo"
+db_profile = "lite"
+
+[lookup]
+dns_servers = ["1.1.1.1"]
+asn_omit_unknown = true
+
+[access]
+admin_token = "s3cret"
+client_deny = ["10.0.0.0/8"]
+"#).unwrap();
+    let bind: Vec<SocketAddr> = vec!["127.0.0.1:9000".parse().unwrap(), "[::1]:9000".parse().unwrap()];
+    assert_eq!(config.bind, bind);
+    assert_eq!(config.request_timeout, Duration::from_millis(3000));
+    assert_eq!(config.data_dir, PathBuf::from("/srv/ipgeo"));
+    assert_eq!(config.db_profile, DbProfile::Lite);
+    assert_eq!(config.dns_servers, vec!["1.1.1.1".parse::<std::net::IpAddr>().unwrap()]);
+    assert!(config.asn_omit_unknown);
+    assert_eq!(config.admin_token.as_deref(), Some("s3cret"));
+    assert!(!config.client_acl.permits("10.1.2.3".parse().unwrap()));
+    // 未出现的设置保持默认值
+    assert_eq!(config.batch_max_size, Config::default().batch_max_size);
+}
+
+#[test]
+fn environment_overrides_file() {
+    let path = write_config("override.toml", "[server]\nslow_request_ms = 900\n\n[lookup]\nstream_max_rows = 10\n");
+    let _guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    std::env::set_var("SLOW_REQUEST_MS", "250");
+    let config = Config::load(Some(&path));
+    std::env::remove_var("SLOW_REQUEST_MS");
+    let config = config.unwrap();
+    assert_eq!(config.slow_request, Duration::from_millis(250));
+    assert_eq!(config.stream_max_rows, 10);
+}
+
+#[test]
+fn invalid_values_are_reported_with_their_location() {
+    let error = load("invalid.toml", "[server]\n\nmax_in_flight = \"many\"\nbind = [\"localhost:80\"]\n").unwrap_err();
+    assert!(error.contains("invalid.toml:3: [server] max_in_flight: invalid value 'many'"), "{}", error);
+    assert!(error.contains("invalid.toml:4: [server] bind: invalid value 'localhost:80'"), "{}", error);
+
+    // 环境变量中的无效值同样报错，只有 from_env 仍回退到默认值
+    let path = write_config("empty.toml", "");
+    let _guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    std::env::set_var("TCP_NODELAY", "maybe");
+    let error = Config::load(Some(&path)).unwrap_err().to_string();
+    let fallback = Config::from_env();
+    std::env::remove_var("TCP_NODELAY");
+    assert!(error.contains("TCP_NODELAY: invalid value 'maybe'"), "{}", error);
+    assert!(!fallback.tcp_nodelay);
+}
+
+#[test]
+fn unknown_and_misplaced_keys_are_rejected() {
+    let error = load("unknown.toml", "[server]\nmax_inflight = 10\ndb_profile = \"lite\"\n\n[storage]\nsize = 1\n").unwrap_err();
+    assert!(error.contains("unknown.toml:2: [server] max_inflight: unknown key"), "{}", error);
+    assert!(error.contains("unknown.toml:3: [server] db_profile: belongs in [database]"), "{}", error);
+    assert!(error.contains("unknown.toml:5: unknown section [storage]"), "{}", error);
+}
+
+#[test]
+fn syntax_errors_include_the_line() {
+    let error = load("syntax.toml", "[server]\nbind = [\"127.0.0.1:80\"\n").unwrap_err();
+    assert!(error.contains("syntax.toml"), "{}", error);
+    assert!(error.contains("line 2"), "{}", error);
+}
+
+#[test]
+fn validation_runs_on_the_merged_config() {
+    let error = load("zero.toml", "[server]\nrequest_timeout_ms = 0\n").unwrap_err();
+    assert!(error.contains("REQUEST_TIMEOUT_MS must be greater than 0"), "{}", error);
+    let error = load("keepalive.toml", "[server]\nrequest_timeout_ms = 5000\nkeep_alive_timeout_secs = 3\n").unwrap_err();
+    assert!(error.contains("KEEP_ALIVE_TIMEOUT_SECS"), "{}", error);
+    let error = load("tls.toml", "[server]\ntls_cert_path = \"cert.pem\"\n").unwrap_err();
+    assert!(error.contains("TLS_KEY_PATH is missing"), "{}", error);
+    let error = load("acl.toml", "[access]\nadmin_allow = [\"10.0.0.0/33\"]\n").unwrap_err();
+    assert!(error.contains("10.0.0.0/33"), "{}", error);
+}
+
+#[test]
+fn effective_config_redacts_secrets_and_round_trips() {
+    let config = load("effective.toml", "[access]\nadmin_token = \"s3cret\"\n\n[database]\ndb_update_at = \"03:30\"\n").unwrap();
+    let printed = config.to_toml();
+    assert!(printed.contains("admin_token = \"<redacted>\""), "{}", printed);
+    assert!(!printed.contains("s3cret"));
+    assert!(printed.contains("db_update_at = \"03:30\""), "{}", printed);
+    assert!(printed.contains("privacy_mode = \"full\""), "{}", printed);
+    assert!(printed.starts_with("[server]\n"), "{}", printed);
+
+    // 输出本身是有效的配置文件
+    let reloaded = load("printed.toml", &printed).unwrap();
+    assert_eq!(reloaded.to_toml(), printed);
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+use axum::body::Body;
+use axum::http::{Request, StatusCode, Version};
+use hyper::client::conn::{http1, http2};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use ipgeo::api::{create_router, AppState};
+use ipgeo::config::Config;
+use ipgeo::server::tune;
+use tokio::net::TcpStream;
+
+// 在随机端口上用调整后的参数启动完整服务
+async fn start(config: Config) -> SocketAddr {
+    common::setup();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = create_router(AppState::new());
+    tokio::spawn(async move {
+        tune(axum_server::from_tcp(listener), &config, false)
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .await
+    });
+    addr
+}
+
+fn lookup(uri: &str) -> Request<Body> {
+    Request::get(uri).header("host", "localhost").body(Body::empty()).unwrap()
+}
+
+async fn connect_h1(addr: SocketAddr) -> (http1::SendRequest<Body>, tokio::task::JoinHandle<()>) {
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let (sender, conn) = http1::handshake(TokioIo::new(stream)).await.unwrap();
+    let conn = tokio::spawn(async move {
+        let _ = conn.await;
+    });
+    (sender, conn)
+}
+
+#[tokio::test]
+async fn http2_requests_are_multiplexed() {
+    let addr = start(Config { http2: true, ..Config::default() }).await;
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let (sender, conn) = http2::handshake(TokioExecutor::new(), TokioIo::new(stream)).await.unwrap();
+    tokio::spawn(conn);
+
+    let requests = ["8.8.8.8", "1.1.1.1", "114.114.114.114"].iter().cycle().take(12).map(|ip| {
+        let mut sender = sender.clone();
+        async move { sender.send_request(lookup(&format!("/api/{}", ip))).await.unwrap() }
+    });
+    for response in futures::future::join_all(requests).await {
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.version(), Version::HTTP_2);
+    }
+}
+
+#[tokio::test]
+async fn http2_is_disabled_by_default() {
+    assert!(!Config::default().http2);
+    let addr = start(Config::default()).await;
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let (mut sender, conn) = http2::handshake(TokioExecutor::new(), TokioIo::new(stream)).await.unwrap();
+    tokio::spawn(conn);
+    assert!(sender.send_request(lookup("/api/8.8.8.8")).await.is_err());
+
+    let (mut sender, _conn) = connect_h1(addr).await;
+    let response = sender.send_request(lookup("/api/8.8.8.8")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn connections_per_ip_are_limited() {
+    let addr = start(Config { max_connections_per_ip: 1, ..Config::default() }).await;
+    let (mut first, first_conn) = connect_h1(addr).await;
+    assert_eq!(first.send_request(lookup("/api/8.8.8.8")).await.unwrap().status(), StatusCode::OK);
+
+    let (mut second, _conn) = connect_h1(addr).await;
+    assert!(second.send_request(lookup("/api/8.8.8.8")).await.is_err());
+
+    // 关闭第一个连接后名额归还
+    drop(first);
+    first_conn.await.unwrap();
+    let mut attempts = 0;
+    loop {
+        let (mut third, _conn) = connect_h1(addr).await;
+        match third.send_request(lookup("/api/8.8.8.8")).await {
+            Ok(response) => {
+                assert_eq!(response.status(), StatusCode::OK);
+                break;
+            }
+            Err(_) if attempts < 20 => {
+                attempts += 1;
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            Err(e) => panic!("slot was not released: {}", e),
+        }
+    }
+}
+
+#[tokio::test]
+async fn idle_keep_alive_connections_are_closed() {
+    let addr = start(Config { keep_alive_timeout: Duration::from_millis(200), ..Config::default() }).await;
+    let (mut sender, conn) = connect_h1(addr).await;
+    assert_eq!(sender.send_request(lookup("/api/8.8.8.8")).await.unwrap().status(), StatusCode::OK);
+
+    tokio::time::timeout(Duration::from_secs(5), conn).await
+        .expect("idle connection should be closed by the server")
+        .unwrap();
+}
@@ -0,0 +1,86 @@
+//! v1 响应契约：与 tests/snapshots/v1 下的快照逐字段比较。
+//! 有意修改 v1 输出时需要同时更新快照，可用 `UPDATE_SNAPSHOTS=1 cargo test --test contract` 重新生成。
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{assert_snapshot, get, post_json};
+use serde_json::json;
+
+#[tokio::test]
+async fn v1_geocn_lookup() {
+    let response = get("/v1/api/114.114.114.114?detail=full").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_snapshot("v1", "lookup_114.114.114.114", &response.body);
+}
+
+#[tokio::test]
+async fn v1_query_lookup() {
+    let response = get("/v1/api?host=8.8.8.8").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_snapshot("v1", "lookup_8.8.8.8", &response.body);
+}
+
+#[tokio::test]
+async fn v1_private_lookup() {
+    let response = get("/v1/10.1.2.3").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_snapshot("v1", "lookup_private", &response.body);
+}
+
+#[tokio::test]
+async fn v1_batch() {
+    let response = post_json("/v1/api/batch", &json!(["1.0.0.1", "0.0.0.0"])).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_snapshot("v1", "batch", &response.body);
+}
+
+#[tokio::test]
+async fn v1_error_envelope() {
+    let response = get("/v1/0.0.0.0?lang=en").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    // 请求ID每次都不同，核对与响应头一致后从快照中去掉
+    let mut body = response.body.clone();
+    let request_id = body.as_object_mut().unwrap().remove("request_id").unwrap();
+    assert_eq!(request_id, response.headers["x-request-id"].to_str().unwrap());
+    assert_snapshot("v1", "error_invalid_ip", &body);
+}
+
+#[tokio::test]
+async fn v1_root_uses_peer_address() {
+    let response = get("/v1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["ip"], "8.8.8.8");
+    assert!(response.headers.get("deprecation").is_none());
+}
+
+#[tokio::test]
+async fn unversioned_routes_alias_latest_with_deprecation() {
+    for (path, successor) in [
+        ("/api/114.114.114.114?detail=full", "</v2/api/114.114.114.114>; rel=\"successor-version\""),
+        ("/", "</v2>; rel=\"successor-version\""),
+    ] {
+        let unversioned = get(path).await;
+        assert_eq!(unversioned.headers["deprecation"], "true");
+        assert_eq!(unversioned.headers["link"], successor);
+        let versioned = get(&format!("/v2{}", path.trim_end_matches('/'))).await;
+        assert_eq!(unversioned.body, versioned.body);
+    }
+
+    let response = get("/metrics").await;
+    assert!(response.headers.get("deprecation").is_none());
+}
+
+#[tokio::test]
+async fn v1_omits_fields_added_later() {
+    let latest = get("/v2/api/8.8.8.8").await;
+    assert_eq!(latest.body["as"]["route"], "8.8.8.0/24");
+    assert_eq!(latest.body["category"], "hosting");
+
+    let frozen = get("/v1/api/8.8.8.8?echo=1&sources=1&asn_format=string").await;
+    assert_eq!(frozen.status, StatusCode::OK);
+    assert_eq!(frozen.body["as"], json!({ "number": 15169, "name": "谷歌", "info": "谷歌" }));
+    for field in ["category", "is_datacenter", "query", "timestamp", "sources"] {
+        assert!(frozen.body.get(field).is_none(), "{} in {}", field, frozen.body);
+    }
+}
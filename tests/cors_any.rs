@@ -0,0 +1,47 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use common::{send, setup_with, TestResponse};
+use ipgeo::config::Config;
+
+fn any_origin(config: Config) -> Config {
+    Config { cors_allow_origins: vec!["*".to_string()], ..config }
+}
+
+async fn preflight(uri: &str, origin: &str) -> TestResponse {
+    setup_with(any_origin);
+    send(Request::builder()
+        .method(Method::OPTIONS)
+        .uri(uri)
+        .header(header::ORIGIN, origin)
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+        .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "x-request-id")
+        .body(Body::empty())
+        .unwrap()).await
+}
+
+#[tokio::test]
+async fn preflight_allows_any_origin() {
+    for uri in ["/8.8.8.8", "/api"] {
+        let response = preflight(uri, "https://example.com").await;
+        assert_eq!(response.status, StatusCode::OK, "{}", uri);
+        assert_eq!(response.headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*", "{}", uri);
+        assert_eq!(response.headers[header::ACCESS_CONTROL_ALLOW_METHODS], "GET,POST", "{}", uri);
+        let allowed = response.headers[header::ACCESS_CONTROL_ALLOW_HEADERS].to_str().unwrap();
+        assert!(allowed.contains("x-request-id"), "{}", allowed);
+        assert_eq!(response.headers[header::ACCESS_CONTROL_MAX_AGE], "3600", "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn simple_requests_expose_the_request_id() {
+    setup_with(any_origin);
+    let response = send(Request::get("/8.8.8.8")
+        .header(header::ORIGIN, "https://example.com")
+        .body(Body::empty())
+        .unwrap()).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    assert_eq!(response.headers[header::ACCESS_CONTROL_EXPOSE_HEADERS], "x-request-id");
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use common::{send, setup_with, TestResponse};
+use ipgeo::config::Config;
+
+const ALLOWED: &str = "https://app.example.com";
+
+fn listed_origins(config: Config) -> Config {
+    Config {
+        cors_allow_origins: vec![ALLOWED.to_string(), "https://admin.example.com".to_string()],
+        ..config
+    }
+}
+
+async fn preflight(uri: &str, origin: &str) -> TestResponse {
+    setup_with(listed_origins);
+    send(Request::builder()
+        .method(Method::OPTIONS)
+        .uri(uri)
+        .header(header::ORIGIN, origin)
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+        .body(Body::empty())
+        .unwrap()).await
+}
+
+#[tokio::test]
+async fn preflight_echoes_listed_origins() {
+    for uri in ["/8.8.8.8", "/api"] {
+        let response = preflight(uri, ALLOWED).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", uri);
+        assert_eq!(response.headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], ALLOWED, "{}", uri);
+        assert_eq!(response.headers[header::ACCESS_CONTROL_ALLOW_METHODS], "GET,POST", "{}", uri);
+        // 按来源返回不同的头部，缓存需要区分 Origin
+        let vary = response.headers.get_all(header::VARY).iter()
+            .map(|v| v.to_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        assert!(vary.contains("origin"), "{}", vary);
+    }
+}
+
+#[tokio::test]
+async fn other_origins_get_no_allow_origin() {
+    for uri in ["/8.8.8.8", "/api"] {
+        let response = preflight(uri, "https://evil.example.net").await;
+        assert!(response.headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none(), "{}", uri);
+    }
+
+    let response = send(Request::get("/8.8.8.8")
+        .header(header::ORIGIN, "https://evil.example.net")
+        .body(Body::empty())
+        .unwrap()).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+}
+
+#[tokio::test]
+async fn simple_requests_from_listed_origins() {
+    setup_with(listed_origins);
+    let response = send(Request::get("/api?host=8.8.8.8")
+        .header(header::ORIGIN, ALLOWED)
+        .body(Body::empty())
+        .unwrap()).await;
+    assert_eq!(response.headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], ALLOWED);
+    assert_eq!(response.headers[header::ACCESS_CONTROL_EXPOSE_HEADERS], "x-request-id");
+}
@@ -0,0 +1,44 @@
+//! 数据目录的选择：--data-dir、MMDB_PATH、IPGEO_DATA_DIR、默认的 data
+
+use std::path::{Path, PathBuf};
+use ipgeo::config::{data_dir_from_env, known_data_dirs, Config};
+use ipgeo::geo::misplaced_data_dirs;
+
+// 环境变量是进程级的，所有情形放在同一个测试里顺序执行
+#[test]
+fn data_dir_resolution_and_misplaced_databases() {
+    std::env::remove_var("MMDB_PATH");
+    std::env::remove_var("IPGEO_DATA_DIR");
+    assert_eq!(data_dir_from_env(), PathBuf::from("data"));
+    assert_eq!(known_data_dirs()[0], PathBuf::from("data"));
+
+    std::env::set_var("IPGEO_DATA_DIR", "/srv/ipgeo");
+    assert_eq!(Config::from_env().data_dir, PathBuf::from("/srv/ipgeo"));
+
+    // 兼容旧版本的 MMDB_PATH 优先
+    std::env::set_var("MMDB_PATH", "/opt/mmdb");
+    assert_eq!(Config::from_env().data_dir, PathBuf::from("/opt/mmdb"));
+    let known = known_data_dirs();
+    assert_eq!(&known[..3], &[PathBuf::from("/opt/mmdb"), PathBuf::from("/srv/ipgeo"), PathBuf::from("data")]);
+
+    // 空值视为未设置
+    std::env::set_var("MMDB_PATH", "");
+    assert_eq!(data_dir_from_env(), PathBuf::from("/srv/ipgeo"));
+    std::env::remove_var("MMDB_PATH");
+    std::env::remove_var("IPGEO_DATA_DIR");
+
+    let root = Path::new(env!("CARGO_TARGET_TMPDIR")).join("data-dir-check");
+    let _ = std::fs::remove_dir_all(&root);
+    let (selected, other, empty) = (root.join("selected"), root.join("other"), root.join("empty"));
+    for dir in [&selected, &other, &empty] {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+    std::fs::write(selected.join("GeoLite2-City.mmdb"), b"").unwrap();
+    std::fs::write(other.join("GeoLite2-ASN.mmdb"), b"").unwrap();
+    std::fs::write(empty.join("notes.txt"), b"").unwrap();
+
+    // 选中的目录即使换一种写法也不算放错位置，没有数据库文件的目录不提示
+    let known = vec![selected.join("."), other.clone(), empty, root.join("missing")];
+    assert_eq!(misplaced_data_dirs(&selected, &known), vec![other.clone()]);
+    assert!(misplaced_data_dirs(&other, std::slice::from_ref(&other)).is_empty());
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::get;
+
+#[tokio::test]
+async fn hosting_type_marks_datacenter() {
+    let response = get("/8.8.8.8?sources=1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["is_datacenter"], true);
+    assert_eq!(response.body["sources"]["is_datacenter"], "asn_info.json type");
+}
+
+#[tokio::test]
+async fn hosting_asns_list_marks_datacenter() {
+    let response = get("/5.161.0.1?sources=1").await;
+    assert!(response.body.get("type").is_none());
+    assert_eq!(response.body["is_datacenter"], true);
+    assert_eq!(response.body["sources"]["is_datacenter"], "asn_info.json hosting_asns");
+}
+
+#[tokio::test]
+async fn geoip2_traits_mark_datacenter_and_anonymous() {
+    let response = get("/1.2.9.9?sources=1").await;
+    assert_eq!(response.body["is_datacenter"], true);
+    assert_eq!(response.body["is_anonymous"], true);
+    let reason = response.body["sources"]["is_datacenter"].as_str().unwrap();
+    assert!(reason.starts_with("GeoLite2-City ") && reason.ends_with(" is_hosting_provider"), "{}", reason);
+}
+
+#[tokio::test]
+async fn flags_are_omitted_otherwise() {
+    let response = get("/114.114.114.114?sources=1").await;
+    for field in ["is_datacenter", "is_anonymous"] {
+        assert!(response.body.get(field).is_none(), "{}", field);
+        assert!(response.body["sources"].get(field).is_none(), "{}", field);
+    }
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{assert_error, get, post_json};
+use serde_json::json;
+
+#[tokio::test]
+async fn minimal_skips_regions_and_geocn() {
+    let response = get("/114.114.114.114?detail=minimal").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let body = &response.body;
+    assert_eq!(body["country"]["code"], "CN");
+    assert_eq!(body["as"]["number"], 21859);
+    for field in ["regions", "regions_short", "city", "location", "continent", "isp", "accuracy_radius"] {
+        assert!(body.get(field).is_none(), "{} should not be computed: {}", field, body);
+    }
+}
+
+#[tokio::test]
+async fn standard_is_the_default() {
+    let default = get("/114.114.114.114").await;
+    let standard = get("/114.114.114.114?detail=standard").await;
+    assert_eq!(default.body, standard.body);
+    assert_eq!(default.body["regions"], json!(["江苏省", "南京市", "玄武区"]));
+    assert!(default.body["location"].get("time_zone").is_none());
+    assert!(default.body.get("rdns").is_none());
+}
+
+#[tokio::test]
+async fn full_adds_time_zone() {
+    let response = get("/api?host=114.114.114.114&detail=full").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["location"]["time_zone"], "Asia/Shanghai");
+    assert_eq!(response.body["regions"], json!(["江苏省", "南京市", "玄武区"]));
+}
+
+#[tokio::test]
+async fn batch_honours_detail() {
+    let response = post_json("/api/batch?detail=minimal", &json!(["8.8.8.8"])).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body[0]["country"]["code"], "US");
+    assert!(response.body[0].get("location").is_none());
+}
+
+#[tokio::test]
+async fn unknown_detail_is_rejected() {
+    assert_error(&get("/8.8.8.8?detail=everything").await, StatusCode::BAD_REQUEST, "INVALID_PARAMETER");
+}
+
+#[tokio::test]
+async fn metro_code_only_for_us() {
+    let us = get("/8.8.8.8").await;
+    assert_eq!(us.body["location"]["metro_code"], 807);
+    let cn = get("/114.114.114.114").await;
+    assert!(cn.body["location"].get("metro_code").is_none());
+}
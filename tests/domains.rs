@@ -0,0 +1,59 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{assert_error, get};
+use ipgeo::geo::is_valid_domain;
+
+#[test]
+fn single_label_hosts_depend_on_the_flag() {
+    for host in ["localhost", "intranet"] {
+        assert!(is_valid_domain(host, true), "{}", host);
+        assert!(!is_valid_domain(host, false), "{}", host);
+    }
+    // 多标签名称不受开关影响
+    assert!(is_valid_domain("example.com", false));
+    assert!(is_valid_domain("example.com", true));
+}
+
+#[test]
+fn underscores_are_allowed_in_labels() {
+    for host in ["foo_bar.example.com", "_dmarc.example.com", "_sip._tcp.example.com"] {
+        assert!(is_valid_domain(host, false), "{}", host);
+    }
+}
+
+#[test]
+fn labels_cannot_start_or_end_with_a_hyphen() {
+    assert!(is_valid_domain("foo-bar.example.com", false));
+    for host in ["-foo.example.com", "foo-.example.com", "example.-com", "example.com-", "-intranet"] {
+        assert!(!is_valid_domain(host, true), "{}", host);
+    }
+}
+
+#[test]
+fn labels_are_limited_to_63_characters() {
+    let label = "a".repeat(63);
+    assert!(is_valid_domain(&format!("{}.example.com", label), false));
+    assert!(!is_valid_domain(&format!("{}a.example.com", label), false));
+
+    // 整个名称最长 253 个字符
+    let name = [label.as_str(); 4].join(".");
+    assert_eq!(name.len(), 255);
+    assert!(!is_valid_domain(&name, false));
+    assert!(is_valid_domain(&name[2..], false));
+}
+
+#[test]
+fn malformed_names_are_rejected() {
+    for host in ["foo bar.example.com", " example.com", "example.com ", "exa\tmple.com", "", "example..com", ".example.com", "1.2.3", "example.com/path"] {
+        assert!(!is_valid_domain(host, true), "{:?}", host);
+    }
+}
+
+#[tokio::test]
+async fn invalid_names_fail_without_resolving() {
+    for host in ["intranet", "foo%20bar.example.com", "-foo.example.com"] {
+        let response = get(&format!("/api?host={}", host)).await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "RESOLVE_ERROR");
+    }
+}
@@ -0,0 +1,88 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use axum::{extract::State, http::StatusCode, routing::get, Router};
+use ipgeo::geo::{download_with_retry, RetryPolicy};
+
+// 按顺序返回给定状态码，用完后返回 200 和文件内容
+async fn mirror(statuses: Vec<StatusCode>) -> (SocketAddr, Arc<AtomicU32>) {
+    let hits = Arc::new(AtomicU32::new(0));
+    let state = (Arc::new(statuses), hits.clone());
+    let app = Router::new()
+        .route("/db", get(|State((statuses, hits)): State<(Arc<Vec<StatusCode>>, Arc<AtomicU32>)>| async move {
+            let hit = hits.fetch_add(1, Ordering::SeqCst) as usize;
+            match statuses.get(hit) {
+                Some(status) => (*status, ""),
+                None => (StatusCode::OK, "mmdb"),
+            }
+        }))
+        .with_state(state);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("bind");
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    (addr, hits)
+}
+
+fn fast_policy() -> RetryPolicy {
+    RetryPolicy {
+        attempts: 3,
+        backoff: Duration::from_millis(10),
+        attempt_timeout: Duration::from_secs(5),
+        deadline: Duration::from_secs(10),
+    }
+}
+
+fn target(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("download");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[tokio::test]
+async fn retries_server_errors() {
+    let (addr, hits) = mirror(vec![StatusCode::SERVICE_UNAVAILABLE, StatusCode::BAD_GATEWAY]).await;
+    let path = target("retry.mmdb");
+    download_with_retry(&format!("http://{}/db", addr), &path, &fast_policy()).await.expect("download");
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "mmdb");
+}
+
+#[tokio::test]
+async fn gives_up_after_max_attempts() {
+    let (addr, hits) = mirror(vec![StatusCode::INTERNAL_SERVER_ERROR; 5]).await;
+    let path = target("exhausted.mmdb");
+    let error = download_with_retry(&format!("http://{}/db", addr), &path, &fast_policy()).await.expect_err("fails");
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+    assert!(error.to_string().contains("3 attempt"), "{}", error);
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn does_not_retry_permanent_errors() {
+    let (addr, hits) = mirror(vec![StatusCode::NOT_FOUND]).await;
+    let path = target("missing.mmdb");
+    let error = download_with_retry(&format!("http://{}/db", addr), &path, &fast_policy()).await.expect_err("fails");
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+    assert!(error.to_string().contains("404"), "{}", error);
+
+    let error = download_with_retry("not a url", &path, &fast_policy()).await.expect_err("invalid url");
+    assert!(error.to_string().contains("1 attempt"), "{}", error);
+}
+
+#[tokio::test]
+async fn stops_at_overall_deadline() {
+    let (addr, hits) = mirror(vec![StatusCode::SERVICE_UNAVAILABLE; 5]).await;
+    let policy = RetryPolicy {
+        attempts: 10,
+        backoff: Duration::from_millis(200),
+        deadline: Duration::from_millis(300),
+        ..fast_policy()
+    };
+    let path = target("deadline.mmdb");
+    download_with_retry(&format!("http://{}/db", addr), &path, &policy).await.expect_err("deadline");
+    assert!(hits.load(Ordering::SeqCst) < 3);
+}
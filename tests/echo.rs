@@ -0,0 +1,57 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{assert_error, get, get_admin};
+use ipgeo::utils::sanitize_echo;
+
+fn message_of(response: &common::TestResponse) -> &str {
+    response.body["message"].as_str().expect("message")
+}
+
+#[test]
+fn sanitize_strips_controls_and_encodes_invisible() {
+    assert_eq!(sanitize_echo("1.2.3.4\r\nforged=1"), "1.2.3.4forged=1");
+    assert_eq!(sanitize_echo("\u{1b}[31mred\u{1b}[0m"), "[31mred[0m");
+    assert_eq!(sanitize_echo("a\u{200B}b\u{00A0}c"), "a%E2%80%8Bb%C2%A0c");
+    assert_eq!(sanitize_echo("例子.测试"), "例子.测试");
+}
+
+#[test]
+fn sanitize_truncates_long_input() {
+    let long = "a".repeat(10 * 1024);
+    let echoed = sanitize_echo(&long);
+    assert_eq!(echoed.chars().count(), 65);
+    assert!(echoed.ends_with('…'));
+    assert_eq!(sanitize_echo(&"b".repeat(64)), "b".repeat(64));
+}
+
+#[tokio::test]
+async fn invalid_ip_with_crlf_is_not_reflected() {
+    let response = get_admin("/admin/debug/1.2.3.4%0D%0AX-Injected:%201").await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_IP");
+    let message = message_of(&response);
+    assert!(!message.contains('\r') && !message.contains('\n'), "{:?}", message);
+    assert!(message.contains("1.2.3.4X-Injected: 1"));
+}
+
+#[tokio::test]
+async fn invalid_ip_with_10kb_input_is_truncated() {
+    let response = get_admin(&format!("/admin/debug/{}", "9".repeat(10 * 1024))).await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_IP");
+    let message = message_of(&response);
+    assert!(message.chars().count() < 100, "{} chars", message.chars().count());
+    assert!(message.ends_with('…'));
+}
+
+#[tokio::test]
+async fn not_found_echo_is_sanitized() {
+    let response = get(&format!("/%0D%0A{}.php", "x".repeat(10 * 1024))).await;
+    assert_error(&response, StatusCode::NOT_FOUND, "NOT_FOUND");
+    let message = message_of(&response);
+    assert!(!message.contains('\r') && !message.contains('\n'));
+    assert!(message.chars().count() < 100);
+
+    let response = get(&format!("/a/{}/b", "y".repeat(10 * 1024))).await;
+    assert_error(&response, StatusCode::NOT_FOUND, "NOT_FOUND");
+    assert!(message_of(&response).chars().count() < 100);
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::routing::get;
+use axum::Router;
+use common::setup;
+use ipgeo::api::router_with_state;
+use ipgeo::config::Config;
+use ipgeo::geo::GeoResolver;
+use serde_json::Value;
+use tower::ServiceExt;
+
+// 宿主应用把查询接口挂在 /geo 下，请求不带 ConnectInfo
+fn host_app() -> Router {
+    setup();
+    // 全局配置已由夹具初始化，这里传入的配置被忽略
+    let resolver = Arc::new(GeoResolver::new(Config::default()).expect("open fixture data"));
+    Router::new()
+        .route("/", get(|| async { "host" }))
+        .nest("/geo", router_with_state(resolver))
+}
+
+async fn call(request: Request<Body>) -> (StatusCode, axum::http::HeaderMap, Value) {
+    let response = host_app().oneshot(request).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, headers, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn nested_lookup_routes() {
+    let (status, _, body) = call(Request::get("/geo/1.1.1.1").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["ip"], "1.1.1.1");
+
+    let (status, _, body) = call(Request::get("/geo/v1/api?host=8.8.8.8").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["as"]["number"], 15169);
+
+    let response = host_app().oneshot(Request::get("/").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn caller_comes_from_headers_without_connect_info() {
+    let request = Request::get("/geo").header("x-forwarded-for", "8.8.8.8").body(Body::empty()).unwrap();
+    let (status, _, body) = call(request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["ip"], "8.8.8.8");
+}
+
+#[tokio::test]
+async fn successor_link_keeps_mount_point() {
+    let (_, headers, _) = call(Request::get("/geo/api/1.1.1.1").body(Body::empty()).unwrap()).await;
+    assert_eq!(headers["link"], "</geo/v2/api/1.1.1.1>; rel=\"successor-version\"");
+}
+
+#[tokio::test]
+async fn unknown_paths_use_the_json_fallback() {
+    let (status, _, body) = call(Request::get("/geo/api/batch/missing").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"], "NOT_FOUND");
+}
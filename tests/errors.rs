@@ -0,0 +1,48 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{assert_error, get, post_json, send};
+use serde_json::json;
+
+#[tokio::test]
+async fn invalid_ip() {
+    assert_error(&get("/255.255.255.255").await, StatusCode::BAD_REQUEST, "INVALID_IP");
+}
+
+#[tokio::test]
+async fn unresolvable_host() {
+    assert_error(&get("/-bad-.example").await, StatusCode::BAD_REQUEST, "RESOLVE_ERROR");
+}
+
+#[tokio::test]
+async fn invalid_batch_parameter() {
+    assert_error(&post_json("/api/batch", &json!("8.8.8.8")).await, StatusCode::BAD_REQUEST, "INVALID_PARAMETER");
+}
+
+#[tokio::test]
+async fn unknown_route() {
+    let response = get("/api/8.8.8.8/extra").await;
+    assert_error(&response, StatusCode::NOT_FOUND, "NOT_FOUND");
+    assert!(response.body["message"].as_str().unwrap().contains("/api/8.8.8.8/extra"));
+}
+
+#[tokio::test]
+async fn method_not_allowed() {
+    let response = send(Request::delete("/api").body(Body::empty()).unwrap()).await;
+    assert_error(&response, StatusCode::METHOD_NOT_ALLOWED, "METHOD_NOT_ALLOWED");
+
+    let response = send(Request::get("/api/batch").body(Body::empty()).unwrap()).await;
+    assert_error(&response, StatusCode::METHOD_NOT_ALLOWED, "METHOD_NOT_ALLOWED");
+}
+
+#[tokio::test]
+async fn extractor_rejection_is_json() {
+    // 非UTF-8的路径参数由 axum 的 Path 提取器拒绝
+    let response = get("/%FF").await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "BAD_REQUEST");
+    assert_eq!(
+        response.headers["content-type"],
+        "application/json; charset=utf-8"
+    );
+}
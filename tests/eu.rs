@@ -0,0 +1,39 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{get, setup};
+use ipgeo::utils::{ipinfo_to_csv, CSV_HEADER};
+
+#[tokio::test]
+async fn eu_members_are_flagged_on_both_countries() {
+    for uri in ["/1.2.7.3", "/v1/1.2.7.3"] {
+        let response = get(uri).await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.body["country"]["is_eu"], true, "{}", uri);
+        assert_eq!(response.body["registered_country"]["code"], "DE", "{}", uri);
+        assert_eq!(response.body["registered_country"]["is_eu"], true, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn non_eu_countries_omit_the_flag() {
+    let response = get("/8.8.8.8").await;
+    assert!(response.body["country"].get("is_eu").is_none());
+    assert!(response.body["registered_country"].get("is_eu").is_none());
+
+    // 实际位置在欧盟、注册地不在欧盟
+    let response = get("/1.2.11.11").await;
+    assert_eq!(response.body["country"]["is_eu"], true);
+    assert_eq!(response.body["registered_country"]["code"], "US");
+    assert!(response.body["registered_country"].get("is_eu").is_none());
+}
+
+#[tokio::test]
+async fn csv_reports_country_is_eu() {
+    setup();
+    let column = CSV_HEADER.split(',').position(|name| name == "country_is_eu").unwrap();
+    for (ip, expected) in [("1.2.7.3", "true"), ("8.8.8.8", "false")] {
+        let row = ipinfo_to_csv(&ipgeo::geo::get_ip_info(ip).await.unwrap());
+        assert_eq!(row.split(',').nth(column).unwrap(), expected, "{}", ip);
+    }
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use common::{setup_with, PEER};
+use futures::StreamExt;
+use ipgeo::api::{create_router, AppState};
+use ipgeo::config::Config;
+use serde_json::Value;
+use tower::ServiceExt;
+
+fn fast(config: Config) -> Config {
+    Config { events_interval: Duration::from_secs(1), ..config }
+}
+
+async fn open(uri: &str) -> (StatusCode, String, axum::body::BodyDataStream) {
+    setup_with(fast);
+    let mut request = Request::get(uri).body(Body::empty()).unwrap();
+    request.extensions_mut().insert(ConnectInfo(PEER.parse::<std::net::SocketAddr>().unwrap()));
+    let response = create_router(AppState::new()).oneshot(request).await.unwrap();
+    let content_type = response.headers()["content-type"].to_str().unwrap().to_string();
+    (response.status(), content_type, response.into_body().into_data_stream())
+}
+
+// 读取下一个 `ip` 事件的 data，跳过心跳注释
+async fn next_ip_event(body: &mut axum::body::BodyDataStream) -> Value {
+    loop {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), body.next())
+            .await
+            .expect("event within timeout")
+            .expect("stream still open")
+            .unwrap();
+        let text = std::str::from_utf8(&chunk).unwrap();
+        if text.lines().any(|line| line == "event: ip") {
+            let data = text.lines().find_map(|line| line.strip_prefix("data: ")).expect("data line");
+            return serde_json::from_str(data).unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn emits_caller_info_immediately() {
+    let (status, content_type, mut body) = open("/events/self").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "text/event-stream");
+
+    let event = next_ip_event(&mut body).await;
+    assert_eq!(event["changed"], false);
+    assert_eq!(event["info"]["ip"], "8.8.8.8");
+    assert_eq!(event["info"]["country"]["code"], "US");
+}
+
+#[tokio::test]
+async fn re_emits_on_interval_with_changed_flag() {
+    let (_, _, mut body) = open("/v1/events/self?detail=minimal").await;
+    let first = next_ip_event(&mut body).await;
+    let second = next_ip_event(&mut body).await;
+    assert_eq!(second["changed"], false);
+    assert_eq!(first["info"], second["info"]);
+    assert!(second["info"].get("location").is_none());
+}
+
+#[tokio::test]
+async fn invalid_options_are_rejected() {
+    let (status, content_type, _) = open("/events/self?detail=everything").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(content_type.starts_with("application/json"));
+}
@@ -0,0 +1,59 @@
+use std::net::IpAddr;
+use ipgeo::geo::{encode_csv, FallbackTable};
+
+fn table_bytes() -> Vec<u8> {
+    let csv = std::fs::read_to_string(
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/fallback-country.csv"),
+    ).unwrap();
+    let (bytes, skipped) = encode_csv(&csv);
+    // 表头注释不计，倒序的段、非IP和 ZZ 各一行
+    assert_eq!(skipped, 3);
+    bytes
+}
+
+fn lookup(table: &FallbackTable, ip: &str) -> Option<String> {
+    table.lookup(ip.parse::<IpAddr>().unwrap()).map(str::to_string)
+}
+
+#[test]
+fn finds_country_by_range() {
+    let bytes = table_bytes();
+    let table = FallbackTable::parse(&bytes).expect("valid table");
+    assert_eq!(lookup(&table, "8.8.8.8").as_deref(), Some("US"));
+    assert_eq!(lookup(&table, "1.0.0.0").as_deref(), Some("AU"));
+    assert_eq!(lookup(&table, "114.114.255.255").as_deref(), Some("CN"));
+    assert_eq!(lookup(&table, "9.9.9.9").as_deref(), Some("CH"));
+    assert_eq!(lookup(&table, "2001:4860::8888").as_deref(), Some("US"));
+    for miss in ["8.8.9.0", "0.0.0.1", "255.255.255.255", "2001:db8::1"] {
+        assert_eq!(lookup(&table, miss), None, "{}", miss);
+    }
+}
+
+#[test]
+fn names_are_optional() {
+    let bytes = table_bytes();
+    let table = FallbackTable::parse(&bytes).unwrap();
+    assert_eq!(table.name("CN"), Some("China"));
+    assert_eq!(table.name("CH"), None);
+}
+
+#[test]
+fn rejects_truncated_tables() {
+    let bytes = table_bytes();
+    assert!(FallbackTable::parse(&bytes[..bytes.len() / 2]).is_none());
+    assert!(FallbackTable::parse(b"garbage").is_none());
+    assert!(FallbackTable::parse(&encode_csv("").0).unwrap().is_empty());
+}
+
+#[test]
+fn bundled_table_is_complete() {
+    let csv = std::fs::read_to_string(
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/fallback-country.csv"),
+    ).unwrap();
+    let (bytes, skipped) = encode_csv(&csv);
+    assert_eq!(skipped, 0);
+    let table = FallbackTable::parse(&bytes).unwrap();
+    assert_eq!(lookup(&table, "8.8.8.8").as_deref(), Some("US"));
+    assert_eq!(lookup(&table, "114.114.114.114").as_deref(), Some("CN"));
+    assert_eq!(lookup(&table, "10.1.2.3"), None);
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{get, send};
+use ipgeo::models::IpInfo;
+
+fn get_accepting(uri: &str, accept: &str) -> Request<Body> {
+    Request::get(uri).header("accept", accept).body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn compact_by_default() {
+    let response = get("/114.114.114.114").await;
+    assert!(!response.text().contains('\n'));
+    assert!(response.text().contains("江苏省"));
+}
+
+#[tokio::test]
+async fn pretty_query_parameter() {
+    let response = get("/114.114.114.114?pretty=1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let text = response.text();
+    assert!(text.contains("\n  \"ip\": \"114.114.114.114\""), "{}", text);
+    // 中文按 UTF-8 原样输出，不转义为 \uXXXX
+    assert!(text.contains("江苏省") && !text.contains("\\u"));
+    assert_eq!(response.body["regions"][0], "江苏省");
+
+    let response = get("/114.114.114.114?pretty=0").await;
+    assert!(!response.text().contains('\n'));
+}
+
+#[tokio::test]
+async fn pretty_accept_header() {
+    let response = send(get_accepting("/api?host=8.8.8.8", "application/json+pretty")).await;
+    assert!(response.text().starts_with("{\n"));
+    assert!(response.headers["content-type"].to_str().unwrap().starts_with("application/json"));
+}
+
+#[tokio::test]
+async fn pretty_errors_and_batch() {
+    let response = get("/0.0.0.0?pretty").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert!(response.text().contains("\n  \"error\": \"INVALID_IP\""));
+
+    let response = get("/nope/nested/route?pretty=true").await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    assert!(response.text().starts_with("{\n"));
+
+    let response = send(
+        Request::post("/api/batch?pretty=1")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"["8.8.8.8","1.0.0.1"]"#))
+            .unwrap(),
+    ).await;
+    assert!(response.text().starts_with("[\n  {"));
+    assert_eq!(response.body.as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn non_json_responses_are_untouched() {
+    let response = get("/robots.txt?pretty=1").await;
+    assert_eq!(response.text(), "User-agent: *\nDisallow: /\n");
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Envelope {
+    code: u16,
+    error: String,
+    message: String,
+}
+
+fn assert_msgpack(response: &common::TestResponse) {
+    assert_eq!(response.headers["content-type"], "application/msgpack");
+}
+
+#[tokio::test]
+async fn msgpack_round_trip() {
+    let response = send(get_accepting("/114.114.114.114", "application/msgpack")).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_msgpack(&response);
+    let info: IpInfo = rmp_serde::from_slice(&response.bytes).expect("msgpack IpInfo");
+    assert_eq!(info.ip, "114.114.114.114");
+    assert_eq!(info.asn.unwrap().number, Some(21859));
+    assert_eq!(info.country.unwrap().code, "CN");
+    assert_eq!(info.regions.unwrap(), ["江苏省", "南京市", "玄武区"]);
+}
+
+#[tokio::test]
+async fn msgpack_query_parameter() {
+    let response = get("/api?host=8.8.8.8&format=msgpack").await;
+    assert_msgpack(&response);
+    let info: IpInfo = rmp_serde::from_slice(&response.bytes).unwrap();
+    assert_eq!(info.addr, "8.8.0.0/16");
+
+    // format=json 优先于 Accept 头
+    let response = send(get_accepting("/8.8.8.8?format=json", "application/msgpack")).await;
+    assert_eq!(response.body["ip"], "8.8.8.8");
+}
+
+#[tokio::test]
+async fn msgpack_error_envelope() {
+    let response = get("/0.0.0.0?format=msgpack").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_msgpack(&response);
+    let envelope: Envelope = rmp_serde::from_slice(&response.bytes).unwrap();
+    assert_eq!(envelope.code, 400);
+    assert_eq!(envelope.error, "INVALID_IP");
+    assert!(!envelope.message.is_empty());
+
+    let response = send(get_accepting("/a/b/c", "application/x-msgpack")).await;
+    assert_msgpack(&response);
+    let envelope: Envelope = rmp_serde::from_slice(&response.bytes).unwrap();
+    assert_eq!(envelope.error, "NOT_FOUND");
+}
+
+#[tokio::test]
+async fn msgpack_batch() {
+    let response = send(
+        Request::post("/api/batch?format=msgpack")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"["8.8.8.8","1.0.0.1"]"#))
+            .unwrap(),
+    ).await;
+    assert_msgpack(&response);
+    let infos: Vec<IpInfo> = rmp_serde::from_slice(&response.bytes).unwrap();
+    assert_eq!(infos.iter().map(|info| info.ip.as_str()).collect::<Vec<_>>(), ["8.8.8.8", "1.0.0.1"]);
+}
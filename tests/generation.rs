@@ -0,0 +1,39 @@
+use std::path::{Path, PathBuf};
+use ipgeo::geo::{data_generation, get_ip_info, reload_database};
+use ipnet::IpNet;
+use mmdb_writer::Writer;
+use serde_json::json;
+
+fn asn_database(dir: &Path, number: u32) -> PathBuf {
+    let mut writer = Writer::new("GeoLite2-ASN");
+    let network: IpNet = "8.8.8.0/24".parse().unwrap();
+    writer.insert(network, &json!({ "autonomous_system_number": number })).unwrap();
+    let path = dir.join(format!("GeoLite2-ASN-{}.mmdb", number));
+    std::fs::write(&path, writer.to_bytes().unwrap()).unwrap();
+    path
+}
+
+async fn lookup_asn() -> Option<u32> {
+    get_ip_info("8.8.8.8").await.unwrap().asn.and_then(|asn| asn.number)
+}
+
+#[tokio::test]
+async fn reload_bumps_generation_and_next_lookup_sees_new_data() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("generation-data");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    reload_database("ASN", &asn_database(&dir, 64500)).unwrap();
+    let generation = data_generation();
+    assert_eq!(lookup_asn().await, Some(64500));
+
+    reload_database("ASN", &asn_database(&dir, 64501)).unwrap();
+    assert!(data_generation() > generation);
+    assert_eq!(lookup_asn().await, Some(64501));
+
+    // 损坏的文件不替换数据库，代数不变
+    let generation = data_generation();
+    let corrupt = dir.join("corrupt.mmdb");
+    std::fs::write(&corrupt, b"not a database").unwrap();
+    assert!(reload_database("ASN", &corrupt).is_err());
+    assert_eq!(data_generation(), generation);
+}
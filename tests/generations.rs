@@ -0,0 +1,92 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+use axum::http::StatusCode;
+use common::{assert_error, get_admin, post_admin};
+use ipgeo::geo::{install_database, list_generations, rollback_database};
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("generations").join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// 模拟下载完成：写入临时文件后安装
+fn install(dir: &Path, content: &str, keep: usize) {
+    let staged = dir.join("GeoCN.partial");
+    std::fs::write(&staged, content).unwrap();
+    install_database(&staged, &dir.join("GeoCN.mmdb"), keep).unwrap();
+}
+
+fn read(dir: &Path, name: &str) -> Option<String> {
+    std::fs::read_to_string(dir.join(name)).ok()
+}
+
+#[test]
+fn keeps_configured_generations() {
+    let dir = scratch("keep");
+    for version in ["v1", "v2", "v3", "v4"] {
+        install(&dir, version, 2);
+    }
+    assert_eq!(read(&dir, "GeoCN.mmdb").as_deref(), Some("v4"));
+    assert_eq!(read(&dir, "GeoCN.mmdb.1").as_deref(), Some("v3"));
+    assert_eq!(read(&dir, "GeoCN.mmdb.2").as_deref(), Some("v2"));
+    assert!(read(&dir, "GeoCN.mmdb.3").is_none());
+
+    // 调小保留数量后，多余的旧版本在下次更新时删除
+    install(&dir, "v5", 1);
+    assert_eq!(read(&dir, "GeoCN.mmdb.1").as_deref(), Some("v4"));
+    assert!(read(&dir, "GeoCN.mmdb.2").is_none());
+
+    install(&dir, "v6", 0);
+    assert_eq!(read(&dir, "GeoCN.mmdb").as_deref(), Some("v6"));
+    assert!(read(&dir, "GeoCN.mmdb.1").is_none());
+}
+
+#[test]
+fn rollback_restores_previous_generation() {
+    let dir = scratch("rollback");
+    for version in ["v1", "v2", "v3"] {
+        install(&dir, version, 2);
+    }
+    let path = dir.join("GeoCN.mmdb");
+    rollback_database(&path).unwrap();
+    assert_eq!(read(&dir, "GeoCN.mmdb").as_deref(), Some("v2"));
+    assert_eq!(read(&dir, "GeoCN.mmdb.1").as_deref(), Some("v1"));
+    assert!(read(&dir, "GeoCN.mmdb.2").is_none());
+
+    let generations = list_generations(&path);
+    assert_eq!(generations.len(), 2);
+    assert_eq!(generations[1].file, "GeoCN.mmdb.1");
+    assert_eq!(generations[1].build_epoch, None);
+
+    rollback_database(&path).unwrap();
+    let error = rollback_database(&path).expect_err("nothing left to roll back to");
+    assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+    assert_eq!(read(&dir, "GeoCN.mmdb").as_deref(), Some("v1"));
+}
+
+#[tokio::test]
+async fn admin_lists_generations() {
+    let response = get_admin("/admin/generations").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let city = &response.body["GeoLite2-City.mmdb"][0];
+    assert_eq!(city["generation"], 0);
+    assert!(city["build_epoch"].is_u64(), "{}", response.body);
+}
+
+#[tokio::test]
+async fn admin_rollback_errors() {
+    let response = post_admin("/admin/rollback?db=nope").await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_PARAMETER");
+
+    // 夹具数据库没有旧版本
+    let response = post_admin("/admin/rollback?db=GeoCN").await;
+    assert_error(&response, StatusCode::NOT_FOUND, "NOT_FOUND");
+
+    let response = common::send(
+        axum::http::Request::post("/admin/rollback?db=GeoCN").body(axum::body::Body::empty()).unwrap(),
+    ).await;
+    assert_error(&response, StatusCode::UNAUTHORIZED, "UNAUTHORIZED");
+}
@@ -0,0 +1,114 @@
+#![cfg(feature = "grpc")]
+
+mod common;
+
+use std::net::{SocketAddr, TcpListener};
+use std::time::Duration;
+use futures::StreamExt;
+use ipgeo::grpc::pb::batch_lookup_reply::Result as BatchResult;
+use ipgeo::grpc::pb::ip_geo_client::IpGeoClient;
+use ipgeo::grpc::pb::{BatchLookupRequest, Detail, LookupRequest};
+use ipgeo::grpc::serve_grpc;
+use tokio_util::sync::CancellationToken;
+use tonic::transport::Channel;
+use tonic::{Code, Request};
+
+// 在随机端口上启动 gRPC 服务并返回已连接的客户端
+async fn client() -> (IpGeoClient<Channel>, CancellationToken) {
+    common::setup();
+    let addr: SocketAddr = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("free port");
+    let shutdown = CancellationToken::new();
+    tokio::spawn(serve_grpc(addr, shutdown.clone()));
+
+    for _ in 0..50 {
+        if let Ok(client) = IpGeoClient::connect(format!("http://{}", addr)).await {
+            return (client, shutdown);
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("gRPC server did not start on {}", addr);
+}
+
+#[tokio::test]
+async fn lookup_matches_http_fields() {
+    let (mut client, shutdown) = client().await;
+    let reply = client.lookup(LookupRequest { host: "114.114.114.114".into(), detail: 0 })
+        .await
+        .expect("lookup")
+        .into_inner();
+    assert_eq!(reply.ip, "114.114.114.114");
+    assert_eq!(reply.addr, "114.114.0.0/16");
+    assert_eq!(reply.country.expect("country").code, "CN");
+    assert_eq!(reply.regions, ["江苏省", "南京市", "玄武区"]);
+    assert_eq!(reply.isp.as_deref(), Some("中国电信"));
+    assert_eq!(reply.r#as.expect("as").number, 21859);
+    shutdown.cancel();
+}
+
+#[tokio::test]
+async fn lookup_honours_detail() {
+    let (mut client, shutdown) = client().await;
+    let reply = client.lookup(LookupRequest { host: "8.8.8.8".into(), detail: Detail::Minimal.into() })
+        .await
+        .expect("lookup")
+        .into_inner();
+    assert_eq!(reply.country.expect("country").code, "US");
+    assert!(reply.location.is_none());
+    assert!(reply.continent.is_none());
+    shutdown.cancel();
+}
+
+#[tokio::test]
+async fn lookup_errors_map_to_status_codes() {
+    let (mut client, shutdown) = client().await;
+    let status = client.lookup(LookupRequest { host: "0.0.0.0".into(), detail: 0 })
+        .await
+        .expect_err("invalid ip");
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let mut request = Request::new(LookupRequest { host: "0.0.0.0".into(), detail: 0 });
+    request.metadata_mut().insert("accept-language", "en".parse().unwrap());
+    let english = client.lookup(request).await.expect_err("invalid ip");
+    assert_ne!(status.message(), english.message());
+    shutdown.cancel();
+}
+
+#[tokio::test]
+async fn batch_streams_in_input_order() {
+    let (mut client, shutdown) = client().await;
+    let hosts = vec!["114.114.114.114".to_string(), "0.0.0.0".to_string(), "8.8.8.8".to_string()];
+    let mut stream = client.batch_lookup(BatchLookupRequest { hosts: hosts.clone(), detail: 0 })
+        .await
+        .expect("batch lookup")
+        .into_inner();
+
+    let mut replies = Vec::new();
+    while let Some(reply) = stream.next().await {
+        replies.push(reply.expect("batch item"));
+    }
+    let queries: Vec<_> = replies.iter().map(|r| r.query.clone()).collect();
+    assert_eq!(queries, hosts);
+    assert!(matches!(&replies[0].result, Some(BatchResult::Info(info)) if info.ip == "114.114.114.114"));
+    match &replies[1].result {
+        Some(BatchResult::Error(error)) => {
+            assert_eq!(error.code, 400);
+            assert_eq!(error.error, "INVALID_IP");
+        }
+        other => panic!("expected error reply, got {:?}", other),
+    }
+    assert!(matches!(&replies[2].result, Some(BatchResult::Info(info)) if info.ip == "8.8.8.8"));
+    shutdown.cancel();
+}
+
+#[tokio::test]
+async fn batch_rejects_oversized_request() {
+    let (mut client, shutdown) = client().await;
+    let hosts = vec!["8.8.8.8".to_string(); 101];
+    let status = client.batch_lookup(BatchLookupRequest { hosts, detail: 0 })
+        .await
+        .expect_err("too many hosts");
+    assert_eq!(status.code(), Code::InvalidArgument);
+    shutdown.cancel();
+}
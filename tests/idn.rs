@@ -0,0 +1,52 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{assert_error, get};
+use ipgeo::geo::{parse_domain, DomainName};
+use ipgeo::models::IpGeoError;
+use ipgeo::utils::normalize_host;
+
+fn parse(input: &str) -> Result<DomainName, IpGeoError> {
+    parse_domain(&normalize_host(input), false)
+}
+
+fn name(echo: &str, ascii: &str) -> DomainName {
+    DomainName { echo: echo.to_string(), ascii: ascii.to_string() }
+}
+
+#[test]
+fn unicode_names_resolve_as_punycode_and_echo_as_typed() {
+    assert_eq!(parse("münchen.de").unwrap(), name("münchen.de", "xn--mnchen-3ya.de"));
+    assert_eq!(parse("例え.jp").unwrap(), name("例え.jp", "xn--r8jz45g.jp"));
+    // 先规范化再编码
+    assert_eq!(parse("https://münchen.de/path?q=1").unwrap(), name("münchen.de", "xn--mnchen-3ya.de"));
+    assert_eq!(parse("例え.jp.").unwrap(), name("例え.jp", "xn--r8jz45g.jp"));
+    // 非 ASCII 的大写字母由 IDNA 映射为小写
+    assert_eq!(parse("MÜNCHEN.DE").unwrap().ascii, "xn--mnchen-3ya.de");
+}
+
+#[test]
+fn punycode_input_is_kept() {
+    assert_eq!(parse("xn--mnchen-3ya.de").unwrap(), name("xn--mnchen-3ya.de", "xn--mnchen-3ya.de"));
+    assert_eq!(parse("XN--R8JZ45G.JP").unwrap(), name("xn--r8jz45g.jp", "xn--r8jz45g.jp"));
+}
+
+#[test]
+fn length_limits_apply_to_the_encoded_form() {
+    // 57 个字节的标签编码后为 63 个字符，再长一个字符就超过标签上限
+    let label = format!("{}ü", "a".repeat(55));
+    assert_eq!(parse(&format!("{}.de", label)).unwrap().ascii.split('.').next().unwrap().len(), 63);
+    assert!(parse(&format!("a{}.de", label)).is_err());
+
+    // UTF-8 下 231 个字节，编码后 255 个字符，超过名称上限
+    let name = [label.as_str(); 4].join(".");
+    assert!(name.len() <= 253);
+    assert!(parse(&name).is_err());
+}
+
+#[tokio::test]
+async fn overlong_names_fail_without_resolving() {
+    // a×56 + ü，ü 按百分号编码
+    let response = get(&format!("/api?host={}%C3%BC.de", "a".repeat(56))).await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "RESOLVE_ERROR");
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::get;
+use ipgeo::utils::china_isp;
+
+#[test]
+fn names_from_every_source_normalize() {
+    for (name, code) in [
+        ("CHINANET-BACKBONE", "CT"),
+        ("中国电信", "CT"),
+        ("CHINA UNICOM China169 Backbone", "CU"),
+        ("China Mobile Communications Group Co., Ltd.", "CM"),
+        ("江苏移动", "CM"),
+        ("中国铁通", "CTT"),
+        ("China Education and Research Network Center (CERNET)", "CERNET"),
+    ] {
+        assert_eq!(china_isp(name).map(|isp| isp.code), Some(code), "{}", name);
+    }
+    assert_eq!(china_isp("GOOGLE"), None);
+    // 整词匹配，"Telecommunications" 不算 telecom
+    assert_eq!(china_isp("Example Telecommunications"), None);
+}
+
+#[tokio::test]
+async fn geocn_isp_gets_a_code() {
+    let response = get("/114.114.114.114").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["isp"], "中国电信");
+    assert_eq!(response.body["isp_code"], "CT");
+}
+
+#[tokio::test]
+async fn geocn_wins_when_sources_disagree() {
+    // GeoCN 为联通，ASN 为 CHINANET（电信）
+    let response = get("/1.2.8.8?sources=1").await;
+    assert_eq!(response.body["as"]["number"], 4134);
+    assert_eq!(response.body["isp"], "中国联通");
+    assert_eq!(response.body["isp_code"], "CU");
+    assert_eq!(response.body["sources"]["isp"], response.body["sources"]["regions"]);
+}
+
+#[tokio::test]
+async fn asn_name_is_used_without_geocn() {
+    let response = get("/1.2.4.4?sources=1").await;
+    assert_eq!(response.body["isp"], "中国联通");
+    assert_eq!(response.body["isp_code"], "CU");
+    assert!(response.body["sources"]["isp"].as_str().unwrap().starts_with("GeoLite2-ASN "));
+}
+
+#[tokio::test]
+async fn foreign_addresses_have_no_isp_code() {
+    let response = get("/8.8.8.8").await;
+    assert!(response.body.get("isp_code").is_none());
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{assert_error, get, post_json, setup_with};
+use ipgeo::config::Config;
+use serde_json::json;
+
+fn limits(config: Config) -> Config {
+    Config { max_body_bytes: 1024, batch_max_size: 5, ..config }
+}
+
+#[tokio::test]
+async fn oversized_body_is_rejected() {
+    setup_with(limits);
+    let hosts = vec!["8.8.8.8"; 200];
+    let response = post_json("/api/batch", &json!(hosts)).await;
+    assert_error(&response, StatusCode::PAYLOAD_TOO_LARGE, "PAYLOAD_TOO_LARGE");
+}
+
+#[tokio::test]
+async fn batch_over_configured_size_is_rejected() {
+    setup_with(limits);
+    let response = post_json("/api/batch", &json!(vec!["8.8.8.8"; 6])).await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_PARAMETER");
+
+    let response = post_json("/api/batch", &json!(vec!["8.8.8.8"; 5])).await;
+    assert_eq!(response.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn overlong_host_is_rejected_before_resolution() {
+    setup_with(limits);
+    let host = format!("{}.example.com", "a".repeat(40 * 1024));
+    for uri in [format!("/api?host={}", host), format!("/api/{}", "b".repeat(300))] {
+        let response = get(&uri).await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "HOST_TOO_LONG");
+    }
+}
+
+#[tokio::test]
+async fn overlong_batch_item_fails_alone() {
+    setup_with(limits);
+    let response = post_json("/api/batch", &json!(["8.8.8.8", "c".repeat(254)])).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let results = response.body.as_array().unwrap();
+    assert_eq!(results[0]["ip"], "8.8.8.8");
+    assert_eq!(results[1]["error"], "HOST_TOO_LONG");
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{get_admin, setup_with};
+use ipgeo::config::{Config, DbProfile};
+use serde_json::{json, Value};
+
+fn lite(config: Config) -> Config {
+    Config { db_profile: DbProfile::Lite, ..config }
+}
+
+async fn get(uri: &str) -> common::TestResponse {
+    setup_with(lite);
+    common::get(uri).await
+}
+
+async fn lookup(uri: &str) -> Value {
+    let response = get(uri).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    response.body
+}
+
+#[tokio::test]
+async fn country_database_answers_without_city() {
+    let body = lookup("/api?host=8.8.8.8&detail=full&sources=1").await;
+    assert_eq!(body["country"], json!({ "code": "US", "name": "美国" }));
+    assert_eq!(body["registered_country"]["code"], "US");
+    assert_eq!(body["continent"]["code"], "NA");
+    assert!(body["sources"]["country"].as_str().unwrap().starts_with("GeoLite2-Country "), "{}", body);
+    for field in ["location", "city", "regions", "regions_short", "warnings"] {
+        assert!(body.get(field).is_none(), "{} in {}", field, body);
+    }
+    assert_eq!(body["as"]["number"], 15169);
+}
+
+#[tokio::test]
+async fn geocn_still_fills_chinese_regions() {
+    let body = lookup("/api?host=114.114.114.114").await;
+    assert_eq!(body["country"]["code"], "CN");
+    assert_eq!(body["regions"], json!(["江苏省", "南京市", "玄武区"]));
+    assert!(body.get("location").is_none());
+}
+
+#[tokio::test]
+async fn health_reports_the_profile() {
+    let response = get("/healthz").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["status"], "ready");
+    assert_eq!(response.body["profile"], "lite");
+}
+
+#[tokio::test]
+async fn country_replaces_city_in_the_managed_databases() {
+    setup_with(lite);
+    let body = get_admin("/admin/generations").await.body;
+    assert!(body.get("GeoLite2-Country.mmdb").is_some(), "{}", body);
+    assert!(body.get("GeoLite2-City.mmdb").is_none(), "{}", body);
+
+    let raw = get_admin("/admin/debug/8.8.8.8?db=country").await;
+    assert_eq!(raw.status, StatusCode::OK);
+    assert_eq!(raw.body["database_type"], "GeoLite2-Country");
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{get, send};
+use ipgeo::models::NameLocales;
+use serde_json::Value;
+
+fn order(header: &str) -> Option<Vec<&'static str>> {
+    NameLocales::from_accept_language(header).map(|locales| locales.as_slice().to_vec())
+}
+
+async fn lookup(uri: &str, accept_language: &str) -> Value {
+    let request = Request::get(uri)
+        .header("accept-language", accept_language)
+        .body(Body::empty())
+        .unwrap();
+    let response = send(request).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    response.body
+}
+
+#[test]
+fn weighted_headers_are_ordered_by_q() {
+    // 繁体中文没有对应的名称，取简体中文而不是回退到英文
+    assert_eq!(order("zh-TW,zh;q=0.9,en;q=0.8"), Some(vec!["zh-CN", "en"]));
+    assert_eq!(order("en;q=0.5, ja"), Some(vec!["ja", "en"]));
+    assert_eq!(order("fr-CH, fr;q=0.9, de;q=0.7, en;q=0.8, *;q=0.5"), Some(vec!["fr", "en", "de"]));
+    assert_eq!(order("pt-PT,es;q=0.4"), Some(vec!["pt-BR", "es", "en"]));
+    assert_eq!(order("ru;q=0.2, ko"), Some(vec!["ru", "en"]));
+}
+
+#[test]
+fn unsupported_or_rejected_languages_leave_the_default() {
+    assert_eq!(order("ko, *"), None);
+    assert_eq!(order("de;q=0"), None);
+    assert_eq!(order("en;q=abc"), None);
+    assert_eq!(order(""), None);
+    assert_eq!(NameLocales::default().as_slice(), ["zh-CN", "en"]);
+}
+
+#[tokio::test]
+async fn accept_language_selects_name_locales() {
+    let english = lookup("/api?host=8.8.8.8", "en-US,en;q=0.9").await;
+    assert_eq!(english["country"]["name"], "United States");
+    assert_eq!(english["continent"]["name"], "North America");
+
+    let japanese = lookup("/api?host=1.2.7.3", "ja-JP,ja;q=0.9").await;
+    assert_eq!(japanese["country"]["name"], "ドイツ");
+    assert_eq!(japanese["city"]["name"], "ベルリン");
+    // 没有日文名称时回退到英文
+    assert_eq!(japanese["continent"]["name"], "Europe");
+
+    assert_eq!(lookup("/api?host=8.8.8.8", "zh-TW,zh;q=0.9").await["country"]["name"], "美国");
+    assert_eq!(get("/api?host=8.8.8.8").await.body["country"]["name"], "美国");
+}
+
+#[tokio::test]
+async fn lang_parameter_wins_over_the_header() {
+    let body = lookup("/api?host=1.2.7.3&lang=de", "ja").await;
+    assert_eq!(body["country"]["name"], "Deutschland");
+    assert_eq!(body["city"]["name"], "Berlin");
+    assert_eq!(lookup("/8.8.8.8?lang=zh-CN", "en").await["country"]["name"], "美国");
+    // 不认识的 lang 视为未指定
+    assert_eq!(lookup("/8.8.8.8?lang=ko", "en").await["country"]["name"], "United States");
+}
+
+#[tokio::test]
+async fn geocn_city_names_apply_only_to_chinese() {
+    let english = lookup("/api?host=114.114.114.114", "en").await;
+    assert_eq!(english["city"]["name"], "Nanjing");
+    assert_eq!(english["country"]["name"], "China");
+
+    let chinese = lookup("/api?host=114.114.114.114", "zh").await;
+    assert_eq!(chinese["city"]["name"], "南京市");
+}
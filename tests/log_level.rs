@@ -0,0 +1,58 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{assert_error, get_admin, send, ADMIN_TOKEN};
+use ipgeo::server::{install_log_filter, log_filter, toggle_debug_log_filter, DEBUG_FILTER};
+use serde_json::json;
+use tracing::Level;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter};
+
+async fn put_filter(body: &str, token: Option<&str>) -> common::TestResponse {
+    let mut request = Request::put("/admin/log_level").header("content-type", "application/json");
+    if let Some(token) = token {
+        request = request.header("authorization", format!("Bearer {}", token));
+    }
+    send(request.body(Body::from(body.to_string())).unwrap()).await
+}
+
+// 进程内只能安装一次订阅者，所有步骤放在同一个测试里按顺序执行
+#[tokio::test]
+async fn log_filter_is_swapped_at_runtime() {
+    // 没有 reload 层时无法切换
+    let response = get_admin("/admin/log_level").await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+
+    let (layer, handle) = reload::Layer::new(EnvFilter::new("info"));
+    tracing_subscriber::registry().with(layer).init();
+    install_log_filter("info", handle);
+    assert!(!tracing::enabled!(Level::DEBUG));
+
+    let response = get_admin("/admin/log_level").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body, json!({ "filter": "info", "default": "info" }));
+
+    let response = put_filter(r#"{"filter": "ipgeo=debug,tower_http=info"}"#, Some(ADMIN_TOKEN)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    assert_eq!(response.body["filter"], "ipgeo=debug,tower_http=info");
+    assert!(tracing::enabled!(target: "ipgeo::geo", Level::DEBUG));
+    assert!(!tracing::enabled!(target: "tower_http", Level::DEBUG));
+
+    // 无法解析的规则被拒绝，原有规则不变
+    for invalid in [r#"{"filter": "ipgeo=loudest"}"#, r#"{"filter": "  "}"#, r#"{"level": "debug"}"#] {
+        assert_error(&put_filter(invalid, Some(ADMIN_TOKEN)).await, StatusCode::BAD_REQUEST, "INVALID_PARAMETER");
+    }
+    assert_eq!(log_filter().unwrap().filter, "ipgeo=debug,tower_http=info");
+    assert!(tracing::enabled!(target: "ipgeo::geo", Level::DEBUG));
+
+    assert_error(&put_filter(r#"{"filter": "trace"}"#, None).await, StatusCode::UNAUTHORIZED, "UNAUTHORIZED");
+    assert_eq!(get_admin("/admin/log_level").await.body["filter"], "ipgeo=debug,tower_http=info");
+
+    // SIGUSR1 的切换：不是启动规则时先换回启动规则，再次切换到 debug
+    assert_eq!(toggle_debug_log_filter().unwrap().filter, "info");
+    assert!(!tracing::enabled!(target: "ipgeo::geo", Level::DEBUG));
+    assert_eq!(toggle_debug_log_filter().unwrap().filter, DEBUG_FILTER);
+    assert!(tracing::enabled!(target: "tower_http", Level::DEBUG));
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{assert_error, get, send};
+use ipgeo::models::{IpGeoError, Lang, ParamError};
+
+fn all_variants() -> Vec<IpGeoError> {
+    vec![
+        IpGeoError::InvalidIp("1.2.3".into()),
+        IpGeoError::ResolveError,
+        IpGeoError::IoError(std::io::Error::other("disk")),
+        IpGeoError::ParseError("x".parse::<std::net::IpAddr>().unwrap_err()),
+        IpGeoError::TimeoutError,
+        IpGeoError::PrivateIp("10.0.0.1".into()),
+        IpGeoError::InvalidParameter(ParamError::LookupOptions),
+        IpGeoError::Unauthorized,
+        IpGeoError::AdminForbidden,
+        IpGeoError::Forbidden,
+        IpGeoError::PrivateTarget("10.0.0.1".into()),
+        IpGeoError::HostTooLong(253),
+        IpGeoError::NotFound("/x".into()),
+        IpGeoError::Overloaded,
+        IpGeoError::RequestTimeout,
+        IpGeoError::DatabaseUnavailable("city"),
+        IpGeoError::MethodNotAllowed,
+        IpGeoError::InternalPanic,
+        IpGeoError::Rejected(StatusCode::BAD_REQUEST, "Invalid URL".into()),
+    ]
+}
+
+#[test]
+fn every_variant_has_both_languages() {
+    for error in all_variants() {
+        let zh = error.message(Lang::Zh);
+        let en = error.message(Lang::En);
+        assert!(!en.is_empty() && en.is_ascii(), "{:?}: {}", error, en);
+        if matches!(error, IpGeoError::Rejected(..)) {
+            assert_eq!(zh, en);
+        } else {
+            assert!(!zh.is_ascii(), "{:?}: {}", error, zh);
+        }
+
+        let zh_json = error.to_json_in(Lang::Zh);
+        let en_json = error.to_json_in(Lang::En);
+        assert_eq!(zh_json["error"], en_json["error"]);
+        assert_eq!(zh_json["code"], en_json["code"]);
+    }
+}
+
+fn all_param_errors() -> Vec<ParamError> {
+    vec![
+        ParamError::LookupOptions,
+        ParamError::BatchBody("expected a sequence".into()),
+        ParamError::BatchTooLarge(100),
+        ParamError::TooManyRows(10000),
+        ParamError::UnreadableBody("connection reset".into()),
+        ParamError::HostConflict,
+        ParamError::StatsWindow,
+        ParamError::UnknownDebugDatabase("db".into()),
+        ParamError::UnknownRollbackDatabase("db".into()),
+        ParamError::LogFilter("ipgeo=loud".into(), "invalid level".into()),
+        ParamError::LogLevelBody("missing field `filter`".into()),
+    ]
+}
+
+#[test]
+fn every_parameter_error_has_both_languages() {
+    for param in all_param_errors() {
+        let error = IpGeoError::InvalidParameter(param);
+        let en = error.message(Lang::En);
+        assert!(en.starts_with("Invalid parameter: ") && en.is_ascii(), "{:?}: {}", error, en);
+        let zh = error.message(Lang::Zh);
+        assert!(zh.starts_with("参数错误: ") && !zh.is_ascii(), "{:?}: {}", error, zh);
+    }
+}
+
+#[tokio::test]
+async fn parameter_errors_follow_the_request_language() {
+    let hosts = vec!["8.8.8.8"; 101].join(",");
+    for uri in [format!("/api?host={}&lang=en", hosts), "/8.8.8.8?detail=bogus&lang=en".to_string(), "/api?host=8.8.8.8&ip=1.0.0.1&lang=en".to_string()] {
+        let response = get(&uri).await;
+        assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_PARAMETER");
+        let message = response.body["message"].as_str().unwrap();
+        assert!(message.is_ascii(), "{}: {}", uri, message);
+    }
+    let response = get("/8.8.8.8?detail=bogus").await;
+    assert!(response.body["message"].as_str().unwrap().starts_with("参数错误: detail 只能是"));
+}
+
+#[test]
+fn defaults_to_chinese_outside_requests() {
+    assert_eq!(Lang::current(), Lang::Zh);
+    assert_eq!(IpGeoError::ResolveError.to_json()["message"], IpGeoError::ResolveError.message(Lang::Zh));
+}
+
+#[test]
+fn accept_language_negotiation() {
+    assert_eq!(Lang::from_accept_language("en-US,en;q=0.9"), Some(Lang::En));
+    assert_eq!(Lang::from_accept_language("en;q=0.5, zh-CN;q=0.8"), Some(Lang::Zh));
+    assert_eq!(Lang::from_accept_language("fr-FR, en;q=0.7"), Some(Lang::En));
+    assert_eq!(Lang::from_accept_language("en;q=0, de"), None);
+    assert_eq!(Lang::from_accept_language("*"), None);
+}
+
+#[tokio::test]
+async fn lang_query_parameter() {
+    let response = get("/0.0.0.0?lang=en").await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_IP");
+    assert!(response.body["message"].as_str().unwrap().starts_with("Invalid IP address"));
+
+    let response = get("/0.0.0.0").await;
+    assert!(response.body["message"].as_str().unwrap().starts_with("无效的IP地址"));
+}
+
+#[tokio::test]
+async fn accept_language_header() {
+    let request = |value: &str| Request::get("/nope/extra")
+        .header("accept-language", value)
+        .body(Body::empty())
+        .unwrap();
+
+    let response = send(request("en-GB,en;q=0.9")).await;
+    assert_error(&response, StatusCode::NOT_FOUND, "NOT_FOUND");
+    assert!(response.body["message"].as_str().unwrap().starts_with("Not found"));
+
+    let response = send(request("zh-CN,en;q=0.9")).await;
+    assert_error(&response, StatusCode::NOT_FOUND, "NOT_FOUND");
+    assert!(response.body["message"].as_str().unwrap().starts_with("资源不存在"));
+}
+
+#[tokio::test]
+async fn query_parameter_overrides_header() {
+    let response = send(
+        Request::get("/api?host=bad..host&lang=zh")
+            .header("accept-language", "en")
+            .body(Body::empty())
+            .unwrap(),
+    ).await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "RESOLVE_ERROR");
+    assert!(!response.body["message"].as_str().unwrap().is_ascii());
+}
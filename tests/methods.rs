@@ -0,0 +1,87 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use common::{assert_error, send};
+
+async fn request(method: Method, uri: &str) -> common::TestResponse {
+    send(Request::builder().method(method).uri(uri).body(Body::empty()).unwrap()).await
+}
+
+fn allow(response: &common::TestResponse) -> Vec<String> {
+    let allow = response.headers.get(header::ALLOW).expect("Allow header").to_str().unwrap();
+    allow.split(',').map(|method| method.trim().to_string()).collect()
+}
+
+#[tokio::test]
+async fn head_matches_get_without_body() {
+    for uri in ["/", "/api?host=8.8.8.8", "/api/8.8.8.8", "/8.8.8.8", "/v1/8.8.8.8", "/healthz"] {
+        let get = request(Method::GET, uri).await;
+        let head = request(Method::HEAD, uri).await;
+        assert_eq!(head.status, StatusCode::OK, "{}", uri);
+        assert!(head.bytes.is_empty(), "{}", uri);
+        assert_eq!(head.headers.get(header::CONTENT_TYPE), get.headers.get(header::CONTENT_TYPE), "{}", uri);
+        assert_eq!(head.headers[header::CONTENT_LENGTH], get.bytes.len().to_string(), "{}", uri);
+    }
+
+    // 错误响应同样只有头部
+    let head = request(Method::HEAD, "/api/not-a-host!").await;
+    assert_eq!(head.status, StatusCode::BAD_REQUEST);
+    assert!(head.bytes.is_empty());
+}
+
+#[tokio::test]
+async fn options_lists_allowed_methods() {
+    for uri in ["/", "/api", "/api/8.8.8.8", "/8.8.8.8", "/metrics"] {
+        let response = request(Method::OPTIONS, uri).await;
+        assert_eq!(response.status, StatusCode::NO_CONTENT, "{}", uri);
+        assert!(response.bytes.is_empty(), "{}", uri);
+        let mut methods = allow(&response);
+        methods.sort();
+        assert_eq!(methods, ["GET", "HEAD", "OPTIONS"], "{}", uri);
+    }
+
+    let response = request(Method::OPTIONS, "/api/batch").await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+    let mut methods = allow(&response);
+    methods.sort();
+    assert_eq!(methods, ["OPTIONS", "POST"]);
+}
+
+#[tokio::test]
+async fn unsupported_methods_return_json_envelope() {
+    for (method, uri) in [
+        (Method::PUT, "/api"),
+        (Method::DELETE, "/api/8.8.8.8"),
+        (Method::PATCH, "/8.8.8.8"),
+        (Method::PUT, "/api/batch"),
+        (Method::POST, "/"),
+    ] {
+        let response = request(method.clone(), uri).await;
+        assert_error(&response, StatusCode::METHOD_NOT_ALLOWED, "METHOD_NOT_ALLOWED");
+        assert!(allow(&response).contains(&"OPTIONS".to_string()), "{} {}", method, uri);
+    }
+}
+
+#[tokio::test]
+async fn no_cors_headers_without_allowed_origins() {
+    let preflight = Request::builder()
+        .method(Method::OPTIONS)
+        .uri("/8.8.8.8")
+        .header(header::ORIGIN, "https://example.com")
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+        .body(Body::empty())
+        .unwrap();
+    let simple = Request::get("/api")
+        .header(header::ORIGIN, "https://example.com")
+        .body(Body::empty())
+        .unwrap();
+    for request in [preflight, simple] {
+        let response = send(request).await;
+        assert!(response.status.is_success());
+        let cors: Vec<_> = response.headers.keys()
+            .filter(|name| name.as_str().starts_with("access-control-"))
+            .collect();
+        assert!(cors.is_empty(), "{:?}", cors);
+    }
+}
@@ -0,0 +1,68 @@
+use std::net::IpAddr;
+
+use ipgeo::utils::{network_for, private_network};
+use ipnet::IpNet;
+
+const SAMPLES: [&str; 6] = [
+    "8.8.8.8",
+    "255.255.255.255",
+    "0.0.0.1",
+    "2001:db8:85a3:8d3:1319:8a2e:370:7348",
+    "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff",
+    "::1",
+];
+
+// 以 ipnet 的网段计算作为参照
+fn reference(ip: IpAddr, prefix_len: u8) -> String {
+    IpNet::new(ip, prefix_len).unwrap().trunc().to_string()
+}
+
+#[test]
+fn network_for_matches_ipnet_for_every_prefix() {
+    for sample in SAMPLES {
+        let ip: IpAddr = sample.parse().unwrap();
+        let max = if ip.is_ipv4() { 32 } else { 128 };
+        for prefix_len in 0..=max {
+            assert_eq!(
+                network_for(ip, prefix_len).to_string(),
+                reference(ip, prefix_len),
+                "{}/{}", ip, prefix_len
+            );
+        }
+    }
+}
+
+#[test]
+fn network_for_clamps_oversized_prefix() {
+    assert_eq!(network_for("8.8.8.8".parse().unwrap(), 40).to_string(), "8.8.8.8/32");
+    assert_eq!(network_for("::1".parse().unwrap(), 200).to_string(), "::1/128");
+}
+
+#[test]
+fn network_for_edge_prefixes() {
+    assert_eq!(network_for("8.8.8.8".parse().unwrap(), 0).to_string(), "0.0.0.0/0");
+    assert_eq!(network_for("2001:db8::1".parse().unwrap(), 0).to_string(), "::/0");
+    // 前缀边界落在16位分段中间
+    assert_eq!(network_for("2001:db8:ffff::1".parse().unwrap(), 36).to_string(), "2001:db8:f000::/36");
+}
+
+// 响应中 addr 字段的默认前缀，字符串格式与之前手写的拼接保持一致
+#[test]
+fn default_addr_prefixes() {
+    assert_eq!(network_for("223.5.5.5".parse().unwrap(), 16).to_string(), "223.5.0.0/16");
+    assert_eq!(network_for("2400:3200:baba::1".parse().unwrap(), 32).to_string(), "2400:3200::/32");
+    assert_eq!(network_for("2400:3200:baba::1".parse().unwrap(), 48).to_string(), "2400:3200:baba::/48");
+}
+
+#[test]
+fn private_network_lookup() {
+    let net = |ip: &str| private_network(ip.parse().unwrap()).map(|net| net.to_string());
+    assert_eq!(net("10.1.2.3").as_deref(), Some("10.0.0.0/8"));
+    assert_eq!(net("172.31.255.1").as_deref(), Some("172.16.0.0/12"));
+    assert_eq!(net("172.32.0.1"), None);
+    assert_eq!(net("127.0.0.1").as_deref(), Some("127.0.0.0/8"));
+    assert_eq!(net("169.254.1.1").as_deref(), Some("169.254.0.0/16"));
+    assert_eq!(net("fe80::1").as_deref(), Some("fe80::/10"));
+    assert_eq!(net("fd00::1").as_deref(), Some("fc00::/7"));
+    assert_eq!(net("::1"), None);
+}
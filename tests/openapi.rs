@@ -0,0 +1,179 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{get, post_json};
+use serde_json::{json, Value};
+
+async fn spec() -> Value {
+    let response = get("/openapi.json").await;
+    assert_eq!(response.status, StatusCode::OK);
+    response.body
+}
+
+fn schema<'a>(spec: &'a Value, name: &str) -> &'a Value {
+    &spec["components"]["schemas"][name]
+}
+
+// 解开 $ref 和单元素 allOf（可选字段的包装），返回实际的对象定义
+fn resolve<'a>(spec: &'a Value, mut schema: &'a Value) -> &'a Value {
+    loop {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.strip_prefix("#/components/schemas/").expect("local ref");
+            schema = &spec["components"]["schemas"][name];
+        } else if let Some([inner]) = schema["allOf"].as_array().map(Vec::as_slice) {
+            schema = inner;
+        } else {
+            return schema;
+        }
+    }
+}
+
+// 响应中的每个字段都必须在文档中声明，必填字段必须出现
+fn assert_conforms(spec: &Value, schema: &Value, value: &Value, path: &str) {
+    let schema = resolve(spec, schema);
+    match value {
+        Value::Object(map) => {
+            let properties = schema["properties"].as_object()
+                .unwrap_or_else(|| panic!("{} is not documented as an object: {}", path, schema));
+            for (key, field) in map {
+                let field_schema = properties.get(key)
+                    .unwrap_or_else(|| panic!("{}.{} is missing from the spec", path, key));
+                assert_conforms(spec, field_schema, field, &format!("{}.{}", path, key));
+            }
+            for required in schema["required"].as_array().into_iter().flatten() {
+                let required = required.as_str().unwrap();
+                assert!(map.contains_key(required), "{}.{} is required but absent", path, required);
+            }
+        }
+        Value::Array(items) => {
+            assert_eq!(schema["type"], "array", "{} is not documented as an array", path);
+            for (index, item) in items.iter().enumerate() {
+                assert_conforms(spec, &schema["items"], item, &format!("{}[{}]", path, index));
+            }
+        }
+        Value::String(_) => assert_eq!(schema["type"], "string", "{}", path),
+        Value::Bool(_) => assert_eq!(schema["type"], "boolean", "{}", path),
+        Value::Number(n) if n.is_f64() => assert_eq!(schema["type"], "number", "{}", path),
+        Value::Number(_) => assert_eq!(schema["type"], "integer", "{}", path),
+        Value::Null => panic!("{} is null; optional fields should be omitted", path),
+    }
+}
+
+fn parameter_names(spec: &Value, path: &str, method: &str) -> Vec<String> {
+    spec["paths"][path][method]["parameters"]
+        .as_array()
+        .unwrap_or_else(|| panic!("{} {} has no parameters", method, path))
+        .iter()
+        .map(|p| p["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn document_is_valid_openapi_3_0() {
+    let spec = spec().await;
+    let parsed: openapiv3::OpenAPI = serde_json::from_value(spec.clone()).expect("valid OpenAPI 3.0 document");
+    assert!(parsed.openapi.starts_with("3.0"), "unexpected version {}", parsed.openapi);
+    assert_eq!(parsed.info.version, env!("CARGO_PKG_VERSION"));
+
+    // 所有引用都要能在 components 中找到
+    fn refs(value: &Value, out: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(r)) = map.get("$ref") {
+                    out.push(r.clone());
+                }
+                map.values().for_each(|v| refs(v, out));
+            }
+            Value::Array(items) => items.iter().for_each(|v| refs(v, out)),
+            _ => {}
+        }
+    }
+    let mut all = Vec::new();
+    refs(&spec, &mut all);
+    assert!(!all.is_empty());
+    for reference in &all {
+        let name = reference.trim_start_matches("#/components/schemas/");
+        assert!(schema(&spec, name).is_object(), "dangling reference {}", reference);
+    }
+    // 反过来，文档中的每个对象模型都要被某个接口用到，不描述从不返回的结构；
+    // Detail 等枚举是查询参数的取值，在参数中内联
+    for (name, definition) in spec["components"]["schemas"].as_object().unwrap() {
+        if definition["enum"].is_array() {
+            continue;
+        }
+        let reference = format!("#/components/schemas/{}", name);
+        assert!(all.contains(&reference), "schema {} is never used", name);
+    }
+}
+
+#[tokio::test]
+async fn documents_routes_and_parameters() {
+    let spec = spec().await;
+    for path in ["/", "/api", "/api/{host}", "/{host}"] {
+        assert!(spec["paths"][path]["get"].is_object(), "missing GET {}", path);
+        let names = parameter_names(&spec, path, "get");
+        for name in ["detail", "lang", "format", "pretty"] {
+            assert!(names.iter().any(|n| n == name), "GET {} lacks {}", path, name);
+        }
+        for status in ["200", "400", "503", "504"] {
+            assert!(spec["paths"][path]["get"]["responses"][status].is_object(), "GET {} lacks {}", path, status);
+        }
+    }
+    assert!(parameter_names(&spec, "/api", "get").contains(&"host".to_string()));
+    assert!(spec["paths"]["/api/batch"]["post"]["requestBody"].is_object());
+    assert_eq!(
+        spec["paths"]["/"]["get"]["responses"]["200"]["content"]["application/msgpack"]["schema"]["$ref"],
+        "#/components/schemas/IpInfo",
+    );
+    assert_eq!(schema(&spec, "Detail")["enum"], json!(["minimal", "standard", "full"]));
+}
+
+#[tokio::test]
+async fn documents_versioned_routes() {
+    let spec = spec().await;
+    for prefix in ["/v1", "/v2"] {
+        for path in ["", "/api", "/api/{host}", "/{host}"] {
+            let path = format!("{}{}", prefix, path);
+            assert!(spec["paths"][&path]["get"].is_object(), "missing GET {}", path);
+            assert!(spec["paths"][&path]["get"]["deprecated"].is_null(), "{} should not be deprecated", path);
+        }
+        assert!(spec["paths"][format!("{}/api/batch", prefix)]["post"].is_object());
+    }
+    assert_eq!(spec["paths"]["/api"]["get"]["deprecated"], true);
+    assert_eq!(spec["paths"]["/v1/api"]["get"]["operationId"], "v1_api");
+    assert_eq!(spec["paths"]["/v2/api"]["get"]["operationId"], "v2_api");
+}
+
+#[tokio::test]
+async fn responses_match_documented_schemas() {
+    let spec = spec().await;
+    let ip_info = json!({ "$ref": "#/components/schemas/IpInfo" });
+
+    for uri in ["/api?host=114.114.114.114&detail=full", "/8.8.8.8", "/10.1.2.3"] {
+        let response = get(uri).await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_conforms(&spec, &ip_info, &response.body, uri);
+    }
+
+    let response = get("/0.0.0.0").await;
+    assert_conforms(&spec, &json!({ "$ref": "#/components/schemas/ErrorBody" }), &response.body, "error");
+
+    let response = post_json("/api/batch", &json!(["8.8.8.8", "0.0.0.0"])).await;
+    let items = response.body.as_array().expect("array");
+    assert_conforms(&spec, &ip_info, &items[0], "batch[0]");
+    assert_conforms(&spec, &json!({ "$ref": "#/components/schemas/BatchError" }), &items[1], "batch[1]");
+}
+
+#[cfg(feature = "swagger-ui")]
+#[tokio::test]
+async fn swagger_ui_points_at_spec() {
+    let response = get("/docs").await;
+    assert_eq!(response.status, StatusCode::PERMANENT_REDIRECT);
+
+    let response = get("/docs/").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.text().contains("swagger"));
+
+    let response = get("/docs/swagger-initializer.js").await;
+    assert!(response.text().contains("/openapi.json"));
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "otlp")]
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::send;
+use opentelemetry::trace::{SpanId, TraceId, TracerProvider};
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::trace::{SdkTracerProvider, SpanData, SpanExporter};
+use tracing_subscriber::layer::SubscriberExt;
+
+const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+// 记录导出的 span，代替发往采集端
+#[derive(Debug, Clone, Default)]
+struct Recorded(Arc<Mutex<Vec<SpanData>>>);
+
+impl SpanExporter for Recorded {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        self.0.lock().unwrap().extend(batch);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn exported_spans_continue_the_gateway_trace() {
+    let recorded = Recorded::default();
+    let provider = SdkTracerProvider::builder().with_simple_exporter(recorded.clone()).build();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_writer(std::io::sink)
+        .finish()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let request = Request::get("/api?host=8.8.8.8")
+        .header("traceparent", TRACEPARENT)
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(request).await.status, StatusCode::OK);
+
+    let spans = recorded.0.lock().unwrap().clone();
+    let trace_id = TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap();
+    let request = spans.iter().find(|span| span.name == "request").expect("request span");
+    assert_eq!(request.span_context.trace_id(), trace_id);
+    assert_eq!(request.parent_span_id, SpanId::from_hex("00f067aa0ba902b7").unwrap());
+    assert!(request.parent_span_is_remote);
+    for name in ["get_ip_info", "mmdb_lookup"] {
+        let span = spans.iter().find(|span| span.name == name).unwrap_or_else(|| panic!("{} span", name));
+        assert_eq!(span.span_context.trace_id(), trace_id, "{}", name);
+    }
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{get, post_admin};
+use ipgeo::geo::OverrideTable;
+use serde_json::json;
+
+#[tokio::test]
+async fn overrides_replace_database_fields() {
+    let response = get("/api?host=9.9.9.9&sources=1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let body = &response.body;
+    assert_eq!(body["country"], json!({ "code": "CN", "name": "中国" }));
+    assert_eq!(body["regions"], json!(["上海市"]));
+    assert_eq!(body["location"]["latitude"], 31.2304);
+    assert_eq!(body["as"]["number"], 64500);
+    assert_eq!(body["as"]["name"], "CORP-EGRESS");
+    assert_eq!(body["addr"], "9.9.0.0/16");
+    for group in ["country", "regions", "location", "asn"] {
+        assert_eq!(body["sources"][group], "override", "{}", group);
+    }
+}
+
+#[tokio::test]
+async fn longest_prefix_wins_and_other_fields_are_kept() {
+    let response = get("/api?host=8.8.8.200&sources=1").await;
+    let body = &response.body;
+    assert_eq!(body["regions"], json!(["Sunnyvale"]));
+    assert_eq!(body["as"]["number"], 15169);
+    assert_eq!(body["as"]["name"], "GOOGLE-EDGE");
+    assert_eq!(body["country"]["code"], "US");
+    assert_ne!(body["sources"]["country"], "override");
+
+    let response = get("/api?host=8.8.8.129").await;
+    assert_eq!(response.body["regions"], json!(["Mountain View"]));
+    assert_ne!(response.body["as"]["name"], "GOOGLE-EDGE");
+
+    // 不在任何覆盖网段中
+    let response = get("/api?host=8.8.8.8").await;
+    assert_ne!(response.body["regions"], json!(["Mountain View"]));
+}
+
+#[test]
+fn malformed_entries_are_skipped() {
+    let content = std::fs::read_to_string(
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/overrides.json"),
+    ).unwrap();
+    let table = OverrideTable::parse(&content).expect("valid JSON");
+    assert_eq!(table.len(), 3);
+    assert!(table.lookup("8.8.8.70".parse().unwrap()).is_none());
+    assert!(table.lookup("2001:db8::1".parse().unwrap()).is_none());
+    assert!(OverrideTable::parse("[1, 2]").is_err());
+}
+
+#[tokio::test]
+async fn admin_reload_reports_override_count() {
+    let response = post_admin("/admin/reload").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["overrides"], 3);
+}
@@ -0,0 +1,33 @@
+//! 持有读取器写锁时 panic 使锁中毒，之后的查询恢复该数据库而不是一直跳过。
+//! 中毒的是进程内的全局读取器，单独放在一个测试程序中
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{get, setup};
+use ipgeo::geo::get_geocn_reader;
+
+#[tokio::test]
+async fn poisoned_reader_lock_is_recovered_counted_and_reported() {
+    setup();
+    let reader = get_geocn_reader();
+    let poisoner = std::thread::spawn(move || {
+        let _guard = reader.write().unwrap();
+        panic!("panic while reloading GeoCN");
+    });
+    assert!(poisoner.join().is_err());
+    assert!(get_geocn_reader().is_poisoned());
+
+    // GeoCN 的省市区仍然出现在结果中
+    let response = get("/114.114.114.114").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["regions"][2], "玄武区", "{}", response.body);
+    assert!(response.body.get("warnings").is_none(), "{}", response.body);
+    assert!(!get_geocn_reader().is_poisoned());
+
+    let metrics = String::from_utf8(get("/metrics").await.bytes).unwrap();
+    assert!(metrics.contains("ipgeo_lock_recoveries_total{database=\"GeoCN\"} 1"), "{}", metrics);
+    let health = get("/healthz").await;
+    assert_eq!(health.body["lock_recoveries"]["GeoCN"], 1, "{}", health.body);
+    assert_eq!(health.body["status"], "ready");
+}
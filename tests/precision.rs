@@ -0,0 +1,48 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{get, setup_with};
+use ipgeo::config::Config;
+use ipgeo::utils::round_coord;
+
+fn coarse(config: Config) -> Config {
+    Config { coord_precision: Some(2), ..config }
+}
+
+#[test]
+fn rounding_has_no_float_artifacts() {
+    assert_eq!(round_coord(39.94, 1).to_string(), "39.9");
+    assert_eq!(round_coord(39.95001, 1).to_string(), "40");
+    assert_eq!(round_coord(-97.822, 2).to_string(), "-97.82");
+    assert_eq!(round_coord(118.7778, 0), 119.0);
+}
+
+#[tokio::test]
+async fn server_precision_applies_to_coordinates() {
+    setup_with(coarse);
+    let response = get("/114.114.114.114").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["location"]["latitude"], 32.06);
+    assert_eq!(response.body["location"]["longitude"], 118.78);
+    assert!(response.text().contains("32.06,"), "{}", response.text());
+}
+
+#[tokio::test]
+async fn request_can_only_lower_precision() {
+    setup_with(coarse);
+    let coarser = get("/114.114.114.114?precision=1").await;
+    assert_eq!(coarser.body["location"]["latitude"], 32.1);
+    assert_eq!(coarser.body["location"]["longitude"], 118.8);
+
+    let finer = get("/114.114.114.114?precision=4").await;
+    assert_eq!(finer.body["location"]["latitude"], 32.06);
+    assert_eq!(finer.body["location"]["longitude"], 118.78);
+}
+
+#[tokio::test]
+async fn invalid_precision_is_rejected() {
+    setup_with(coarse);
+    let response = get("/114.114.114.114?precision=-1").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.body["error"], "INVALID_PARAMETER");
+}
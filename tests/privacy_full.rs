@@ -0,0 +1,43 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{assert_error, capture_logs, send_from, setup_with, TestResponse};
+use ipgeo::config::{Config, PrivacyMode};
+
+fn full(config: Config) -> Config {
+    Config { privacy_mode: PrivacyMode::Full, ..config }
+}
+
+async fn get_via(uri: &str, peer: &str, forwarded_for: Option<&str>) -> TestResponse {
+    setup_with(full);
+    let mut request = Request::get(uri);
+    if let Some(ip) = forwarded_for {
+        request = request.header("x-forwarded-for", ip);
+    }
+    send_from(request.body(Body::empty()).unwrap(), peer.parse().unwrap()).await
+}
+
+#[tokio::test]
+async fn logs_keep_full_addresses() {
+    let (captured, _guard) = capture_logs();
+    let response = get_via("/api/8.8.4.4", "9.9.9.9:1000", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let response = get_via("/api", "9.9.9.9:1000", Some("2001:4860:4860::8844")).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let output = captured.output();
+    // 访问日志
+    assert!(output.contains("path=/api/8.8.4.4"), "{}", output);
+    assert!(output.contains("client_ip=9.9.9.9"), "{}", output);
+    assert!(output.contains("client_ip=2001:4860:4860::8844"), "{}", output);
+    // 从请求头取到客户端IP时的日志
+    assert!(output.contains("x-forwarded-for(General) 中的IP: 2001:4860:4860::8844"), "{}", output);
+}
+
+#[tokio::test]
+async fn invalid_ip_message_echoes_the_input() {
+    let response = get_via("/api/192.0.2.55", "9.9.9.9:1000", None).await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_IP");
+    assert!(response.body["message"].as_str().unwrap().contains("192.0.2.55"), "{}", response.body);
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{assert_error, capture_logs, send_from, setup_with, TestResponse};
+use ipgeo::config::{Config, PrivacyMode};
+
+fn none(config: Config) -> Config {
+    Config { privacy_mode: PrivacyMode::None, ..config }
+}
+
+async fn get_via(uri: &str, peer: &str, forwarded_for: Option<&str>) -> TestResponse {
+    setup_with(none);
+    let mut request = Request::get(uri);
+    if let Some(ip) = forwarded_for {
+        request = request.header("x-forwarded-for", ip);
+    }
+    send_from(request.body(Body::empty()).unwrap(), peer.parse().unwrap()).await
+}
+
+#[tokio::test]
+async fn logs_contain_no_addresses() {
+    let (captured, _guard) = capture_logs();
+    let response = get_via("/api/8.8.4.4", "9.9.9.9:1000", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let response = get_via("/api", "9.9.9.9:1000", Some("2001:4860:4860::8844")).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let output = captured.output();
+    assert!(output.contains("path=/api/{host}"), "{}", output);
+    assert!(output.contains("client_ip=[redacted]"), "{}", output);
+    assert!(output.contains("x-forwarded-for(General) 中的IP: [redacted]"), "{}", output);
+    for raw in ["8.8.4", "9.9.9", "2001:4860"] {
+        assert!(!output.contains(raw), "{} leaked:\n{}", raw, output);
+    }
+}
+
+#[tokio::test]
+async fn invalid_ip_message_does_not_echo_the_input() {
+    let response = get_via("/api/192.0.2.55", "9.9.9.9:1000", None).await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_IP");
+    let message = response.body["message"].as_str().unwrap();
+    assert!(message.ends_with("[redacted]"), "{}", message);
+    assert!(!message.contains("192.0.2"), "{}", message);
+}
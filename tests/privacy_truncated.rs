@@ -0,0 +1,51 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{assert_error, capture_logs, send_from, setup_with, TestResponse};
+use ipgeo::config::{Config, PrivacyMode};
+
+fn truncated(config: Config) -> Config {
+    Config { privacy_mode: PrivacyMode::Truncated, ..config }
+}
+
+async fn get_via(uri: &str, peer: &str, forwarded_for: Option<&str>) -> TestResponse {
+    setup_with(truncated);
+    let mut request = Request::get(uri);
+    if let Some(ip) = forwarded_for {
+        request = request.header("x-forwarded-for", ip);
+    }
+    send_from(request.body(Body::empty()).unwrap(), peer.parse().unwrap()).await
+}
+
+#[tokio::test]
+async fn logs_keep_only_the_network_prefix() {
+    let (captured, _guard) = capture_logs();
+    let response = get_via("/api/8.8.4.4", "9.9.9.9:1000", None).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let response = get_via("/api", "9.9.9.9:1000", Some("2001:4860:4860::8844")).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let output = captured.output();
+    // 访问日志只记录路由模板，客户端IP截断到 /24 和 /48
+    assert!(output.contains("path=/api/{host}"), "{}", output);
+    assert!(output.contains("client_ip=9.9.9.0 "), "{}", output);
+    assert!(output.contains("client_ip=2001:4860:4860:: "), "{}", output);
+    assert!(output.contains("x-forwarded-for(General) 中的IP: 2001:4860:4860::\n"), "{}", output);
+    for raw in ["8.8.4.4", "9.9.9.9", "::8844"] {
+        assert!(!output.contains(raw), "{} leaked:\n{}", raw, output);
+    }
+}
+
+#[tokio::test]
+async fn invalid_ip_message_is_truncated() {
+    let response = get_via("/api/192.0.2.55", "9.9.9.9:1000", None).await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_IP");
+    let message = response.body["message"].as_str().unwrap();
+    assert!(message.contains("192.0.2.0"), "{}", message);
+    assert!(!message.contains("192.0.2.55"), "{}", message);
+
+    let response = get_via("/api/2001:db8::1234", "9.9.9.9:1000", None).await;
+    let message = response.body["message"].as_str().unwrap();
+    assert!(message.ends_with("2001:db8::"), "{}", message);
+}
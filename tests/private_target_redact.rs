@@ -0,0 +1,37 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{send_from, setup_with, TestResponse};
+use ipgeo::config::{Config, PrivateTargetPolicy};
+use serde_json::json;
+
+fn redact(config: Config) -> Config {
+    Config { private_target_policy: PrivateTargetPolicy::Redact, ..config }
+}
+
+async fn get_from(uri: &str, peer: &str) -> TestResponse {
+    setup_with(redact);
+    send_from(Request::get(uri).body(Body::empty()).unwrap(), peer.parse().unwrap()).await
+}
+
+#[tokio::test]
+async fn private_targets_only_report_ip_and_type() {
+    let response = get_from("/api?host=10.1.2.3", "8.8.8.8:1000").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body, json!({ "ip": "10.1.2.3", "type": "私有地址" }));
+
+    let response = get_from("/api/fd00::1", "8.8.8.8:1000").await;
+    assert_eq!(response.body, json!({ "ip": "fd00::1", "type": "私有地址" }));
+
+    let response = get_from("/fe80::1", "8.8.8.8:1000").await;
+    assert_eq!(response.body, json!({ "ip": "fe80::1", "type": "链路本地地址" }));
+}
+
+#[tokio::test]
+async fn callers_own_private_address_keeps_its_network() {
+    let response = get_from("/", "10.1.2.3:1000").await;
+    assert_eq!(response.body, json!({ "ip": "10.1.2.3", "addr": "10.0.0.0/8" }));
+    let response = get_from("/api", "[fd00::1]:1000").await;
+    assert_eq!(response.body["addr"], "fc00::/7");
+}
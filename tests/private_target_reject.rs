@@ -0,0 +1,42 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{assert_error, post_json, send_from, setup_with};
+use ipgeo::config::{Config, PrivateTargetPolicy};
+use serde_json::json;
+
+fn reject(config: Config) -> Config {
+    Config { private_target_policy: PrivateTargetPolicy::Reject, ..config }
+}
+
+async fn get_from(uri: &str, peer: &str) -> common::TestResponse {
+    setup_with(reject);
+    send_from(Request::get(uri).body(Body::empty()).unwrap(), peer.parse().unwrap()).await
+}
+
+#[tokio::test]
+async fn private_targets_are_rejected() {
+    for uri in ["/10.1.2.3", "/api/192.168.1.1", "/api?host=fd00::1", "/api/fe80::1", "/v1/api?host=127.0.0.1"] {
+        let response = get_from(uri, "8.8.8.8:1000").await;
+        assert_error(&response, StatusCode::FORBIDDEN, "PRIVATE_TARGET");
+    }
+    // 公网目标不受影响
+    assert_eq!(get_from("/api?host=1.0.0.1", "8.8.8.8:1000").await.status, StatusCode::OK);
+
+    setup_with(reject);
+    let response = post_json("/api/batch", &json!(["10.1.2.3", "1.0.0.1"])).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body[0]["error"], "PRIVATE_TARGET");
+    assert_eq!(response.body[0]["query"], "10.1.2.3");
+    assert_eq!(response.body[1]["country"]["code"], "AU");
+}
+
+#[tokio::test]
+async fn callers_own_private_address_is_still_answered() {
+    for (uri, peer) in [("/", "10.1.2.3:1000"), ("/api", "[fd00::1]:1000"), ("/api/me", "10.1.2.3:1000")] {
+        let response = get_from(uri, peer).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", uri);
+        assert!(response.body["addr"].is_string(), "{}: {}", uri, response.body);
+    }
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use std::time::{Duration, SystemTime};
+use axum::http::StatusCode;
+use common::{get, post_json};
+use ipgeo::utils::format_rfc3339;
+use serde_json::json;
+
+#[test]
+fn rfc3339_timestamps() {
+    let at = |millis: u64| SystemTime::UNIX_EPOCH + Duration::from_millis(millis);
+    assert_eq!(format_rfc3339(at(0)), "1970-01-01T00:00:00.000Z");
+    assert_eq!(format_rfc3339(at(1_714_552_245_007)), "2024-05-01T08:30:45.007Z");
+    assert_eq!(format_rfc3339(at(951_868_799_999)), "2000-02-29T23:59:59.999Z");
+}
+
+// 同一时区的 RFC3339 字符串可以直接按字典序比较
+fn assert_recent(timestamp: &serde_json::Value, since: &str) {
+    let timestamp = timestamp.as_str().expect("timestamp");
+    assert!(timestamp >= since && timestamp <= format_rfc3339(SystemTime::now()).as_str(), "{} not after {}", timestamp, since);
+}
+
+#[tokio::test]
+async fn single_lookups_echo_only_when_asked() {
+    let response = get("/api/8.8.8.8").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.get("query").is_none());
+    assert!(response.body.get("timestamp").is_none());
+
+    let since = format_rfc3339(SystemTime::now());
+    let response = get("/api?host=%208.8.8.8&echo=1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["query"], "8.8.8.8");
+    assert_recent(&response.body["timestamp"], &since);
+
+    // 私有地址不查数据库，同样带上时间
+    let response = get("/v2/api/10.1.2.3?echo").await;
+    assert_eq!(response.body["query"], "10.1.2.3");
+    assert_recent(&response.body["timestamp"], &since);
+
+    // 查询自身时没有输入
+    let response = get("/?echo=1").await;
+    assert!(response.body.get("query").is_none());
+    assert!(response.body["timestamp"].is_string());
+}
+
+#[tokio::test]
+async fn batch_echoes_by_default() {
+    let since = format_rfc3339(SystemTime::now());
+    let response = post_json("/api/batch", &json!(["1.0.0.1", "me"])).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body[0]["query"], "1.0.0.1");
+    assert_recent(&response.body[0]["timestamp"], &since);
+    assert_eq!(response.body[1]["query"], "me");
+
+    let response = post_json("/api/batch?echo=0", &json!(["1.0.0.1"])).await;
+    assert!(response.body[0].get("query").is_none());
+    assert!(response.body[0].get("timestamp").is_none());
+
+    let response = get("/api?host=1.0.0.1,8.8.8.8").await;
+    assert_eq!(response.body[1]["query"], "8.8.8.8");
+}
+
+#[tokio::test]
+async fn v1_batches_never_echo() {
+    let response = post_json("/v2/api/batch", &json!(["1.0.0.1"])).await;
+    assert_eq!(response.body[0]["query"], "1.0.0.1");
+    assert!(response.body[0]["timestamp"].is_string());
+
+    // /v1 的响应格式固定，指定 echo=1 也不输出
+    for path in ["/v1/api/batch", "/v1/api/batch?echo=1"] {
+        let response = post_json(path, &json!(["1.0.0.1"])).await;
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.body[0]["ip"], "1.0.0.1", "{}", response.body);
+        for field in ["query", "timestamp", "cached"] {
+            assert!(response.body[0].get(field).is_none(), "{}: {}", path, response.body);
+        }
+    }
+}
+
+#[tokio::test]
+async fn shared_results_keep_the_original_computation_time() {
+    let hosts = vec!["1.1.1.1"; 64];
+    let response = post_json("/api/batch", &json!(hosts)).await;
+    let items = response.body.as_array().unwrap();
+    let shared: Vec<_> = items.iter().filter(|item| item["cached"] == true).collect();
+    let computed: Vec<_> = items.iter().filter(|item| item.get("cached").is_none()).collect();
+    assert!(!computed.is_empty() && !shared.is_empty());
+    // 并发的相同查询共享结果时，timestamp 与计算它的那一项相同
+    for item in shared {
+        assert!(computed.iter().any(|c| c["timestamp"] == item["timestamp"]));
+    }
+}
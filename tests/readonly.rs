@@ -0,0 +1,38 @@
+//! 数据目录由外部维护（DB_AUTO_UPDATE=false、只读挂载）时的启动行为
+
+mod common;
+
+use std::path::Path;
+use axum::http::StatusCode;
+use common::get;
+use ipgeo::cache::CacheManager;
+use ipgeo::config::Config;
+use ipgeo::geo::{database_state, init_asn_data, init_mmdb_readers, DatabaseManager, DatabaseState};
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn auto_update_disabled_uses_existing_files_only() {
+    // 外部维护的空目录，没有 asn_info.json
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("external-data");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    Config::init(Config { data_dir: dir.clone(), db_auto_update: false, ..Config::default() });
+
+    init_mmdb_readers(CancellationToken::new()).await.expect("starts without downloading");
+    // 什么都没有写入数据目录
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+    // 内置的 asn_info.json 已加载
+    init_asn_data(&DatabaseManager::new(dir.clone())).expect("bundled asn_info.json");
+    assert!(CacheManager::global().get_asn_info(4134).is_some());
+
+    // 下载步骤被跳过，缺失的数据库直接判定为降级，不会一直停在 initializing
+    assert_eq!(database_state(), DatabaseState::Degraded);
+
+    // 外部放入数据库后（这里由夹具加载）恢复正常，健康检查标明不会自行更新
+    let response = get("/healthz").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["status"], "ready");
+    assert_eq!(response.body["auto_update"], false);
+    assert_eq!(response.body["data_dir"], dir.to_str().unwrap());
+}
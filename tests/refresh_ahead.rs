@@ -0,0 +1,84 @@
+mod common;
+
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use common::setup_with;
+use ipgeo::cache::RefreshQueue;
+use ipgeo::config::Config;
+use ipgeo::geo::get_ip_info_with;
+use ipgeo::metrics::Metrics;
+use ipgeo::models::{IpInfo, LookupOptions};
+use tokio::sync::Semaphore;
+
+// TTL 的一半之后命中即在后台刷新
+fn with_refresh_ahead(config: Config) -> Config {
+    Config { result_cache_ttl: Duration::from_secs(2), result_cache_refresh_ahead: 0.5, ..config }
+}
+
+fn refreshes() -> u64 {
+    Metrics::global().result_cache_refreshes.load(Ordering::Relaxed)
+}
+
+fn drops() -> u64 {
+    Metrics::global().result_cache_refresh_drops.load(Ordering::Relaxed)
+}
+
+async fn lookup(ip: &str) -> IpInfo {
+    get_ip_info_with(ip, LookupOptions { echo: Some(true), ..LookupOptions::default() }).await.unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn aging_hits_are_served_and_refreshed_in_the_background() {
+    setup_with(with_refresh_ahead);
+    let first = lookup("8.8.8.8").await;
+    assert!(lookup("8.8.8.8").await.cached);
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let before = refreshes();
+    let aging = lookup("8.8.8.8").await;
+    assert!(aging.cached);
+    assert_eq!(aging.timestamp, first.timestamp);
+
+    for _ in 0..100 {
+        if refreshes() > before {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(refreshes() > before);
+    // 刷新后的条目带着新的计算时间，并在原来的过期时间之后仍然有效
+    let refreshed = lookup("8.8.8.8").await;
+    assert!(refreshed.cached);
+    assert!(refreshed.timestamp > first.timestamp, "{:?} {:?}", refreshed.timestamp, first.timestamp);
+}
+
+#[tokio::test]
+async fn refresh_queue_is_bounded_and_deduplicated() {
+    let blocked = Arc::new(Semaphore::new(0));
+    let permits = Arc::clone(&blocked);
+    let queue = RefreshQueue::spawn(1, 1, move |_key| {
+        let permits = Arc::clone(&permits);
+        async move {
+            let _ = permits.acquire().await;
+        }
+    });
+    let key = |ip: &str| (ip.parse::<IpAddr>().unwrap(), LookupOptions::default());
+    let drops_before = drops();
+
+    // 第一个键被后台任务取走并阻塞，第二个留在队列中，队列已满时第三个被丢弃
+    queue.enqueue(key("1.0.0.1"));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    queue.enqueue(key("1.0.0.2"));
+    queue.enqueue(key("1.0.0.2"));
+    assert_eq!(drops(), drops_before);
+    queue.enqueue(key("1.0.0.3"));
+    assert_eq!(drops(), drops_before + 1);
+
+    blocked.add_permits(2);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    // 刷新完成后同一个键可以再次入队
+    queue.enqueue(key("1.0.0.1"));
+    assert_eq!(drops(), drops_before + 1);
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::get;
+use serde_json::json;
+
+#[tokio::test]
+async fn both_is_the_default() {
+    let default = get("/114.114.114.114").await;
+    let both = get("/114.114.114.114?regions=both").await;
+    assert_eq!(default.body, both.body);
+    assert_eq!(both.body["regions"], json!(["江苏省", "南京市", "玄武区"]));
+    assert_eq!(both.body["regions_short"], json!(["江苏", "南京", "玄武"]));
+}
+
+#[tokio::test]
+async fn full_omits_short_names() {
+    let response = get("/114.114.114.114?regions=full").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["regions"], json!(["江苏省", "南京市", "玄武区"]));
+    assert!(response.body.get("regions_short").is_none(), "{}", response.body);
+}
+
+#[tokio::test]
+async fn short_omits_full_names() {
+    let response = get("/114.114.114.114?regions=short").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["regions_short"], json!(["江苏", "南京", "玄武"]));
+    assert!(response.body.get("regions").is_none(), "{}", response.body);
+}
+
+#[tokio::test]
+async fn none_omits_both() {
+    let response = get("/114.114.114.114?regions=none").await;
+    assert_eq!(response.status, StatusCode::OK);
+    for field in ["regions", "regions_short", "region_confidence"] {
+        assert!(response.body.get(field).is_none(), "{}: {}", field, response.body);
+    }
+    assert_eq!(response.body["city"]["name"], "南京市");
+}
+
+#[tokio::test]
+async fn none_with_minimal_is_smallest() {
+    let minimal = get("/114.114.114.114?detail=minimal").await;
+    let smallest = get("/114.114.114.114?detail=minimal&regions=none").await;
+    assert_eq!(smallest.status, StatusCode::OK);
+    assert!(smallest.text().len() <= minimal.text().len());
+    assert!(smallest.body.get("regions").is_none());
+    assert!(smallest.body.get("regions_short").is_none());
+    assert_eq!(smallest.body["country"]["code"], "CN");
+}
+
+#[tokio::test]
+async fn overridden_regions_follow_style() {
+    let full = get("/9.9.9.9?regions=full").await;
+    assert_eq!(full.body["regions"], json!(["上海市"]));
+    let short = get("/9.9.9.9?regions=short").await;
+    assert!(short.body.get("regions").is_none(), "{}", short.body);
+}
+
+#[tokio::test]
+async fn unknown_style_is_rejected() {
+    let response = get("/114.114.114.114?regions=all").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.body["error"], "INVALID_PARAMETER");
+}
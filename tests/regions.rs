@@ -0,0 +1,83 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::get;
+use serde_json::json;
+
+#[tokio::test]
+async fn municipality_is_listed_once() {
+    // City 数据库中北京的省级和市级同为“北京市”
+    let response = get("/1.2.4.4").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["regions"], json!(["北京市"]));
+    assert_eq!(response.body["regions_short"], json!(["北京"]));
+}
+
+#[tokio::test]
+async fn geocn_province_equal_to_city_is_listed_once() {
+    let response = get("/1.2.5.5").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["regions"], json!(["重庆市", "渝中区"]));
+    assert_eq!(response.body["regions_short"], json!(["重庆", "渝中"]));
+}
+
+#[tokio::test]
+async fn province_and_city_are_both_kept() {
+    let response = get("/1.2.6.6").await;
+    assert_eq!(response.body["regions"], json!(["广东省", "广州市"]));
+    assert_eq!(response.body["regions_short"], json!(["广东", "广州"]));
+
+    let response = get("/114.114.114.114").await;
+    assert_eq!(response.body["regions"], json!(["江苏省", "南京市", "玄武区"]));
+    assert_eq!(response.body["regions_short"], json!(["江苏", "南京", "玄武"]));
+}
+
+#[test]
+fn same_named_province_and_city_are_distinct() {
+    let (mut regions, mut regions_short) = (Vec::new(), Vec::new());
+    for name in ["吉林省", "吉林市", "北京", "北京市"] {
+        ipgeo::utils::push_region(&mut regions, &mut regions_short, name.to_string());
+    }
+    assert_eq!(regions, ["吉林省", "吉林市", "北京"]);
+    assert_eq!(regions_short, ["吉林", "吉林", "北京"]);
+}
+
+#[tokio::test]
+async fn city_regions_carry_iso_subdivision_codes() {
+    let response = get("/1.2.6.6").await;
+    assert_eq!(response.body["region_codes"], json!(["CN-GD", ""]));
+    let response = get("/1.2.4.4").await;
+    assert_eq!(response.body["region_codes"], json!(["CN-BJ"]));
+    let response = get("/1.2.7.3").await;
+    assert_eq!(response.body["region_codes"], json!(["DE-BE", ""]));
+
+    // 与 regions_short 同样对齐，regions=none 时不输出
+    let response = get("/1.2.6.6?regions=short").await;
+    assert_eq!(response.body["regions_short"], json!(["广东", "广州"]));
+    assert_eq!(response.body["region_codes"], json!(["CN-GD", ""]));
+    let response = get("/1.2.6.6?regions=none").await;
+    assert!(response.body.get("region_codes").is_none());
+}
+
+#[tokio::test]
+async fn geocn_regions_use_the_bundled_province_table() {
+    let response = get("/1.2.5.5").await;
+    assert_eq!(response.body["regions"], json!(["重庆市", "渝中区"]));
+    assert_eq!(response.body["region_codes"], json!(["CN-CQ", ""]));
+
+    let response = get("/114.114.114.114").await;
+    assert_eq!(response.body["region_codes"], json!(["CN-JS", "", ""]));
+}
+
+#[test]
+fn china_province_names_map_to_iso_codes() {
+    use ipgeo::utils::china_province_code;
+    assert_eq!(china_province_code("浙江省"), Some("CN-ZJ"));
+    assert_eq!(china_province_code("广西壮族自治区"), Some("CN-GX"));
+    assert_eq!(china_province_code("内蒙古"), Some("CN-NM"));
+    assert_eq!(china_province_code("山西省"), Some("CN-SX"));
+    assert_eq!(china_province_code("陕西省"), Some("CN-SN"));
+    assert_eq!(china_province_code("香港特别行政区"), Some("CN-HK"));
+    assert_eq!(china_province_code("Zhejiang"), None);
+    assert_eq!(china_province_code(""), None);
+}
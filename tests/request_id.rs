@@ -0,0 +1,52 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{assert_error, get, send};
+
+fn request_with_id(uri: &str, id: &str) -> Request<Body> {
+    Request::builder().uri(uri).header("X-Request-Id", id).body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn error_envelope_carries_header_request_id() {
+    let response = get("/api/0.0.0.0").await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_IP");
+    let header = response.headers["x-request-id"].to_str().unwrap();
+    assert_eq!(response.body["request_id"], header);
+
+    let id = uuid::Uuid::parse_str(header).expect("generated id is a UUID");
+    assert_eq!(id.get_version_num(), 7);
+}
+
+#[tokio::test]
+async fn successful_responses_carry_header_only() {
+    let response = get("/api/8.8.8.8").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.headers.contains_key("x-request-id"));
+    assert!(response.body.get("request_id").is_none());
+
+    // 批量结果中的单项错误沿用原来的信封
+    let response = common::post_json("/api/batch", &serde_json::json!(["8.8.8.8", "0.0.0.0"])).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body[1].get("request_id").is_none());
+}
+
+#[tokio::test]
+async fn well_formed_incoming_id_is_adopted() {
+    let response = send(request_with_id("/api/0.0.0.0", "edge-01:abc_123.4")).await;
+    assert_eq!(response.headers["x-request-id"], "edge-01:abc_123.4");
+    assert_eq!(response.body["request_id"], "edge-01:abc_123.4");
+}
+
+#[tokio::test]
+async fn malformed_incoming_id_is_replaced() {
+    let too_long = "a".repeat(129);
+    for id in ["has space", "<script>", "a/b", too_long.as_str()] {
+        let response = send(request_with_id("/api/0.0.0.0", id)).await;
+        let header = response.headers["x-request-id"].to_str().unwrap();
+        assert_ne!(header, id);
+        assert_eq!(uuid::Uuid::parse_str(header).unwrap().get_version_num(), 7);
+        assert_eq!(response.body["request_id"], header);
+    }
+}
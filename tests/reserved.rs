@@ -0,0 +1,43 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{assert_error, get};
+use ipgeo::api::routes::{is_reserved, reserve};
+
+#[tokio::test]
+async fn reserved_and_mistyped_paths_are_not_resolved() {
+    for uri in ["/metricz", "/healthzz", "/version", "/Admin", "/debug", "/v3", "/v1x"] {
+        let response = get(uri).await;
+        assert_error(&response, StatusCode::NOT_FOUND, "NOT_FOUND");
+    }
+
+    // 启用 swagger-ui 时 /docs 跳转到文档页面，否则同样不会被当作域名
+    let response = get("/docs").await;
+    if cfg!(feature = "swagger-ui") {
+        assert_eq!(response.status, StatusCode::PERMANENT_REDIRECT);
+        assert!(response.headers["location"].to_str().unwrap().ends_with("docs/"));
+    } else {
+        assert_error(&response, StatusCode::NOT_FOUND, "NOT_FOUND");
+    }
+}
+
+#[tokio::test]
+async fn hosts_still_resolve_at_top_level() {
+    for uri in ["/8.8.8.8", "/2001:4860:4860::8888", "/me", "/v1/8.8.8.8"] {
+        let response = get(uri).await;
+        assert_eq!(response.status, StatusCode::OK, "{}: {}", uri, response.body);
+    }
+}
+
+#[tokio::test]
+async fn registered_routes_extend_the_reserved_set() {
+    // 构建一次路由，注册过程会把首段加入集合
+    get("/").await;
+    for segment in ["api", "metrics", "healthz", "openapi.json", "robots.txt", "v1", "v2"] {
+        assert!(is_reserved(segment), "{} should be reserved", segment);
+    }
+    assert!(!is_reserved("example.com"));
+
+    reserve("/status/{id}");
+    assert!(is_reserved("STATUS"));
+}
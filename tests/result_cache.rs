@@ -0,0 +1,85 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime};
+use common::setup_with;
+use ipgeo::cache::{load_snapshot, persist_result_cache, restore_result_cache, save_snapshot, ResultCache};
+use ipgeo::config::Config;
+use ipgeo::geo::{data_generation, get_ip_info_with, reload_database};
+use ipgeo::metrics::Metrics;
+use ipgeo::models::{Detail, LookupOptions, NameLocales};
+use mmdb_writer::Writer;
+use serde_json::json;
+
+fn snapshot_dir() -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join("result-cache")
+}
+
+fn with_result_cache(config: Config) -> Config {
+    Config {
+        result_cache_ttl: Duration::from_secs(3600),
+        cache_snapshot: true,
+        cache_snapshot_path: Some(snapshot_dir().join("result_cache.msgpack")),
+        ..config
+    }
+}
+
+fn echo() -> LookupOptions {
+    LookupOptions { echo: Some(true), ..LookupOptions::default() }
+}
+
+// 与夹具 ASN 数据库内容相同、构建时间不同的数据库
+fn rebuilt_asn_database(dir: &Path) -> PathBuf {
+    let mut writer = Writer::builder("GeoLite2-ASN").build_epoch(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)).build();
+    let network: ipnet::IpNet = "8.8.8.0/24".parse().unwrap();
+    writer.insert(network, &json!({ "autonomous_system_number": 15169, "autonomous_system_organization": "GOOGLE" })).unwrap();
+    let path = dir.join("GeoLite2-ASN-rebuilt.mmdb");
+    std::fs::write(&path, writer.to_bytes().unwrap()).unwrap();
+    path
+}
+
+// 代数和数据库在各步之间不能变化，全部放在一个测试中按顺序执行
+#[tokio::test]
+async fn snapshot_round_trip_and_invalidation() {
+    setup_with(with_result_cache);
+    std::fs::create_dir_all(snapshot_dir()).unwrap();
+    let cache = ResultCache::global().expect("result cache enabled");
+
+    // 命中时 cached 为 true，timestamp 为最初的计算时间
+    let first = get_ip_info_with("8.8.8.8", echo()).await.unwrap();
+    assert!(!first.cached);
+    let second = get_ip_info_with("8.8.8.8", echo()).await.unwrap();
+    assert!(second.cached);
+    assert_eq!(second.timestamp, first.timestamp);
+
+    let full = LookupOptions { detail: Detail::Full, locales: NameLocales::from_tags(["ja"]), ..LookupOptions::default() };
+    let before = get_ip_info_with("114.114.114.114", full).await.unwrap();
+
+    // 关闭时写入，清空后重新启动时恢复
+    persist_result_cache();
+    cache.clear();
+    restore_result_cache();
+    assert_eq!(Metrics::global().result_cache_restored.load(Ordering::Relaxed), 2);
+    let restored = get_ip_info_with("8.8.8.8", echo()).await.unwrap();
+    assert!(restored.cached);
+    assert_eq!(restored.timestamp, first.timestamp);
+    let after = get_ip_info_with("114.114.114.114", full).await.unwrap();
+    assert_eq!(serde_json::to_value(&after).unwrap(), serde_json::to_value(&before).unwrap());
+
+    // 损坏的快照不恢复任何条目
+    let corrupt = snapshot_dir().join("corrupt.msgpack");
+    std::fs::write(&corrupt, b"not a snapshot").unwrap();
+    assert_eq!(load_snapshot(cache, &corrupt), 0);
+    assert_eq!(load_snapshot(cache, &snapshot_dir().join("missing.msgpack")), 0);
+
+    // 数据库换了构建时间之后快照作废
+    let path = snapshot_dir().join("epochs.msgpack");
+    assert_eq!(save_snapshot(cache, &path).unwrap(), 2);
+    let generation = data_generation();
+    reload_database("ASN", &rebuilt_asn_database(&snapshot_dir())).unwrap();
+    assert!(data_generation() > generation);
+    let fresh = ResultCache::new(Duration::from_secs(3600), 1 << 20);
+    assert_eq!(load_snapshot(&fresh, &path), 0);
+    assert_eq!(fresh.usage().0, 0);
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::net::IpAddr;
+
+use axum::http::StatusCode;
+use common::get;
+use ipgeo::geo::{encode_allocations, parse_delegated, RirTable};
+use serde_json::json;
+
+const SAMPLE: &str = include_str!("fixtures/delegated-sample.txt");
+
+fn sample_table() -> RirTable {
+    let (allocations, _) = parse_delegated(SAMPLE);
+    RirTable::parse(encode_allocations(allocations, 0)).expect("valid table")
+}
+
+fn lookup(table: &RirTable, ip: &str) -> Option<(&'static str, Option<String>, String)> {
+    table.lookup(ip.parse::<IpAddr>().unwrap())
+        .map(|record| (record.registry, record.allocated, record.cidr.to_string()))
+}
+
+#[test]
+fn parses_allocated_and_assigned_records() {
+    let (allocations, skipped) = parse_delegated(SAMPLE);
+    // 版本行、汇总行和 reserved 不算记录，地址无法解析的行被跳过
+    assert_eq!(allocations.len(), 9);
+    assert_eq!(skipped, 1);
+    assert_eq!(allocations[0].registry(), "APNIC");
+    assert_eq!(allocations[0].end, "1.0.0.255".parse::<IpAddr>().unwrap());
+    assert_eq!(allocations[0].holder.as_deref(), Some("A91872ED"));
+}
+
+#[test]
+fn adjacent_blocks_of_one_holder_are_merged() {
+    let table = sample_table();
+    assert_eq!(table.len(), 8);
+    assert_eq!(lookup(&table, "8.8.9.9"), Some(("ARIN", Some("1992-12-01".to_string()), "8.8.8.0/23".to_string())));
+}
+
+#[test]
+fn uneven_ipv4_blocks_split_into_cidrs() {
+    let table = sample_table();
+    assert_eq!(lookup(&table, "80.81.193.1").unwrap().2, "80.81.192.0/23");
+    assert_eq!(lookup(&table, "80.81.194.1").unwrap().2, "80.81.194.0/24");
+    assert_eq!(lookup(&table, "80.81.195.1"), None);
+}
+
+#[test]
+fn ipv6_and_missing_dates() {
+    let table = sample_table();
+    assert_eq!(lookup(&table, "2001:7f8:1::a"), Some(("RIPE NCC", Some("2001-09-12".to_string()), "2001:7f8::/29".to_string())));
+    assert_eq!(lookup(&table, "41.0.2.3"), Some(("AFRINIC", None, "41.0.0.0/16".to_string())));
+    assert_eq!(lookup(&table, "9.9.9.9"), None);
+}
+
+#[test]
+fn rejects_truncated_tables() {
+    let (allocations, _) = parse_delegated(SAMPLE);
+    let mut bytes = encode_allocations(allocations, 0);
+    bytes.pop();
+    assert!(RirTable::parse(bytes).is_none());
+    assert!(RirTable::parse(b"IPFB1".to_vec()).is_none());
+}
+
+#[tokio::test]
+async fn rir_is_opt_in() {
+    let response = get("/8.8.8.8").await;
+    assert!(response.body.get("rir").is_none());
+
+    let response = get("/8.8.8.8?rir=1&sources=1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["rir"], json!({ "registry": "ARIN", "allocated": "1992-12-01", "cidr": "8.8.8.0/23" }));
+    assert_eq!(response.body["sources"]["rir"], "RIR delegated 2024-05-01");
+}
+
+#[tokio::test]
+async fn full_detail_includes_rir() {
+    let response = get("/api?host=114.114.114.114&detail=full").await;
+    assert_eq!(response.body["rir"]["registry"], "APNIC");
+    assert_eq!(response.body["rir"]["cidr"], "114.114.0.0/16");
+}
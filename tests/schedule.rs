@@ -0,0 +1,38 @@
+use ipgeo::config::DailyTime;
+use ipgeo::geo::seconds_until_daily;
+
+const HOUR: u64 = 3600;
+const DAY: u64 = 86400;
+
+fn at(s: &str) -> DailyTime {
+    s.parse().expect("valid HH:MM")
+}
+
+#[test]
+fn parses_update_time() {
+    assert_eq!(at("03:30"), DailyTime { hour: 3, minute: 30 });
+    assert_eq!(at(" 0:05 ").seconds_of_day(), 300);
+    for invalid in ["24:00", "12:60", "1230", "ab:cd", ""] {
+        assert!(invalid.parse::<DailyTime>().is_err(), "{} should be rejected", invalid);
+    }
+}
+
+#[test]
+fn schedules_later_today_or_tomorrow() {
+    // 本地时间某天 01:00
+    let now = 10 * DAY + HOUR;
+    assert_eq!(seconds_until_daily(at("03:00"), now, 0), 2 * HOUR);
+    assert_eq!(seconds_until_daily(at("03:00"), now, -600), 2 * HOUR - 600);
+    // 已经过了当天的更新时间
+    assert_eq!(seconds_until_daily(at("00:30"), now, 0), DAY - 30 * 60);
+}
+
+#[test]
+fn skips_runs_too_close_to_now() {
+    let now = 10 * DAY + 3 * HOUR;
+    // 刚好在更新时间醒来，不会立即再跑一次
+    assert_eq!(seconds_until_daily(at("03:00"), now, 0), DAY);
+    assert_eq!(seconds_until_daily(at("03:10"), now, 0), DAY + 600);
+    // 偏移跨过午夜
+    assert_eq!(seconds_until_daily(at("00:05"), 10 * DAY + 12 * HOUR, -900), 12 * HOUR - 600);
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use std::future::Future;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use common::{capture_logs, fixtures_dir, setup_with};
+use ipgeo::api::AppState;
+use ipgeo::config::Config;
+use ipgeo::geo::DatabaseManager;
+use ipgeo::server::run_until_shutdown;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+const DRAIN_TIMEOUT: Duration = Duration::from_millis(300);
+
+fn short_drain(config: Config) -> Config {
+    Config { shutdown_timeout: DRAIN_TIMEOUT, ..config }
+}
+
+// 收到停止通知后自行结束的服务，模拟在途请求全部完成
+fn stoppable() -> (Arc<Notify>, impl Future<Output = std::io::Result<()>>) {
+    let stop = Arc::new(Notify::new());
+    let notified = stop.clone();
+    (stop, async move {
+        notified.notified().await;
+        Ok(())
+    })
+}
+
+#[tokio::test]
+async fn clean_drain_exits_successfully() {
+    setup_with(short_drain);
+    let state = AppState::new();
+    let (stop, server) = stoppable();
+
+    let code = run_until_shutdown(server, &state, async {}, || stop.notify_one()).await.unwrap();
+    assert_eq!(code, ExitCode::SUCCESS);
+    assert!(state.shutdown.is_cancelled());
+}
+
+#[tokio::test]
+async fn stuck_requests_are_aborted_at_the_deadline() {
+    setup_with(short_drain);
+    let state = AppState::new();
+    let began = Arc::new(AtomicBool::new(false));
+    let started = Instant::now();
+
+    let code = run_until_shutdown(
+        std::future::pending::<std::io::Result<()>>(),
+        &state,
+        async {},
+        || began.store(true, Ordering::SeqCst),
+    ).await.unwrap();
+    assert_eq!(code, ExitCode::FAILURE);
+    assert!(began.load(Ordering::SeqCst));
+    let elapsed = started.elapsed();
+    assert!(elapsed >= DRAIN_TIMEOUT && elapsed < DRAIN_TIMEOUT * 5, "{:?}", elapsed);
+
+    // gRPC 随后等待时沿用同一个截止时间，不会再得到完整的 drain 超时
+    let before = Instant::now();
+    let grpc = tokio::time::timeout_at(state.drain_deadline(Duration::from_secs(60)), std::future::pending::<()>()).await;
+    assert!(grpc.is_err());
+    assert!(before.elapsed() < Duration::from_millis(50), "{:?}", before.elapsed());
+}
+
+#[tokio::test]
+async fn server_errors_are_returned_without_a_signal() {
+    setup_with(short_drain);
+    let state = AppState::new();
+    let server = async { Err(std::io::Error::other("listener failed")) };
+    let error = run_until_shutdown(server, &state, std::future::pending(), || panic!("not shutting down")).await.unwrap_err();
+    assert_eq!(error.to_string(), "listener failed");
+    assert!(!state.shutdown.is_cancelled());
+}
+
+#[tokio::test]
+async fn shutdown_cancels_auto_update() {
+    setup_with(short_drain);
+    let (captured, _guard) = capture_logs();
+    let state = AppState::new();
+    DatabaseManager::new(fixtures_dir()).start_auto_update(state.shutdown.clone()).await;
+    tokio::task::yield_now().await;
+    assert!(captured.output().contains("Next database update at"), "{}", captured.output());
+
+    let (stop, server) = stoppable();
+    let code = run_until_shutdown(server, &state, async {}, || stop.notify_one()).await.unwrap();
+    assert_eq!(code, ExitCode::SUCCESS);
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    assert!(captured.output().contains("Database auto-update task stopped"), "{}", captured.output());
+}
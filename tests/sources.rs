@@ -0,0 +1,63 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::get;
+use ipgeo::utils::format_epoch_date;
+
+fn assert_label(label: &serde_json::Value, database_type: &str) {
+    let label = label.as_str().unwrap_or_else(|| panic!("missing source for {}", database_type));
+    let date = label.strip_prefix(database_type)
+        .and_then(|rest| rest.strip_prefix(' '))
+        .unwrap_or_else(|| panic!("unexpected source {:?}", label));
+    assert_eq!(date.len(), 10, "{:?}", label);
+    assert_eq!(&date[4..5], "-");
+}
+
+#[tokio::test]
+async fn sources_are_omitted_by_default() {
+    let response = get("/api/114.114.114.114").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.get("sources").is_none());
+
+    let response = get("/api/114.114.114.114?sources=0").await;
+    assert!(response.body.get("sources").is_none());
+}
+
+#[tokio::test]
+async fn geocn_fields_are_attributed_to_geocn() {
+    let response = get("/api/114.114.114.114?sources=1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let sources = &response.body["sources"];
+    assert_label(&sources["regions"], "GeoCN");
+    assert_label(&sources["city"], "GeoCN");
+    assert_label(&sources["isp"], "GeoCN");
+    assert_label(&sources["asn"], "GeoLite2-ASN");
+    assert_label(&sources["location"], "GeoLite2-City");
+    assert_label(&sources["country"], "GeoLite2-City");
+    // 114.114.114.0/24 的网络类型来自 GeoCN 的运营商名称
+    assert_label(&sources["type"], "GeoCN");
+}
+
+#[tokio::test]
+async fn sources_only_cover_present_fields() {
+    let response = get("/api/8.8.8.8?sources").await;
+    let sources = response.body["sources"].as_object().expect("sources object");
+    assert_label(&sources["asn"], "GeoLite2-ASN");
+    assert_eq!(sources["type"], "asn_info.json");
+    assert!(!sources.contains_key("regions"));
+    assert!(!sources.contains_key("isp"));
+
+    let response = get("/api/8.8.8.8?sources=1&detail=minimal").await;
+    let sources = response.body["sources"].as_object().expect("sources object");
+    assert!(sources.contains_key("country"));
+    assert!(!sources.contains_key("location"));
+}
+
+#[test]
+fn epoch_dates_are_utc_calendar_days() {
+    assert_eq!(format_epoch_date(0), "1970-01-01");
+    assert_eq!(format_epoch_date(951_782_400), "2000-02-29");
+    assert_eq!(format_epoch_date(1_714_521_600), "2024-05-01");
+    assert_eq!(format_epoch_date(1_714_607_999), "2024-05-01");
+    assert_eq!(format_epoch_date(4_107_542_400), "2100-03-01");
+}
@@ -0,0 +1,68 @@
+//! 数据目录为空时启动：先以降级状态提供服务，数据库就绪后无需重启即可查询
+
+mod common;
+
+use std::path::Path;
+use axum::http::StatusCode;
+use common::{assert_error, get};
+use ipgeo::config::Config;
+
+#[tokio::test]
+async fn serves_degraded_until_databases_load() {
+    // 抢在 common::setup 之前用空目录初始化配置，全局读取器因此全部为空
+    let empty = Path::new(env!("CARGO_TARGET_TMPDIR")).join("empty-data");
+    let _ = std::fs::remove_dir_all(&empty);
+    std::fs::create_dir_all(&empty).unwrap();
+    Config::init(Config { data_dir: empty, ..Config::default() });
+    assert!(ipgeo::geo::get_city_reader().read().unwrap().is_none());
+
+    let response = get_without_databases("/healthz").await;
+    assert_eq!(response.0, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.1["status"], "initializing");
+
+    let response = get_without_databases("/8.8.8.8").await;
+    if cfg!(feature = "embedded-fallback") {
+        // 由内置表回答
+        assert_eq!(response.0, StatusCode::OK);
+        assert_eq!(response.1["country"]["code"], "US");
+        assert_eq!(response.1["type"], "embedded-fallback");
+        assert_eq!(response.1["addr"], "8.8.0.0/16");
+    } else {
+        assert_eq!(response.0, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.1["error"], "DB_UNAVAILABLE");
+    }
+
+    // 模拟后台下载完成：加载夹具数据库后立即可用
+    let response = get("/8.8.8.8").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["country"]["code"], "US");
+
+    // 真实数据库加载后不再使用内置表
+    assert_ne!(response.body["type"], "embedded-fallback");
+
+    let response = get("/healthz").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["status"], "ready");
+
+    let response = get("/0.0.0.0").await;
+    assert_error(&response, StatusCode::BAD_REQUEST, "INVALID_IP");
+}
+
+// 不经过 common::setup，直接把请求交给路由
+async fn get_without_databases(uri: &str) -> (StatusCode, serde_json::Value) {
+    use axum::body::Body;
+    use axum::extract::ConnectInfo;
+    use axum::http::Request;
+    use std::net::SocketAddr;
+    use tower::ServiceExt;
+
+    let mut request = Request::get(uri).body(Body::empty()).unwrap();
+    request.extensions_mut().insert(ConnectInfo(common::PEER.parse::<SocketAddr>().unwrap()));
+    let response = ipgeo::api::create_router(ipgeo::api::AppState::new())
+        .oneshot(request)
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or_default())
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use common::{assert_error, get};
+use ipgeo::metrics::stats::{parse_window, CountryCount, TrafficStats, TypeCount};
+
+const NOW: u64 = 29_000_000;
+
+#[test]
+fn per_minute_buckets_add_up_within_the_window() {
+    let stats = TrafficStats::new(Duration::from_secs(3 * 3600));
+    // 最近 3 小时内每分钟 2 次 CN 和 1 次 US，共 540 次
+    for minute in NOW - 179..=NOW {
+        stats.record_at(minute, Some("CN"), Some("电信网络"));
+        stats.record_at(minute, Some("CN"), Some("数据中心"));
+        stats.record_at(minute, Some("US"), Some("数据中心"));
+    }
+
+    let hour = stats.summary_at(NOW, 60, 10);
+    assert_eq!(hour.window_minutes, 60);
+    assert_eq!(hour.total, 180);
+    assert_eq!(hour.countries, vec![
+        CountryCount { code: "CN".to_string(), count: 120 },
+        CountryCount { code: "US".to_string(), count: 60 },
+    ]);
+    assert_eq!(hour.types, vec![
+        TypeCount { kind: "数据中心".to_string(), count: 120 },
+        TypeCount { kind: "电信网络".to_string(), count: 60 },
+    ]);
+
+    assert_eq!(stats.summary_at(NOW, 180, 10).total, 540);
+    // 超过保留时长的窗口按保留时长计算
+    let capped = stats.summary_at(NOW, 24 * 60, 10);
+    assert_eq!((capped.window_minutes, capped.total), (180, 540));
+    assert_eq!(stats.summary_at(NOW, 1, 1).countries, vec![CountryCount { code: "CN".to_string(), count: 2 }]);
+}
+
+#[test]
+fn old_buckets_expire_and_are_reused() {
+    let stats = TrafficStats::new(Duration::from_secs(3600));
+    for i in 0..300 {
+        stats.record_at(NOW - 100, Some("JP"), None);
+        if i % 3 == 0 {
+            stats.record_at(NOW, Some("DE"), None);
+        }
+    }
+    // NOW - 100 已超出 60 分钟的保留时长，和 NOW - 40 落在同一个桶里
+    stats.record_at(NOW - 40, Some("FR"), None);
+
+    let summary = stats.summary_at(NOW, 60, 10);
+    assert_eq!(summary.total, 101);
+    assert_eq!(summary.countries, vec![
+        CountryCount { code: "DE".to_string(), count: 100 },
+        CountryCount { code: "FR".to_string(), count: 1 },
+    ]);
+    assert_eq!(summary.types, vec![TypeCount { kind: "unknown".to_string(), count: 101 }]);
+    // 早于窗口的分钟不计入
+    assert_eq!(stats.summary_at(NOW, 30, 10).total, 100);
+}
+
+#[test]
+fn labels_are_collapsed_to_the_fixed_key_space() {
+    let stats = TrafficStats::new(Duration::from_secs(60));
+    stats.record_at(NOW, Some("127.0.0.1"), Some("某个 ISP"));
+    stats.record_at(NOW, Some("cn"), Some("数据中心"));
+    stats.record_at(NOW, None, None);
+
+    let summary = stats.summary_at(NOW, 1, 10);
+    assert_eq!(summary.countries, vec![
+        CountryCount { code: "other".to_string(), count: 2 },
+        CountryCount { code: "unknown".to_string(), count: 1 },
+    ]);
+    assert!(!serde_json::to_string(&summary).unwrap().contains("127.0.0.1"));
+}
+
+#[test]
+fn windows_are_parsed_in_minutes() {
+    assert_eq!(parse_window("30m"), Some(30));
+    assert_eq!(parse_window("1h"), Some(60));
+    assert_eq!(parse_window("2d"), Some(2880));
+    for invalid in ["", "h", "0h", "1", "1w", "-1h", "1.5h"] {
+        assert_eq!(parse_window(invalid), None, "{}", invalid);
+    }
+}
+
+#[tokio::test]
+async fn lookups_are_counted_by_country_and_type() {
+    for _ in 0..3 {
+        assert_eq!(get("/api?host=8.8.8.8").await.status, StatusCode::OK);
+    }
+    assert_eq!(get("/api?host=114.114.114.114").await.status, StatusCode::OK);
+    // 失败的查询不计入
+    assert_eq!(get("/api?host=not..valid").await.status, StatusCode::BAD_REQUEST);
+
+    let response = get("/stats?window=1h&top=1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers["cache-control"], "no-store");
+    assert_eq!(response.body["window_minutes"], 60);
+    assert_eq!(response.body["total"], 4);
+    assert_eq!(response.body["countries"], serde_json::json!([{ "code": "US", "count": 3 }]));
+    assert_eq!(response.body["types"].as_array().unwrap().len(), 1);
+
+    assert_eq!(get("/stats").await.body["window_minutes"], 24 * 60);
+    assert_error(&get("/stats?window=1w").await, StatusCode::BAD_REQUEST, "INVALID_PARAMETER");
+    let response = get("/stats?window=1w&lang=en").await;
+    assert!(response.body["message"].as_str().unwrap().is_ascii(), "{}", response.body);
+}
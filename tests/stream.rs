@@ -0,0 +1,102 @@
+mod common;
+
+use axum::body::{Body, Bytes};
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use common::{assert_error, send, setup_with, ADMIN_TOKEN, PEER};
+use futures::channel::mpsc;
+use futures::StreamExt;
+use ipgeo::api::{create_router, AppState};
+use ipgeo::config::Config;
+use serde_json::Value;
+use tower::ServiceExt;
+
+fn limited(config: Config) -> Config {
+    Config { stream_max_rows: 3, ..config }
+}
+
+fn stream_request(body: Body) -> Request<Body> {
+    Request::post("/api/batch/stream")
+        .header("authorization", format!("Bearer {}", ADMIN_TOKEN))
+        .header("content-type", "text/plain")
+        .body(body)
+        .unwrap()
+}
+
+fn lines(text: &str) -> Vec<Value> {
+    text.lines().map(|line| serde_json::from_str(line).expect("one JSON object per line")).collect()
+}
+
+#[tokio::test]
+async fn one_result_per_line_in_input_order() {
+    setup_with(limited);
+    let response = send(stream_request(Body::from("8.8.8.8\n\n0.0.0.0\r\n1.1.1.1"))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers["content-type"], "application/x-ndjson");
+
+    let results = lines(response.text());
+    assert_eq!(results.len(), 3, "{}", response.text());
+    assert_eq!(results[0]["ip"], "8.8.8.8");
+    assert_eq!(results[1]["error"], "INVALID_IP");
+    assert_eq!(results[1]["query"], "0.0.0.0");
+    assert_eq!(results[1]["line"], 3);
+    assert_eq!(results[2]["ip"], "1.1.1.1");
+}
+
+#[tokio::test]
+async fn stops_after_row_limit() {
+    setup_with(limited);
+    // 远超 MAX_BODY_BYTES 的请求体也照常读取
+    let body = format!("8.8.8.8\n1.1.1.1\n114.114.114.114\n8.8.4.4\n1.0.0.1\n{}", "\n".repeat(100_000));
+    let response = send(stream_request(Body::from(body))).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let results = lines(response.text());
+    assert_eq!(results.len(), 4, "{}", response.text());
+    assert_eq!(results[2]["ip"], "114.114.114.114");
+    assert_eq!(results[3]["error"], "INVALID_PARAMETER");
+    assert_eq!(results[3]["query"], "8.8.4.4");
+    assert_eq!(results[3]["line"], 4);
+}
+
+#[tokio::test]
+async fn overlong_line_ends_the_stream() {
+    setup_with(limited);
+    let body = format!("8.8.8.8\n{}\n1.1.1.1\n", "a".repeat(5000));
+    let response = send(stream_request(Body::from(body))).await;
+
+    let results = lines(response.text());
+    assert_eq!(results.len(), 2, "{}", response.text());
+    assert_eq!(results[1]["error"], "INVALID_PARAMETER");
+    assert_eq!(results[1]["line"], 2);
+}
+
+#[tokio::test]
+async fn results_are_sent_before_input_ends() {
+    setup_with(limited);
+    let (mut tx, rx) = mpsc::channel::<Result<Bytes, std::io::Error>>(1);
+    let mut request = stream_request(Body::from_stream(rx));
+    request.extensions_mut().insert(ConnectInfo(PEER.parse::<std::net::SocketAddr>().unwrap()));
+
+    let response = create_router(AppState::new()).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut body = response.into_body().into_data_stream();
+
+    tx.try_send(Ok(Bytes::from("8.8.8.8\n"))).unwrap();
+    let first = body.next().await.expect("first line").unwrap();
+    assert_eq!(lines(std::str::from_utf8(&first).unwrap())[0]["ip"], "8.8.8.8");
+
+    tx.try_send(Ok(Bytes::from("1.1.1.1\n"))).unwrap();
+    drop(tx);
+    let second = body.next().await.expect("second line").unwrap();
+    assert_eq!(lines(std::str::from_utf8(&second).unwrap())[0]["ip"], "1.1.1.1");
+    assert!(body.next().await.is_none());
+}
+
+#[tokio::test]
+async fn requires_admin_token() {
+    setup_with(limited);
+    let request = Request::post("/api/batch/stream").body(Body::from("8.8.8.8\n")).unwrap();
+    let response = send(request).await;
+    assert_error(&response, StatusCode::UNAUTHORIZED, "UNAUTHORIZED");
+}
@@ -0,0 +1,56 @@
+#![cfg(unix)]
+
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::time::Duration;
+use ipgeo::server::{listen_fd, notify, notify_ready};
+use tokio_util::sync::CancellationToken;
+
+fn receive(socket: &UnixDatagram) -> String {
+    socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut buf = [0u8; 512];
+    let len = socket.recv(&mut buf).expect("notification");
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+// 环境变量是进程级的，所有场景放在同一个测试中顺序执行
+#[tokio::test]
+async fn systemd_integration() {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("notify-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let socket = UnixDatagram::bind(&path).unwrap();
+    std::env::set_var("NOTIFY_SOCKET", &path);
+
+    notify("STOPPING=1");
+    assert_eq!(receive(&socket), "STOPPING=1");
+
+    // 数据库未加载时先报告就绪，STATUS 说明仍在初始化
+    let shutdown = CancellationToken::new();
+    notify_ready(shutdown.clone());
+    assert_eq!(receive(&socket), "READY=1\nSTATUS=Serving, databases are still initializing");
+    shutdown.cancel();
+
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::linux::net::SocketAddrExt;
+        let name = format!("ipgeo-notify-{}", std::process::id());
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes()).unwrap();
+        let abstract_socket = UnixDatagram::bind_addr(&addr).unwrap();
+        std::env::set_var("NOTIFY_SOCKET", format!("@{}", name));
+        notify("READY=1");
+        assert_eq!(receive(&abstract_socket), "READY=1");
+    }
+
+    // 未由 systemd 启动时不发送也不报错
+    std::env::remove_var("NOTIFY_SOCKET");
+    notify("READY=1");
+    let _ = std::fs::remove_file(&path);
+
+    // 只接受发给本进程的套接字，读取后清除环境变量
+    assert!(listen_fd().unwrap().is_none());
+    std::env::set_var("LISTEN_PID", (std::process::id() + 1).to_string());
+    std::env::set_var("LISTEN_FDS", "1");
+    assert!(listen_fd().unwrap().is_none());
+    assert!(std::env::var_os("LISTEN_PID").is_none());
+    assert!(std::env::var_os("LISTEN_FDS").is_none());
+}
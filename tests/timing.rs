@@ -0,0 +1,45 @@
+mod common;
+
+use std::time::Duration;
+use axum::http::StatusCode;
+use common::{get, get_admin};
+use ipgeo::metrics::timing::{record_stage, timed, with_timings, RequestTimings};
+
+#[tokio::test]
+async fn admin_requests_can_ask_for_a_timing_breakdown() {
+    let response = get_admin("/api?host=8.8.8.8&debug_timing=1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let stages: Vec<&str> = response.body["timing"]["stages"].as_array().expect("timing stages")
+        .iter()
+        .map(|stage| stage["stage"].as_str().unwrap())
+        .collect();
+    for stage in ["ip_extraction", "asn_lookup", "city_lookup", "geocn_lookup", "serialization"] {
+        assert!(stages.contains(&stage), "{} missing from {:?}", stage, stages);
+    }
+    assert!(response.body["timing"]["shared"].is_boolean());
+
+    // 没有管理令牌时忽略该参数
+    let response = get("/api?host=8.8.8.8&debug_timing=1").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.get("timing").is_none());
+    assert!(get_admin("/api?host=8.8.8.8").await.body.get("timing").is_none());
+}
+
+#[tokio::test]
+async fn repeated_stages_are_summed_in_first_seen_order() {
+    let timings = RequestTimings::new(false);
+    with_timings(timings.clone(), async {
+        record_stage("resolution", Duration::from_millis(2));
+        record_stage("asn_lookup", Duration::from_millis(1));
+        timed("resolution", async {}).await;
+    }).await;
+    // 作用域之外的记录被忽略
+    record_stage("resolution", Duration::from_secs(1));
+
+    let breakdown = timings.breakdown();
+    let stages: Vec<&str> = breakdown.stages.iter().map(|stage| stage.stage).collect();
+    assert_eq!(stages, ["resolution", "asn_lookup"]);
+    assert!(breakdown.stages[0].ms >= 2.0 && breakdown.stages[0].ms < 1000.0);
+    assert!(breakdown.to_string().starts_with("resolution="));
+    assert!(!breakdown.shared);
+}
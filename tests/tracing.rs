@@ -0,0 +1,57 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use common::{send, Captured};
+use ipgeo::api::trace_context::parse_traceparent;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+#[test]
+fn traceparent_is_parsed_and_validated() {
+    let context = parse_traceparent(TRACEPARENT).expect("valid traceparent");
+    assert_eq!(context.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(context.parent_id, "00f067aa0ba902b7");
+    assert!(context.sampled);
+    assert!(!parse_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00").unwrap().sampled);
+
+    // 更高的版本允许附加字段
+    assert!(parse_traceparent("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra").is_some());
+    for invalid in [
+        "",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+        "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6-00f067aa0ba902b7-01",
+    ] {
+        assert!(parse_traceparent(invalid).is_none(), "{}", invalid);
+    }
+}
+
+#[tokio::test]
+async fn lookup_spans_carry_trace_id_and_database_attributes() {
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let request = Request::get("/api?host=8.8.8.8")
+        .header("traceparent", TRACEPARENT)
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(request).await.status, StatusCode::OK);
+
+    let output = captured.output();
+    assert!(output.contains("trace_id=4bf92f3577b34da6a3ce929d0e0e4736"), "{}", output);
+    assert!(output.contains("get_ip_info{ip.family=\"ipv4\""), "{}", output);
+    assert!(output.contains("mmdb_lookup{db=\"ASN\" answered=true}"), "{}", output);
+    assert!(output.contains("mmdb_lookup{db=\"City\""), "{}", output);
+}
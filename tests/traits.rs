@@ -0,0 +1,60 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{get, setup};
+use ipgeo::utils::{ipinfo_to_csv, CSV_HEADER};
+use serde_json::json;
+
+#[tokio::test]
+async fn represented_country_and_traits_are_reported() {
+    let response = get("/1.2.11.11").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["country"]["code"], "DE");
+    assert_eq!(response.body["registered_country"]["code"], "US");
+    assert_eq!(response.body["represented_country"], json!({ "code": "US", "name": "美国" }));
+    assert_eq!(response.body["traits"], json!({ "is_anonymous_proxy": true, "is_satellite_provider": true }));
+    // 匿名代理同时计入 is_anonymous
+    assert_eq!(response.body["is_anonymous"], true);
+}
+
+#[tokio::test]
+async fn v1_keeps_the_same_shape() {
+    let response = get("/v1/1.2.11.11").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["represented_country"], json!({ "code": "US", "name": "美国" }));
+    assert_eq!(response.body["traits"], json!({ "is_anonymous_proxy": true, "is_satellite_provider": true }));
+}
+
+#[tokio::test]
+async fn fields_are_omitted_without_the_records() {
+    let response = get("/8.8.8.8").await;
+    assert!(response.body.get("represented_country").is_none());
+    assert!(response.body.get("traits").is_none());
+
+    // 只有商业版匿名和托管标记时不输出 traits
+    let response = get("/1.2.9.9").await;
+    assert!(response.body.get("traits").is_none());
+}
+
+#[tokio::test]
+async fn csv_has_represented_country_and_trait_columns() {
+    setup();
+    let header: Vec<_> = CSV_HEADER.split(',').collect();
+    let column = |row: &str, name: &str| {
+        let index = header.iter().position(|column| *column == name).unwrap();
+        row.split(',').nth(index).unwrap().to_string()
+    };
+
+    let row = ipinfo_to_csv(&ipgeo::geo::get_ip_info("1.2.11.11").await.unwrap());
+    assert_eq!(row.split(',').count(), header.len());
+    assert_eq!(column(&row, "represented_country_code"), "US");
+    assert_eq!(column(&row, "represented_country_name"), "美国");
+    assert_eq!(column(&row, "is_anonymous_proxy"), "true");
+    assert_eq!(column(&row, "is_satellite_provider"), "true");
+
+    let row = ipinfo_to_csv(&ipgeo::geo::get_ip_info("8.8.8.8").await.unwrap());
+    assert_eq!(column(&row, "represented_country_code"), "");
+    assert_eq!(column(&row, "represented_country_name"), "");
+    assert_eq!(column(&row, "is_anonymous_proxy"), "false");
+    assert_eq!(column(&row, "is_satellite_provider"), "false");
+}
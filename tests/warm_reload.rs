@@ -0,0 +1,45 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use common::setup_with;
+use ipgeo::config::Config;
+use ipgeo::geo::{get_ip_info_with, reload_database};
+use ipgeo::models::{IpInfo, LookupOptions};
+use mmdb_writer::Writer;
+use serde_json::json;
+
+fn with_result_cache(config: Config) -> Config {
+    Config { result_cache_ttl: Duration::from_secs(3600), ..config }
+}
+
+fn asn_database(dir: &Path, number: u32) -> PathBuf {
+    let mut writer = Writer::new("GeoLite2-ASN");
+    let network: ipnet::IpNet = "8.8.8.0/24".parse().unwrap();
+    writer.insert(network, &json!({ "autonomous_system_number": number })).unwrap();
+    let path = dir.join(format!("GeoLite2-ASN-{}.mmdb", number));
+    std::fs::write(&path, writer.to_bytes().unwrap()).unwrap();
+    path
+}
+
+async fn lookup() -> IpInfo {
+    get_ip_info_with("8.8.8.8", LookupOptions { echo: Some(true), ..LookupOptions::default() }).await.unwrap()
+}
+
+#[tokio::test]
+async fn reload_with_a_warm_result_cache_serves_new_data() {
+    setup_with(with_result_cache);
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("warm-reload");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    lookup().await;
+    let warm = lookup().await;
+    assert!(warm.cached);
+    assert_eq!(warm.asn.and_then(|asn| asn.number), Some(15169));
+
+    reload_database("ASN", &asn_database(&dir, 64510)).unwrap();
+    let fresh = lookup().await;
+    assert!(!fresh.cached);
+    assert_eq!(fresh.asn.and_then(|asn| asn.number), Some(64510));
+    assert!(lookup().await.cached);
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use common::setup_with;
+use ipgeo::cache::{read_hot_ips, save_hot_ips, warm_up, ResultCache};
+use ipgeo::config::Config;
+use ipgeo::geo::get_ip_info_with;
+use ipgeo::models::{Detail, LookupOptions};
+use tokio_util::sync::CancellationToken;
+
+fn warmup_dir() -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("warmup");
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn with_warmup(config: Config) -> Config {
+    Config { result_cache_ttl: Duration::from_secs(3600), cache_warmup: true, cache_warmup_concurrency: 2, ..config }
+}
+
+fn echo() -> LookupOptions {
+    LookupOptions { echo: Some(true), ..LookupOptions::default() }
+}
+
+fn ips(list: &[&str]) -> Vec<IpAddr> {
+    list.iter().map(|ip| ip.parse().unwrap()).collect()
+}
+
+#[test]
+fn hot_ips_file_skips_comments_and_invalid_lines() {
+    let path = warmup_dir().join("handwritten.txt");
+    std::fs::write(&path, "# comment\n8.8.8.8\n\nnot an ip\n 2a03:2880::1 \n").unwrap();
+    assert_eq!(read_hot_ips(&path), ips(&["8.8.8.8", "2a03:2880::1"]));
+    assert!(read_hot_ips(&warmup_dir().join("missing.txt")).is_empty());
+}
+
+// 结果缓存是全局的，各步放在一个测试中按顺序执行
+#[tokio::test]
+async fn hot_ips_are_dumped_by_hits_and_warm_the_cache() {
+    setup_with(with_warmup);
+    let cache = ResultCache::global().expect("result cache enabled");
+    for _ in 0..3 {
+        get_ip_info_with("114.114.114.114", LookupOptions::default()).await.unwrap();
+    }
+    get_ip_info_with("114.114.114.114", LookupOptions { detail: Detail::Full, ..LookupOptions::default() }).await.unwrap();
+    get_ip_info_with("8.8.8.8", LookupOptions::default()).await.unwrap();
+    get_ip_info_with("8.8.8.8", LookupOptions::default()).await.unwrap();
+    get_ip_info_with("1.0.0.1", LookupOptions::default()).await.unwrap();
+
+    // 同一IP的多种选项只记一行，按命中次数降序
+    let path = warmup_dir().join("hot_ips.txt");
+    assert_eq!(save_hot_ips(cache, &path, 2).unwrap(), 2);
+    assert_eq!(read_hot_ips(&path), ips(&["114.114.114.114", "8.8.8.8"]));
+
+    cache.clear();
+    let cancelled = CancellationToken::new();
+    cancelled.cancel();
+    assert_eq!(warm_up(read_hot_ips(&path), 2, cancelled).await, 0);
+    assert!(!get_ip_info_with("114.114.114.114", echo()).await.unwrap().cached);
+
+    cache.clear();
+    assert_eq!(warm_up(read_hot_ips(&path), 2, CancellationToken::new()).await, 2);
+    assert!(get_ip_info_with("114.114.114.114", echo()).await.unwrap().cached);
+    assert!(get_ip_info_with("8.8.8.8", echo()).await.unwrap().cached);
+    assert!(!get_ip_info_with("1.0.0.1", echo()).await.unwrap().cached);
+}
@@ -0,0 +1,57 @@
+//! 数据库文件损坏时：其余数据照常返回并附带 warnings，City 和 ASN 都出错时返回 DB_UNAVAILABLE
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use axum::http::StatusCode;
+use common::{assert_error, get};
+use ipgeo::geo::reload_database;
+use ipnet::IpNet;
+use mmdb_writer::Writer;
+use serde_json::json;
+
+// 能正常打开的数据库，但搜索树的首个节点被改写，查询任何地址都会出错
+fn write_corrupt(name: &str, database_type: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("corrupt");
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut writer = Writer::new(database_type);
+    let network: IpNet = "8.8.8.0/24".parse().unwrap();
+    writer.insert(network, &json!({ "autonomous_system_number": 15169 })).unwrap();
+    let mut bytes = writer.to_bytes().unwrap();
+    bytes[..8].fill(0xFF);
+    let path = dir.join(name);
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+// 读取器是全局的，各场景放在同一个测试中顺序执行
+#[tokio::test]
+async fn partial_results_carry_warnings() {
+    let response = get("/8.8.8.8").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.get("warnings").is_none());
+
+    reload_database("ASN", &write_corrupt("GeoLite2-ASN.mmdb", "GeoLite2-ASN")).unwrap();
+    let response = get("/8.8.8.8").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body["country"]["code"], "US");
+    assert!(response.body.get("as").is_none());
+    assert_eq!(response.body["warnings"], json!(["ASN lookup error"]));
+
+    // 地址不在库中不算失败
+    let response = get("/114.114.114.114").await;
+    assert_eq!(response.body["regions"], json!(["江苏省", "南京市", "玄武区"]));
+    assert_eq!(response.body["warnings"], json!(["ASN lookup error"]));
+
+    let metrics = String::from_utf8(get("/metrics").await.bytes).unwrap();
+    assert!(metrics.contains("ipgeo_db_stage_failures_total{database=\"ASN\",reason=\"error\"} 2"), "{}", metrics);
+
+    reload_database("City", &write_corrupt("GeoLite2-City.mmdb", "GeoLite2-City")).unwrap();
+    let response = get("/8.8.8.8").await;
+    if cfg!(feature = "embedded-fallback") {
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.body["warnings"], json!(["ASN lookup error", "City lookup error"]));
+    } else {
+        assert_error(&response, StatusCode::SERVICE_UNAVAILABLE, "DB_UNAVAILABLE");
+    }
+}
@@ -0,0 +1,75 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use ipgeo::geo::{get_asn_reader, watch_data_dir};
+use ipgeo::metrics::Metrics;
+use ipnet::IpNet;
+use mmdb_writer::Writer;
+use serde_json::json;
+use tokio_util::sync::CancellationToken;
+
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+fn scratch() -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("watched-data");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn asn_database(number: u32) -> Vec<u8> {
+    let mut writer = Writer::new("GeoLite2-ASN");
+    let network: IpNet = "8.8.8.0/24".parse().unwrap();
+    writer.insert(network, &json!({ "autonomous_system_number": number })).unwrap();
+    writer.to_bytes().unwrap()
+}
+
+fn loaded_asn() -> Option<u64> {
+    let reader = get_asn_reader();
+    let reader = reader.read().unwrap();
+    let record: serde_json::Value = reader.as_ref()?.lookup("8.8.8.8".parse().unwrap()).ok()?;
+    record["autonomous_system_number"].as_u64()
+}
+
+fn reloads() -> u64 {
+    Metrics::global().db_reloads.load(Ordering::Relaxed)
+}
+
+// 等到去抖结束、重新加载完成
+async fn settle() {
+    tokio::time::sleep(DEBOUNCE * 4).await;
+}
+
+#[tokio::test]
+async fn reloads_changed_databases_once_and_keeps_old_on_corruption() {
+    let dir = scratch();
+    let shutdown = CancellationToken::new();
+    let handle = watch_data_dir(&dir, DEBOUNCE, shutdown.clone()).expect("watch");
+    let path = dir.join("GeoLite2-ASN.mmdb");
+
+    // 像 rsync 一样写临时文件再改名，连续几次只触发一次重新加载
+    let before = reloads();
+    for number in [1, 2, 3] {
+        let temp = dir.join(".GeoLite2-ASN.mmdb.tmp");
+        std::fs::write(&temp, asn_database(number)).unwrap();
+        std::fs::rename(&temp, &path).unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    settle().await;
+    assert_eq!(loaded_asn(), Some(3));
+    assert_eq!(reloads() - before, 1);
+
+    // 损坏的文件不替换已加载的数据库
+    std::fs::write(&path, b"not a database").unwrap();
+    settle().await;
+    assert_eq!(loaded_asn(), Some(3));
+
+    // 无关文件被忽略
+    let before = reloads();
+    std::fs::write(dir.join("notes.txt"), "hello").unwrap();
+    settle().await;
+    assert_eq!(reloads(), before);
+
+    shutdown.cancel();
+    tokio::time::timeout(Duration::from_secs(2), handle).await.expect("watcher stops").unwrap();
+}